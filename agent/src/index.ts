@@ -55,6 +55,19 @@ interface MarketConditions {
   market_sentiment: "bullish" | "bearish" | "neutral";
 }
 
+interface FlowMetrics {
+  network: string;
+  token: string;
+  swap_count: number;
+  window_hours: number;
+  volume_usd: number;
+  net_flow_usd: number;
+  flow_direction: "inflow" | "outflow" | "neutral";
+  large_trade_count: number;
+  unique_traders: number;
+  annualized_volatility: number | null;
+}
+
 interface Position {
   token: string;
   balance: string;
@@ -71,9 +84,17 @@ interface TradingConfig {
   networks: string[];
   protocols: string[];
   check_interval_ms: number;
+  /** Execute prepared swaps (only set when a wallet is loaded) */
+  execute_swaps?: boolean;
   risk: RiskParameters;
 }
 
+/** Times a swap is re-quoted after its quote went stale before execution */
+const MAX_REQUOTES = 2;
+
+/** `code` of the quote freshness guard's block reason for a stale quote */
+const STALE_QUOTE_CODE = "stale_quote";
+
 type TradingAction =
   | { action: "query_pools"; protocol: string; network: string; reason: string }
   | {
@@ -117,6 +138,19 @@ interface QueryPlan {
   expected_data_points: number;
 }
 
+interface FailedQueryLeg {
+  network: string;
+  protocol: string;
+  error_class: string;
+  error: string;
+}
+
+interface QueryPlanOutcome {
+  pools: PoolData[];
+  success_rate: number | null;
+  failed_legs: FailedQueryLeg[];
+}
+
 interface TradingContext {
   cycleCount: number;
   positions: Position[];
@@ -209,8 +243,10 @@ function updateContext(
 /**
  * Execute a query plan from the inference strategist
  */
-async function executeQueryPlan(plan: QueryPlan): Promise<PoolData[]> {
+async function executeQueryPlan(plan: QueryPlan): Promise<QueryPlanOutcome> {
   const pools: PoolData[] = [];
+  let successRate: number | null = null;
+  let failedLegs: FailedQueryLeg[] = [];
 
   try {
     const result = await invokeTool("defi/query_subgraph", {
@@ -222,6 +258,9 @@ async function executeQueryPlan(plan: QueryPlan): Promise<PoolData[]> {
       },
     });
 
+    successRate = result.success_rate ?? null;
+    failedLegs = Array.isArray(result.failed_legs) ? result.failed_legs : [];
+
     // Extract pools from query plan results
     if (result.results && Array.isArray(result.results)) {
       for (const networkResult of result.results) {
@@ -232,7 +271,7 @@ async function executeQueryPlan(plan: QueryPlan): Promise<PoolData[]> {
               token0_symbol: (pool.token0 && pool.token0.symbol) || "???",
               token1_symbol: (pool.token1 && pool.token1.symbol) || "???",
               tvl_usd: parseFloat(pool.totalValueLockedUSD || "0"),
-              volume_24h_usd: parseFloat(pool.volumeUSD || "0"),
+              volume_24h_usd: pool.volume_24h_usd ?? 0,
               fee_tier: parseInt(pool.feeTier || "0"),
               token0_price: parseFloat(pool.token0Price || "0"),
               token1_price: parseFloat(pool.token1Price || "0"),
@@ -243,9 +282,18 @@ async function executeQueryPlan(plan: QueryPlan): Promise<PoolData[]> {
     }
   } catch (error) {
     console.error("Failed to execute query plan:", error);
+    successRate = 0;
+    failedLegs = plan.target_networks.flatMap((network) =>
+      plan.target_protocols.map((protocol) => ({
+        network,
+        protocol,
+        error_class: "transport",
+        error: String(error),
+      }))
+    );
   }
 
-  return pools;
+  return { pools, success_rate: successRate, failed_legs: failedLegs };
 }
 
 /**
@@ -268,6 +316,9 @@ async function runTradingLoop(config: TradingConfig): Promise<void> {
       const pools = await gatherPoolData(config);
       console.log(`Found ${pools.length} pools`);
 
+      // Step 1b: Recent WETH order flow (momentum, not just TVL snapshots)
+      const flows = await gatherFlowMetrics(config);
+
       // Step 2: Get current market conditions
       console.log("Fetching market conditions...");
       const market = await getMarketConditions();
@@ -278,7 +329,7 @@ async function runTradingLoop(config: TradingConfig): Promise<void> {
 
       // Step 4: Ask LLM for strategy recommendation
       console.log("Inferring strategy...");
-      const action = await inferStrategy(pools, market, positions, config.risk);
+      const action = await inferStrategy(pools, flows, market, positions, config.risk);
       console.log(`Strategy decision: ${action.action}`);
 
       // Step 5: Execute the recommended action
@@ -356,8 +407,12 @@ async function runBidirectionalTradingLoop(config: TradingConfig): Promise<void>
 
       // Step 4: Expert 1 (Graph Orchestrator) executes query plan
       console.log("Graph Orchestrator: Executing query plan...");
-      const pools = await executeQueryPlan(queryPlan);
+      const outcome = await executeQueryPlan(queryPlan);
+      const pools = outcome.pools;
       console.log(`Retrieved ${pools.length} pools`);
+      for (const leg of outcome.failed_legs) {
+        console.warn(`  Missing ${leg.network}/${leg.protocol}: ${leg.error_class}`);
+      }
 
       const queryKey = `${queryPlan.target_networks.join(",")}:${queryPlan.target_protocols.join(",")}`;
       context = updateContext(context, {
@@ -375,6 +430,8 @@ async function runBidirectionalTradingLoop(config: TradingConfig): Promise<void>
           risk_params: config.risk,
           query_plan: queryPlan,
           has_more_data: pools.length < queryPlan.expected_data_points,
+          success_rate: outcome.success_rate,
+          failed_legs: outcome.failed_legs,
         },
       });
 
@@ -449,6 +506,49 @@ async function gatherPoolData(config: TradingConfig): Promise<PoolData[]> {
   return pools;
 }
 
+/**
+ * Gather trailing 24h WETH swap flow metrics on each configured network
+ */
+async function gatherFlowMetrics(config: TradingConfig): Promise<FlowMetrics[]> {
+  const flows: FlowMetrics[] = [];
+
+  for (const network of config.networks) {
+    const tokens = (TOKENS as Record<string, { WETH: string }>)[network];
+    if (!tokens) continue;
+
+    try {
+      const result = await invokeTool("defi/query_subgraph", {
+        protocol: "uniswap_v3",
+        network,
+        query_type: "swaps",
+        params: { token_address: tokens.WETH },
+      });
+
+      const metrics = result.metrics;
+      if (metrics) {
+        // Truncated results cover only the newest swaps
+        const since = result.truncated ? result.oldest_timestamp : result.start_time;
+        flows.push({
+          network,
+          token: "WETH",
+          swap_count: metrics.event_count,
+          window_hours: (result.end_time - (since ?? result.start_time)) / 3600,
+          volume_usd: metrics.volume_usd,
+          net_flow_usd: metrics.net_flow_usd,
+          flow_direction: metrics.flow_direction,
+          large_trade_count: metrics.large_trade_count,
+          unique_traders: metrics.unique_traders,
+          annualized_volatility: metrics.annualized_volatility ?? null,
+        });
+      }
+    } catch (error) {
+      console.error(`Failed to query swap flow on ${network}:`, error);
+    }
+  }
+
+  return flows;
+}
+
 /**
  * Get current market conditions with real-time ETH price from Odos
  */
@@ -643,6 +743,7 @@ function estimateUsdValue(symbol: string, amount: number): number {
  */
 async function inferStrategy(
   pools: PoolData[],
+  flows: FlowMetrics[],
   market: MarketConditions,
   positions: Position[],
   risk: RiskParameters
@@ -651,6 +752,7 @@ async function inferStrategy(
   const result = await InferStrategy({
     input: {
       pools,
+      flows,
       market,
       positions,
       risk_params: risk,
@@ -698,7 +800,15 @@ async function executeAction(action: TradingAction, config: TradingConfig): Prom
         console.log("Preparing swap transaction...");
         const prepared = await prepareSwap(action, quote);
         console.log(`Swap prepared: ${prepared.status}`);
-        // Actual signing/submission happens in Rust after interceptor approval
+        if (config.execute_swaps) {
+          // Signing and submission happen in Rust after interceptor approval
+          try {
+            const executed = await executeSwap(prepared);
+            console.log(`Swap executed: ${executed.status} (${executed.tx_hash})`);
+          } catch (error) {
+            console.error("Swap execution failed:", error);
+          }
+        }
       } else {
         console.log(`Trade skipped: ${analysis.reasoning}`);
         if (analysis.concerns.length > 0) {
@@ -768,6 +878,56 @@ async function prepareSwap(action: any, quote: any) {
   return result;
 }
 
+/**
+ * `code` of an interceptor's structured (JSON) block reason, if it has one
+ */
+function blockCode(error: unknown): string | undefined {
+  const message = error instanceof Error ? error.message : String(error);
+  const start = message.indexOf("{");
+  if (start < 0) {
+    return undefined;
+  }
+  try {
+    const reason = JSON.parse(message.slice(start));
+    return typeof reason?.code === "string" ? reason.code : undefined;
+  } catch {
+    return undefined;
+  }
+}
+
+/**
+ * Re-quote a prepared swap whose quote went stale
+ *
+ * Rust prepares the recorded trade again and replaces it, so this is neither
+ * gated by the trade cooldown nor charged against the daily limit again.
+ */
+async function requoteSwap(prepared: any) {
+  return await invokeTool("defi/odos_swap", {
+    action: "requote",
+    transaction: prepared.transaction,
+  });
+}
+
+/**
+ * Execute a prepared swap, re-quoting it whenever the quote freshness guard
+ * finds it stale
+ */
+async function executeSwap(prepared: any) {
+  for (let attempt = 0; ; attempt++) {
+    try {
+      return await invokeTool("defi/execute_swap", {
+        transaction: prepared.transaction,
+      });
+    } catch (error) {
+      if (attempt >= MAX_REQUOTES || blockCode(error) !== STALE_QUOTE_CODE) {
+        throw error;
+      }
+      console.log("Quote went stale before execution, re-quoting...");
+      prepared = await requoteSwap(prepared);
+    }
+  }
+}
+
 /**
  * Sleep helper
  */
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PreparedSwapTransaction } from "./PreparedSwapTransaction";
+
+export type ExecuteSwapInput = { 
+/**
+ * The `transaction` object from a `prepare_swap` result
+ */
+transaction: PreparedSwapTransaction, 
+/**
+ * Wait for the receipt (default: true)
+ */
+wait_for_receipt: boolean | null, };
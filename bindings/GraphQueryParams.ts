@@ -2,4 +2,44 @@
 import type { QueryFilters } from "./QueryFilters";
 import type { QueryPlan } from "./QueryPlan";
 
-export type GraphQueryParams = { limit: number | null, pool_id: string | null, token_address: string | null, filters: QueryFilters | null, query_plan: QueryPlan | null, };
+export type GraphQueryParams = { limit: number | null, pool_id: string | null, token_address: string | null, filters: QueryFilters | null, query_plan: QueryPlan | null, user_address: string | null, 
+/**
+ * Start of the time range (unix seconds, inclusive)
+ */
+start_time: bigint | null, 
+/**
+ * End of the time range (unix seconds, inclusive; defaults to now)
+ */
+end_time: bigint | null, 
+/**
+ * Pin all queries to this block number (time-travel query)
+ */
+block_number: bigint | null, 
+/**
+ * Pin all queries to this block hash (mutually exclusive with `block_number`)
+ */
+block_hash: string | null, 
+/**
+ * For query plans: pin every query on a network to one block and report it
+ */
+snapshot: boolean | null, 
+/**
+ * For swap/mint/burn queries: USD size at which an event counts as large
+ */
+large_trade_usd: number | null, 
+/**
+ * For tick queries: tick spacings fetched either side of the current tick
+ */
+tick_spacings: number | null, 
+/**
+ * For tick queries: input size to simulate, in whole tokens of `token_address`
+ */
+amount_in: number | null, 
+/**
+ * For custom queries: GraphQL query text (read-only, single operation)
+ */
+query: string | null, 
+/**
+ * For custom queries: variables for `query`
+ */
+variables: any | null, };
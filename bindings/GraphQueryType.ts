@@ -1,3 +1,45 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type GraphQueryType = "top_pools" | "pool_info" | "token_price" | "filtered_pools" | "query_plan";
+export type GraphQueryType = "top_pools" | "pool_info" | "token_price" | "filtered_pools" | "query_plan" | 
+/**
+ * Aave V3 reserves (supply/borrow APY, utilization, liquidity)
+ */
+"reserves" | 
+/**
+ * Aave V3 positions and health factor for `user_address`
+ */
+"user_positions" | 
+/**
+ * Daily pool snapshots for `pool_id` between `start_time` and `end_time`
+ */
+"pool_day_data" | 
+/**
+ * Hourly pool snapshots for `pool_id` between `start_time` and `end_time`
+ */
+"pool_hour_data" | 
+/**
+ * Daily token snapshots for `token_address` between `start_time` and `end_time`
+ */
+"token_day_data" | 
+/**
+ * Swaps on `pool_id` or `token_address` between `start_time` and `end_time`, with flow metrics
+ */
+"swaps" | 
+/**
+ * Liquidity additions on `pool_id` or `token_address`, with flow metrics
+ */
+"mints" | 
+/**
+ * Liquidity removals on `pool_id` or `token_address`, with flow metrics
+ */
+"burns" | 
+/**
+ * Initialized ticks around the current tick of `pool_id`, with a depth curve
+ * and an optional swap estimate for `amount_in` of `token_address`
+ */
+"ticks" | 
+/**
+ * Agent-authored `query` (with `variables`), checked against the subgraph
+ * schema from `defi/subgraph_schema` and the query budget before it runs
+ */
+"custom";
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Impermanent loss after a move in the pool price (token0 in token1)
+ */
+export type IlScenario = { price_move_percent: number, 
+/**
+ * Loss versus holding the deposited tokens (non-positive)
+ */
+impermanent_loss_percent: number, };
\ No newline at end of file
@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type OdosAction = "quote" | "prepare_swap" | "get_price" | "get_prices";
+export type OdosAction = "quote" | "prepare_swap" | "requote" | "get_price" | "get_prices";
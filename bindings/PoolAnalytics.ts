@@ -0,0 +1,43 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { IlScenario } from "./IlScenario";
+
+/**
+ * Derived metrics for one pool
+ */
+export type PoolAnalytics = { 
+/**
+ * Position by volume/TVL, 1 = most volume per dollar of liquidity
+ */
+rank: number, pool_id: string, 
+/**
+ * Token symbols, e.g. "WETH/USDC"
+ */
+pair: string, 
+/**
+ * Fee in hundredths of a bip (3000 = 0.3%)
+ */
+fee_tier: number, 
+/**
+ * Current TVL
+ */
+tvl_usd: number, avg_daily_volume_usd: number | null, 
+/**
+ * Average daily volume over average TVL
+ */
+volume_tvl_ratio: number | null, 
+/**
+ * Annualized fees to LPs over average TVL, without compounding
+ */
+fee_apr_percent: number | null, 
+/**
+ * Annualized realized volatility of the daily pool price
+ */
+volatility_percent: number | null, 
+/**
+ * Impermanent loss after a one standard deviation move over the lookback
+ */
+il_one_sigma_percent: number | null, 
+/**
+ * Daily snapshots the metrics are based on
+ */
+days_sampled: number, impermanent_loss: Array<IlScenario>, };
\ No newline at end of file
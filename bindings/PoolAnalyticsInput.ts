@@ -0,0 +1,23 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type PoolAnalyticsInput = { network: string, 
+/**
+ * Pools to analyze (defaults to the top pools by TVL)
+ */
+pool_ids: Array<string> | null, 
+/**
+ * Number of top pools to analyze when `pool_ids` is not set
+ */
+limit: number | null, 
+/**
+ * Lookback window in days for fees and volatility
+ */
+days: number | null, 
+/**
+ * Lower edge of the LP range, percent below the current price (full range if unset)
+ */
+range_lower_percent: number | null, 
+/**
+ * Upper edge of the LP range, percent above the current price (full range if unset)
+ */
+range_upper_percent: number | null, };
\ No newline at end of file
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PoolAnalytics } from "./PoolAnalytics";
+
+export type PoolAnalyticsOutput = { network: string, days: number, 
+/**
+ * LP range the IL figures assume, as percent below/above the current price
+ */
+range_lower_percent: number | null, range_upper_percent: number | null, 
+/**
+ * Pools ordered by rank
+ */
+pools: Array<PoolAnalytics>, };
\ No newline at end of file
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Transaction fields as returned by `prepare_swap`
+ */
+export type PreparedSwapTransaction = { to: string, data: string, value: string, gas_limit: bigint | null, chain_id: bigint, };
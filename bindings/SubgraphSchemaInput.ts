@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SubgraphSchemaInput = { protocol: string, network: string, 
+/**
+ * Only these entity types and the queries returning them (all if unset)
+ */
+entities: Array<string> | null, };
\ No newline at end of file
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Unsigned EIP-1559 transaction fields
+ */
+export type UnsignedTxInput = { chain_id: bigint, nonce: bigint, to: string, data: string, 
+/**
+ * Native value in wei (decimal string)
+ */
+value: string, gas_limit: bigint, 
+/**
+ * Max fee per gas in wei (decimal string)
+ */
+max_fee_per_gas: string, 
+/**
+ * Max priority fee per gas in wei (decimal string)
+ */
+max_priority_fee_per_gas: string, };
@@ -1,3 +1,16 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { UnsignedTxInput } from "./UnsignedTxInput";
 
-export type WalletSignTxInput = { tx_hash: string | null, tx_bytes: string | null, };
+export type WalletSignTxInput = { 
+/**
+ * Structured unsigned transaction (preferred)
+ */
+transaction: UnsignedTxInput | null, 
+/**
+ * Unsigned EIP-1559 transaction bytes (`0x02 || rlp(fields)`)
+ */
+tx_bytes: string | null, 
+/**
+ * Raw 32-byte hash (blind signing; disabled unless enabled in config)
+ */
+tx_hash: string | null, };
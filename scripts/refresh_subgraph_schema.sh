@@ -0,0 +1,60 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+# Re-vendor the Uniswap V3 subgraph schema from the deployment's introspection.
+#
+# Usage: GRAPH_API_KEY=... ./scripts/refresh_subgraph_schema.sh [subgraph-id]
+#
+# Needs curl and python3 with graphql-core (`pip install graphql-core`).
+
+ROOT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")/.." && pwd)"
+SCHEMA_PATH="${ROOT_DIR}/src/graphql/uniswap_v3/schema.graphql"
+SUBGRAPH_ID="${1:-5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV}"
+GATEWAY_URL="${GRAPH_GATEWAY_URL:-https://gateway.thegraph.com/api}"
+
+if [[ -z "${GRAPH_API_KEY:-}" ]]; then
+  echo "GRAPH_API_KEY must be set" >&2
+  exit 1
+fi
+
+if ! python3 -c "import graphql" 2>/dev/null; then
+  echo "python3 with graphql-core is required (pip install graphql-core)" >&2
+  exit 1
+fi
+
+INTROSPECTION="$(mktemp)"
+trap 'rm -f "${INTROSPECTION}"' EXIT
+
+echo "Introspecting subgraph ${SUBGRAPH_ID}..."
+python3 -c '
+import json
+from graphql import get_introspection_query
+print(json.dumps({"query": get_introspection_query(descriptions=True)}))
+' | curl -sSf \
+  -H "Content-Type: application/json" \
+  -H "Authorization: Bearer ${GRAPH_API_KEY}" \
+  --data-binary @- \
+  "${GATEWAY_URL}/subgraphs/id/${SUBGRAPH_ID}" >"${INTROSPECTION}"
+
+python3 - "${INTROSPECTION}" "${SCHEMA_PATH}" <<'EOF'
+import json
+import sys
+from graphql import build_client_schema, print_schema
+
+response = json.load(open(sys.argv[1]))
+if response.get("errors"):
+    sys.exit(f"Introspection failed: {response['errors']}")
+
+header = """# Uniswap V3 subgraph API schema
+#
+# Introspected from the deployment the agent queries by default. Do not edit by
+# hand: run scripts/refresh_subgraph_schema.sh after a subgraph upgrade, and
+# the typed queries in this directory will fail to build on any drift.
+"""
+schema = print_schema(build_client_schema(response["data"]))
+with open(sys.argv[2], "w") as out:
+    out.write(header + "\n" + schema + "\n")
+EOF
+
+echo "Wrote ${SCHEMA_PATH}"
+echo "Run cargo check to see which typed queries no longer match."
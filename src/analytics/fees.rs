@@ -0,0 +1,86 @@
+//! Fee yield and activity from daily pool snapshots
+
+use crate::graphql::uniswap_v3::PoolSnapshot;
+
+/// Fee tier denominator (fees are in hundredths of a bip)
+const FEE_DENOMINATOR: f64 = 1_000_000.0;
+
+/// Days in a year, for annualizing
+const DAYS_PER_YEAR: f64 = 365.0;
+
+/// Average daily volume and TVL over a set of daily snapshots
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DailyActivity {
+    pub days: usize,
+    pub avg_volume_usd: f64,
+    pub avg_tvl_usd: f64,
+}
+
+impl DailyActivity {
+    /// Average the snapshots (None when there are none)
+    pub fn from_snapshots(days: &[PoolSnapshot]) -> Option<Self> {
+        if days.is_empty() {
+            return None;
+        }
+        let n = days.len() as f64;
+        Some(Self {
+            days: days.len(),
+            avg_volume_usd: days.iter().map(|d| d.volume_usd).sum::<f64>() / n,
+            avg_tvl_usd: days.iter().map(|d| d.tvl_usd).sum::<f64>() / n,
+        })
+    }
+
+    /// Average daily volume / average TVL
+    pub fn volume_tvl_ratio(&self) -> Option<f64> {
+        (self.avg_tvl_usd > 0.0).then(|| self.avg_volume_usd / self.avg_tvl_usd)
+    }
+
+    /// Annualized fee yield to liquidity providers (no compounding)
+    ///
+    /// Fees are derived from volume and the fee tier rather than the
+    /// subgraph's `feesUSD`, which some deployments misreport.
+    pub fn fee_apr(&self, fee_tier: u32) -> Option<f64> {
+        let fee_rate = f64::from(fee_tier) / FEE_DENOMINATOR;
+        self.volume_tvl_ratio()
+            .map(|ratio| ratio * fee_rate * DAYS_PER_YEAR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(volume_usd: f64, tvl_usd: f64) -> PoolSnapshot {
+        PoolSnapshot {
+            timestamp: 0,
+            volume_usd,
+            fees_usd: 0.0,
+            tvl_usd,
+            liquidity: 0.0,
+            token0_price: 1.0,
+            token1_price: 1.0,
+            open: 1.0,
+            high: 1.0,
+            low: 1.0,
+            close: 1.0,
+            tx_count: 0,
+        }
+    }
+
+    #[test]
+    fn test_fee_apr_from_volume_and_tier() {
+        // $1M/day on $10M TVL at 0.3%: 0.1 * 0.003 * 365 = 10.95%
+        let days = [day(500_000.0, 9_000_000.0), day(1_500_000.0, 11_000_000.0)];
+        let activity = DailyActivity::from_snapshots(&days).unwrap();
+        assert_eq!(activity.avg_volume_usd, 1_000_000.0);
+        assert!((activity.volume_tvl_ratio().unwrap() - 0.1).abs() < 1e-12);
+        assert!((activity.fee_apr(3000).unwrap() - 0.1095).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_no_fee_apr_without_tvl() {
+        assert!(DailyActivity::from_snapshots(&[]).is_none());
+        let activity = DailyActivity::from_snapshots(&[day(100.0, 0.0)]).unwrap();
+        assert_eq!(activity.fee_apr(500), None);
+    }
+}
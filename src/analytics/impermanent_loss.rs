@@ -0,0 +1,99 @@
+//! Impermanent loss for full-range and concentrated positions
+//!
+//! Compares a position's value after a price move with simply holding the
+//! tokens it started with. Prices are token0 in token1 and normalized to the
+//! entry price, so a range is given as multiples of it (e.g. 0.8..1.25).
+
+/// Price range of a position, as multiples of the entry price
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceRange {
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl PriceRange {
+    /// Full-range (Uniswap V2 style) position
+    pub const FULL: PriceRange = PriceRange {
+        lower: 0.0,
+        upper: f64::INFINITY,
+    };
+
+    /// Range from percentages below and above the entry price
+    pub fn from_percent(below: f64, above: f64) -> Self {
+        Self {
+            lower: (1.0 - below / 100.0).max(0.0),
+            upper: 1.0 + above / 100.0,
+        }
+    }
+
+    /// Whether the entry price lies strictly inside the range
+    pub fn contains_entry(&self) -> bool {
+        self.lower < 1.0 && self.upper > 1.0
+    }
+}
+
+/// Impermanent loss as a (non-positive) fraction of the hold value
+///
+/// # Arguments
+/// * `price_ratio` - Price after the move divided by the entry price
+/// * `range` - Position range as multiples of the entry price
+///
+/// Returns None if the entry price is outside the range or the ratio is not
+/// a positive number.
+pub fn impermanent_loss(price_ratio: f64, range: PriceRange) -> Option<f64> {
+    if !range.contains_entry() || !(price_ratio.is_finite() && price_ratio > 0.0) {
+        return None;
+    }
+    let sqrt_lower = range.lower.sqrt();
+    let sqrt_upper = range.upper.sqrt();
+
+    // Token amounts per unit of liquidity at a sqrt price
+    let amounts = |sqrt_price: f64| {
+        let sp = sqrt_price.clamp(sqrt_lower, sqrt_upper);
+        (1.0 / sp - 1.0 / sqrt_upper, sp - sqrt_lower)
+    };
+
+    let (x0, y0) = amounts(1.0);
+    let (x1, y1) = amounts(price_ratio.sqrt());
+    let hold = x0 * price_ratio + y0;
+    let position = x1 * price_ratio + y1;
+    Some(position / hold - 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_range_matches_closed_form() {
+        for r in [0.25, 0.5, 1.0, 2.0, 4.0] {
+            let expected = 2.0 * f64::sqrt(r) / (1.0 + r) - 1.0;
+            let il = impermanent_loss(r, PriceRange::FULL).unwrap();
+            assert!((il - expected).abs() < 1e-12, "r={}", r);
+        }
+        // 2x move loses ~5.72%
+        let il = impermanent_loss(2.0, PriceRange::FULL).unwrap();
+        assert!((il + 0.057191).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_concentrated_range_amplifies_loss() {
+        let narrow = PriceRange::from_percent(10.0, 10.0);
+        let full = impermanent_loss(1.05, PriceRange::FULL).unwrap();
+        let concentrated = impermanent_loss(1.05, narrow).unwrap();
+        assert!(concentrated < full);
+        assert_eq!(impermanent_loss(1.0, narrow), Some(0.0));
+    }
+
+    #[test]
+    fn test_loss_keeps_growing_beyond_range() {
+        let range = PriceRange::from_percent(10.0, 10.0);
+        // Above the range the position is all token1 and the loss vs hold
+        // keeps growing only through the hold leg
+        let at_edge = impermanent_loss(1.1, range).unwrap();
+        let beyond = impermanent_loss(1.5, range).unwrap();
+        assert!(beyond < at_edge);
+        assert!(impermanent_loss(1.0, PriceRange::from_percent(0.0, 10.0)).is_none());
+        assert!(impermanent_loss(0.0, range).is_none());
+    }
+}
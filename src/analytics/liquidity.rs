@@ -0,0 +1,411 @@
+//! Uniswap V3 concentrated-liquidity swap math
+//!
+//! Reproduces the pool's swap loop over a window of initialized ticks: within
+//! a tick range liquidity is constant and the price moves along
+//! `x = L / sqrtP`, `y = L * sqrtP`; crossing an initialized tick adds or
+//! removes that tick's `liquidityNet`.
+//!
+//! Everything is in raw token units and `f64`. The results are estimates to
+//! sanity-check aggregator quotes, not exact on-chain amounts (the pool uses
+//! Q64.96 fixed point and rounds each step).
+
+use serde::Serialize;
+
+/// 2^96, the fixed-point scale of `sqrtPriceX96`
+pub const Q96: f64 = 79_228_162_514_264_337_593_543_950_336.0;
+
+/// Fee tier denominator (fees are in hundredths of a bip)
+const FEE_DENOMINATOR: f64 = 1_000_000.0;
+
+/// Tick spacing for a fee tier, as enabled by the Uniswap V3 factory
+pub fn tick_spacing(fee_tier: u32) -> Option<i32> {
+    match fee_tier {
+        100 => Some(1),
+        500 => Some(10),
+        3000 => Some(60),
+        10000 => Some(200),
+        _ => None,
+    }
+}
+
+/// sqrt(price) at a tick, unscaled (`sqrt(1.0001^tick)`)
+pub fn sqrt_price_at_tick(tick: i32) -> f64 {
+    1.0001_f64.powf(f64::from(tick) / 2.0)
+}
+
+/// Liquidity change at an initialized tick
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TickLiquidity {
+    pub tick: i32,
+    /// Liquidity added when crossing the tick left to right (price rising)
+    pub liquidity_net: f64,
+    /// Total liquidity referencing the tick
+    pub liquidity_gross: f64,
+}
+
+/// Pool state plus the initialized ticks within a window around the current tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiquidityCurve {
+    /// Current sqrt(price), unscaled
+    sqrt_price: f64,
+    tick: i32,
+    /// In-range liquidity
+    liquidity: f64,
+    /// Fee in hundredths of a bip
+    fee_tier: u32,
+    /// Initialized ticks, ascending
+    ticks: Vec<TickLiquidity>,
+    /// Window the ticks were fetched for; liquidity beyond it is unknown
+    tick_lower: i32,
+    tick_upper: i32,
+}
+
+/// Estimated outcome of an exact-input swap
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SwapEstimate {
+    pub amount_in: f64,
+    /// Input actually consumed (less than `amount_in` when `exhausted`)
+    pub amount_in_filled: f64,
+    pub amount_out: f64,
+    /// Output per unit of filled input, after fees
+    pub execution_price: f64,
+    /// Output per unit of input at the current pool price
+    pub spot_price: f64,
+    /// Shortfall of the execution price against spot (includes the fee)
+    pub price_impact_percent: f64,
+    /// Move in the pool price caused by the swap
+    pub price_move_percent: f64,
+    pub ticks_crossed: u32,
+    /// Ran past the fetched tick window before filling the input
+    pub exhausted: bool,
+}
+
+impl SwapEstimate {
+    /// Convert amounts and prices from raw units to whole tokens
+    pub fn to_whole_units(self, decimals_in: u32, decimals_out: u32) -> Self {
+        let scale_in = 10_f64.powi(decimals_in as i32);
+        let scale_out = 10_f64.powi(decimals_out as i32);
+        Self {
+            amount_in: self.amount_in / scale_in,
+            amount_in_filled: self.amount_in_filled / scale_in,
+            amount_out: self.amount_out / scale_out,
+            execution_price: self.execution_price * scale_in / scale_out,
+            spot_price: self.spot_price * scale_in / scale_out,
+            ..self
+        }
+    }
+}
+
+/// Input needed to move the pool price by a given amount
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct DepthPoint {
+    pub price_move_percent: f64,
+    /// Input required including fees (None if the move is beyond the tick window)
+    pub amount_in: Option<f64>,
+}
+
+/// Result of walking the tick ranges in one direction
+struct Walk {
+    /// Input consumed, net of fees
+    amount_in: f64,
+    amount_out: f64,
+    sqrt_price: f64,
+    ticks_crossed: u32,
+    /// Stopped at the tick window boundary
+    exhausted: bool,
+}
+
+impl LiquidityCurve {
+    /// Build a curve from pool state and the initialized ticks in `[tick_lower, tick_upper]`
+    ///
+    /// # Arguments
+    /// * `sqrt_price_x96` - Pool `sqrtPrice` (Q64.96)
+    /// * `tick` - Pool current tick
+    /// * `liquidity` - Pool in-range liquidity
+    /// * `fee_tier` - Fee in hundredths of a bip (3000 = 0.3%)
+    /// * `ticks` - Initialized ticks in the window, in any order
+    pub fn new(
+        sqrt_price_x96: f64,
+        tick: i32,
+        liquidity: f64,
+        fee_tier: u32,
+        mut ticks: Vec<TickLiquidity>,
+        tick_lower: i32,
+        tick_upper: i32,
+    ) -> Self {
+        ticks.sort_by_key(|t| t.tick);
+        Self {
+            sqrt_price: sqrt_price_x96 / Q96,
+            tick,
+            liquidity,
+            fee_tier,
+            ticks,
+            tick_lower,
+            tick_upper,
+        }
+    }
+
+    /// Current price of token0 in token1 (raw units)
+    pub fn spot_price(&self) -> f64 {
+        self.sqrt_price * self.sqrt_price
+    }
+
+    /// Estimate an exact-input swap
+    ///
+    /// `zero_for_one` sells token0 for token1 (price falls); otherwise token1
+    /// is sold for token0 (price rises).
+    pub fn estimate_exact_input(&self, amount_in: f64, zero_for_one: bool) -> SwapEstimate {
+        let fee_factor = 1.0 - f64::from(self.fee_tier) / FEE_DENOMINATOR;
+        let limit = if zero_for_one { 0.0 } else { f64::INFINITY };
+        let walk = self.walk(zero_for_one, amount_in * fee_factor, limit);
+
+        let amount_in_filled = walk.amount_in / fee_factor;
+        let spot_price = self.price_out_per_in(self.sqrt_price, zero_for_one);
+        let execution_price = if amount_in_filled > 0.0 {
+            walk.amount_out / amount_in_filled
+        } else {
+            spot_price
+        };
+        let price_after = self.price_out_per_in(walk.sqrt_price, zero_for_one);
+
+        SwapEstimate {
+            amount_in,
+            amount_in_filled,
+            amount_out: walk.amount_out,
+            execution_price,
+            spot_price,
+            price_impact_percent: (1.0 - execution_price / spot_price) * 100.0,
+            price_move_percent: (1.0 - price_after / spot_price) * 100.0,
+            ticks_crossed: walk.ticks_crossed,
+            exhausted: walk.exhausted,
+        }
+    }
+
+    /// Input needed to move the pool price by each of `moves_percent`
+    ///
+    /// The move is measured on the token0 price: selling token0 moves it
+    /// down, selling token1 moves it up.
+    pub fn depth(&self, zero_for_one: bool, moves_percent: &[f64]) -> Vec<DepthPoint> {
+        let fee_factor = 1.0 - f64::from(self.fee_tier) / FEE_DENOMINATOR;
+        moves_percent
+            .iter()
+            .map(|&price_move_percent| {
+                let factor = if zero_for_one {
+                    1.0 - price_move_percent / 100.0
+                } else {
+                    1.0 + price_move_percent / 100.0
+                };
+                let target = self.sqrt_price * factor.max(0.0).sqrt();
+                let walk = self.walk(zero_for_one, f64::INFINITY, target);
+                DepthPoint {
+                    price_move_percent,
+                    amount_in: (!walk.exhausted).then(|| walk.amount_in / fee_factor),
+                }
+            })
+            .collect()
+    }
+
+    /// Price of the input token in the output token at a given sqrt(price)
+    fn price_out_per_in(&self, sqrt_price: f64, zero_for_one: bool) -> f64 {
+        let price = sqrt_price * sqrt_price;
+        if zero_for_one {
+            price
+        } else {
+            1.0 / price
+        }
+    }
+
+    /// Swap up to `max_in` (net of fees), stopping at `sqrt_price_limit`
+    fn walk(&self, zero_for_one: bool, max_in: f64, sqrt_price_limit: f64) -> Walk {
+        let current = self.tick;
+        let mut next_ticks: Box<dyn Iterator<Item = &TickLiquidity>> = if zero_for_one {
+            Box::new(self.ticks.iter().rev().filter(move |t| t.tick <= current))
+        } else {
+            Box::new(self.ticks.iter().filter(move |t| t.tick > current))
+        };
+        let boundary = sqrt_price_at_tick(if zero_for_one {
+            self.tick_lower
+        } else {
+            self.tick_upper
+        });
+
+        let mut sqrt_price = self.sqrt_price;
+        let mut liquidity = self.liquidity;
+        let mut filled = 0.0;
+        let mut amount_out = 0.0;
+        let mut ticks_crossed = 0;
+
+        loop {
+            let next = next_ticks.next();
+            let next_sqrt = next.map_or(boundary, |t| sqrt_price_at_tick(t.tick));
+            let limit_first = if zero_for_one {
+                sqrt_price_limit >= next_sqrt
+            } else {
+                sqrt_price_limit <= next_sqrt
+            };
+            let target = if limit_first {
+                sqrt_price_limit
+            } else {
+                next_sqrt
+            };
+
+            if liquidity > 0.0 {
+                let step_in = if zero_for_one {
+                    liquidity * (1.0 / target - 1.0 / sqrt_price)
+                } else {
+                    liquidity * (target - sqrt_price)
+                };
+                let remaining = max_in - filled;
+                if remaining < step_in {
+                    // Input runs out inside this range. Output is written in
+                    // terms of the input so small steps don't cancel out.
+                    let end = if zero_for_one {
+                        1.0 / (1.0 / sqrt_price + remaining / liquidity)
+                    } else {
+                        sqrt_price + remaining / liquidity
+                    };
+                    amount_out += if zero_for_one {
+                        remaining * sqrt_price * end
+                    } else {
+                        remaining / (sqrt_price * end)
+                    };
+                    sqrt_price = end;
+                    filled = max_in;
+                    break;
+                }
+                filled += step_in.max(0.0);
+                amount_out += Self::step_out(liquidity, sqrt_price, target, zero_for_one);
+            }
+            sqrt_price = target;
+
+            if limit_first {
+                break;
+            }
+            match next {
+                Some(t) => {
+                    liquidity += if zero_for_one {
+                        -t.liquidity_net
+                    } else {
+                        t.liquidity_net
+                    };
+                    liquidity = liquidity.max(0.0);
+                    ticks_crossed += 1;
+                }
+                None => {
+                    return Walk {
+                        amount_in: filled,
+                        amount_out,
+                        sqrt_price,
+                        ticks_crossed,
+                        exhausted: true,
+                    }
+                }
+            }
+        }
+
+        Walk {
+            amount_in: filled,
+            amount_out,
+            sqrt_price,
+            ticks_crossed,
+            exhausted: false,
+        }
+    }
+
+    /// Output of moving the price between two points at constant liquidity
+    fn step_out(liquidity: f64, from: f64, to: f64, zero_for_one: bool) -> f64 {
+        if zero_for_one {
+            liquidity * (from - to)
+        } else {
+            liquidity * (1.0 / from - 1.0 / to)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Full-range-like position: constant liquidity across a wide window
+    fn flat_curve(liquidity: f64, fee_tier: u32) -> LiquidityCurve {
+        LiquidityCurve::new(Q96, 0, liquidity, fee_tier, Vec::new(), -100_000, 100_000)
+    }
+
+    #[test]
+    fn test_whole_units_preserve_impact() {
+        let raw = flat_curve(1e18, 3000).estimate_exact_input(1e15, true);
+        let whole = raw.clone().to_whole_units(18, 6);
+        assert_eq!(whole.amount_in, 0.001);
+        assert!((whole.amount_out - raw.amount_out / 1e6).abs() < 1e-12);
+        assert!((whole.spot_price - 1e12).abs() < 1e-3);
+        assert_eq!(whole.price_impact_percent, raw.price_impact_percent);
+    }
+
+    #[test]
+    fn test_tick_spacing() {
+        assert_eq!(tick_spacing(500), Some(10));
+        assert_eq!(tick_spacing(3000), Some(60));
+        assert_eq!(tick_spacing(2500), None);
+    }
+
+    #[test]
+    fn test_small_swap_matches_constant_product() {
+        // At price 1 with liquidity L, reserves are x = y = L
+        let curve = flat_curve(1e18, 0);
+        let estimate = curve.estimate_exact_input(1e15, true);
+        let expected = 1e18 * 1e15 / (1e18 + 1e15);
+        assert!((estimate.amount_out - expected).abs() / expected < 1e-9);
+        assert!(estimate.price_impact_percent > 0.0 && estimate.price_impact_percent < 0.2);
+        assert!(!estimate.exhausted);
+    }
+
+    #[test]
+    fn test_fee_is_part_of_price_impact() {
+        // Negligible size against deep liquidity: impact is just the fee
+        let curve = flat_curve(1e24, 3000);
+        let estimate = curve.estimate_exact_input(1e12, false);
+        assert!((estimate.price_impact_percent - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_crossing_tick_changes_liquidity() {
+        // Liquidity drops to a tenth below tick -100
+        let ticks = vec![TickLiquidity {
+            tick: -100,
+            liquidity_net: 9e18,
+            liquidity_gross: 9e18,
+        }];
+        let thin = LiquidityCurve::new(Q96, 0, 1e19, 0, ticks, -10_000, 10_000);
+        let deep = flat_curve(1e19, 0);
+
+        let amount = 2e17;
+        let through = thin.estimate_exact_input(amount, true);
+        let flat = deep.estimate_exact_input(amount, true);
+        assert_eq!(through.ticks_crossed, 1);
+        assert!(through.amount_out < flat.amount_out);
+        assert!(through.price_move_percent > flat.price_move_percent);
+    }
+
+    #[test]
+    fn test_swap_past_window_is_exhausted() {
+        let curve = LiquidityCurve::new(Q96, 0, 1e18, 0, Vec::new(), -10, 10);
+        let estimate = curve.estimate_exact_input(1e18, true);
+        assert!(estimate.exhausted);
+        assert!(estimate.amount_in_filled < 1e18);
+        // The fill stops at the window edge
+        let edge = 1e18 * (1.0 / sqrt_price_at_tick(-10) - 1.0);
+        assert!((estimate.amount_in_filled - edge).abs() / edge < 1e-9);
+    }
+
+    #[test]
+    fn test_depth_round_trips_through_estimate() {
+        let curve = flat_curve(1e18, 500);
+        let depth = curve.depth(true, &[1.0, 50.0, 99.99999]);
+        let one_percent = depth[0].amount_in.unwrap();
+        let estimate = curve.estimate_exact_input(one_percent, true);
+        assert!((estimate.price_move_percent - 1.0).abs() < 1e-6);
+        assert!(depth[1].amount_in.unwrap() > one_percent);
+        // Beyond the fetched window
+        assert_eq!(depth[2].amount_in, None);
+    }
+}
@@ -0,0 +1,22 @@
+//! Deterministic pool analytics
+//!
+//! Numbers derived in Rust from subgraph data, so the strategist reasons over
+//! computed values instead of doing arithmetic on raw JSON strings.
+//!
+//! - [`liquidity`] - Uniswap V3 concentrated-liquidity swap math (depth and
+//!   price impact from initialized ticks)
+//! - [`fees`] - Fee APR and volume/TVL from daily snapshots
+//! - [`volatility`] - Realized volatility from price series
+//! - [`impermanent_loss`] - Impermanent loss for full and concentrated ranges
+
+pub mod fees;
+pub mod impermanent_loss;
+pub mod liquidity;
+pub mod volatility;
+
+pub use fees::DailyActivity;
+pub use impermanent_loss::{impermanent_loss, PriceRange};
+
+pub use liquidity::{
+    sqrt_price_at_tick, tick_spacing, DepthPoint, LiquidityCurve, SwapEstimate, TickLiquidity,
+};
@@ -0,0 +1,96 @@
+//! Realized volatility from price series
+
+/// Seconds in a 365-day year
+pub const SECONDS_PER_YEAR: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+
+/// Seconds in a day
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Realized volatility (root sum of squared log returns) of a price series
+///
+/// Non-positive prices are skipped. Returns None for fewer than two returns.
+pub fn realized_volatility(prices: &[f64]) -> Option<f64> {
+    let prices: Vec<f64> = prices
+        .iter()
+        .copied()
+        .filter(|p| p.is_finite() && *p > 0.0)
+        .collect();
+    if prices.len() < 3 {
+        return None;
+    }
+    let sum_sq: f64 = prices.windows(2).map(|w| (w[1] / w[0]).ln().powi(2)).sum();
+    Some(sum_sq.sqrt())
+}
+
+/// Scale volatility realized over `window_secs` to one year
+pub fn annualize(realized: f64, window_secs: i64) -> Option<f64> {
+    (window_secs > 0).then(|| realized * (SECONDS_PER_YEAR / window_secs as f64).sqrt())
+}
+
+/// Annualized volatility from daily prices, given as `(day start, price)`
+///
+/// Returns are taken only between consecutive days that both have a positive
+/// price, so a missing or unpriced day never stretches one return across
+/// several days. Returns None for fewer than two such returns.
+pub fn annualized_daily_volatility(daily_prices: &[(i64, f64)]) -> Option<f64> {
+    let valid = |p: f64| p.is_finite() && p > 0.0;
+    let returns: Vec<f64> = daily_prices
+        .windows(2)
+        .filter(|w| w[1].0 - w[0].0 == SECONDS_PER_DAY && valid(w[0].1) && valid(w[1].1))
+        .map(|w| (w[1].1 / w[0].1).ln())
+        .collect();
+    if returns.len() < 2 {
+        return None;
+    }
+    let realized = returns.iter().map(|r| r.powi(2)).sum::<f64>().sqrt();
+    annualize(realized, returns.len() as i64 * SECONDS_PER_DAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_realized_volatility_needs_two_returns() {
+        assert_eq!(realized_volatility(&[]), None);
+        assert_eq!(realized_volatility(&[1.0, 2.0]), None);
+        assert_eq!(realized_volatility(&[1.0, 1.0, 1.0]), Some(0.0));
+        // Zero prices (unpriced days) are skipped rather than producing inf
+        assert_eq!(realized_volatility(&[1.0, 0.0, 1.0]), None);
+    }
+
+    #[test]
+    fn test_annualized_daily_volatility() {
+        // Alternating +/-1% daily moves: ~1% daily vol, ~19% annualized
+        let prices = daily(&[100.0, 101.0, 100.0, 101.0, 100.0]);
+        let vol = annualized_daily_volatility(&prices).unwrap();
+        let daily_vol = (1.01f64).ln();
+        assert!((vol - daily_vol * 365f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_daily_volatility_skips_gaps() {
+        let daily_vol = (1.01f64).ln() * 365f64.sqrt();
+
+        // An unpriced day breaks the series instead of joining its neighbours
+        let prices = daily(&[100.0, 101.0, 0.0, 150.0, 151.5, 150.0]);
+        let vol = annualized_daily_volatility(&prices).unwrap();
+        assert!((vol - daily_vol).abs() < 1e-3);
+
+        // So does a missing day
+        let mut prices = daily(&[100.0, 101.0, 100.0]);
+        prices.push((5 * SECONDS_PER_DAY, 200.0));
+        let vol = annualized_daily_volatility(&prices).unwrap();
+        assert!((vol - daily_vol).abs() < 1e-9);
+
+        assert_eq!(annualized_daily_volatility(&daily(&[1.0, 0.0, 1.0])), None);
+    }
+
+    fn daily(prices: &[f64]) -> Vec<(i64, f64)> {
+        prices
+            .iter()
+            .enumerate()
+            .map(|(day, price)| (day as i64 * SECONDS_PER_DAY, *price))
+            .collect()
+    }
+}
@@ -3,7 +3,7 @@
 pub mod rpc;
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 // Re-export RPC config
 pub use rpc::RpcConfig;
@@ -67,76 +67,205 @@ impl SubgraphIds {
     pub const UNISWAP_V3_ARBITRUM: &'static str = "FbCGRftH4a3yZugY7TnbYgPJVEv2LvMT6oF1fxPe9aJM";
     pub const UNISWAP_V3_OPTIMISM: &'static str = "Cghf4LfVqPiFw6fp6Y5X5Ubc8UpmUhSfJL82zwiBFLaj";
     pub const UNISWAP_V3_BASE: &'static str = "43Hwfi3dJSoGpyas9VwNoDAv28pNwMgNGVi8CKNS9r6R";
+
+    /// Aave V3 subgraph IDs on The Graph decentralized network
+    pub const AAVE_V3_ETHEREUM: &'static str = "Cd2gEDVeqnjBn1hSeqFMitw8Q1iiyV9FYUZkLNRcL87g";
+    pub const AAVE_V3_ARBITRUM: &'static str = "DLuE98kEb5pQNXAcKFQGQgfSQ57Xdou4jnVbAEqMfy3B";
+    pub const AAVE_V3_OPTIMISM: &'static str = "DSfLz8oQBUeU5atALgUFQKMTSYV9mZAVYp4noLSXAfvb";
+    pub const AAVE_V3_BASE: &'static str = "GQFbb95cE6d8mV989mL5figjaGaKCQB3xqYrr1bRyXqF";
+}
+
+/// The Graph's hosted gateway
+pub const DEFAULT_GATEWAY_URL: &str = "https://gateway.thegraph.com";
+
+/// Where a subgraph is served from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubgraphSource {
+    /// Subgraph ID on The Graph gateway (follows the latest published version)
+    SubgraphId(String),
+    /// Deployment ID (`Qm...` IPFS hash) on The Graph gateway, pinned to one version
+    DeploymentId(String),
+    /// Any GraphQL endpoint: a self-hosted graph-node, a local stand-in, etc.
+    Url(String),
+}
+
+/// Subgraph for one network/protocol pair
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubgraphEndpoint {
+    pub network: Network,
+    pub protocol: Protocol,
+    pub source: SubgraphSource,
+    /// Extra HTTP headers sent with every query (e.g. `Authorization` for a
+    /// self-hosted graph-node)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Name reported as the endpoint that served a query (derived from the
+    /// source if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl SubgraphEndpoint {
+    /// Endpoint served by The Graph gateway under a subgraph ID
+    pub fn subgraph_id(network: Network, protocol: Protocol, id: impl Into<String>) -> Self {
+        Self::new(network, protocol, SubgraphSource::SubgraphId(id.into()))
+    }
+
+    /// Endpoint served by The Graph gateway under a deployment ID
+    pub fn deployment_id(network: Network, protocol: Protocol, id: impl Into<String>) -> Self {
+        Self::new(network, protocol, SubgraphSource::DeploymentId(id.into()))
+    }
+
+    /// Endpoint at an arbitrary GraphQL URL
+    pub fn url(network: Network, protocol: Protocol, url: impl Into<String>) -> Self {
+        Self::new(network, protocol, SubgraphSource::Url(url.into()))
+    }
+
+    fn new(network: Network, protocol: Protocol, source: SubgraphSource) -> Self {
+        Self {
+            network,
+            protocol,
+            source,
+            headers: BTreeMap::new(),
+            name: None,
+        }
+    }
+
+    /// Add an HTTP header sent with every query
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Name the endpoint in query responses
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// How the endpoint is identified in query responses: its name, or its
+    /// source (with any gateway API key in a URL masked)
+    pub fn label(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        match &self.source {
+            SubgraphSource::SubgraphId(id) => format!("gateway/subgraphs/{}", id),
+            SubgraphSource::DeploymentId(id) => format!("gateway/deployments/{}", id),
+            SubgraphSource::Url(url) => match url.split_once("/api/") {
+                Some((base, rest)) => match rest.split_once('/') {
+                    Some((_key, path)) => format!("{}/api/***/{}", base, path),
+                    None => url.clone(),
+                },
+                None => url.clone(),
+            },
+        }
+    }
+
+    /// Subgraph or deployment ID when the endpoint is served by the gateway
+    pub fn gateway_id(&self) -> Option<&str> {
+        match &self.source {
+            SubgraphSource::SubgraphId(id) | SubgraphSource::DeploymentId(id) => Some(id),
+            SubgraphSource::Url(_) => None,
+        }
+    }
+
+    /// Query URL, or None for a gateway source without an API key
+    pub fn query_url(&self, gateway_url: &str, api_key: Option<&str>) -> Option<String> {
+        match &self.source {
+            SubgraphSource::Url(url) => Some(url.clone()),
+            SubgraphSource::SubgraphId(id) => {
+                api_key.map(|key| gateway_query_url(gateway_url, key, "subgraphs", id))
+            }
+            SubgraphSource::DeploymentId(id) => {
+                api_key.map(|key| gateway_query_url(gateway_url, key, "deployments", id))
+            }
+        }
+    }
+}
+
+/// Gateway query URL for a subgraph (`kind` = "subgraphs") or deployment (`kind` = "deployments")
+pub fn gateway_query_url(gateway_url: &str, api_key: &str, kind: &str, id: &str) -> String {
+    format!(
+        "{}/api/{}/{}/id/{}",
+        gateway_url.trim_end_matches('/'),
+        api_key,
+        kind,
+        id
+    )
 }
 
 /// The Graph subgraph endpoints
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Gateway sources need an API key (`GRAPH_API_KEY`), which is applied when
+/// the URL is built so it never ends up in a config file. A configured list
+/// replaces the defaults entirely. Several entries for one network/protocol
+/// pair are failover endpoints, tried in the order listed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SubgraphEndpoints {
-    pub endpoints: HashMap<(Network, Protocol), String>,
+    /// Base URL of The Graph gateway for subgraph/deployment ID sources
+    #[serde(default = "default_gateway_url")]
+    pub gateway_url: String,
+    #[serde(default)]
+    pub endpoints: Vec<SubgraphEndpoint>,
+}
+
+fn default_gateway_url() -> String {
+    DEFAULT_GATEWAY_URL.to_string()
 }
 
 impl SubgraphEndpoints {
-    /// Build endpoints using The Graph decentralized network with API key
-    pub fn with_api_key(api_key: &str) -> Self {
-        let mut endpoints = HashMap::new();
+    /// Endpoints from an explicit list, served through the default gateway
+    pub fn new(endpoints: Vec<SubgraphEndpoint>) -> Self {
+        Self {
+            gateway_url: default_gateway_url(),
+            endpoints,
+        }
+    }
 
-        // Uniswap V3 on The Graph decentralized network
-        endpoints.insert(
-            (Network::Ethereum, Protocol::UniswapV3),
-            format!(
-                "https://gateway.thegraph.com/api/{}/subgraphs/id/{}",
-                api_key,
-                SubgraphIds::UNISWAP_V3_ETHEREUM
-            ),
-        );
-        endpoints.insert(
-            (Network::Arbitrum, Protocol::UniswapV3),
-            format!(
-                "https://gateway.thegraph.com/api/{}/subgraphs/id/{}",
-                api_key,
-                SubgraphIds::UNISWAP_V3_ARBITRUM
-            ),
-        );
-        endpoints.insert(
-            (Network::Optimism, Protocol::UniswapV3),
-            format!(
-                "https://gateway.thegraph.com/api/{}/subgraphs/id/{}",
-                api_key,
-                SubgraphIds::UNISWAP_V3_OPTIMISM
-            ),
-        );
-        endpoints.insert(
-            (Network::Base, Protocol::UniswapV3),
-            format!(
-                "https://gateway.thegraph.com/api/{}/subgraphs/id/{}",
-                api_key,
-                SubgraphIds::UNISWAP_V3_BASE
-            ),
-        );
+    /// Uniswap V3 and Aave V3 on The Graph decentralized network
+    pub fn gateway_defaults() -> Self {
+        use Network::*;
+        use Protocol::*;
+        Self::new(vec![
+            SubgraphEndpoint::subgraph_id(Ethereum, UniswapV3, SubgraphIds::UNISWAP_V3_ETHEREUM),
+            SubgraphEndpoint::subgraph_id(Arbitrum, UniswapV3, SubgraphIds::UNISWAP_V3_ARBITRUM),
+            SubgraphEndpoint::subgraph_id(Optimism, UniswapV3, SubgraphIds::UNISWAP_V3_OPTIMISM),
+            SubgraphEndpoint::subgraph_id(Base, UniswapV3, SubgraphIds::UNISWAP_V3_BASE),
+            SubgraphEndpoint::subgraph_id(Ethereum, AaveV3, SubgraphIds::AAVE_V3_ETHEREUM),
+            SubgraphEndpoint::subgraph_id(Arbitrum, AaveV3, SubgraphIds::AAVE_V3_ARBITRUM),
+            SubgraphEndpoint::subgraph_id(Optimism, AaveV3, SubgraphIds::AAVE_V3_OPTIMISM),
+            SubgraphEndpoint::subgraph_id(Base, AaveV3, SubgraphIds::AAVE_V3_BASE),
+        ])
+    }
 
-        Self { endpoints }
+    /// Use a different gateway (e.g. a local stand-in)
+    pub fn with_gateway_url(mut self, gateway_url: impl Into<String>) -> Self {
+        self.gateway_url = gateway_url.into();
+        self
     }
 
-    /// Try to build endpoints from GRAPH_API_KEY environment variable
-    pub fn from_env() -> Option<Self> {
-        std::env::var(GRAPH_API_KEY_ENV)
-            .ok()
-            .map(|key| Self::with_api_key(&key))
+    /// Primary endpoint for a network/protocol pair (the first match)
+    pub fn get(&self, network: Network, protocol: Protocol) -> Option<&SubgraphEndpoint> {
+        self.all(network, protocol).next()
+    }
+
+    /// Endpoints for a network/protocol pair, in failover order
+    pub fn all(
+        &self,
+        network: Network,
+        protocol: Protocol,
+    ) -> impl Iterator<Item = &SubgraphEndpoint> {
+        self.endpoints
+            .iter()
+            .filter(move |e| e.network == network && e.protocol == protocol)
     }
 }
 
 impl Default for SubgraphEndpoints {
     fn default() -> Self {
-        // Try to load from environment, fall back to placeholder
-        Self::from_env().unwrap_or_else(|| {
-            let mut endpoints = HashMap::new();
-            // Placeholder - requires GRAPH_API_KEY to be set
-            endpoints.insert(
-                (Network::Ethereum, Protocol::UniswapV3),
-                "https://gateway.thegraph.com/api/YOUR_API_KEY/subgraphs/id/5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV".to_string(),
-            );
-            Self { endpoints }
-        })
+        Self::gateway_defaults()
     }
 }
 
@@ -171,6 +300,431 @@ pub struct PolicySettings {
     pub require_file: bool,
 }
 
+/// Default hard upper bound on entities returned by paginated subgraph queries
+pub const DEFAULT_MAX_GRAPH_RESULTS: usize = 5000;
+
+/// Default number of query plan legs executed concurrently
+pub const DEFAULT_PLAN_CONCURRENCY: usize = 4;
+
+/// Default timeout for a single query plan leg (seconds)
+pub const DEFAULT_PLAN_LEG_TIMEOUT_SECS: u64 = 30;
+
+/// Default maximum age of subgraph data before it is rejected (seconds)
+pub const DEFAULT_MAX_DATA_AGE_SECS: u64 = 300;
+
+/// Default maximum selection depth of an agent-authored subgraph query
+pub const DEFAULT_CUSTOM_QUERY_MAX_DEPTH: usize = 5;
+
+/// Default maximum `first` argument in an agent-authored subgraph query
+pub const DEFAULT_CUSTOM_QUERY_MAX_FIRST: i64 = 1000;
+
+/// Default maximum root fields (aliases included) in an agent-authored query
+pub const DEFAULT_CUSTOM_QUERY_MAX_ROOT_FIELDS: usize = 5;
+
+/// Default maximum aliased fields in an agent-authored subgraph query
+pub const DEFAULT_CUSTOM_QUERY_MAX_ALIASES: usize = 10;
+
+/// Default lifetime of a cached gateway query result (seconds)
+pub const DEFAULT_GATEWAY_CACHE_TTL_SECS: u64 = 60;
+
+/// Default size cap of the on-disk gateway cache (bytes)
+pub const DEFAULT_GATEWAY_CACHE_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Default entry bound of the in-memory gateway cache
+pub const DEFAULT_GATEWAY_CACHE_MAX_ENTRIES: usize = 1024;
+
+/// Default retries of a transient gateway failure
+pub const DEFAULT_GATEWAY_MAX_RETRIES: u32 = 2;
+
+/// Default delay before the first gateway retry (milliseconds)
+pub const DEFAULT_GATEWAY_RETRY_BASE_DELAY_MS: u64 = 250;
+
+/// Default cap on the delay between gateway retries (milliseconds)
+pub const DEFAULT_GATEWAY_RETRY_MAX_DELAY_MS: u64 = 5_000;
+
+/// Default timeout of a single gateway request (seconds)
+pub const DEFAULT_GATEWAY_REQUEST_TIMEOUT_SECS: u64 = 30;
+
+/// Default consecutive failed requests that open a subgraph's circuit
+pub const DEFAULT_CIRCUIT_FAILURE_THRESHOLD: u32 = 5;
+
+/// Default time an open circuit fails fast before a trial request (seconds)
+pub const DEFAULT_CIRCUIT_OPEN_SECS: u64 = 30;
+
+/// The Graph Network subgraph (Arbitrum One), the source of indexer allocations
+pub const DEFAULT_NETWORK_SUBGRAPH_ID: &str = "DZz4kDTdmzWLWsV373w2bSmoar3umKKH9y82SUKr5qmp";
+
+/// Default cap on the x402 payment for a single query (USD)
+pub const DEFAULT_X402_MAX_PAYMENT_PER_QUERY_USD: f64 = 0.01;
+
+/// Default cap on x402 query payments per UTC day (USD)
+pub const DEFAULT_X402_MAX_DAILY_PAYMENT_USD: f64 = 5.0;
+
+/// Default estimated price of one gateway query (USD; $4 per 100k queries)
+pub const DEFAULT_GRAPH_USD_PER_QUERY: f64 = 0.00004;
+
+/// Default GRT price used to express query cost in GRT (USD)
+pub const DEFAULT_GRT_USD_PRICE: f64 = 0.10;
+
+/// Default maximum age of a prepared swap's quote at signing/execution
+/// (seconds; an Odos path can't be assembled after `ODOS_QUOTE_TTL_SECS` anyway)
+pub const DEFAULT_MAX_QUOTE_AGE_SECS: u64 = 30;
+
+/// The Graph query settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphSettings {
+    /// Hard upper bound on entities returned by a paginated query
+    #[serde(default = "default_max_graph_results")]
+    pub max_results: usize,
+    /// Maximum query plan legs (network/protocol pairs) in flight at once
+    #[serde(default = "default_plan_concurrency")]
+    pub plan_concurrency: usize,
+    /// Timeout for a single query plan leg (seconds)
+    #[serde(default = "default_plan_leg_timeout_secs")]
+    pub plan_leg_timeout_secs: u64,
+    /// Reject subgraph data older than this (seconds; `null` disables the check)
+    #[serde(default = "default_max_data_age_secs")]
+    pub max_data_age_secs: Option<u64>,
+    /// Reject subgraph data more than this many blocks behind the RPC chain
+    /// head (`null`, the default, disables the check and the head lookup)
+    #[serde(default)]
+    pub max_lag_blocks: Option<u64>,
+    /// Reject data from subgraphs reporting indexing errors
+    #[serde(default = "default_true")]
+    pub reject_indexing_errors: bool,
+    /// Maximum selection depth of a custom (agent-authored) query
+    #[serde(default = "default_custom_query_max_depth")]
+    pub custom_query_max_depth: usize,
+    /// Maximum `first` argument in a custom query
+    #[serde(default = "default_custom_query_max_first")]
+    pub custom_query_max_first: i64,
+    /// Maximum root fields in a custom query, each alias counted
+    #[serde(default = "default_custom_query_max_root_fields")]
+    pub custom_query_max_root_fields: usize,
+    /// Maximum aliased fields in a custom query
+    #[serde(default = "default_custom_query_max_aliases")]
+    pub custom_query_max_aliases: usize,
+    /// Gateway result cache
+    #[serde(default)]
+    pub cache: GatewayCacheSettings,
+    /// Gateway request retries
+    #[serde(default)]
+    pub retry: GatewayRetrySettings,
+    /// Per-subgraph circuit breaker
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerSettings,
+    /// Indexer discovery and routing preferences
+    #[serde(default)]
+    pub indexers: IndexerRoutingSettings,
+    /// Pay-per-query gateway access
+    #[serde(default)]
+    pub x402: X402Settings,
+    /// Query cost estimates and the daily query budget
+    #[serde(default)]
+    pub usage: GraphUsageSettings,
+}
+
+fn default_max_graph_results() -> usize {
+    DEFAULT_MAX_GRAPH_RESULTS
+}
+
+fn default_plan_concurrency() -> usize {
+    DEFAULT_PLAN_CONCURRENCY
+}
+
+fn default_plan_leg_timeout_secs() -> u64 {
+    DEFAULT_PLAN_LEG_TIMEOUT_SECS
+}
+
+fn default_max_data_age_secs() -> Option<u64> {
+    Some(DEFAULT_MAX_DATA_AGE_SECS)
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_custom_query_max_depth() -> usize {
+    DEFAULT_CUSTOM_QUERY_MAX_DEPTH
+}
+
+fn default_custom_query_max_first() -> i64 {
+    DEFAULT_CUSTOM_QUERY_MAX_FIRST
+}
+
+fn default_custom_query_max_root_fields() -> usize {
+    DEFAULT_CUSTOM_QUERY_MAX_ROOT_FIELDS
+}
+
+fn default_custom_query_max_aliases() -> usize {
+    DEFAULT_CUSTOM_QUERY_MAX_ALIASES
+}
+
+impl Default for GraphSettings {
+    fn default() -> Self {
+        Self {
+            max_results: DEFAULT_MAX_GRAPH_RESULTS,
+            plan_concurrency: DEFAULT_PLAN_CONCURRENCY,
+            plan_leg_timeout_secs: DEFAULT_PLAN_LEG_TIMEOUT_SECS,
+            max_data_age_secs: Some(DEFAULT_MAX_DATA_AGE_SECS),
+            max_lag_blocks: None,
+            reject_indexing_errors: true,
+            custom_query_max_depth: DEFAULT_CUSTOM_QUERY_MAX_DEPTH,
+            custom_query_max_first: DEFAULT_CUSTOM_QUERY_MAX_FIRST,
+            custom_query_max_root_fields: DEFAULT_CUSTOM_QUERY_MAX_ROOT_FIELDS,
+            custom_query_max_aliases: DEFAULT_CUSTOM_QUERY_MAX_ALIASES,
+            cache: GatewayCacheSettings::default(),
+            retry: GatewayRetrySettings::default(),
+            circuit_breaker: CircuitBreakerSettings::default(),
+            indexers: IndexerRoutingSettings::default(),
+            x402: X402Settings::default(),
+            usage: GraphUsageSettings::default(),
+        }
+    }
+}
+
+/// Gateway result cache settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayCacheSettings {
+    /// Directory for a cache that persists across restarts (in-memory if unset)
+    #[serde(default)]
+    pub dir: Option<String>,
+    /// Size cap of the cache directory (bytes); least recently used entries
+    /// are evicted first
+    #[serde(default = "default_gateway_cache_max_bytes")]
+    pub max_bytes: u64,
+    /// Entry bound of the in-memory cache; least recently used entries are
+    /// evicted first
+    #[serde(default = "default_gateway_cache_max_entries")]
+    pub max_entries: usize,
+    /// Lifetime of a cached result (seconds)
+    #[serde(default = "default_gateway_cache_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Serve expired results immediately while refreshing them in the background
+    #[serde(default)]
+    pub stale_while_revalidate: bool,
+}
+
+fn default_gateway_cache_max_bytes() -> u64 {
+    DEFAULT_GATEWAY_CACHE_MAX_BYTES
+}
+
+fn default_gateway_cache_max_entries() -> usize {
+    DEFAULT_GATEWAY_CACHE_MAX_ENTRIES
+}
+
+fn default_gateway_cache_ttl_secs() -> u64 {
+    DEFAULT_GATEWAY_CACHE_TTL_SECS
+}
+
+impl Default for GatewayCacheSettings {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            max_bytes: DEFAULT_GATEWAY_CACHE_MAX_BYTES,
+            max_entries: DEFAULT_GATEWAY_CACHE_MAX_ENTRIES,
+            ttl_secs: DEFAULT_GATEWAY_CACHE_TTL_SECS,
+            stale_while_revalidate: false,
+        }
+    }
+}
+
+/// Gateway request retry settings
+///
+/// Connection errors, timeouts, 5xx and 429 responses are retried with
+/// jittered exponential backoff; GraphQL errors are not.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayRetrySettings {
+    /// Retries after the first attempt
+    #[serde(default = "default_gateway_max_retries")]
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each later one (milliseconds)
+    #[serde(default = "default_gateway_retry_base_delay_ms")]
+    pub base_delay_ms: u64,
+    /// Cap on the delay between retries (milliseconds)
+    #[serde(default = "default_gateway_retry_max_delay_ms")]
+    pub max_delay_ms: u64,
+    /// Timeout of a single request (seconds)
+    #[serde(default = "default_gateway_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+}
+
+fn default_gateway_max_retries() -> u32 {
+    DEFAULT_GATEWAY_MAX_RETRIES
+}
+
+fn default_gateway_retry_base_delay_ms() -> u64 {
+    DEFAULT_GATEWAY_RETRY_BASE_DELAY_MS
+}
+
+fn default_gateway_retry_max_delay_ms() -> u64 {
+    DEFAULT_GATEWAY_RETRY_MAX_DELAY_MS
+}
+
+fn default_gateway_request_timeout_secs() -> u64 {
+    DEFAULT_GATEWAY_REQUEST_TIMEOUT_SECS
+}
+
+impl Default for GatewayRetrySettings {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_GATEWAY_MAX_RETRIES,
+            base_delay_ms: DEFAULT_GATEWAY_RETRY_BASE_DELAY_MS,
+            max_delay_ms: DEFAULT_GATEWAY_RETRY_MAX_DELAY_MS,
+            request_timeout_secs: DEFAULT_GATEWAY_REQUEST_TIMEOUT_SECS,
+        }
+    }
+}
+
+/// Per-subgraph circuit breaker settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitBreakerSettings {
+    /// Consecutive failed requests (after retries) that open the circuit
+    #[serde(default = "default_circuit_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Time an open circuit fails fast before letting a trial request through
+    /// (seconds)
+    #[serde(default = "default_circuit_open_secs")]
+    pub open_secs: u64,
+}
+
+fn default_circuit_failure_threshold() -> u32 {
+    DEFAULT_CIRCUIT_FAILURE_THRESHOLD
+}
+
+fn default_circuit_open_secs() -> u64 {
+    DEFAULT_CIRCUIT_OPEN_SECS
+}
+
+impl Default for CircuitBreakerSettings {
+    fn default() -> Self {
+        Self {
+            failure_threshold: DEFAULT_CIRCUIT_FAILURE_THRESHOLD,
+            open_secs: DEFAULT_CIRCUIT_OPEN_SECS,
+        }
+    }
+}
+
+/// Indexer routing settings
+///
+/// With preferred indexers set, gateway queries go straight to those
+/// allocated to the deployment, unpaid, and fall back to the gateway when
+/// none fits or all of them fail.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexerRoutingSettings {
+    /// Indexer addresses to route to, most preferred first
+    #[serde(default)]
+    pub preferred: Vec<String>,
+    /// Skip preferred indexers whose observed latency exceeds this
+    /// (milliseconds); also the timeout of a request sent straight to an
+    /// indexer, and the threshold for flagging slow gateway answers
+    #[serde(default)]
+    pub max_latency_ms: Option<u64>,
+    /// Network subgraph queried for indexer allocations
+    #[serde(default = "default_network_subgraph_id")]
+    pub network_subgraph_id: String,
+}
+
+fn default_network_subgraph_id() -> String {
+    DEFAULT_NETWORK_SUBGRAPH_ID.to_string()
+}
+
+impl Default for IndexerRoutingSettings {
+    fn default() -> Self {
+        Self {
+            preferred: Vec::new(),
+            max_latency_ms: None,
+            network_subgraph_id: DEFAULT_NETWORK_SUBGRAPH_ID.to_string(),
+        }
+    }
+}
+
+/// x402 pay-per-query settings
+///
+/// When enabled and a wallet is loaded, gateway queries are paid for from the
+/// wallet (in USDC) instead of authenticated with `GRAPH_API_KEY`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct X402Settings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// x402 gateway base URL (`subgraphs.gateway_url` if unset)
+    #[serde(default)]
+    pub gateway_url: Option<String>,
+    /// Most a single query may cost (USD)
+    #[serde(default = "default_x402_max_payment_per_query_usd")]
+    pub max_payment_per_query_usd: f64,
+    /// Most all queries may cost per UTC day (USD)
+    #[serde(default = "default_x402_max_daily_payment_usd")]
+    pub max_daily_payment_usd: f64,
+    /// Addresses payments may go to; offers to pay anyone else are refused,
+    /// so nothing is paid while this is empty
+    #[serde(default)]
+    pub pay_to: Vec<String>,
+}
+
+fn default_x402_max_payment_per_query_usd() -> f64 {
+    DEFAULT_X402_MAX_PAYMENT_PER_QUERY_USD
+}
+
+fn default_x402_max_daily_payment_usd() -> f64 {
+    DEFAULT_X402_MAX_DAILY_PAYMENT_USD
+}
+
+impl Default for X402Settings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            gateway_url: None,
+            max_payment_per_query_usd: DEFAULT_X402_MAX_PAYMENT_PER_QUERY_USD,
+            max_daily_payment_usd: DEFAULT_X402_MAX_DAILY_PAYMENT_USD,
+            pay_to: Vec::new(),
+        }
+    }
+}
+
+/// Gateway query accounting settings
+///
+/// Queries sent upstream are counted and priced per subgraph (x402-paid
+/// queries at what was actually paid); cached answers are free.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphUsageSettings {
+    /// Most queries sent upstream per UTC day (unlimited if unset)
+    #[serde(default)]
+    pub daily_query_limit: Option<u64>,
+    /// Estimated price of one query (USD)
+    #[serde(default = "default_graph_usd_per_query")]
+    pub usd_per_query: f64,
+    /// GRT price for expressing cost in GRT (USD)
+    #[serde(default = "default_grt_usd_price")]
+    pub grt_usd_price: f64,
+}
+
+fn default_graph_usd_per_query() -> f64 {
+    DEFAULT_GRAPH_USD_PER_QUERY
+}
+
+fn default_grt_usd_price() -> f64 {
+    DEFAULT_GRT_USD_PRICE
+}
+
+impl Default for GraphUsageSettings {
+    fn default() -> Self {
+        Self {
+            daily_query_limit: None,
+            usd_per_query: DEFAULT_GRAPH_USD_PER_QUERY,
+            grt_usd_price: DEFAULT_GRT_USD_PRICE,
+        }
+    }
+}
+
+/// Transaction signing settings
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SigningSettings {
+    /// Allow `defi/wallet_sign_tx` to sign raw hashes without decoding them
+    #[serde(default)]
+    pub allow_blind_signing: bool,
+}
+
 /// Risk management configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RiskConfig {
@@ -185,6 +739,17 @@ pub struct RiskConfig {
     /// Spend limit enforcement mode
     #[serde(default)]
     pub spend_limit_mode: SpendLimitMode,
+    /// Maximum seconds between quoting a swap and signing/executing it
+    #[serde(default = "default_max_quote_age_secs")]
+    pub max_quote_age_secs: u64,
+    /// Maximum blocks between a swap's quote and the chain head at
+    /// signing/execution (unchecked if unset; block times differ per chain)
+    #[serde(default)]
+    pub max_quote_block_distance: Option<u64>,
+}
+
+fn default_max_quote_age_secs() -> u64 {
+    DEFAULT_MAX_QUOTE_AGE_SECS
 }
 
 impl Default for RiskConfig {
@@ -195,6 +760,8 @@ impl Default for RiskConfig {
             max_slippage_percent: 1.0,                  // 1% max slippage
             cooldown_seconds: 300,                      // 5 minutes between trades
             spend_limit_mode: SpendLimitMode::FailOpen, // Default to existing behavior
+            max_quote_age_secs: DEFAULT_MAX_QUOTE_AGE_SECS,
+            max_quote_block_distance: None,
         }
     }
 }
@@ -206,15 +773,26 @@ pub struct Config {
     pub networks: Vec<Network>,
     /// Protocols to query
     pub protocols: Vec<Protocol>,
-    /// Subgraph endpoints
+    /// Subgraph endpoints (defaults to The Graph gateway)
+    #[serde(default)]
     pub subgraphs: SubgraphEndpoints,
     /// Risk management settings
     pub risk: RiskConfig,
     /// Policy settings
     #[serde(default)]
     pub policy: PolicySettings,
+    /// Signing settings
+    #[serde(default)]
+    pub signing: SigningSettings,
+    /// The Graph query settings
+    #[serde(default)]
+    pub graph: GraphSettings,
     /// Trading loop interval (milliseconds)
     pub check_interval_ms: u64,
+    /// Register `defi/execute_swap` and execute prepared swaps from the
+    /// trading loop (requires a wallet)
+    #[serde(default)]
+    pub execute_swaps: bool,
     /// Path to audit log file
     pub audit_log_path: Option<String>,
 }
@@ -227,7 +805,10 @@ impl Default for Config {
             subgraphs: SubgraphEndpoints::default(),
             risk: RiskConfig::default(),
             policy: PolicySettings::default(),
+            signing: SigningSettings::default(),
+            graph: GraphSettings::default(),
             check_interval_ms: 60_000, // 1 minute
+            execute_swaps: false,
             audit_log_path: Some("audit.jsonl".to_string()),
         }
     }
@@ -242,7 +823,7 @@ mod tests {
         let value = serde_json::json!({
             "networks": ["ethereum"],
             "protocols": ["uniswap_v3"],
-            "subgraphs": { "endpoints": {} },
+            "subgraphs": { "endpoints": [] },
             "risk": {
                 "max_trade_usd": 100.0,
                 "max_daily_usd": 500.0,
@@ -256,6 +837,67 @@ mod tests {
         let parsed: Config = serde_json::from_value(value).expect("parse config");
         assert_eq!(parsed.policy.default_mode, PolicyDefaultMode::AllowAll);
         assert!(!parsed.policy.require_file);
+        assert!(!parsed.signing.allow_blind_signing);
+        assert_eq!(parsed.graph.max_results, DEFAULT_MAX_GRAPH_RESULTS);
+        assert_eq!(parsed.graph.plan_concurrency, DEFAULT_PLAN_CONCURRENCY);
+        assert_eq!(
+            parsed.graph.plan_leg_timeout_secs,
+            DEFAULT_PLAN_LEG_TIMEOUT_SECS
+        );
+        assert_eq!(
+            parsed.graph.max_data_age_secs,
+            Some(DEFAULT_MAX_DATA_AGE_SECS)
+        );
+        assert!(parsed.graph.max_lag_blocks.is_none());
+        assert!(parsed.graph.reject_indexing_errors);
+        assert_eq!(
+            parsed.graph.custom_query_max_depth,
+            DEFAULT_CUSTOM_QUERY_MAX_DEPTH
+        );
+        assert_eq!(
+            parsed.graph.custom_query_max_first,
+            DEFAULT_CUSTOM_QUERY_MAX_FIRST
+        );
+        assert_eq!(
+            parsed.graph.custom_query_max_root_fields,
+            DEFAULT_CUSTOM_QUERY_MAX_ROOT_FIELDS
+        );
+        assert_eq!(
+            parsed.graph.custom_query_max_aliases,
+            DEFAULT_CUSTOM_QUERY_MAX_ALIASES
+        );
+        assert!(parsed.graph.cache.dir.is_none());
+        assert_eq!(parsed.graph.cache.ttl_secs, DEFAULT_GATEWAY_CACHE_TTL_SECS);
+        assert_eq!(
+            parsed.graph.cache.max_entries,
+            DEFAULT_GATEWAY_CACHE_MAX_ENTRIES
+        );
+        assert!(!parsed.graph.cache.stale_while_revalidate);
+        assert_eq!(parsed.graph.retry.max_retries, DEFAULT_GATEWAY_MAX_RETRIES);
+        assert_eq!(
+            parsed.graph.circuit_breaker.failure_threshold,
+            DEFAULT_CIRCUIT_FAILURE_THRESHOLD
+        );
+        assert!(parsed.graph.indexers.preferred.is_empty());
+        assert!(parsed.graph.indexers.max_latency_ms.is_none());
+        assert_eq!(
+            parsed.graph.indexers.network_subgraph_id,
+            DEFAULT_NETWORK_SUBGRAPH_ID
+        );
+        assert!(!parsed.graph.x402.enabled);
+        assert!(parsed.graph.x402.pay_to.is_empty());
+        assert_eq!(
+            parsed.graph.x402.max_daily_payment_usd,
+            DEFAULT_X402_MAX_DAILY_PAYMENT_USD
+        );
+        assert!(parsed.graph.usage.daily_query_limit.is_none());
+        assert_eq!(
+            parsed.graph.usage.usd_per_query,
+            DEFAULT_GRAPH_USD_PER_QUERY
+        );
+        assert_eq!(parsed.risk.max_quote_age_secs, DEFAULT_MAX_QUOTE_AGE_SECS);
+        assert!(parsed.risk.max_quote_block_distance.is_none());
+        assert!(!parsed.execute_swaps);
     }
 
     #[test]
@@ -263,7 +905,7 @@ mod tests {
         let value = serde_json::json!({
             "networks": ["ethereum"],
             "protocols": ["uniswap_v3"],
-            "subgraphs": { "endpoints": {} },
+            "subgraphs": { "endpoints": [] },
             "risk": {
                 "max_trade_usd": 100.0,
                 "max_daily_usd": 500.0,
@@ -282,4 +924,137 @@ mod tests {
         assert_eq!(parsed.policy.default_mode, PolicyDefaultMode::DefaultDeny);
         assert!(parsed.policy.require_file);
     }
+
+    #[test]
+    fn config_round_trips_through_json() {
+        let mut config = Config::default();
+        config.subgraphs = SubgraphEndpoints::new(vec![
+            SubgraphEndpoint::deployment_id(
+                Network::Base,
+                Protocol::UniswapV3,
+                "QmTZ8ejXJxRo7vDBS4uwqBeGoxLSWbhaA7oXa1RvxunLy7",
+            ),
+            SubgraphEndpoint::url(
+                Network::Ethereum,
+                Protocol::AaveV3,
+                "http://localhost:8000/subgraphs/name/aave/v3",
+            )
+            .with_header("Authorization", "Bearer token"),
+        ]);
+        let json = serde_json::to_string(&config).expect("serialize config");
+        let parsed: Config = serde_json::from_str(&json).expect("parse config");
+        assert_eq!(parsed.subgraphs, config.subgraphs);
+    }
+
+    #[test]
+    fn subgraph_endpoints_parse_from_config_file() {
+        let value = serde_json::json!({
+            "endpoints": [
+                {
+                    "network": "ethereum",
+                    "protocol": "uniswap_v3",
+                    "source": { "url": "http://127.0.0.1:8000/subgraphs/name/uniswap/v3" },
+                    "headers": { "Authorization": "Bearer local" }
+                },
+                {
+                    "network": "arbitrum",
+                    "protocol": "uniswap_v3",
+                    "source": { "subgraph_id": SubgraphIds::UNISWAP_V3_ARBITRUM }
+                }
+            ]
+        });
+        let parsed: SubgraphEndpoints = serde_json::from_value(value).expect("parse endpoints");
+        assert_eq!(parsed.gateway_url, DEFAULT_GATEWAY_URL);
+
+        let local = parsed.get(Network::Ethereum, Protocol::UniswapV3).unwrap();
+        assert_eq!(local.gateway_id(), None);
+        assert_eq!(
+            local.query_url(&parsed.gateway_url, None).as_deref(),
+            Some("http://127.0.0.1:8000/subgraphs/name/uniswap/v3")
+        );
+        assert_eq!(local.headers["Authorization"], "Bearer local");
+
+        let gateway = parsed.get(Network::Arbitrum, Protocol::UniswapV3).unwrap();
+        assert_eq!(gateway.query_url(&parsed.gateway_url, None), None);
+        assert_eq!(
+            gateway.query_url(&parsed.gateway_url, Some("key")).unwrap(),
+            format!(
+                "https://gateway.thegraph.com/api/key/subgraphs/id/{}",
+                SubgraphIds::UNISWAP_V3_ARBITRUM
+            )
+        );
+        assert!(parsed.get(Network::Base, Protocol::AaveV3).is_none());
+    }
+
+    #[test]
+    fn failover_endpoints_keep_config_order() {
+        let value = serde_json::json!({
+            "endpoints": [
+                {
+                    "network": "ethereum",
+                    "protocol": "uniswap_v3",
+                    "source": { "url": "http://graph-node:8000/subgraphs/name/uniswap/v3" },
+                    "name": "self-hosted"
+                },
+                {
+                    "network": "ethereum",
+                    "protocol": "uniswap_v3",
+                    "source": { "subgraph_id": SubgraphIds::UNISWAP_V3_ETHEREUM }
+                },
+                {
+                    "network": "ethereum",
+                    "protocol": "uniswap_v3",
+                    "source": { "url": "https://gateway.thegraph.com/api/secret/subgraphs/id/abc" }
+                }
+            ]
+        });
+        let parsed: SubgraphEndpoints = serde_json::from_value(value).expect("parse endpoints");
+
+        let labels: Vec<String> = parsed
+            .all(Network::Ethereum, Protocol::UniswapV3)
+            .map(SubgraphEndpoint::label)
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                "self-hosted".to_string(),
+                format!("gateway/subgraphs/{}", SubgraphIds::UNISWAP_V3_ETHEREUM),
+                "https://gateway.thegraph.com/api/***/subgraphs/id/abc".to_string(),
+            ]
+        );
+        assert_eq!(
+            parsed
+                .get(Network::Ethereum, Protocol::UniswapV3)
+                .unwrap()
+                .name
+                .as_deref(),
+            Some("self-hosted")
+        );
+        assert_eq!(parsed.all(Network::Base, Protocol::UniswapV3).count(), 0);
+    }
+
+    #[test]
+    fn default_endpoints_cover_every_pair_without_a_key() {
+        let defaults = SubgraphEndpoints::default();
+        for network in [
+            Network::Ethereum,
+            Network::Arbitrum,
+            Network::Optimism,
+            Network::Base,
+        ] {
+            for protocol in [Protocol::UniswapV3, Protocol::AaveV3] {
+                let endpoint = defaults.get(network, protocol).expect("default endpoint");
+                assert!(endpoint.gateway_id().is_some());
+                // No placeholder URL: without a key there is nothing to query
+                assert_eq!(endpoint.query_url(&defaults.gateway_url, None), None);
+            }
+        }
+        let deployment = SubgraphEndpoint::deployment_id(Network::Base, Protocol::AaveV3, "QmX");
+        assert_eq!(
+            deployment
+                .query_url("http://localhost:7700/", Some("k"))
+                .unwrap(),
+            "http://localhost:7700/api/k/deployments/id/QmX"
+        );
+    }
 }
@@ -0,0 +1,514 @@
+//! Swap execution pipeline
+//!
+//! Takes a transaction prepared by `OdosTool::prepare_swap` and lands it on-chain:
+//!
+//! 1. **Prepare** - decode the transaction's intent (Odos router swaps to this
+//!    wallet only) and validate it against the RPC chain
+//! 2. **Simulate** - run it through `TransactionSimulator` (aborts on revert)
+//! 3. **Sign** - build an EIP-1559 transaction and sign it with `SecureWallet`
+//! 4. **Broadcast** - send the raw transaction via the `RpcConfig` provider
+//! 5. **Receipt** - wait for the receipt (bounded by a timeout)
+//!
+//! SECURITY NOTE:
+//! - Signing only happens through `SecureWallet::sign_transaction`
+//! - Only swaps through an allowlisted Odos router that pay out to this wallet
+//!   are signed (`crate::wallet::intent`), whatever the caller passes in
+//! - A failed simulation never reaches the signing step
+//! - Dry-run wallets refuse to sign, so nothing is broadcast in dry-run mode
+//! - Tool-level gating (policy, spend limits, audit) happens in the interceptor
+//!   pipeline before `ExecuteSwapTool` ever calls into this module, and
+//!   `ExecuteSwapTool` only executes transactions recorded in [`PreparedSwaps`]
+
+mod prepared;
+
+pub use prepared::{PreparedSwap, PreparedSwaps};
+
+use crate::config::RpcConfig;
+use crate::wallet::intent::{decode_intent, IntentError, TxIntent};
+use crate::wallet::{PreparedTransaction, SecureWallet, TransactionSimulator};
+use alloy::consensus::TxEip1559;
+use alloy::eips::Encodable2718;
+use alloy::primitives::{Address, TxKind};
+use alloy::providers::{PendingTransactionError, Provider, ProviderBuilder, WatchTxError};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Gas buffer applied to simulated gas usage when no gas limit was prepared (percent)
+const GAS_BUFFER_PERCENT: u64 = 20;
+
+/// Final status of an executed swap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionStatus {
+    /// Broadcast, but no receipt yet (timed out or not waited for)
+    Pending,
+    /// Included on-chain with a successful status
+    Confirmed,
+    /// Included on-chain but reverted
+    Reverted,
+}
+
+/// Result of executing a prepared transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionResult {
+    /// Execution status
+    pub status: ExecutionStatus,
+    /// Transaction hash (0x-prefixed)
+    pub tx_hash: String,
+    /// Chain the transaction was sent to
+    pub chain_id: u64,
+    /// Nonce used for the transaction
+    pub nonce: u64,
+    /// Gas limit used for the transaction
+    pub gas_limit: u64,
+    /// Gas estimated by the pre-flight simulation
+    pub simulated_gas: Option<u64>,
+    /// Block the transaction was included in (if confirmed/reverted)
+    pub block_number: Option<u64>,
+    /// Gas actually used (if confirmed/reverted)
+    pub gas_used: Option<u64>,
+    /// Effective gas price in wei (if confirmed/reverted)
+    pub effective_gas_price: Option<String>,
+    /// Decoded effect of the transaction
+    pub intent: TxIntent,
+}
+
+/// Error type for execution failures
+#[derive(Debug, thiserror::Error)]
+pub enum ExecutionError {
+    #[error("RPC URL not configured for chain {0}")]
+    NoRpcUrl(u64),
+
+    #[error("Invalid RPC URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("Transaction rejected: {0}")]
+    Intent(#[from] IntentError),
+
+    #[error("Transaction rejected: only Odos router swaps can be executed")]
+    NotASwap,
+
+    #[error("Chain mismatch: transaction prepared for chain {expected}, RPC reports {actual}")]
+    ChainMismatch { expected: u64, actual: u64 },
+
+    #[error("Simulation reverted: {0}")]
+    SimulationReverted(String),
+
+    #[error("Simulation failed: {0}")]
+    Simulation(String),
+
+    #[error("Could not determine gas limit for transaction")]
+    NoGasLimit,
+
+    #[error("Signing failed: {0}")]
+    Signing(String),
+
+    #[error("Broadcast failed: {0}")]
+    Broadcast(String),
+
+    #[error("RPC error: {0}")]
+    Rpc(String),
+
+    #[error("Broadcast {tx_hash}, but failed to get its receipt: {reason}")]
+    Receipt { tx_hash: String, reason: String },
+}
+
+impl ExecutionError {
+    /// Whether the transaction may have been broadcast before the error
+    pub fn was_broadcast(&self) -> bool {
+        matches!(self, Self::Receipt { .. })
+    }
+}
+
+/// Execution settings
+#[derive(Debug, Clone)]
+pub struct ExecutionConfig {
+    /// How long to wait for a receipt before reporting `Pending`
+    pub receipt_timeout: Duration,
+    /// Confirmations required before reporting a final status
+    pub confirmations: u64,
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        Self {
+            receipt_timeout: Duration::from_secs(120),
+            confirmations: 1,
+        }
+    }
+}
+
+/// Executes prepared swap transactions end-to-end
+pub struct SwapExecutor {
+    wallet: Arc<SecureWallet>,
+    rpc_config: RpcConfig,
+    config: ExecutionConfig,
+}
+
+impl SwapExecutor {
+    /// Create a new executor with default settings
+    pub fn new(wallet: Arc<SecureWallet>, rpc_config: RpcConfig) -> Self {
+        Self {
+            wallet,
+            rpc_config,
+            config: ExecutionConfig::default(),
+        }
+    }
+
+    /// Override the execution settings
+    pub fn with_config(mut self, config: ExecutionConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Public address of the executing wallet
+    pub fn wallet_address(&self) -> String {
+        self.wallet.address_string()
+    }
+
+    /// Run the full prepare → simulate → sign → broadcast → receipt pipeline
+    ///
+    /// # Arguments
+    /// * `prepared` - Transaction prepared by `OdosTool::prepare_swap`
+    /// * `wait_for_receipt` - Wait for the receipt, or return `Pending` right after broadcast
+    pub async fn execute(
+        &self,
+        prepared: &PreparedTransaction,
+        wait_for_receipt: bool,
+    ) -> Result<ExecutionResult, ExecutionError> {
+        let rpc_url = self
+            .rpc_config
+            .get(prepared.chain_id)
+            .ok_or(ExecutionError::NoRpcUrl(prepared.chain_id))?
+            .to_string();
+
+        // Step 1: only swaps through an allowlisted router, paying this wallet
+        let from = self.wallet.address();
+        let intent = Self::swap_intent(prepared, from)?;
+
+        let url: url::Url = rpc_url
+            .parse()
+            .map_err(|e| ExecutionError::InvalidUrl(format!("{}", e)))?;
+        let provider = ProviderBuilder::new().connect_http(url);
+
+        // Make sure the RPC is actually on the chain we prepared for
+        let actual_chain = provider
+            .get_chain_id()
+            .await
+            .map_err(|e| ExecutionError::Rpc(format!("Failed to get chain ID: {}", e)))?;
+        if actual_chain != prepared.chain_id {
+            return Err(ExecutionError::ChainMismatch {
+                expected: prepared.chain_id,
+                actual: actual_chain,
+            });
+        }
+
+        // Step 2: simulate before anything touches the key
+        let simulator = TransactionSimulator::new(rpc_url, prepared.chain_id);
+        let simulation = simulator
+            .simulate_request(from, prepared.to, prepared.data.clone(), prepared.value)
+            .await
+            .map_err(|e| ExecutionError::Simulation(e.to_string()))?;
+        if !simulation.success {
+            return Err(ExecutionError::SimulationReverted(
+                simulation
+                    .revert_reason
+                    .unwrap_or_else(|| "unknown reason".to_string()),
+            ));
+        }
+
+        let gas_limit = Self::resolve_gas_limit(prepared.gas_limit, simulation.gas_used)
+            .ok_or(ExecutionError::NoGasLimit)?;
+
+        // Step 3: build and sign the EIP-1559 transaction
+        let nonce = provider
+            .get_transaction_count(from)
+            .pending()
+            .await
+            .map_err(|e| ExecutionError::Rpc(format!("Failed to get nonce: {}", e)))?;
+        let fees = provider
+            .estimate_eip1559_fees()
+            .await
+            .map_err(|e| ExecutionError::Rpc(format!("Failed to estimate fees: {}", e)))?;
+
+        let tx = TxEip1559 {
+            chain_id: prepared.chain_id,
+            nonce,
+            gas_limit,
+            max_fee_per_gas: fees.max_fee_per_gas,
+            max_priority_fee_per_gas: fees.max_priority_fee_per_gas,
+            to: TxKind::Call(prepared.to),
+            value: prepared.value,
+            input: prepared.data.clone(),
+            ..Default::default()
+        };
+
+        let envelope = self
+            .wallet
+            .sign_transaction(tx)
+            .await
+            .map_err(|e| ExecutionError::Signing(e.to_string()))?;
+        let tx_hash = format!("{}", envelope.tx_hash());
+        let raw = envelope.encoded_2718();
+
+        // Step 4: broadcast
+        let pending = provider
+            .send_raw_transaction(&raw)
+            .await
+            .map_err(|e| ExecutionError::Broadcast(e.to_string()))?;
+
+        tracing::info!(
+            tx_hash = %tx_hash,
+            chain_id = prepared.chain_id,
+            nonce = nonce,
+            gas_limit = gas_limit,
+            "Broadcast swap transaction"
+        );
+
+        let mut result = ExecutionResult {
+            status: ExecutionStatus::Pending,
+            tx_hash,
+            chain_id: prepared.chain_id,
+            nonce,
+            gas_limit,
+            simulated_gas: simulation.gas_used,
+            block_number: None,
+            gas_used: None,
+            effective_gas_price: None,
+            intent,
+        };
+
+        if !wait_for_receipt {
+            return Ok(result);
+        }
+
+        // Step 5: wait for the receipt
+        let receipt = pending
+            .with_required_confirmations(self.config.confirmations)
+            .with_timeout(Some(self.config.receipt_timeout))
+            .get_receipt()
+            .await;
+
+        match receipt {
+            Ok(receipt) => {
+                result.status = if receipt.status() {
+                    ExecutionStatus::Confirmed
+                } else {
+                    ExecutionStatus::Reverted
+                };
+                result.block_number = receipt.block_number;
+                result.gas_used = Some(receipt.gas_used);
+                result.effective_gas_price = Some(receipt.effective_gas_price.to_string());
+            }
+            Err(PendingTransactionError::TxWatcher(WatchTxError::Timeout)) => {
+                tracing::warn!(
+                    tx_hash = %result.tx_hash,
+                    timeout_secs = self.config.receipt_timeout.as_secs(),
+                    "Timed out waiting for receipt; transaction still pending"
+                );
+            }
+            Err(e) => {
+                return Err(ExecutionError::Receipt {
+                    tx_hash: result.tx_hash,
+                    reason: e.to_string(),
+                })
+            }
+        }
+
+        tracing::info!(
+            tx_hash = %result.tx_hash,
+            status = ?result.status,
+            block_number = ?result.block_number,
+            "Swap execution finished"
+        );
+
+        Ok(result)
+    }
+
+    /// Decode the transaction's intent and require a router swap
+    fn swap_intent(
+        prepared: &PreparedTransaction,
+        from: Address,
+    ) -> Result<TxIntent, ExecutionError> {
+        let tx = TxEip1559 {
+            chain_id: prepared.chain_id,
+            to: TxKind::Call(prepared.to),
+            value: prepared.value,
+            input: prepared.data.clone(),
+            ..Default::default()
+        };
+        match decode_intent(&tx, from)? {
+            TxIntent::Approve { .. } => Err(ExecutionError::NotASwap),
+            intent => Ok(intent),
+        }
+    }
+
+    /// Pick the gas limit: prepared value if set, otherwise simulated gas plus a buffer
+    fn resolve_gas_limit(prepared: u64, simulated: Option<u64>) -> Option<u64> {
+        if prepared > 0 {
+            return Some(prepared);
+        }
+        simulated
+            .filter(|gas| *gas > 0)
+            .map(|gas| gas + gas * GAS_BUFFER_PERCENT / 100)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{Bytes, U256};
+    use std::collections::HashMap;
+
+    /// Anvil's first default account (DO NOT use in production!)
+    const ANVIL_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+    #[test]
+    fn test_resolve_gas_limit_prefers_prepared() {
+        assert_eq!(
+            SwapExecutor::resolve_gas_limit(300_000, Some(100_000)),
+            Some(300_000)
+        );
+    }
+
+    #[test]
+    fn test_resolve_gas_limit_buffers_simulation() {
+        assert_eq!(
+            SwapExecutor::resolve_gas_limit(0, Some(100_000)),
+            Some(120_000)
+        );
+        assert_eq!(SwapExecutor::resolve_gas_limit(0, Some(0)), None);
+        assert_eq!(SwapExecutor::resolve_gas_limit(0, None), None);
+    }
+
+    #[test]
+    fn test_execution_status_serialization() {
+        assert_eq!(
+            serde_json::to_value(ExecutionStatus::Confirmed).unwrap(),
+            serde_json::json!("confirmed")
+        );
+        assert_eq!(
+            serde_json::to_value(ExecutionStatus::Reverted).unwrap(),
+            serde_json::json!("reverted")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_rpc_url() {
+        let wallet = Arc::new(SecureWallet::from_hex(ANVIL_KEY).unwrap());
+        let executor = SwapExecutor::new(wallet, RpcConfig::with_urls(HashMap::new()));
+
+        let prepared = PreparedTransaction {
+            to: Address::ZERO,
+            data: Bytes::new(),
+            value: U256::ZERO,
+            gas_limit: 21_000,
+            chain_id: 999,
+        };
+
+        let err = executor.execute(&prepared, true).await.unwrap_err();
+        assert!(matches!(err, ExecutionError::NoRpcUrl(999)));
+    }
+
+    #[tokio::test]
+    async fn test_refuses_non_router_transaction() {
+        // The RPC URL is never contacted: the intent check comes first
+        let mut urls = HashMap::new();
+        urls.insert(1, "http://127.0.0.1:9".to_string());
+
+        let wallet = Arc::new(SecureWallet::from_hex(ANVIL_KEY).unwrap());
+        let executor = SwapExecutor::new(wallet, RpcConfig::with_urls(urls));
+
+        // A plain native transfer to Anvil's second default account
+        let recipient: Address = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8"
+            .parse()
+            .unwrap();
+        let prepared = PreparedTransaction {
+            to: recipient,
+            data: Bytes::new(),
+            value: U256::from(1_000_000_000_000_000u64),
+            gas_limit: 0,
+            chain_id: 1,
+        };
+
+        let err = executor.execute(&prepared, true).await.unwrap_err();
+        assert!(matches!(
+            err,
+            ExecutionError::Intent(IntentError::TargetNotAllowed { .. })
+        ));
+    }
+
+    /// Router stand-in: succeeds with more than 100k gas left, reverts otherwise.
+    /// Simulation runs with plenty of gas, so a low prepared gas limit gives a
+    /// transaction that passes simulation and reverts once mined.
+    const GAS_GATED_ROUTER: &str = "0x5a620186a010600d57600080fd5b00";
+
+    fn anvil_swap_calldata(recipient: Address) -> Bytes {
+        use crate::wallet::intent::{swapCall, swapTokenInfo};
+        use alloy::sol_types::SolCall;
+
+        swapCall {
+            tokenInfo: swapTokenInfo {
+                inputToken: Address::ZERO,
+                inputAmount: U256::ZERO,
+                inputReceiver: Address::ZERO,
+                outputToken: Address::repeat_byte(0x22),
+                outputQuote: U256::from(500u64),
+                outputMin: U256::from(495u64),
+                outputReceiver: recipient,
+            },
+            pathDefinition: Bytes::new(),
+            executor: Address::repeat_byte(0x33),
+            referralCode: 0,
+        }
+        .abi_encode()
+        .into()
+    }
+
+    /// Runs against a local node: `ANVIL_RPC_URL=http://127.0.0.1:8545 cargo test -- --ignored anvil`
+    #[tokio::test]
+    #[ignore]
+    async fn test_anvil_executes_swap_pipeline() {
+        use crate::wallet::intent::ODOS_ROUTER_V3;
+
+        let Ok(rpc_url) = std::env::var("ANVIL_RPC_URL") else {
+            eprintln!("ANVIL_RPC_URL not set; skipping");
+            return;
+        };
+
+        let provider = ProviderBuilder::new().connect_http(rpc_url.parse().unwrap());
+        let chain_id = provider.get_chain_id().await.unwrap();
+        let _: serde_json::Value = provider
+            .raw_request("anvil_setCode".into(), (ODOS_ROUTER_V3, GAS_GATED_ROUTER))
+            .await
+            .unwrap();
+
+        let mut urls = HashMap::new();
+        urls.insert(chain_id, rpc_url);
+        let wallet = Arc::new(SecureWallet::from_hex(ANVIL_KEY).unwrap());
+        let data = anvil_swap_calldata(wallet.address());
+        let executor = SwapExecutor::new(wallet, RpcConfig::with_urls(urls));
+
+        let prepared = PreparedTransaction {
+            to: ODOS_ROUTER_V3,
+            data: data,
+            value: U256::ZERO,
+            gas_limit: 300_000,
+            chain_id,
+        };
+        let confirmed = executor.execute(&prepared, true).await.unwrap();
+        assert_eq!(confirmed.status, ExecutionStatus::Confirmed);
+        assert!(confirmed.block_number.is_some());
+        assert!(confirmed.gas_used.is_some());
+
+        let prepared = PreparedTransaction {
+            gas_limit: 60_000,
+            ..prepared
+        };
+        let reverted = executor.execute(&prepared, true).await.unwrap();
+        assert_eq!(reverted.status, ExecutionStatus::Reverted);
+        assert_eq!(reverted.nonce, confirmed.nonce + 1);
+        assert!(reverted.simulated_gas.is_some());
+    }
+}
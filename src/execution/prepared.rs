@@ -0,0 +1,124 @@
+//! Server-side record of prepared swaps
+//!
+//! `OdosTool::prepare_swap` records every transaction it builds, keyed by a
+//! hash of its target, calldata, value and chain. Execution only accepts
+//! transactions found here, so the agent cannot hand the executor calldata of
+//! its own, and spend and quote freshness checks read the quoted amounts,
+//! time and block instead of trusting what the agent reports about the trade.
+
+use crate::wallet::PreparedTransaction;
+use alloy::primitives::{Address, U256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long an unexecuted prepared swap is remembered
+const RETENTION: Duration = Duration::from_secs(3600);
+
+/// What `prepare_swap` quoted for a transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparedSwap {
+    pub chain_id: u64,
+    /// Token sold (the native placeholder for native-currency swaps)
+    pub input_token: Address,
+    /// Amount sold, in the input token's base units
+    pub input_amount: U256,
+    /// Token bought
+    pub output_token: Address,
+    /// Slippage the swap was quoted with
+    pub slippage_percent: f64,
+    /// Native value sent with the transaction (wei)
+    pub native_value: U256,
+    /// Unix time the quote was received
+    pub issued_at: i64,
+    /// Unix time after which the quoted path can no longer be assembled
+    pub expires_at: i64,
+    /// Block the quote was priced at
+    pub block_number: u64,
+}
+
+/// Prepared swaps awaiting execution, shared by the swap and execution tools
+#[derive(Default)]
+pub struct PreparedSwaps {
+    entries: Mutex<HashMap<String, (PreparedSwap, Instant)>>,
+}
+
+impl PreparedSwaps {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Key of a transaction: hex blake3 of chain, target, value and calldata
+    ///
+    /// The gas limit is left out; it is re-estimated at execution anyway.
+    fn key(tx: &PreparedTransaction) -> String {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&tx.chain_id.to_be_bytes());
+        hasher.update(tx.to.as_slice());
+        hasher.update(&tx.value.to_be_bytes::<32>());
+        hasher.update(&tx.data);
+        hasher.finalize().to_hex().to_string()
+    }
+
+    /// Remember a transaction built by `prepare_swap`
+    pub fn record(&self, tx: &PreparedTransaction, swap: PreparedSwap) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, (_, recorded)| recorded.elapsed() < RETENTION);
+        entries.insert(Self::key(tx), (swap, Instant::now()));
+    }
+
+    /// The swap `tx` was prepared for, if it was
+    pub fn get(&self, tx: &PreparedTransaction) -> Option<PreparedSwap> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(&Self::key(tx))
+            .filter(|(_, recorded)| recorded.elapsed() < RETENTION)
+            .map(|(swap, _)| swap.clone())
+    }
+
+    /// Forget `tx` once it has been broadcast, so it cannot be executed twice
+    pub fn remove(&self, tx: &PreparedTransaction) -> Option<PreparedSwap> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.remove(&Self::key(tx)).map(|(swap, _)| swap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::Bytes;
+
+    fn tx(data: &'static [u8], gas_limit: u64) -> PreparedTransaction {
+        PreparedTransaction {
+            to: Address::repeat_byte(0x11),
+            data: Bytes::from_static(data),
+            value: U256::ZERO,
+            gas_limit,
+            chain_id: 1,
+        }
+    }
+
+    #[test]
+    fn test_matches_only_recorded_transactions() {
+        let swaps = PreparedSwaps::new();
+        let swap = PreparedSwap {
+            chain_id: 1,
+            input_token: Address::repeat_byte(0x22),
+            input_amount: U256::from(1_000_000u64),
+            output_token: Address::repeat_byte(0x33),
+            slippage_percent: 0.5,
+            native_value: U256::ZERO,
+            issued_at: 1_700_000_000,
+            expires_at: 1_700_000_060,
+            block_number: 21_000_000,
+        };
+        swaps.record(&tx(&[0x83, 0xbd], 300_000), swap.clone());
+
+        // Gas limit doesn't matter; calldata does
+        assert_eq!(swaps.get(&tx(&[0x83, 0xbd], 0)), Some(swap.clone()));
+        assert_eq!(swaps.get(&tx(&[0x83, 0xbe], 300_000)), None);
+
+        assert_eq!(swaps.remove(&tx(&[0x83, 0xbd], 0)), Some(swap));
+        assert_eq!(swaps.get(&tx(&[0x83, 0xbd], 0)), None);
+    }
+}
@@ -0,0 +1,292 @@
+//! Compact subgraph schemas from GraphQL introspection
+//!
+//! Graph Node schemas are large once filter inputs and order-by enums are
+//! included. The agent only needs the entity types, their fields and the root
+//! query fields; argument conventions (`first`, `skip`, `where`, `orderBy`,
+//! `orderDirection`, `block`) are the same for every subgraph.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Introspection query for the types and fields that make up a [`SubgraphSchema`]
+pub const INTROSPECTION_QUERY: &str = r#"
+query IntrospectSchema {
+  __schema {
+    queryType { name }
+    types {
+      name
+      kind
+      fields {
+        name
+        type { ...TypeRef }
+      }
+    }
+  }
+}
+
+fragment TypeRef on __Type {
+  kind
+  name
+  ofType {
+    kind
+    name
+    ofType {
+      kind
+      name
+      ofType { kind name }
+    }
+  }
+}
+"#;
+
+/// Entity types and query fields of a subgraph
+///
+/// Field types are rendered in SDL notation (`"[Pool!]!"`, `"BigDecimal!"`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SubgraphSchema {
+    /// Name of the root query type
+    #[serde(skip)]
+    pub query_type: String,
+    /// Root query fields and their return types
+    pub queries: BTreeMap<String, String>,
+    /// Object and interface types with their fields
+    pub entities: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl SubgraphSchema {
+    /// Build from the `data` of an [`INTROSPECTION_QUERY`] response
+    pub fn from_introspection(data: Value) -> Result<Self, serde_json::Error> {
+        let response: IntrospectionData = serde_json::from_value(data)?;
+        let schema = response.schema;
+        let query_type = schema.query_type.name;
+
+        let mut queries = BTreeMap::new();
+        let mut entities = BTreeMap::new();
+        for ty in schema.types {
+            let Some(fields) = ty.fields else { continue };
+            let fields: BTreeMap<String, String> = fields
+                .into_iter()
+                .filter(|f| !f.name.starts_with("__"))
+                .map(|f| (f.name, f.ty.render()))
+                .collect();
+            if ty.name == query_type {
+                queries = fields;
+            } else if matches!(ty.kind.as_str(), "OBJECT" | "INTERFACE")
+                && !ty.name.starts_with("__")
+                && ty.name != "Subscription"
+            {
+                entities.insert(ty.name, fields);
+            }
+        }
+
+        Ok(Self {
+            query_type,
+            queries,
+            entities,
+        })
+    }
+
+    /// Fields of a type (the query type resolves to the root query fields)
+    pub fn fields(&self, type_name: &str) -> Option<&BTreeMap<String, String>> {
+        if type_name == self.query_type {
+            Some(&self.queries)
+        } else {
+            self.entities.get(type_name)
+        }
+    }
+
+    /// Restrict to the named entities (case-insensitive) and the queries returning them
+    pub fn only_entities(&self, names: &[String]) -> Self {
+        let keep = |name: &str| names.iter().any(|n| n.eq_ignore_ascii_case(name));
+        Self {
+            query_type: self.query_type.clone(),
+            queries: self
+                .queries
+                .iter()
+                .filter(|(_, ty)| keep(named_type(ty)))
+                .map(|(name, ty)| (name.clone(), ty.clone()))
+                .collect(),
+            entities: self
+                .entities
+                .iter()
+                .filter(|(name, _)| keep(name))
+                .map(|(name, fields)| (name.clone(), fields.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Named type inside list/non-null wrappers (`"[Pool!]!"` -> `"Pool"`)
+pub fn named_type(rendered: &str) -> &str {
+    rendered.trim_matches(|c| matches!(c, '[' | ']' | '!'))
+}
+
+#[derive(Deserialize)]
+struct IntrospectionData {
+    #[serde(rename = "__schema")]
+    schema: RawSchema,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSchema {
+    query_type: NamedRef,
+    types: Vec<RawType>,
+}
+
+#[derive(Deserialize)]
+struct NamedRef {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct RawType {
+    name: String,
+    kind: String,
+    fields: Option<Vec<RawField>>,
+}
+
+#[derive(Deserialize)]
+struct RawField {
+    name: String,
+    #[serde(rename = "type")]
+    ty: TypeRef,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TypeRef {
+    kind: String,
+    name: Option<String>,
+    of_type: Option<Box<TypeRef>>,
+}
+
+impl TypeRef {
+    fn render(&self) -> String {
+        let inner = || {
+            self.of_type
+                .as_ref()
+                .map(|t| t.render())
+                .unwrap_or_default()
+        };
+        match self.kind.as_str() {
+            "NON_NULL" => format!("{}!", inner()),
+            "LIST" => format!("[{}]", inner()),
+            _ => self.name.clone().unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn named(kind: &str, name: &str) -> Value {
+        json!({ "kind": kind, "name": name, "ofType": null })
+    }
+
+    fn non_null(inner: Value) -> Value {
+        json!({ "kind": "NON_NULL", "name": null, "ofType": inner })
+    }
+
+    fn list(inner: Value) -> Value {
+        json!({ "kind": "LIST", "name": null, "ofType": inner })
+    }
+
+    /// Introspection response for a two-entity subgraph
+    fn introspection_fixture() -> Value {
+        json!({
+            "__schema": {
+                "queryType": { "name": "Query" },
+                "types": [
+                    {
+                        "name": "Query",
+                        "kind": "OBJECT",
+                        "fields": [
+                            { "name": "pool", "type": named("OBJECT", "Pool") },
+                            {
+                                "name": "pools",
+                                "type": non_null(list(non_null(named("OBJECT", "Pool"))))
+                            },
+                            {
+                                "name": "tokens",
+                                "type": non_null(list(non_null(named("OBJECT", "Token"))))
+                            },
+                            { "name": "_meta", "type": named("OBJECT", "_Meta_") },
+                            { "name": "__type", "type": named("OBJECT", "__Type") }
+                        ]
+                    },
+                    {
+                        "name": "Pool",
+                        "kind": "OBJECT",
+                        "fields": [
+                            { "name": "id", "type": non_null(named("SCALAR", "ID")) },
+                            { "name": "token0", "type": non_null(named("OBJECT", "Token")) },
+                            {
+                                "name": "totalValueLockedUSD",
+                                "type": non_null(named("SCALAR", "BigDecimal"))
+                            }
+                        ]
+                    },
+                    {
+                        "name": "Token",
+                        "kind": "OBJECT",
+                        "fields": [
+                            { "name": "id", "type": non_null(named("SCALAR", "ID")) },
+                            { "name": "symbol", "type": non_null(named("SCALAR", "String")) }
+                        ]
+                    },
+                    {
+                        "name": "_Meta_",
+                        "kind": "OBJECT",
+                        "fields": [
+                            { "name": "hasIndexingErrors", "type": non_null(named("SCALAR", "Boolean")) }
+                        ]
+                    },
+                    { "name": "Pool_filter", "kind": "INPUT_OBJECT", "fields": null },
+                    { "name": "__Type", "kind": "OBJECT", "fields": [] }
+                ]
+            }
+        })
+    }
+
+    #[test]
+    fn test_schema_from_introspection() {
+        let schema = SubgraphSchema::from_introspection(introspection_fixture()).unwrap();
+        assert_eq!(schema.query_type, "Query");
+        assert_eq!(schema.queries["pools"], "[Pool!]!");
+        assert_eq!(schema.queries["pool"], "Pool");
+        assert!(!schema.queries.contains_key("__type"));
+        assert_eq!(
+            schema.entities["Pool"]["totalValueLockedUSD"],
+            "BigDecimal!"
+        );
+        assert!(schema.entities.contains_key("_Meta_"));
+        assert!(!schema.entities.contains_key("Pool_filter"));
+        assert!(!schema.entities.contains_key("__Type"));
+        assert_eq!(
+            schema
+                .fields("Query")
+                .unwrap()
+                .get("tokens")
+                .map(String::as_str),
+            Some("[Token!]!")
+        );
+    }
+
+    #[test]
+    fn test_only_entities() {
+        let schema = SubgraphSchema::from_introspection(introspection_fixture()).unwrap();
+        let tokens = schema.only_entities(&["token".to_string()]);
+        assert_eq!(tokens.entities.keys().collect::<Vec<_>>(), vec!["Token"]);
+        assert_eq!(tokens.queries.keys().collect::<Vec<_>>(), vec!["tokens"]);
+    }
+
+    #[test]
+    fn test_named_type() {
+        assert_eq!(named_type("[Pool!]!"), "Pool");
+        assert_eq!(named_type("BigInt"), "BigInt");
+    }
+}
@@ -1,16 +1,119 @@
-//! GraphQL schemas and generated types for The Graph subgraphs
+//! GraphQL schemas and typed queries for The Graph subgraphs
 //!
-//! This module contains:
-//! - GraphQL schema files for each protocol (uniswap_v3/, aave_v3/, etc.)
-//! - Generated Rust types from graphql-client
+//! Each protocol directory vendors the subgraph's API schema next to its query
+//! documents. `graphql_client` validates every query against the schema and
+//! generates request/response types at compile time, so a renamed or removed
+//! field is a build error instead of a silently missing value at runtime.
 //!
-//! To regenerate types after schema changes:
-//! ```bash
-//! cargo build  # graphql-client generates types at compile time
-//! ```
-
-// Note: The actual GraphQL schemas are stored as .graphql files in the
-// subdirectories. The graphql_client derive macro reads these at compile time.
-//
-// For now, TheGraphTool uses raw GraphQL strings for flexibility.
-// A future enhancement could use graphql_client for compile-time type safety.
+//! Subgraph scalars (`BigDecimal`, `BigInt`) arrive as JSON strings. They are
+//! parsed into the domain types in each protocol module, in one place.
+//!
+//! Agent-authored queries can't be checked at compile time. They are checked
+//! at runtime against the subgraph's introspected schema instead
+//! ([`introspection`], [`validation`]).
+
+pub mod introspection;
+pub mod uniswap_v3;
+pub mod validation;
+
+use std::str::FromStr;
+
+/// Block that subgraph queries are pinned to
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum BlockPin {
+    /// Latest indexed block
+    #[default]
+    Latest,
+    Number(u64),
+    Hash(String),
+}
+
+impl BlockPin {
+    pub fn is_latest(&self) -> bool {
+        matches!(self, BlockPin::Latest)
+    }
+
+    /// Value for a `$block: Block_height` variable in an untyped query (null means latest)
+    pub fn to_variable(&self) -> serde_json::Value {
+        match self {
+            BlockPin::Latest => serde_json::Value::Null,
+            BlockPin::Number(number) => serde_json::json!({ "number": number }),
+            BlockPin::Hash(hash) => serde_json::json!({ "hash": hash }),
+        }
+    }
+
+    /// Typed `Block_height` variable for a generated query (None means latest)
+    pub fn height<T>(&self) -> Option<T>
+    where
+        for<'a> T: From<&'a BlockPin>,
+    {
+        (!self.is_latest()).then(|| T::from(self))
+    }
+}
+
+/// Error converting a subgraph response into domain types
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DecodeError {
+    #[error("Invalid numeric value for {field}: '{value}'")]
+    InvalidNumber { field: &'static str, value: String },
+}
+
+/// Parse a `BigDecimal` (or large `BigInt`) scalar into a float
+pub(crate) fn parse_decimal(field: &'static str, value: &str) -> Result<f64, DecodeError> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| DecodeError::InvalidNumber {
+            field,
+            value: value.to_string(),
+        })
+}
+
+/// Parse a `BigInt` scalar that fits a native integer
+pub(crate) fn parse_int<T: FromStr>(field: &'static str, value: &str) -> Result<T, DecodeError> {
+    value.parse::<T>().map_err(|_| DecodeError::InvalidNumber {
+        field,
+        value: value.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!(parse_decimal("tvl", "1234.5").unwrap(), 1234.5);
+        assert_eq!(parse_decimal("price", "1e-18").unwrap(), 1e-18);
+        assert!(parse_decimal("tvl", "").is_err());
+        assert!(parse_decimal("tvl", "NaN").is_err());
+    }
+
+    #[test]
+    fn test_parse_int() {
+        assert_eq!(parse_int::<u32>("feeTier", "3000").unwrap(), 3000);
+        assert_eq!(parse_int::<i32>("tick", "-887272").unwrap(), -887_272);
+        let err = parse_int::<u8>("decimals", "1.5").unwrap_err();
+        assert_eq!(
+            err,
+            DecodeError::InvalidNumber {
+                field: "decimals",
+                value: "1.5".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_block_pin_variables() {
+        assert_eq!(BlockPin::Latest.to_variable(), serde_json::Value::Null);
+        assert_eq!(
+            BlockPin::Number(19_000_000).to_variable(),
+            serde_json::json!({ "number": 19_000_000 })
+        );
+        assert_eq!(
+            BlockPin::Hash("0xabc".to_string()).to_variable(),
+            serde_json::json!({ "hash": "0xabc" })
+        );
+    }
+}
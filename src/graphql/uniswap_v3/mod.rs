@@ -0,0 +1,912 @@
+//! Uniswap V3 subgraph queries and domain types
+//!
+//! Generated modules (`pools`, `token_price`, ...) carry the raw, string-typed
+//! scalars; the `TryFrom` impls below turn them into the numeric domain types
+//! returned by `TheGraphTool`. Serialized field names follow the subgraph so
+//! agent code reads the same keys as before.
+
+use super::{parse_decimal, parse_int, BlockPin, DecodeError};
+use graphql_client::GraphQLQuery;
+use serde::Serialize;
+
+type BigDecimal = String;
+type BigInt = String;
+type Bytes = String;
+
+/// Pools ordered by TVL, filtered by `Pool_filter`
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/uniswap_v3/schema.graphql",
+    query_path = "src/graphql/uniswap_v3/pools.graphql",
+    variables_derives = "Debug, Clone, Default",
+    response_derives = "Debug, Clone",
+    skip_serializing_none
+)]
+pub struct Pools;
+
+/// Pools ordered by id, for walking a run of pools with equal TVL
+///
+/// Declares the same variables and selection as `Pools`, so it is sent with
+/// `pools::Variables` and decoded as `pools::ResponseData`.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/uniswap_v3/schema.graphql",
+    query_path = "src/graphql/uniswap_v3/pools_at_tvl.graphql",
+    variables_derives = "Debug, Clone, Default",
+    response_derives = "Debug, Clone",
+    skip_serializing_none
+)]
+pub struct PoolsAtTvl;
+
+/// Token fields plus the ETH/USD bundle price
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/uniswap_v3/schema.graphql",
+    query_path = "src/graphql/uniswap_v3/token_price.graphql",
+    variables_derives = "Debug, Clone, Default",
+    response_derives = "Debug, Clone",
+    skip_serializing_none
+)]
+pub struct TokenPrice;
+
+/// Hourly volume/fees for a set of pools since a timestamp
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/uniswap_v3/schema.graphql",
+    query_path = "src/graphql/uniswap_v3/trailing_activity.graphql",
+    variables_derives = "Debug, Clone, Default",
+    response_derives = "Debug, Clone",
+    skip_serializing_none
+)]
+pub struct TrailingActivity;
+
+/// Daily snapshots of one pool
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/uniswap_v3/schema.graphql",
+    query_path = "src/graphql/uniswap_v3/pool_day_series.graphql",
+    variables_derives = "Debug, Clone, Default",
+    response_derives = "Debug, Clone",
+    skip_serializing_none
+)]
+pub struct PoolDaySeries;
+
+/// Hourly snapshots of one pool
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/uniswap_v3/schema.graphql",
+    query_path = "src/graphql/uniswap_v3/pool_hour_series.graphql",
+    variables_derives = "Debug, Clone, Default",
+    response_derives = "Debug, Clone",
+    skip_serializing_none
+)]
+pub struct PoolHourSeries;
+
+/// Daily snapshots of one token
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/uniswap_v3/schema.graphql",
+    query_path = "src/graphql/uniswap_v3/token_day_series.graphql",
+    variables_derives = "Debug, Clone, Default",
+    response_derives = "Debug, Clone",
+    skip_serializing_none
+)]
+pub struct TokenDaySeries;
+
+/// Initialized ticks of one pool within a tick range
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/uniswap_v3/schema.graphql",
+    query_path = "src/graphql/uniswap_v3/pool_ticks.graphql",
+    variables_derives = "Debug, Clone, Default",
+    response_derives = "Debug, Clone",
+    skip_serializing_none
+)]
+pub struct PoolTicks;
+
+/// Recent swaps on a pool or token, newest first
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/uniswap_v3/schema.graphql",
+    query_path = "src/graphql/uniswap_v3/swaps.graphql",
+    variables_derives = "Debug, Clone, Default",
+    response_derives = "Debug, Clone",
+    skip_serializing_none
+)]
+pub struct Swaps;
+
+/// Recent liquidity additions on a pool or token, newest first
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/uniswap_v3/schema.graphql",
+    query_path = "src/graphql/uniswap_v3/mints.graphql",
+    variables_derives = "Debug, Clone, Default",
+    response_derives = "Debug, Clone",
+    skip_serializing_none
+)]
+pub struct Mints;
+
+/// Recent liquidity removals on a pool or token, newest first
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/uniswap_v3/schema.graphql",
+    query_path = "src/graphql/uniswap_v3/burns.graphql",
+    variables_derives = "Debug, Clone, Default",
+    response_derives = "Debug, Clone",
+    skip_serializing_none
+)]
+pub struct Burns;
+
+macro_rules! block_height_from_pin {
+    ($($module:ident),+ $(,)?) => {
+        $(
+            impl From<&BlockPin> for $module::Block_height {
+                fn from(pin: &BlockPin) -> Self {
+                    match pin {
+                        BlockPin::Latest => Self {
+                            hash: None,
+                            number: None,
+                            number_gte: None,
+                        },
+                        BlockPin::Number(number) => Self {
+                            hash: None,
+                            number: Some(i64::try_from(*number).unwrap_or(i64::MAX)),
+                            number_gte: None,
+                        },
+                        BlockPin::Hash(hash) => Self {
+                            hash: Some(hash.clone()),
+                            number: None,
+                            number_gte: None,
+                        },
+                    }
+                }
+            }
+        )+
+    };
+}
+
+block_height_from_pin!(
+    pools,
+    token_price,
+    trailing_activity,
+    pool_day_series,
+    pool_hour_series,
+    token_day_series,
+    pool_ticks,
+    swaps,
+    mints,
+    burns,
+);
+
+/// Entity an event query is scoped to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventScope {
+    /// Events on one pool (id)
+    Pool(String),
+    /// Events on any pool containing a token (address)
+    Token(String),
+}
+
+/// Scope and inclusive time range for an event query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventWindow {
+    pub scope: EventScope,
+    pub start: i64,
+    pub end: i64,
+}
+
+macro_rules! event_filter_from {
+    ($($module:ident :: $filter:ident),+ $(,)?) => {
+        $(
+            impl From<&EventWindow> for $module::$filter {
+                fn from(window: &EventWindow) -> Self {
+                    let start = Some(window.start.to_string());
+                    let end = Some(window.end.to_string());
+                    match &window.scope {
+                        EventScope::Pool(pool) => Self {
+                            pool: Some(pool.clone()),
+                            timestamp_gte: start,
+                            timestamp_lte: end,
+                            ..Default::default()
+                        },
+                        // Graph Node rejects column filters next to `or`, so
+                        // the time range is repeated in each branch
+                        EventScope::Token(token) => Self {
+                            or: Some(vec![
+                                Some(Self {
+                                    token0: Some(token.clone()),
+                                    timestamp_gte: start.clone(),
+                                    timestamp_lte: end.clone(),
+                                    ..Default::default()
+                                }),
+                                Some(Self {
+                                    token1: Some(token.clone()),
+                                    timestamp_gte: start,
+                                    timestamp_lte: end,
+                                    ..Default::default()
+                                }),
+                            ]),
+                            ..Default::default()
+                        },
+                    }
+                }
+            }
+        )+
+    };
+}
+
+event_filter_from!(swaps::Swap_filter, mints::Mint_filter, burns::Burn_filter);
+
+/// ERC20 token as indexed by the subgraph
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Token {
+    pub id: String,
+    pub symbol: String,
+    pub name: String,
+    pub decimals: u32,
+    /// Price in ETH, as derived by the subgraph
+    #[serde(rename = "derivedETH")]
+    pub derived_eth: f64,
+    /// All-time volume (price queries only)
+    #[serde(rename = "volumeUSD", skip_serializing_if = "Option::is_none")]
+    pub volume_usd: Option<f64>,
+    /// Current TVL (price queries only)
+    #[serde(
+        rename = "totalValueLockedUSD",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub total_value_locked_usd: Option<f64>,
+}
+
+impl Token {
+    fn parse(
+        id: String,
+        symbol: String,
+        name: String,
+        decimals: &str,
+        derived_eth: &str,
+    ) -> Result<Self, DecodeError> {
+        Ok(Self {
+            id,
+            symbol,
+            name,
+            decimals: parse_int("decimals", decimals)?,
+            derived_eth: parse_decimal("derivedETH", derived_eth)?,
+            volume_usd: None,
+            total_value_locked_usd: None,
+        })
+    }
+}
+
+/// Uniswap V3 pool
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Pool {
+    pub id: String,
+    pub token0: Token,
+    pub token1: Token,
+    /// Fee in hundredths of a bip (3000 = 0.3%)
+    #[serde(rename = "feeTier")]
+    pub fee_tier: u32,
+    /// In-range liquidity (uint128, as a float)
+    pub liquidity: f64,
+    /// Current sqrt(price) as Q64.96 (uint160, as a float)
+    #[serde(rename = "sqrtPrice")]
+    pub sqrt_price: f64,
+    /// Current tick (null before initialization)
+    pub tick: Option<i32>,
+    #[serde(rename = "token0Price")]
+    pub token0_price: f64,
+    #[serde(rename = "token1Price")]
+    pub token1_price: f64,
+    /// All-time cumulative volume
+    #[serde(rename = "volumeUSD")]
+    pub volume_usd: f64,
+    #[serde(rename = "totalValueLockedUSD")]
+    pub total_value_locked_usd: f64,
+    #[serde(rename = "txCount")]
+    pub tx_count: u64,
+    /// Trailing 24h volume (filled in from hourly data)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume_24h_usd: Option<f64>,
+    /// Trailing 24h fees (filled in from hourly data)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fees_24h_usd: Option<f64>,
+    /// Trailing 24h volume / TVL (filled in from hourly data)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume_tvl_ratio_24h: Option<f64>,
+}
+
+impl TryFrom<pools::PoolsPools> for Pool {
+    type Error = DecodeError;
+
+    fn try_from(raw: pools::PoolsPools) -> Result<Self, Self::Error> {
+        let token0 = raw.token0;
+        let token1 = raw.token1;
+        Ok(Self {
+            id: raw.id,
+            token0: Token::parse(
+                token0.id,
+                token0.symbol,
+                token0.name,
+                &token0.decimals,
+                &token0.derived_eth,
+            )?,
+            token1: Token::parse(
+                token1.id,
+                token1.symbol,
+                token1.name,
+                &token1.decimals,
+                &token1.derived_eth,
+            )?,
+            fee_tier: parse_int("feeTier", &raw.fee_tier)?,
+            liquidity: parse_decimal("liquidity", &raw.liquidity)?,
+            sqrt_price: parse_decimal("sqrtPrice", &raw.sqrt_price)?,
+            tick: raw
+                .tick
+                .as_deref()
+                .map(|t| parse_int("tick", t))
+                .transpose()?,
+            token0_price: parse_decimal("token0Price", &raw.token0_price)?,
+            token1_price: parse_decimal("token1Price", &raw.token1_price)?,
+            volume_usd: parse_decimal("volumeUSD", &raw.volume_usd)?,
+            total_value_locked_usd: parse_decimal(
+                "totalValueLockedUSD",
+                &raw.total_value_locked_usd,
+            )?,
+            tx_count: parse_int("txCount", &raw.tx_count)?,
+            volume_24h_usd: None,
+            fees_24h_usd: None,
+            volume_tvl_ratio_24h: None,
+        })
+    }
+}
+
+impl TryFrom<token_price::TokenPriceToken> for Token {
+    type Error = DecodeError;
+
+    fn try_from(raw: token_price::TokenPriceToken) -> Result<Self, Self::Error> {
+        let mut token = Token::parse(
+            raw.id,
+            raw.symbol,
+            raw.name,
+            &raw.decimals,
+            &raw.derived_eth,
+        )?;
+        token.volume_usd = Some(parse_decimal("volumeUSD", &raw.volume_usd)?);
+        token.total_value_locked_usd = Some(parse_decimal(
+            "totalValueLockedUSD",
+            &raw.total_value_locked_usd,
+        )?);
+        Ok(token)
+    }
+}
+
+/// One hour of a pool's volume and fees
+#[derive(Debug, Clone, PartialEq)]
+pub struct HourlyActivity {
+    pub pool_id: String,
+    pub period_start: i64,
+    pub volume_usd: f64,
+    pub fees_usd: f64,
+}
+
+impl TryFrom<trailing_activity::TrailingActivityPoolHourDatas> for HourlyActivity {
+    type Error = DecodeError;
+
+    fn try_from(
+        raw: trailing_activity::TrailingActivityPoolHourDatas,
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
+            pool_id: raw.pool.id.to_lowercase(),
+            period_start: raw.period_start_unix,
+            volume_usd: parse_decimal("volumeUSD", &raw.volume_usd)?,
+            fees_usd: parse_decimal("feesUSD", &raw.fees_usd)?,
+        })
+    }
+}
+
+/// Daily or hourly pool snapshot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PoolSnapshot {
+    /// Start of the period (unix seconds)
+    pub timestamp: i64,
+    #[serde(rename = "volumeUSD")]
+    pub volume_usd: f64,
+    #[serde(rename = "feesUSD")]
+    pub fees_usd: f64,
+    #[serde(rename = "tvlUSD")]
+    pub tvl_usd: f64,
+    pub liquidity: f64,
+    #[serde(rename = "token0Price")]
+    pub token0_price: f64,
+    #[serde(rename = "token1Price")]
+    pub token1_price: f64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    #[serde(rename = "txCount")]
+    pub tx_count: u64,
+}
+
+macro_rules! pool_snapshot_from {
+    ($raw:ty, $time_field:ident) => {
+        impl TryFrom<$raw> for PoolSnapshot {
+            type Error = DecodeError;
+
+            fn try_from(raw: $raw) -> Result<Self, Self::Error> {
+                Ok(Self {
+                    timestamp: raw.$time_field,
+                    volume_usd: parse_decimal("volumeUSD", &raw.volume_usd)?,
+                    fees_usd: parse_decimal("feesUSD", &raw.fees_usd)?,
+                    tvl_usd: parse_decimal("tvlUSD", &raw.tvl_usd)?,
+                    liquidity: parse_decimal("liquidity", &raw.liquidity)?,
+                    token0_price: parse_decimal("token0Price", &raw.token0_price)?,
+                    token1_price: parse_decimal("token1Price", &raw.token1_price)?,
+                    open: parse_decimal("open", &raw.open)?,
+                    high: parse_decimal("high", &raw.high)?,
+                    low: parse_decimal("low", &raw.low)?,
+                    close: parse_decimal("close", &raw.close)?,
+                    tx_count: parse_int("txCount", &raw.tx_count)?,
+                })
+            }
+        }
+    };
+}
+
+pool_snapshot_from!(pool_day_series::PoolDaySeriesPoolDayDatas, date);
+pool_snapshot_from!(
+    pool_hour_series::PoolHourSeriesPoolHourDatas,
+    period_start_unix
+);
+
+/// Daily token snapshot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenSnapshot {
+    /// Start of the day (unix seconds)
+    pub timestamp: i64,
+    #[serde(rename = "volumeUSD")]
+    pub volume_usd: f64,
+    #[serde(rename = "feesUSD")]
+    pub fees_usd: f64,
+    #[serde(rename = "totalValueLockedUSD")]
+    pub total_value_locked_usd: f64,
+    #[serde(rename = "priceUSD")]
+    pub price_usd: f64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+impl TryFrom<token_day_series::TokenDaySeriesTokenDayDatas> for TokenSnapshot {
+    type Error = DecodeError;
+
+    fn try_from(raw: token_day_series::TokenDaySeriesTokenDayDatas) -> Result<Self, Self::Error> {
+        Ok(Self {
+            timestamp: raw.date,
+            volume_usd: parse_decimal("volumeUSD", &raw.volume_usd)?,
+            fees_usd: parse_decimal("feesUSD", &raw.fees_usd)?,
+            total_value_locked_usd: parse_decimal(
+                "totalValueLockedUSD",
+                &raw.total_value_locked_usd,
+            )?,
+            price_usd: parse_decimal("priceUSD", &raw.price_usd)?,
+            open: parse_decimal("open", &raw.open)?,
+            high: parse_decimal("high", &raw.high)?,
+            low: parse_decimal("low", &raw.low)?,
+            close: parse_decimal("close", &raw.close)?,
+        })
+    }
+}
+
+/// Initialized tick with its liquidity (uint128/int128, as floats)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Tick {
+    #[serde(rename = "tickIdx")]
+    pub tick_idx: i32,
+    #[serde(rename = "liquidityNet")]
+    pub liquidity_net: f64,
+    #[serde(rename = "liquidityGross")]
+    pub liquidity_gross: f64,
+}
+
+impl TryFrom<pool_ticks::PoolTicksTicks> for Tick {
+    type Error = DecodeError;
+
+    fn try_from(raw: pool_ticks::PoolTicksTicks) -> Result<Self, Self::Error> {
+        Ok(Self {
+            tick_idx: parse_int("tickIdx", &raw.tick_idx)?,
+            liquidity_net: parse_decimal("liquidityNet", &raw.liquidity_net)?,
+            liquidity_gross: parse_decimal("liquidityGross", &raw.liquidity_gross)?,
+        })
+    }
+}
+
+/// Token id and symbol attached to an event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenRef {
+    pub id: String,
+    pub symbol: String,
+}
+
+/// Event with an id and block timestamp, for keyset pagination
+pub trait TimedEvent {
+    fn id(&self) -> &str;
+    fn timestamp(&self) -> i64;
+}
+
+/// Swap on a pool
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Swap {
+    pub id: String,
+    pub timestamp: i64,
+    #[serde(rename = "pool")]
+    pub pool_id: String,
+    pub token0: TokenRef,
+    pub token1: TokenRef,
+    /// Transaction sender (`sender` is usually a router)
+    pub origin: String,
+    /// Token0 delta from the pool's side (positive = sold into the pool)
+    pub amount0: f64,
+    /// Token1 delta from the pool's side (positive = sold into the pool)
+    pub amount1: f64,
+    #[serde(rename = "amountUSD")]
+    pub amount_usd: f64,
+    /// Pool tick after the swap
+    pub tick: i32,
+}
+
+impl TryFrom<swaps::SwapsSwaps> for Swap {
+    type Error = DecodeError;
+
+    fn try_from(raw: swaps::SwapsSwaps) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: raw.id,
+            timestamp: parse_int("timestamp", &raw.timestamp)?,
+            pool_id: raw.pool.id.to_lowercase(),
+            token0: TokenRef {
+                id: raw.token0.id.to_lowercase(),
+                symbol: raw.token0.symbol,
+            },
+            token1: TokenRef {
+                id: raw.token1.id.to_lowercase(),
+                symbol: raw.token1.symbol,
+            },
+            origin: raw.origin.to_lowercase(),
+            amount0: parse_decimal("amount0", &raw.amount0)?,
+            amount1: parse_decimal("amount1", &raw.amount1)?,
+            amount_usd: parse_decimal("amountUSD", &raw.amount_usd)?,
+            tick: parse_int("tick", &raw.tick)?,
+        })
+    }
+}
+
+/// Liquidity added (mint) or removed (burn) on a pool
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LiquidityEvent {
+    pub id: String,
+    pub timestamp: i64,
+    #[serde(rename = "pool")]
+    pub pool_id: String,
+    pub token0: TokenRef,
+    pub token1: TokenRef,
+    /// Transaction sender (the position manager is the owner for most LPs)
+    pub origin: String,
+    pub amount0: f64,
+    pub amount1: f64,
+    /// USD value (null when the subgraph can't price the tokens)
+    #[serde(rename = "amountUSD")]
+    pub amount_usd: Option<f64>,
+    #[serde(rename = "tickLower")]
+    pub tick_lower: i32,
+    #[serde(rename = "tickUpper")]
+    pub tick_upper: i32,
+}
+
+macro_rules! liquidity_event_from {
+    ($raw:ty) => {
+        impl TryFrom<$raw> for LiquidityEvent {
+            type Error = DecodeError;
+
+            fn try_from(raw: $raw) -> Result<Self, Self::Error> {
+                Ok(Self {
+                    id: raw.id,
+                    timestamp: parse_int("timestamp", &raw.timestamp)?,
+                    pool_id: raw.pool.id.to_lowercase(),
+                    token0: TokenRef {
+                        id: raw.token0.id.to_lowercase(),
+                        symbol: raw.token0.symbol,
+                    },
+                    token1: TokenRef {
+                        id: raw.token1.id.to_lowercase(),
+                        symbol: raw.token1.symbol,
+                    },
+                    origin: raw.origin.to_lowercase(),
+                    amount0: parse_decimal("amount0", &raw.amount0)?,
+                    amount1: parse_decimal("amount1", &raw.amount1)?,
+                    amount_usd: raw
+                        .amount_usd
+                        .as_deref()
+                        .map(|v| parse_decimal("amountUSD", v))
+                        .transpose()?,
+                    tick_lower: parse_int("tickLower", &raw.tick_lower)?,
+                    tick_upper: parse_int("tickUpper", &raw.tick_upper)?,
+                })
+            }
+        }
+    };
+}
+
+liquidity_event_from!(mints::MintsMints);
+liquidity_event_from!(burns::BurnsBurns);
+
+impl TimedEvent for Swap {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+}
+
+impl TimedEvent for LiquidityEvent {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+}
+
+/// Decode a list of raw rows, failing on the first malformed value
+pub fn decode_all<R, T>(rows: Vec<R>) -> Result<Vec<T>, DecodeError>
+where
+    T: TryFrom<R, Error = DecodeError>,
+{
+    rows.into_iter().map(T::try_from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn raw_pool(id: &str, tvl: &str) -> serde_json::Value {
+        let token = |id: &str, symbol: &str| {
+            json!({
+                "id": id,
+                "symbol": symbol,
+                "name": symbol,
+                "decimals": "18",
+                "derivedETH": "1"
+            })
+        };
+        json!({
+            "id": id,
+            "token0": token("0x01", "WETH"),
+            "token1": token("0x02", "USDC"),
+            "feeTier": "500",
+            "liquidity": "123456789012345678901234",
+            "sqrtPrice": "1771595571142957166518320255467520",
+            "tick": "-201000",
+            "token0Price": "2000.5",
+            "token1Price": "0.000499875",
+            "volumeUSD": "900000000",
+            "totalValueLockedUSD": tvl,
+            "txCount": "42"
+        })
+    }
+
+    #[test]
+    fn test_decode_pools_response() {
+        let data: pools::ResponseData = serde_json::from_value(json!({
+            "pools": [raw_pool("0xabc", "1500000.25")]
+        }))
+        .unwrap();
+        let pools: Vec<Pool> = decode_all(data.pools).unwrap();
+
+        let pool = &pools[0];
+        assert_eq!(pool.fee_tier, 500);
+        assert_eq!(pool.tick, Some(-201_000));
+        assert_eq!(pool.total_value_locked_usd, 1_500_000.25);
+        assert_eq!(pool.tx_count, 42);
+        assert_eq!(pool.token0.decimals, 18);
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_numbers() {
+        let data: pools::ResponseData = serde_json::from_value(json!({
+            "pools": [raw_pool("0xabc", "not-a-number")]
+        }))
+        .unwrap();
+        let err = decode_all::<_, Pool>(data.pools).unwrap_err();
+        assert!(matches!(
+            err,
+            DecodeError::InvalidNumber {
+                field: "totalValueLockedUSD",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_pool_serializes_with_subgraph_field_names() {
+        let data: pools::ResponseData = serde_json::from_value(json!({
+            "pools": [raw_pool("0xabc", "1000")]
+        }))
+        .unwrap();
+        let mut pool = Pool::try_from(data.pools[0].clone()).unwrap();
+        pool.volume_24h_usd = Some(10.0);
+
+        let value = serde_json::to_value(&pool).unwrap();
+        assert_eq!(value["totalValueLockedUSD"], json!(1000.0));
+        assert_eq!(value["feeTier"], json!(500));
+        assert_eq!(value["token0"]["symbol"], json!("WETH"));
+        assert_eq!(value["volume_24h_usd"], json!(10.0));
+        assert!(value.get("fees_24h_usd").is_none());
+    }
+
+    #[test]
+    fn test_pool_filter_omits_unset_fields() {
+        let filter = pools::Pool_filter {
+            total_value_locked_usd_gte: Some("1000".to_string()),
+            fee_tier_in: Some(vec!["500".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&filter).unwrap(),
+            json!({ "totalValueLockedUSD_gte": "1000", "feeTier_in": ["500"] })
+        );
+    }
+
+    #[test]
+    fn test_block_height_from_pin() {
+        assert!(BlockPin::Latest.height::<pools::Block_height>().is_none());
+        let height: pools::Block_height = BlockPin::Number(19_000_000).height().unwrap();
+        assert_eq!(
+            serde_json::to_value(&height).unwrap(),
+            json!({ "number": 19_000_000 })
+        );
+    }
+
+    #[test]
+    fn test_queries_are_block_pinned() {
+        for query in [
+            pools::QUERY,
+            pools_at_tvl::QUERY,
+            token_price::QUERY,
+            trailing_activity::QUERY,
+            pool_day_series::QUERY,
+            pool_hour_series::QUERY,
+            token_day_series::QUERY,
+            pool_ticks::QUERY,
+            swaps::QUERY,
+            mints::QUERY,
+            burns::QUERY,
+        ] {
+            assert!(query.contains("$block: Block_height"), "{}", query);
+            assert!(query.contains("block: $block"), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_decode_pool_snapshots() {
+        let data: pool_hour_series::ResponseData = serde_json::from_value(json!({
+            "poolHourDatas": [{
+                "periodStartUnix": 1_700_000_000,
+                "volumeUSD": "100.5",
+                "feesUSD": "0.05",
+                "tvlUSD": "1000",
+                "liquidity": "1",
+                "token0Price": "2000",
+                "token1Price": "0.0005",
+                "open": "1",
+                "high": "2",
+                "low": "0.5",
+                "close": "1.5",
+                "txCount": "7"
+            }]
+        }))
+        .unwrap();
+        let series: Vec<PoolSnapshot> = decode_all(data.pool_hour_datas).unwrap();
+        assert_eq!(series[0].timestamp, 1_700_000_000);
+        assert_eq!(series[0].volume_usd, 100.5);
+        assert_eq!(series[0].tx_count, 7);
+    }
+
+    #[test]
+    fn test_decode_ticks() {
+        let data: pool_ticks::ResponseData = serde_json::from_value(json!({
+            "ticks": [{
+                "tickIdx": "-887220",
+                "liquidityNet": "-340282366920938463463374607431768211455",
+                "liquidityGross": "340282366920938463463374607431768211455"
+            }]
+        }))
+        .unwrap();
+        let ticks: Vec<Tick> = decode_all(data.ticks).unwrap();
+        assert_eq!(ticks[0].tick_idx, -887_220);
+        assert!(ticks[0].liquidity_net < 0.0);
+        assert_eq!(ticks[0].liquidity_gross, -ticks[0].liquidity_net);
+    }
+
+    #[test]
+    fn test_decode_swaps() {
+        let data: swaps::ResponseData = serde_json::from_value(json!({
+            "swaps": [{
+                "id": "0xtx#1",
+                "timestamp": "1700000000",
+                "pool": { "id": "0xPOOL" },
+                "token0": { "id": "0x01", "symbol": "WETH" },
+                "token1": { "id": "0x02", "symbol": "USDC" },
+                "origin": "0xAbC",
+                "amount0": "-1.5",
+                "amount1": "3000.25",
+                "amountUSD": "3000.25",
+                "tick": "-201000"
+            }]
+        }))
+        .unwrap();
+        let swaps: Vec<Swap> = decode_all(data.swaps).unwrap();
+        assert_eq!(swaps[0].timestamp, 1_700_000_000);
+        assert_eq!(swaps[0].pool_id, "0xpool");
+        assert_eq!(swaps[0].origin, "0xabc");
+        assert_eq!(swaps[0].amount0, -1.5);
+        assert_eq!(swaps[0].tick, -201_000);
+
+        let value = serde_json::to_value(&swaps[0]).unwrap();
+        assert_eq!(value["amountUSD"], json!(3000.25));
+        assert_eq!(value["pool"], json!("0xpool"));
+    }
+
+    #[test]
+    fn test_decode_unpriced_liquidity_event() {
+        let data: burns::ResponseData = serde_json::from_value(json!({
+            "burns": [{
+                "id": "0xtx#2",
+                "timestamp": "1700000000",
+                "pool": { "id": "0xpool" },
+                "token0": { "id": "0x01", "symbol": "WETH" },
+                "token1": { "id": "0x02", "symbol": "USDC" },
+                "origin": "0xabc",
+                "amount0": "1",
+                "amount1": "2000",
+                "amountUSD": null,
+                "tickLower": "-887220",
+                "tickUpper": "887220"
+            }]
+        }))
+        .unwrap();
+        let burns: Vec<LiquidityEvent> = decode_all(data.burns).unwrap();
+        assert_eq!(burns[0].amount_usd, None);
+        assert_eq!(burns[0].tick_lower, -887_220);
+    }
+
+    #[test]
+    fn test_event_filter_scopes() {
+        let pool = EventWindow {
+            scope: EventScope::Pool("0xpool".to_string()),
+            start: 100,
+            end: 200,
+        };
+        assert_eq!(
+            serde_json::to_value(swaps::Swap_filter::from(&pool)).unwrap(),
+            json!({ "pool": "0xpool", "timestamp_gte": "100", "timestamp_lte": "200" })
+        );
+
+        let token = EventWindow {
+            scope: EventScope::Token("0x01".to_string()),
+            ..pool
+        };
+        assert_eq!(
+            serde_json::to_value(mints::Mint_filter::from(&token)).unwrap(),
+            json!({ "or": [
+                { "token0": "0x01", "timestamp_gte": "100", "timestamp_lte": "200" },
+                { "token1": "0x01", "timestamp_gte": "100", "timestamp_lte": "200" }
+            ]})
+        );
+    }
+}
@@ -0,0 +1,437 @@
+//! Safety checks for agent-authored subgraph queries
+//!
+//! A custom query must be a single read-only operation whose fields all exist
+//! in the subgraph schema, nested no deeper than the budget allows, with every
+//! `first` argument resolved and within the page cap, and no more root fields
+//! or aliases than the budget allows. Anything else is rejected before it
+//! reaches a subgraph.
+
+use super::introspection::{named_type, SubgraphSchema};
+use crate::config::{
+    DEFAULT_CUSTOM_QUERY_MAX_ALIASES, DEFAULT_CUSTOM_QUERY_MAX_DEPTH,
+    DEFAULT_CUSTOM_QUERY_MAX_FIRST, DEFAULT_CUSTOM_QUERY_MAX_ROOT_FIELDS,
+};
+use graphql_parser::query::{
+    parse_query, Definition, FragmentDefinition, OperationDefinition, Selection, SelectionSet,
+    TypeCondition, Value as GqlValue,
+};
+use serde_json::Value;
+use std::cell::Cell;
+use std::collections::HashMap;
+
+/// Limits applied to custom queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryBudget {
+    /// Maximum selection depth (root fields are depth 1)
+    pub max_depth: usize,
+    /// Maximum `first` on any field
+    pub max_first: i64,
+    /// Maximum root fields, counting each alias and fragment use
+    pub max_root_fields: usize,
+    /// Maximum aliased fields anywhere in the query
+    pub max_aliases: usize,
+}
+
+impl Default for QueryBudget {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_CUSTOM_QUERY_MAX_DEPTH,
+            max_first: DEFAULT_CUSTOM_QUERY_MAX_FIRST,
+            max_root_fields: DEFAULT_CUSTOM_QUERY_MAX_ROOT_FIELDS,
+            max_aliases: DEFAULT_CUSTOM_QUERY_MAX_ALIASES,
+        }
+    }
+}
+
+/// Reason a custom query was rejected
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum QueryValidationError {
+    #[error("Query does not parse: {0}")]
+    Parse(String),
+    #[error("Only read-only queries are allowed, found a {0}")]
+    NotAQuery(&'static str),
+    #[error("Expected exactly one operation, found {0}")]
+    OperationCount(usize),
+    #[error("Type '{type_name}' has no field '{field}'")]
+    UnknownField { type_name: String, field: String },
+    #[error("Unknown fragment '{0}'")]
+    UnknownFragment(String),
+    #[error("Fragment '{0}' spreads itself")]
+    FragmentCycle(String),
+    #[error("Query depth {depth} exceeds the maximum of {max}")]
+    TooDeep { depth: usize, max: usize },
+    #[error("'first: {first}' on '{field}' exceeds the maximum of {max}")]
+    FirstTooLarge { field: String, first: i64, max: i64 },
+    #[error("'first' on '{0}' must be an integer literal or a provided Int variable")]
+    UnresolvedFirst(String),
+    #[error("Query selects {count} root fields, more than the maximum of {max}")]
+    TooManyRootFields { count: usize, max: usize },
+    #[error("Query uses {count} aliases, more than the maximum of {max}")]
+    TooManyAliases { count: usize, max: usize },
+}
+
+/// Check an agent-authored query against a subgraph schema and a budget
+///
+/// `variables` are used to resolve `first: $var` arguments; a `first` that
+/// resolves to no integer is rejected rather than left to the subgraph.
+pub fn validate_query(
+    schema: &SubgraphSchema,
+    query: &str,
+    variables: &Value,
+    budget: &QueryBudget,
+) -> Result<(), QueryValidationError> {
+    let document = parse_query::<&str>(query)
+        .map_err(|e| QueryValidationError::Parse(e.to_string().trim().to_string()))?;
+
+    let mut fragments = HashMap::new();
+    let mut operations = Vec::new();
+    for definition in &document.definitions {
+        match definition {
+            Definition::Fragment(fragment) => {
+                fragments.insert(fragment.name, fragment);
+            }
+            Definition::Operation(operation) => operations.push(operation),
+        }
+    }
+    if operations.len() != 1 {
+        return Err(QueryValidationError::OperationCount(operations.len()));
+    }
+
+    let (selection_set, defaults) = match operations[0] {
+        OperationDefinition::SelectionSet(set) => (set, HashMap::new()),
+        OperationDefinition::Query(operation) => (
+            &operation.selection_set,
+            operation
+                .variable_definitions
+                .iter()
+                .filter_map(|v| v.default_value.as_ref().map(|d| (v.name, d)))
+                .collect(),
+        ),
+        OperationDefinition::Mutation(_) => {
+            return Err(QueryValidationError::NotAQuery("mutation"))
+        }
+        OperationDefinition::Subscription(_) => {
+            return Err(QueryValidationError::NotAQuery("subscription"))
+        }
+    };
+
+    let walker = Walker {
+        schema,
+        budget,
+        variables,
+        defaults,
+        fragments,
+        root_fields: Cell::new(0),
+        aliases: Cell::new(0),
+    };
+    walker.walk(selection_set, &schema.query_type, 1, &mut Vec::new())
+}
+
+/// Walks a parsed query's selections against the schema
+struct Walker<'d, 'q> {
+    schema: &'d SubgraphSchema,
+    budget: &'d QueryBudget,
+    variables: &'d Value,
+    defaults: HashMap<&'q str, &'d GqlValue<'q, &'q str>>,
+    fragments: HashMap<&'q str, &'d FragmentDefinition<'q, &'q str>>,
+    /// Root fields seen so far
+    root_fields: Cell<usize>,
+    /// Aliased fields seen so far
+    aliases: Cell<usize>,
+}
+
+impl<'d, 'q> Walker<'d, 'q> {
+    fn walk(
+        &self,
+        set: &'d SelectionSet<'q, &'q str>,
+        type_name: &str,
+        depth: usize,
+        spreads: &mut Vec<&'q str>,
+    ) -> Result<(), QueryValidationError> {
+        for selection in &set.items {
+            match selection {
+                Selection::Field(field) => {
+                    if field.name == "__typename" {
+                        continue;
+                    }
+                    let field_type = self
+                        .schema
+                        .fields(type_name)
+                        .and_then(|fields| fields.get(field.name))
+                        .ok_or_else(|| QueryValidationError::UnknownField {
+                            type_name: type_name.to_string(),
+                            field: field.name.to_string(),
+                        })?;
+                    if depth > self.budget.max_depth {
+                        return Err(QueryValidationError::TooDeep {
+                            depth,
+                            max: self.budget.max_depth,
+                        });
+                    }
+                    if depth == 1 {
+                        Self::count(
+                            &self.root_fields,
+                            self.budget.max_root_fields,
+                            |count, max| QueryValidationError::TooManyRootFields { count, max },
+                        )?;
+                    }
+                    if field.alias.is_some() {
+                        Self::count(&self.aliases, self.budget.max_aliases, |count, max| {
+                            QueryValidationError::TooManyAliases { count, max }
+                        })?;
+                    }
+                    let first = field
+                        .arguments
+                        .iter()
+                        .find(|(name, _)| *name == "first")
+                        .map(|(_, value)| {
+                            self.int_argument(value).ok_or_else(|| {
+                                QueryValidationError::UnresolvedFirst(field.name.to_string())
+                            })
+                        })
+                        .transpose()?;
+                    if let Some(first) = first.filter(|f| *f > self.budget.max_first) {
+                        return Err(QueryValidationError::FirstTooLarge {
+                            field: field.name.to_string(),
+                            first,
+                            max: self.budget.max_first,
+                        });
+                    }
+                    if !field.selection_set.items.is_empty() {
+                        self.walk(
+                            &field.selection_set,
+                            named_type(field_type),
+                            depth + 1,
+                            spreads,
+                        )?;
+                    }
+                }
+                Selection::FragmentSpread(spread) => {
+                    let name = spread.fragment_name;
+                    if spreads.contains(&name) {
+                        return Err(QueryValidationError::FragmentCycle(name.to_string()));
+                    }
+                    let fragment =
+                        self.fragments.get(name).copied().ok_or_else(|| {
+                            QueryValidationError::UnknownFragment(name.to_string())
+                        })?;
+                    let TypeCondition::On(on) = &fragment.type_condition;
+                    spreads.push(name);
+                    self.walk(&fragment.selection_set, on, depth, spreads)?;
+                    spreads.pop();
+                }
+                Selection::InlineFragment(inline) => {
+                    let on = match &inline.type_condition {
+                        Some(TypeCondition::On(on)) => *on,
+                        None => type_name,
+                    };
+                    self.walk(&inline.selection_set, on, depth, spreads)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Bump `counter`, failing once it passes `max`
+    fn count(
+        counter: &Cell<usize>,
+        max: usize,
+        error: impl FnOnce(usize, usize) -> QueryValidationError,
+    ) -> Result<(), QueryValidationError> {
+        let count = counter.get() + 1;
+        counter.set(count);
+        if count > max {
+            return Err(error(count, max));
+        }
+        Ok(())
+    }
+
+    /// Integer value of an argument, resolving variables and their defaults
+    fn int_argument(&self, value: &GqlValue<'q, &'q str>) -> Option<i64> {
+        match value {
+            GqlValue::Int(n) => n.as_i64(),
+            GqlValue::Variable(name) => match self.variables.get(*name) {
+                Some(v) => v.as_i64(),
+                None => self
+                    .defaults
+                    .get(name)
+                    .and_then(|default| self.int_argument(default)),
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    fn fields(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(name, ty)| (name.to_string(), ty.to_string()))
+            .collect()
+    }
+
+    fn schema() -> SubgraphSchema {
+        SubgraphSchema {
+            query_type: "Query".to_string(),
+            queries: fields(&[("pools", "[Pool!]!"), ("swaps", "[Swap!]!")]),
+            entities: BTreeMap::from([
+                (
+                    "Pool".to_string(),
+                    fields(&[("id", "ID!"), ("token0", "Token!"), ("swaps", "[Swap!]!")]),
+                ),
+                (
+                    "Token".to_string(),
+                    fields(&[("id", "ID!"), ("symbol", "String!")]),
+                ),
+                (
+                    "Swap".to_string(),
+                    fields(&[
+                        ("id", "ID!"),
+                        ("pool", "Pool!"),
+                        ("amountUSD", "BigDecimal!"),
+                    ]),
+                ),
+            ]),
+        }
+    }
+
+    fn check(query: &str, variables: Value) -> Result<(), QueryValidationError> {
+        let budget = QueryBudget {
+            max_depth: 3,
+            max_first: 100,
+            max_root_fields: 3,
+            max_aliases: 2,
+        };
+        validate_query(&schema(), query, &variables, &budget)
+    }
+
+    #[test]
+    fn test_accepts_valid_query() {
+        let query = r#"
+            query Top($n: Int = 10) {
+                pools(first: $n, orderBy: id) { id __typename token0 { ...TokenFields } }
+            }
+            fragment TokenFields on Token { symbol }
+        "#;
+        assert_eq!(check(query, json!({})), Ok(()));
+        assert_eq!(
+            check("{ swaps(first: 5) { amountUSD } }", json!(null)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_rejects_mutations_and_unknown_fields() {
+        assert_eq!(
+            check("mutation { pools { id } }", json!({})),
+            Err(QueryValidationError::NotAQuery("mutation"))
+        );
+        assert_eq!(
+            check("{ pools { feeTier } }", json!({})),
+            Err(QueryValidationError::UnknownField {
+                type_name: "Pool".to_string(),
+                field: "feeTier".to_string()
+            })
+        );
+        assert!(matches!(
+            check("{ __schema { types { name } } }", json!({})),
+            Err(QueryValidationError::UnknownField { .. })
+        ));
+        assert!(matches!(
+            check("{ pools { id }", json!({})),
+            Err(QueryValidationError::Parse(_))
+        ));
+        assert_eq!(
+            check(
+                "query A { pools { id } } query B { swaps { id } }",
+                json!({})
+            ),
+            Err(QueryValidationError::OperationCount(2))
+        );
+    }
+
+    #[test]
+    fn test_enforces_depth_and_first() {
+        assert_eq!(
+            check("{ swaps { pool { token0 { symbol } } } }", json!({})),
+            Err(QueryValidationError::TooDeep { depth: 4, max: 3 })
+        );
+        assert_eq!(
+            check("{ pools { swaps(first: 500) { id } } }", json!({})),
+            Err(QueryValidationError::FirstTooLarge {
+                field: "swaps".to_string(),
+                first: 500,
+                max: 100
+            })
+        );
+        // Variables and their defaults are resolved
+        let query = "query($n: Int = 1000) { pools(first: $n) { id } }";
+        assert!(check(query, json!({})).is_err());
+        assert_eq!(check(query, json!({ "n": 50 })), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_unresolved_first() {
+        let unresolved = Err(QueryValidationError::UnresolvedFirst("pools".to_string()));
+        // Missing variable without a default
+        let query = "query($n: Int) { pools(first: $n) { id } }";
+        assert_eq!(check(query, json!({})), unresolved);
+        // Non-integer values
+        assert_eq!(check(query, json!({ "n": "5000" })), unresolved);
+        assert_eq!(check(query, json!({ "n": 5.5 })), unresolved);
+        assert_eq!(
+            check("{ pools(first: \"5000\") { id } }", json!({})),
+            unresolved
+        );
+    }
+
+    #[test]
+    fn test_caps_root_fields_and_aliases() {
+        assert_eq!(
+            check("{ a: pools { id } b: swaps { id } }", json!({})),
+            Ok(())
+        );
+        assert_eq!(
+            check(
+                "{ pools { id } swaps { id } a: pools { id } b: pools { id } }",
+                json!({})
+            ),
+            Err(QueryValidationError::TooManyRootFields { count: 4, max: 3 })
+        );
+        // Fragment uses count once per spread
+        let query = r#"
+            { ...Both ...Both }
+            fragment Both on Query { pools { id } swaps { id } }
+        "#;
+        assert_eq!(
+            check(query, json!({})),
+            Err(QueryValidationError::TooManyRootFields { count: 4, max: 3 })
+        );
+        // Nested aliases count too
+        assert_eq!(
+            check("{ pools { a: id b: id c: id } }", json!({})),
+            Err(QueryValidationError::TooManyAliases { count: 3, max: 2 })
+        );
+    }
+
+    #[test]
+    fn test_rejects_fragment_cycles() {
+        let query = r#"
+            { pools { ...A } }
+            fragment A on Pool { id ...A }
+        "#;
+        assert_eq!(
+            check(query, json!({})),
+            Err(QueryValidationError::FragmentCycle("A".to_string()))
+        );
+        assert_eq!(
+            check("{ pools { ...Missing } }", json!({})),
+            Err(QueryValidationError::UnknownFragment("Missing".to_string()))
+        );
+    }
+}
@@ -1,6 +1,7 @@
 //! Cooldown interceptor
 //!
 //! Enforces a minimum time between trades to prevent rapid-fire trading.
+//! Requotes of an already prepared swap are the same trade and aren't gated.
 
 use crate::tools::TOOL_ODOS_SWAP;
 use async_trait::async_trait;
@@ -152,18 +153,20 @@ mod tests {
             *last_trade = Some(Instant::now());
         }
 
-        // Quote should still be allowed
-        let context = ToolCallContext {
-            tool_name: TOOL_ODOS_SWAP.to_string(),
-            function_name: None,
-            args: json!({
-                "action": "quote"
-            }),
-            context_id: generate_context_id(),
-            metadata: json!({}),
-        };
-
-        let decision = interceptor.intercept_tool_call(&context).await.unwrap();
-        assert!(matches!(decision, InterceptorDecision::Allow));
+        // Quotes and requotes of the prepared swap should still be allowed
+        for action in ["quote", "requote"] {
+            let context = ToolCallContext {
+                tool_name: TOOL_ODOS_SWAP.to_string(),
+                function_name: None,
+                args: json!({
+                    "action": action
+                }),
+                context_id: generate_context_id(),
+                metadata: json!({}),
+            };
+
+            let decision = interceptor.intercept_tool_call(&context).await.unwrap();
+            assert!(matches!(decision, InterceptorDecision::Allow));
+        }
     }
 }
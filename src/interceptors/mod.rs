@@ -6,11 +6,13 @@
 mod audit_log;
 mod cooldown;
 mod policy;
+mod quote_freshness;
 mod slippage_guard;
 mod spend_limit;
 
 pub use audit_log::AuditLogInterceptor;
 pub use cooldown::CooldownInterceptor;
 pub use policy::{PolicyConfig, PolicyInterceptor, PolicyMode};
+pub use quote_freshness::{QuoteFreshnessInterceptor, STALE_QUOTE, STALE_QUOTE_CODE};
 pub use slippage_guard::SlippageGuardInterceptor;
 pub use spend_limit::SpendLimitInterceptor;
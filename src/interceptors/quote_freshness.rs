@@ -0,0 +1,389 @@
+//! Quote freshness interceptor
+//!
+//! Blocks signing or executing a prepared swap whose quote has gone stale:
+//! too many seconds since `prepare_swap` priced it, past the quoted path's
+//! expiry or, if configured, too many blocks behind the chain head. When and at which block a swap was quoted is
+//! read from the record `prepare_swap` kept of its transaction, never from the
+//! agent's arguments. Stale swaps must be re-quoted (`odos_swap` `requote`).
+//!
+//! Any transaction to an Odos router must be one `prepare_swap` recorded, for
+//! signing as well as execution, so altered or hand-built router calldata
+//! can't get past the check. Stale quotes are blocked with a JSON reason whose
+//! `code` is [`STALE_QUOTE_CODE`], for callers to re-quote on.
+
+use crate::config::RpcConfig;
+use crate::execution::PreparedSwaps;
+use crate::tools::{
+    fetch_chain_head, PreparedSwapTransaction, UnsignedTxInput, TOOL_EXECUTE_SWAP,
+    TOOL_WALLET_SIGN_TX,
+};
+use crate::wallet::intent::{decode_unsigned_eip1559, is_allowed_router};
+use crate::wallet::PreparedTransaction;
+use alloy::consensus::TxEip1559;
+use alloy::primitives::hex;
+use async_trait::async_trait;
+use baml_rt::error::Result;
+use baml_rt::interceptor::{InterceptorDecision, ToolCallContext, ToolInterceptor};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// Prefix of every stale quote message
+pub const STALE_QUOTE: &str = "Stale quote";
+
+/// `code` of the JSON block reason for a stale quote, so callers can re-quote
+/// instead of giving up
+pub const STALE_QUOTE_CODE: &str = "stale_quote";
+
+/// Block a stale quote with a structured reason
+fn stale(message: String) -> InterceptorDecision {
+    InterceptorDecision::Block(
+        json!({
+            "code": STALE_QUOTE_CODE,
+            "message": message,
+        })
+        .to_string(),
+    )
+}
+
+/// Interceptor that blocks prepared swaps with stale quotes
+pub struct QuoteFreshnessInterceptor {
+    /// Maximum seconds since the quote was issued
+    max_age_secs: u64,
+    /// Maximum blocks behind the chain head, and where to read the head
+    max_block_distance: Option<(u64, RpcConfig)>,
+    /// Transactions built by `prepare_swap`, with when and where they were quoted
+    prepared_swaps: Arc<PreparedSwaps>,
+}
+
+impl QuoteFreshnessInterceptor {
+    /// Create a new quote freshness guard
+    ///
+    /// # Arguments
+    /// * `max_age_secs` - Maximum seconds between quoting and signing/execution
+    /// * `prepared_swaps` - Record of the swaps `prepare_swap` quoted
+    pub fn new(max_age_secs: u64, prepared_swaps: Arc<PreparedSwaps>) -> Self {
+        Self {
+            max_age_secs,
+            max_block_distance: None,
+            prepared_swaps,
+        }
+    }
+
+    /// Also block quotes more than `max_blocks` behind the chain head
+    pub fn with_max_block_distance(mut self, max_blocks: u64, rpc_config: RpcConfig) -> Self {
+        self.max_block_distance = Some((max_blocks, rpc_config));
+        self
+    }
+
+    /// Transaction an execution or signing call is for, if it can be read
+    fn transaction(tool_name: &str, args: &Value) -> Option<PreparedTransaction> {
+        if tool_name == TOOL_EXECUTE_SWAP {
+            let tx: PreparedSwapTransaction =
+                serde_json::from_value(args.get("transaction")?.clone()).ok()?;
+            return PreparedTransaction::try_from(&tx).ok();
+        }
+        let tx = match (args.get("transaction"), args.get("tx_bytes")) {
+            (Some(transaction), _) if !transaction.is_null() => {
+                let input: UnsignedTxInput = serde_json::from_value(transaction.clone()).ok()?;
+                TxEip1559::try_from(&input).ok()?
+            }
+            (_, Some(tx_bytes)) => {
+                let tx_bytes = tx_bytes.as_str()?;
+                let bytes = hex::decode(tx_bytes.strip_prefix("0x").unwrap_or(tx_bytes)).ok()?;
+                decode_unsigned_eip1559(&bytes).ok()?
+            }
+            _ => return None,
+        };
+        Some(PreparedTransaction {
+            to: *tx.to.to()?,
+            data: tx.input,
+            value: tx.value,
+            gas_limit: tx.gas_limit,
+            chain_id: tx.chain_id,
+        })
+    }
+
+    /// Why a quote issued at `issued_at` and valid until `expires_at` is stale
+    /// at `now`, if it is
+    fn stale_age(&self, issued_at: i64, expires_at: i64, now: i64) -> Option<String> {
+        if now >= expires_at {
+            return Some(format!(
+                "{}: the quoted path expired {}s ago. Requote the swap.",
+                STALE_QUOTE,
+                now - expires_at
+            ));
+        }
+        let age = now - issued_at;
+        if age > i64::try_from(self.max_age_secs).unwrap_or(i64::MAX) {
+            return Some(format!(
+                "{}: quoted {}s ago (max {}s). Requote the swap.",
+                STALE_QUOTE, age, self.max_age_secs
+            ));
+        }
+        None
+    }
+
+    /// Why a quote priced at `block_number` is stale at chain head `head`, if it is
+    fn stale_blocks(max_blocks: u64, block_number: u64, head: u64) -> Option<String> {
+        let distance = head.saturating_sub(block_number);
+        if distance > max_blocks {
+            return Some(format!(
+                "{}: quoted at block {}, {} blocks behind the chain head (max {}). \
+                 Requote the swap.",
+                STALE_QUOTE, block_number, distance, max_blocks
+            ));
+        }
+        None
+    }
+}
+
+#[async_trait]
+impl ToolInterceptor for QuoteFreshnessInterceptor {
+    async fn intercept_tool_call(&self, context: &ToolCallContext) -> Result<InterceptorDecision> {
+        if context.tool_name != TOOL_EXECUTE_SWAP && context.tool_name != TOOL_WALLET_SIGN_TX {
+            return Ok(InterceptorDecision::Allow);
+        }
+        let transaction = Self::transaction(&context.tool_name, &context.args);
+        // Executed swaps always come from prepare_swap, and so must every
+        // signed router call; other signed transactions (approvals) have no quote
+        let require_prepared = context.tool_name == TOOL_EXECUTE_SWAP
+            || transaction
+                .as_ref()
+                .is_some_and(|tx| is_allowed_router(tx.chain_id, tx.to));
+        let swap = transaction.and_then(|tx| self.prepared_swaps.get(&tx));
+        let Some(swap) = swap else {
+            if require_prepared {
+                return Ok(InterceptorDecision::Block(
+                    "Transaction was not built by odos_swap prepare_swap (or was already \
+                     executed), so its quote can't be checked. Pass a prepared transaction \
+                     unchanged."
+                        .to_string(),
+                ));
+            }
+            return Ok(InterceptorDecision::Allow);
+        };
+
+        let now = chrono::Utc::now().timestamp();
+        if let Some(reason) = self.stale_age(swap.issued_at, swap.expires_at, now) {
+            return Ok(stale(reason));
+        }
+
+        if let Some((max_blocks, rpc_config)) = &self.max_block_distance {
+            let chain_id = swap.chain_id;
+            let Some(rpc_url) = rpc_config.get(chain_id) else {
+                return Ok(InterceptorDecision::Block(format!(
+                    "No RPC URL for chain {} to check the quote's block distance",
+                    chain_id
+                )));
+            };
+            let head = match fetch_chain_head(rpc_url).await {
+                Ok(head) => head,
+                Err(e) => {
+                    return Ok(InterceptorDecision::Block(format!(
+                        "Could not check the quote's block distance: {}",
+                        e
+                    )));
+                }
+            };
+            if let Some(reason) = Self::stale_blocks(*max_blocks, swap.block_number, head.number) {
+                return Ok(stale(reason));
+            }
+        }
+
+        tracing::debug!(
+            issued_at = swap.issued_at,
+            block_number = swap.block_number,
+            max_age_secs = self.max_age_secs,
+            "Quote freshness check passed"
+        );
+
+        Ok(InterceptorDecision::Allow)
+    }
+
+    async fn on_tool_call_complete(
+        &self,
+        _context: &ToolCallContext,
+        _result: &Result<Value>,
+        _duration_ms: u64,
+    ) {
+        // No post-execution action needed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::PreparedSwap;
+    use crate::tools::ODOS_QUOTE_TTL_SECS;
+    use alloy::primitives::{Address, Bytes, U256};
+    use baml_rt::generate_context_id;
+    use serde_json::json;
+
+    const ROUTER: &str = "0xCf5540fFFCdC3d510B18bFcA6d2b9987b0772559";
+
+    /// Record a swap quoted `age` seconds ago with calldata `data`
+    fn prepare(swaps: &PreparedSwaps, data: &'static [u8], age: i64) {
+        swaps.record(
+            &PreparedTransaction {
+                to: ROUTER.parse().unwrap(),
+                data: Bytes::from_static(data),
+                value: U256::ZERO,
+                gas_limit: 0,
+                chain_id: 1,
+            },
+            PreparedSwap {
+                chain_id: 1,
+                input_token: Address::repeat_byte(0x22),
+                input_amount: U256::from(1_000_000u64),
+                output_token: Address::repeat_byte(0x33),
+                slippage_percent: 0.5,
+                native_value: U256::ZERO,
+                issued_at: chrono::Utc::now().timestamp() - age,
+                expires_at: chrono::Utc::now().timestamp() - age + ODOS_QUOTE_TTL_SECS,
+                block_number: 21_000_000,
+            },
+        );
+    }
+
+    fn context(tool_name: &str, args: Value) -> ToolCallContext {
+        ToolCallContext {
+            tool_name: tool_name.to_string(),
+            function_name: None,
+            args,
+            context_id: generate_context_id(),
+            metadata: json!({}),
+        }
+    }
+
+    fn execute_context(data: &str) -> ToolCallContext {
+        context(
+            TOOL_EXECUTE_SWAP,
+            json!({ "transaction": {
+                "to": ROUTER,
+                "data": data,
+                "value": "0",
+                "gas_limit": null,
+                "chain_id": 1,
+                // Stamps passed by the agent are ignored
+                "issued_at": chrono::Utc::now().timestamp(),
+                "block_number": u64::MAX
+            } }),
+        )
+    }
+
+    fn sign_context(to: &str, data: &str) -> ToolCallContext {
+        context(
+            TOOL_WALLET_SIGN_TX,
+            json!({ "transaction": {
+                "chain_id": 1,
+                "nonce": 7,
+                "to": to,
+                "data": data,
+                "value": "0",
+                "gas_limit": 300_000,
+                "max_fee_per_gas": "30000000000",
+                "max_priority_fee_per_gas": "1000000000"
+            } }),
+        )
+    }
+
+    /// `code` of a structured block reason
+    fn block_code(decision: &InterceptorDecision) -> Option<String> {
+        let InterceptorDecision::Block(reason) = decision else {
+            return None;
+        };
+        let reason: Value = serde_json::from_str(reason).ok()?;
+        reason["code"].as_str().map(str::to_string)
+    }
+
+    #[tokio::test]
+    async fn test_allows_fresh_quote() {
+        let swaps = Arc::new(PreparedSwaps::new());
+        prepare(&swaps, &[0x83, 0xbd], 5);
+        let interceptor = QuoteFreshnessInterceptor::new(30, swaps);
+
+        let decision = interceptor
+            .intercept_tool_call(&execute_context("0x83bd"))
+            .await
+            .unwrap();
+        assert!(matches!(decision, InterceptorDecision::Allow));
+    }
+
+    #[tokio::test]
+    async fn test_blocks_stale_or_unprepared_execution() {
+        let swaps = Arc::new(PreparedSwaps::new());
+        prepare(&swaps, &[0x83, 0xbd], 120);
+        let interceptor = QuoteFreshnessInterceptor::new(30, swaps);
+
+        let decision = interceptor
+            .intercept_tool_call(&execute_context("0x83bd"))
+            .await
+            .unwrap();
+        assert_eq!(block_code(&decision).as_deref(), Some(STALE_QUOTE_CODE));
+        let InterceptorDecision::Block(reason) = decision else {
+            unreachable!()
+        };
+        let reason: Value = serde_json::from_str(&reason).unwrap();
+        assert!(reason["message"].as_str().unwrap().starts_with(STALE_QUOTE));
+
+        // Unprepared isn't stale: re-quoting can't fix it
+        let decision = interceptor
+            .intercept_tool_call(&execute_context("0x83be"))
+            .await
+            .unwrap();
+        assert!(matches!(decision, InterceptorDecision::Block(_)));
+        assert_eq!(block_code(&decision), None);
+    }
+
+    #[tokio::test]
+    async fn test_signing_requires_prepared_router_calls() {
+        let swaps = Arc::new(PreparedSwaps::new());
+        prepare(&swaps, &[0x83, 0xbd], 120);
+        let interceptor = QuoteFreshnessInterceptor::new(30, swaps);
+
+        // A token approval has no quote to check
+        let approve = sign_context("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", "0x095ea7b3");
+        let decision = interceptor.intercept_tool_call(&approve).await.unwrap();
+        assert!(matches!(decision, InterceptorDecision::Allow));
+
+        // Router calldata altered from what was prepared
+        let decision = interceptor
+            .intercept_tool_call(&sign_context(ROUTER, "0x83be"))
+            .await
+            .unwrap();
+        assert!(matches!(decision, InterceptorDecision::Block(_)));
+        assert_eq!(block_code(&decision), None);
+
+        let decision = interceptor
+            .intercept_tool_call(&sign_context(ROUTER, "0x83bd"))
+            .await
+            .unwrap();
+        assert_eq!(block_code(&decision).as_deref(), Some(STALE_QUOTE_CODE));
+    }
+
+    #[test]
+    fn test_expired_path_is_stale_within_max_age() {
+        let interceptor = QuoteFreshnessInterceptor::new(300, Arc::new(PreparedSwaps::new()));
+        let issued_at = 1_700_000_000;
+        let expires_at = issued_at + ODOS_QUOTE_TTL_SECS;
+
+        assert!(interceptor
+            .stale_age(issued_at, expires_at, expires_at - 1)
+            .is_none());
+        let reason = interceptor
+            .stale_age(issued_at, expires_at, expires_at + 5)
+            .unwrap();
+        assert!(reason.starts_with(STALE_QUOTE));
+        assert!(reason.contains("expired 5s ago"));
+    }
+
+    #[test]
+    fn test_block_distance() {
+        assert!(QuoteFreshnessInterceptor::stale_blocks(3, 100, 103).is_none());
+        // A head behind the quote (lagging RPC) is not stale
+        assert!(QuoteFreshnessInterceptor::stale_blocks(3, 100, 98).is_none());
+        let reason = QuoteFreshnessInterceptor::stale_blocks(3, 100, 104).unwrap();
+        assert!(reason.starts_with(STALE_QUOTE));
+        assert!(reason.contains("4 blocks behind"));
+    }
+}
@@ -2,11 +2,16 @@
 //!
 //! Enforces per-trade and daily spending limits to prevent runaway losses.
 //! Uses the shared token registry for consistent token information.
+//!
+//! Executions are valued from what `prepare_swap` recorded for the transaction
+//! (plus any native value it sends), never from the agent's own arguments.
 
 use crate::config::SpendLimitMode;
-use crate::tokens;
-use crate::tools::TOOL_ODOS_SWAP;
-use alloy::primitives::Address;
+use crate::execution::PreparedSwaps;
+use crate::tokens::{self, addresses};
+use crate::tools::{PreparedSwapTransaction, TOOL_EXECUTE_SWAP, TOOL_ODOS_SWAP};
+use crate::wallet::PreparedTransaction;
+use alloy::primitives::{Address, U256};
 use async_trait::async_trait;
 use baml_rt::error::Result;
 use baml_rt::interceptor::{InterceptorDecision, ToolCallContext, ToolInterceptor};
@@ -70,6 +75,8 @@ pub struct SpendLimitInterceptor {
     daily_spent: Arc<RwLock<DailySpending>>,
     /// Enforcement mode for unknown tokens
     mode: SpendLimitMode,
+    /// Transactions built by `prepare_swap`, used to value executions
+    prepared_swaps: Option<Arc<PreparedSwaps>>,
 }
 
 impl SpendLimitInterceptor {
@@ -84,6 +91,7 @@ impl SpendLimitInterceptor {
             max_daily,
             daily_spent: Arc::new(RwLock::new(DailySpending::new())),
             mode: SpendLimitMode::FailOpen,
+            prepared_swaps: None,
         }
     }
 
@@ -99,9 +107,92 @@ impl SpendLimitInterceptor {
             max_daily,
             daily_spent: Arc::new(RwLock::new(DailySpending::new())),
             mode,
+            prepared_swaps: None,
         }
     }
 
+    /// Value executions from the swaps recorded by `prepare_swap`
+    ///
+    /// Without it every `defi/execute_swap` call is blocked.
+    pub fn with_prepared_swaps(mut self, prepared_swaps: Arc<PreparedSwaps>) -> Self {
+        self.prepared_swaps = Some(prepared_swaps);
+        self
+    }
+
+    /// Value of an execution from its prepared swap: the input amount (None for
+    /// tokens without a price) and the native value sent alongside it
+    ///
+    /// Fails if the transaction was not built by `prepare_swap`.
+    fn execution_value(&self, args: &Value) -> std::result::Result<(Option<f64>, f64), String> {
+        let prepared_swaps = self.prepared_swaps.as_ref().ok_or_else(|| {
+            "No record of prepared swaps to check the execution against".to_string()
+        })?;
+        let transaction = args
+            .get("transaction")
+            .cloned()
+            .and_then(|tx| serde_json::from_value::<PreparedSwapTransaction>(tx).ok())
+            .and_then(|tx| PreparedTransaction::try_from(&tx).ok())
+            .ok_or_else(|| "Missing or invalid transaction".to_string())?;
+        let swap = prepared_swaps.get(&transaction).ok_or_else(|| {
+            "Transaction was not built by odos_swap prepare_swap (or was already executed)"
+                .to_string()
+        })?;
+
+        let registry = tokens::registry();
+        let input_usd =
+            registry.estimate_usd_value(&swap.input_token, &swap.input_amount.to_string());
+        // A native-currency swap's value is its input amount
+        let is_native = swap.input_token == addresses::NATIVE_ETH
+            || swap.input_token == addresses::ZERO_ADDRESS;
+        let native_usd = if is_native || swap.native_value == U256::ZERO {
+            0.0
+        } else {
+            registry
+                .estimate_usd_value(&addresses::NATIVE_ETH, &swap.native_value.to_string())
+                .unwrap_or(0.0)
+        };
+        Ok((input_usd, native_usd))
+    }
+
+    /// Per-trade check for `defi/execute_swap`
+    ///
+    /// The daily limit was charged when the swap was prepared.
+    fn check_execution(&self, args: &Value) -> InterceptorDecision {
+        let (input_usd, native_usd) = match self.execution_value(args) {
+            Ok(value) => value,
+            Err(reason) => return InterceptorDecision::Block(reason),
+        };
+        let trade_value = match (input_usd, self.mode) {
+            (Some(input_usd), _) => input_usd + native_usd,
+            (None, SpendLimitMode::FailOpen) => {
+                tracing::warn!(
+                    "Could not value the executed swap's input token, checking its native \
+                     value only (fail-open mode)"
+                );
+                native_usd
+            }
+            (None, SpendLimitMode::FailClosed) => {
+                return InterceptorDecision::Block(
+                    "Cannot determine USD value of the prepared swap's input token".to_string(),
+                );
+            }
+        };
+
+        if trade_value > self.max_per_trade {
+            return InterceptorDecision::Block(format!(
+                "Trade value ${:.2} exceeds per-trade limit of ${:.2}",
+                trade_value, self.max_per_trade
+            ));
+        }
+
+        tracing::info!(
+            trade_value = trade_value,
+            max_per_trade = self.max_per_trade,
+            "Spend limit check passed for execution"
+        );
+        InterceptorDecision::Allow
+    }
+
     /// Estimate trade value in USD from the args
     ///
     /// Priority:
@@ -175,12 +266,18 @@ impl SpendLimitInterceptor {
 #[async_trait]
 impl ToolInterceptor for SpendLimitInterceptor {
     async fn intercept_tool_call(&self, context: &ToolCallContext) -> Result<InterceptorDecision> {
-        // Only intercept odos_swap tool
+        // Executions were counted against the daily limit when prepared,
+        // so only the per-trade limit is re-checked for them
+        if context.tool_name == TOOL_EXECUTE_SWAP {
+            return Ok(self.check_execution(&context.args));
+        }
+
+        // Only intercept odos_swap prepare_swap actions (not quotes, nor
+        // requotes of a swap already prepared and charged)
         if context.tool_name != TOOL_ODOS_SWAP {
             return Ok(InterceptorDecision::Allow);
         }
 
-        // Only check prepare_swap actions (not quotes)
         let action = context.args.get("action").and_then(|v| v.as_str());
         if action != Some("prepare_swap") {
             return Ok(InterceptorDecision::Allow);
@@ -245,7 +342,8 @@ impl ToolInterceptor for SpendLimitInterceptor {
         result: &Result<Value>,
         _duration_ms: u64,
     ) {
-        // Only track successful prepare_swap operations
+        // Only track successful prepare_swap operations; a requote replaces
+        // the prepared swap it was charged for instead of adding a trade
         if context.tool_name != TOOL_ODOS_SWAP {
             return;
         }
@@ -272,7 +370,6 @@ impl ToolInterceptor for SpendLimitInterceptor {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tokens::addresses;
     use baml_rt::generate_context_id;
     use serde_json::json;
 
@@ -358,6 +455,132 @@ mod tests {
         assert!(matches!(decision, InterceptorDecision::Block(_)));
     }
 
+    /// Record a prepared swap of `amount` USDC sending `value` wei, and return
+    /// execute_swap args for its transaction
+    fn prepared_execution(swaps: &PreparedSwaps, amount: u64, value: u64) -> Value {
+        let transaction = PreparedTransaction {
+            to: crate::wallet::intent::ODOS_ROUTER_V3,
+            data: alloy::primitives::Bytes::from_static(&[0x83, 0xbd, 0x37, 0xf9]),
+            value: U256::from(value),
+            gas_limit: 0,
+            chain_id: 1,
+        };
+        swaps.record(
+            &transaction,
+            crate::execution::PreparedSwap {
+                chain_id: 1,
+                input_token: addresses::USDC_ETH,
+                input_amount: U256::from(amount),
+                output_token: addresses::WETH_ETH,
+                slippage_percent: 0.5,
+                native_value: U256::from(value),
+                issued_at: Utc::now().timestamp(),
+                expires_at: Utc::now().timestamp() + 60,
+                block_number: 21_000_000,
+            },
+        );
+        json!({
+            "transaction": {
+                "to": transaction.to.to_string(),
+                "data": "0x83bd37f9",
+                "value": value.to_string(),
+                "gas_limit": null,
+                "chain_id": 1
+            },
+            // Self-reported values are ignored for executions
+            "amount_usd": 1.0
+        })
+    }
+
+    fn execute_context(args: Value) -> ToolCallContext {
+        ToolCallContext {
+            tool_name: TOOL_EXECUTE_SWAP.to_string(),
+            function_name: None,
+            args,
+            context_id: generate_context_id(),
+            metadata: json!({}),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_values_executions_from_prepared_swaps() {
+        let swaps = Arc::new(PreparedSwaps::new());
+        let interceptor =
+            SpendLimitInterceptor::new(100.0, 500.0).with_prepared_swaps(swaps.clone());
+
+        // 50 USDC passes
+        let args = prepared_execution(&swaps, 50_000_000, 0);
+        let decision = interceptor
+            .intercept_tool_call(&execute_context(args))
+            .await
+            .unwrap();
+        assert!(matches!(decision, InterceptorDecision::Allow));
+
+        // 200 USDC is over the per-trade limit despite amount_usd: 1.0
+        let args = prepared_execution(&swaps, 200_000_000, 0);
+        let decision = interceptor
+            .intercept_tool_call(&execute_context(args))
+            .await
+            .unwrap();
+        assert!(matches!(decision, InterceptorDecision::Block(_)));
+
+        // 50 USDC plus 0.1 ETH (~$350) of native value is too
+        let args = prepared_execution(&swaps, 50_000_000, 100_000_000_000_000_000);
+        let decision = interceptor
+            .intercept_tool_call(&execute_context(args))
+            .await
+            .unwrap();
+        assert!(matches!(decision, InterceptorDecision::Block(_)));
+    }
+
+    #[tokio::test]
+    async fn test_requotes_are_not_charged_again() {
+        let interceptor = SpendLimitInterceptor::new(100.0, 150.0);
+        let odos_context = |args: Value| ToolCallContext {
+            tool_name: TOOL_ODOS_SWAP.to_string(),
+            function_name: None,
+            args,
+            context_id: generate_context_id(),
+            metadata: json!({}),
+        };
+        let prepare = odos_context(json!({
+            "action": "prepare_swap",
+            "input_token": addresses::USDC_ETH.to_string(),
+            "amount": "80000000"
+        }));
+        interceptor
+            .on_tool_call_complete(&prepare, &Ok(json!({})), 100)
+            .await;
+
+        let requote = odos_context(json!({
+            "action": "requote",
+            "transaction": { "to": "0x", "data": "0x", "value": "0", "chain_id": 1 }
+        }));
+        let decision = interceptor.intercept_tool_call(&requote).await.unwrap();
+        assert!(matches!(decision, InterceptorDecision::Allow));
+        interceptor
+            .on_tool_call_complete(&requote, &Ok(json!({})), 100)
+            .await;
+
+        // $80 charged once leaves room for $60 more, but not $80
+        assert_eq!(interceptor.daily_spent.write().await.current_total(), 80.0);
+        let decision = interceptor.intercept_tool_call(&prepare).await.unwrap();
+        assert!(matches!(decision, InterceptorDecision::Block(_)));
+    }
+
+    #[tokio::test]
+    async fn test_blocks_unprepared_execution() {
+        let interceptor = SpendLimitInterceptor::new(100.0, 500.0)
+            .with_prepared_swaps(Arc::new(PreparedSwaps::new()));
+        let args = prepared_execution(&PreparedSwaps::new(), 1_000_000, 0);
+
+        let decision = interceptor
+            .intercept_tool_call(&execute_context(args))
+            .await
+            .unwrap();
+        assert!(matches!(decision, InterceptorDecision::Block(_)));
+    }
+
     #[tokio::test]
     async fn test_allows_quotes() {
         let interceptor = SpendLimitInterceptor::new(100.0, 500.0);
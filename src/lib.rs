@@ -12,7 +12,9 @@
 //! - Private keys never leave the Rust wallet module
 //! - Full audit trail of all operations
 
+pub mod analytics;
 pub mod config;
+pub mod execution;
 pub mod graphql;
 pub mod interceptors;
 pub mod paper_trading;
@@ -58,7 +58,8 @@ enum Commands {
         #[arg(short, long)]
         network: String,
 
-        /// Query type (top_pools, pool_info, token_price)
+        /// Query type (top_pools, pool_info, token_price, filtered_pools, query_plan,
+        /// reserves, user_positions, pool_day_data, pool_hour_data, token_day_data)
         #[arg(short = 't', long)]
         query_type: String,
 
@@ -177,7 +178,7 @@ async fn main() -> Result<()> {
             query_type,
             params,
         } => {
-            run_query(protocol, network, query_type, params).await?;
+            run_query(&config, protocol, network, query_type, params).await?;
         }
         Commands::Quote {
             input,
@@ -279,22 +280,21 @@ async fn run_agent(
 }
 
 async fn run_query(
+    config: &Config,
     protocol: String,
     network: String,
     query_type: String,
     params: Option<String>,
 ) -> Result<()> {
     use baml_rt::tools::BamlTool;
-    use defi_trading_agent::config::GRAPH_API_KEY_ENV;
+    use defi_trading_agent::config::RpcConfig;
     use defi_trading_agent::tools::{
         GraphQueryInput, GraphQueryParams, GraphQueryType, TheGraphTool,
     };
 
     // Use gateway-enabled tool if API key is available
-    let tool = match std::env::var(GRAPH_API_KEY_ENV) {
-        Ok(api_key) => TheGraphTool::with_gateway(api_key),
-        Err(_) => TheGraphTool::new(),
-    };
+    let tool = TheGraphTool::from_env_with_settings(config.subgraphs.clone(), &config.graph)
+        .with_rpc_config(RpcConfig::from_env());
     let params_value: serde_json::Value = match params {
         Some(p) => serde_json::from_str(&p).map_err(|e| {
             defi_trading_agent::Error::InvalidArgument(format!("Invalid --params JSON: {}", e))
@@ -308,6 +308,11 @@ async fn run_query(
         "token_price" => GraphQueryType::TokenPrice,
         "filtered_pools" => GraphQueryType::FilteredPools,
         "query_plan" => GraphQueryType::QueryPlan,
+        "reserves" => GraphQueryType::Reserves,
+        "user_positions" => GraphQueryType::UserPositions,
+        "pool_day_data" => GraphQueryType::PoolDayData,
+        "pool_hour_data" => GraphQueryType::PoolHourData,
+        "token_day_data" => GraphQueryType::TokenDayData,
         other => {
             return Err(defi_trading_agent::Error::Config(format!(
                 "Unknown query_type: {}",
@@ -363,6 +368,7 @@ async fn run_quote(input: String, output: String, amount: String, network: Strin
         slippage_percent: None,
         chain_id: None,
         network: Some(network),
+        transaction: None,
     };
 
     let result = tool
@@ -396,6 +402,7 @@ async fn run_price(token: String, network: String) -> Result<()> {
             slippage_percent: None,
             chain_id: None,
             network: Some(network.clone()),
+            transaction: None,
         };
 
         let result = tool
@@ -416,6 +423,7 @@ async fn run_price(token: String, network: String) -> Result<()> {
             slippage_percent: None,
             chain_id: None,
             network: Some(network),
+            transaction: None,
         };
 
         let result = tool
@@ -3,15 +3,18 @@
 //! Loads and executes the trading agent in the QuickJS sandbox with
 //! full tool and interceptor support.
 
-use crate::config::{Config, PolicyDefaultMode, GRAPH_API_KEY_ENV};
+use crate::config::{Config, PolicyDefaultMode, RpcConfig, GRAPH_API_KEY_ENV};
+use crate::execution::PreparedSwaps;
+use crate::graphql::validation::QueryBudget;
 use crate::interceptors::{
     AuditLogInterceptor, CooldownInterceptor, PolicyConfig, PolicyInterceptor, PolicyMode,
-    SlippageGuardInterceptor, SpendLimitInterceptor,
+    QuoteFreshnessInterceptor, SlippageGuardInterceptor, SpendLimitInterceptor,
 };
 use crate::paper_trading::PaperTradingState;
 use crate::tools::{
-    OdosTool, PaperTradingTool, TheGraphTool, WalletDeriveAddressTool, WalletSignMessageTool,
-    WalletSignTxTool, WalletTool,
+    ExecuteSwapTool, FreshnessPolicy, GraphGatewayStatusTool, GraphUsageTool, OdosTool,
+    PaperTradingTool, PoolAnalyticsTool, SubgraphSchemaTool, TheGraphTool, WalletDeriveAddressTool,
+    WalletSignMessageTool, WalletSignTxTool, WalletTool,
 };
 use crate::wallet::SecureWallet;
 use crate::Result;
@@ -31,6 +34,8 @@ pub struct AgentRunner {
     dry_run: bool,
     wallet: Option<Arc<SecureWallet>>,
     paper_trading: Option<PaperTradingState>,
+    /// Swaps built by `prepare_swap`, shared by execution and its spend check
+    prepared_swaps: Arc<PreparedSwaps>,
 }
 
 fn quickjs_config_from_env() -> QuickJSConfig {
@@ -87,6 +92,7 @@ impl AgentRunner {
             dry_run,
             wallet: None,
             paper_trading: None,
+            prepared_swaps: Arc::new(PreparedSwaps::new()),
         }
     }
 
@@ -271,7 +277,8 @@ impl AgentRunner {
             risk.max_trade_usd,
             risk.max_daily_usd,
             risk.spend_limit_mode,
-        );
+        )
+        .with_prepared_swaps(self.prepared_swaps.clone());
         builder = builder.with_tool_interceptor(spend_limit);
         info!(
             max_trade = risk.max_trade_usd,
@@ -296,7 +303,21 @@ impl AgentRunner {
             "Added cooldown interceptor"
         );
 
-        // 5. Audit log interceptor
+        // 5. Quote freshness interceptor
+        let mut quote_freshness =
+            QuoteFreshnessInterceptor::new(risk.max_quote_age_secs, self.prepared_swaps.clone());
+        if let Some(max_blocks) = risk.max_quote_block_distance {
+            quote_freshness =
+                quote_freshness.with_max_block_distance(max_blocks, RpcConfig::from_env());
+        }
+        builder = builder.with_tool_interceptor(quote_freshness);
+        info!(
+            max_quote_age_secs = risk.max_quote_age_secs,
+            max_quote_block_distance = ?risk.max_quote_block_distance,
+            "Added quote freshness interceptor"
+        );
+
+        // 6. Audit log interceptor
         if let Some(audit_path) = &self.config.audit_log_path {
             let audit_log = AuditLogInterceptor::new(audit_path);
             builder = builder.with_tool_interceptor(audit_log);
@@ -333,19 +354,62 @@ impl AgentRunner {
             let mut manager_guard = baml_manager.lock().await;
 
             // Register The Graph tool
-            // Use gateway-enabled version if GRAPH_API_KEY is set (enables caching)
-            let the_graph_tool = match std::env::var(GRAPH_API_KEY_ENV) {
-                Ok(api_key) => {
-                    info!("Creating TheGraphTool with gateway caching enabled");
-                    TheGraphTool::with_gateway(api_key)
+            // Pay per query with x402 if enabled and a wallet is loaded;
+            // otherwise use the gateway if GRAPH_API_KEY is set (enables caching)
+            let x402_wallet = match (&self.wallet, self.config.graph.x402.enabled) {
+                (Some(wallet), true) => Some(wallet.clone()),
+                (None, true) => {
+                    warn!("graph.x402 is enabled but no wallet is loaded; not paying for queries");
+                    None
                 }
-                Err(_) => {
+                (_, false) => None,
+            };
+            let the_graph_tool = if let Some(wallet) = x402_wallet {
+                info!(
+                    max_daily_payment_usd = self.config.graph.x402.max_daily_payment_usd,
+                    "Creating TheGraphTool with x402 pay-per-query gateway"
+                );
+                TheGraphTool::with_x402_payments(
+                    self.config.subgraphs.clone(),
+                    &self.config.graph,
+                    wallet,
+                )
+            } else {
+                if std::env::var(GRAPH_API_KEY_ENV).is_ok() {
+                    info!("Creating TheGraphTool with gateway caching enabled");
+                } else {
                     warn!(
-                        "GRAPH_API_KEY not set, TheGraphTool will use direct queries (no caching)"
+                        "GRAPH_API_KEY not set, TheGraphTool will only reach self-hosted endpoints (no caching)"
                     );
-                    TheGraphTool::new()
                 }
-            };
+                TheGraphTool::from_env_with_settings(
+                    self.config.subgraphs.clone(),
+                    &self.config.graph,
+                )
+            }
+            .with_max_results(self.config.graph.max_results)
+            .with_plan_concurrency(self.config.graph.plan_concurrency)
+            .with_plan_leg_timeout(std::time::Duration::from_secs(
+                self.config.graph.plan_leg_timeout_secs,
+            ))
+            .with_rpc_config(RpcConfig::from_env())
+            .with_freshness_policy(FreshnessPolicy {
+                max_data_age_secs: self.config.graph.max_data_age_secs,
+                max_lag_blocks: self.config.graph.max_lag_blocks,
+                reject_indexing_errors: self.config.graph.reject_indexing_errors,
+            })
+            .with_query_budget(QueryBudget {
+                max_depth: self.config.graph.custom_query_max_depth,
+                max_first: self.config.graph.custom_query_max_first,
+                max_root_fields: self.config.graph.custom_query_max_root_fields,
+                max_aliases: self.config.graph.custom_query_max_aliases,
+            });
+            // Pool analytics, schema introspection, gateway status and usage
+            // share the subgraph tool's gateway, cache and endpoints
+            let pool_analytics_tool = PoolAnalyticsTool::new(the_graph_tool.clone());
+            let subgraph_schema_tool = SubgraphSchemaTool::new(the_graph_tool.clone());
+            let gateway_status_tool = GraphGatewayStatusTool::new(the_graph_tool.clone());
+            let graph_usage_tool = GraphUsageTool::new(the_graph_tool.clone());
             manager_guard
                 .register_tool(the_graph_tool)
                 .await
@@ -354,10 +418,53 @@ impl AgentRunner {
                 })?;
             info!("Registered TheGraphTool with BAML manager");
 
+            manager_guard
+                .register_tool(pool_analytics_tool)
+                .await
+                .map_err(|e| {
+                    crate::Error::BamlRuntime(format!(
+                        "Failed to register PoolAnalyticsTool: {}",
+                        e
+                    ))
+                })?;
+            info!("Registered PoolAnalyticsTool with BAML manager");
+
+            manager_guard
+                .register_tool(subgraph_schema_tool)
+                .await
+                .map_err(|e| {
+                    crate::Error::BamlRuntime(format!(
+                        "Failed to register SubgraphSchemaTool: {}",
+                        e
+                    ))
+                })?;
+            info!("Registered SubgraphSchemaTool with BAML manager");
+
+            manager_guard
+                .register_tool(gateway_status_tool)
+                .await
+                .map_err(|e| {
+                    crate::Error::BamlRuntime(format!(
+                        "Failed to register GraphGatewayStatusTool: {}",
+                        e
+                    ))
+                })?;
+            info!("Registered GraphGatewayStatusTool with BAML manager");
+
+            manager_guard
+                .register_tool(graph_usage_tool)
+                .await
+                .map_err(|e| {
+                    crate::Error::BamlRuntime(format!("Failed to register GraphUsageTool: {}", e))
+                })?;
+            info!("Registered GraphUsageTool with BAML manager");
+
             // Register Odos tool
-            let odos_tool = OdosTool::try_new(&wallet_address).map_err(|e| {
-                crate::Error::BamlRuntime(format!("Failed to create OdosTool: {}", e))
-            })?;
+            let odos_tool = OdosTool::try_new(&wallet_address)
+                .map_err(|e| {
+                    crate::Error::BamlRuntime(format!("Failed to create OdosTool: {}", e))
+                })?
+                .with_prepared_swaps(self.prepared_swaps.clone());
             manager_guard.register_tool(odos_tool).await.map_err(|e| {
                 crate::Error::BamlRuntime(format!("Failed to register OdosTool: {}", e))
             })?;
@@ -396,7 +503,10 @@ impl AgentRunner {
                         ))
                     })?;
                 manager_guard
-                    .register_tool(WalletSignTxTool::new(wallet.clone()))
+                    .register_tool(
+                        WalletSignTxTool::new(wallet.clone())
+                            .with_blind_signing(self.config.signing.allow_blind_signing),
+                    )
                     .await
                     .map_err(|e| {
                         crate::Error::BamlRuntime(format!(
@@ -405,8 +515,27 @@ impl AgentRunner {
                         ))
                     })?;
                 info!("Registered wallet signing ladder tools");
+
+                if self.config.execute_swaps {
+                    manager_guard
+                        .register_tool(ExecuteSwapTool::new(
+                            wallet.clone(),
+                            RpcConfig::from_env(),
+                            self.prepared_swaps.clone(),
+                        ))
+                        .await
+                        .map_err(|e| {
+                            crate::Error::BamlRuntime(format!(
+                                "Failed to register ExecuteSwapTool: {}",
+                                e
+                            ))
+                        })?;
+                    info!("Registered ExecuteSwapTool with BAML manager");
+                } else {
+                    info!("execute_swaps is off; ExecuteSwapTool not registered");
+                }
             } else {
-                warn!("No wallet configured; signing tools not registered");
+                warn!("No wallet configured; signing and execution tools not registered");
             }
 
             // Register Paper Trading tool if enabled
@@ -478,6 +607,7 @@ impl AgentRunner {
             "networks": self.config.networks.iter().map(|n| n.name()).collect::<Vec<_>>(),
             "protocols": self.config.protocols.iter().map(|p| p.name()).collect::<Vec<_>>(),
             "check_interval_ms": self.config.check_interval_ms,
+            "execute_swaps": self.config.execute_swaps && self.wallet.is_some(),
             "risk": {
                 "max_trade_usd": self.config.risk.max_trade_usd,
                 "max_slippage_percent": self.config.risk.max_slippage_percent,
@@ -92,6 +92,8 @@ pub struct TokenRegistry {
     tokens: HashMap<Address, TokenInfo>,
     /// Tokens per chain for balance queries
     tokens_per_chain: HashMap<u64, Vec<Address>>,
+    /// Addresses by (chain, uppercase symbol), including bridged variants
+    symbols: HashMap<(u64, String), Vec<Address>>,
 }
 
 impl TokenRegistry {
@@ -143,9 +145,25 @@ impl TokenRegistry {
         tokens_per_chain.insert(chains::OPTIMISM, vec![USDC_OPT, USDT_OPT, WETH_OPT]);
         tokens_per_chain.insert(chains::BASE, vec![USDC_BASE, WETH_BASE, DAI_BASE]);
 
+        // Index symbols per chain (bridged tokens aren't queried for balances
+        // but still need to resolve by symbol)
+        let bridged = [
+            (chains::ARBITRUM, USDC_E_ARB),
+            (chains::OPTIMISM, USDC_E_OPT),
+        ];
+        let mut symbols: HashMap<(u64, String), Vec<Address>> = HashMap::new();
+        let per_chain = tokens_per_chain
+            .iter()
+            .flat_map(|(chain, addrs)| addrs.iter().map(move |addr| (*chain, *addr)));
+        for (chain, addr) in per_chain.chain(bridged) {
+            let symbol = tokens[&addr].symbol.to_ascii_uppercase();
+            symbols.entry((chain, symbol)).or_default().push(addr);
+        }
+
         Self {
             tokens,
             tokens_per_chain,
+            symbols,
         }
     }
 
@@ -168,6 +186,17 @@ impl TokenRegistry {
             .unwrap_or(&[])
     }
 
+    /// Get the addresses of a token symbol on a chain (case-insensitive)
+    ///
+    /// Returns an empty slice for unknown symbols. Symbols are not unique
+    /// across chains, so lookups are always scoped to one chain.
+    pub fn addresses_for_symbol(&self, chain_id: u64, symbol: &str) -> &[Address] {
+        self.symbols
+            .get(&(chain_id, symbol.to_ascii_uppercase()))
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+
     /// Check if an address is a known stablecoin
     pub fn is_stablecoin(&self, address: &Address) -> bool {
         self.tokens
@@ -270,6 +299,31 @@ mod tests {
         assert!(arb_tokens.contains(&addresses::USDC_ARB));
     }
 
+    #[test]
+    fn test_addresses_for_symbol() {
+        let registry = TokenRegistry::new();
+
+        assert_eq!(
+            registry.addresses_for_symbol(chains::ETHEREUM, "weth"),
+            &[addresses::WETH_ETH]
+        );
+        assert_eq!(
+            registry.addresses_for_symbol(chains::ARBITRUM, "USDC.e"),
+            &[addresses::USDC_E_ARB]
+        );
+        // Same address on two chains resolves per chain
+        assert_eq!(
+            registry.addresses_for_symbol(chains::BASE, "WETH"),
+            &[addresses::WETH_BASE]
+        );
+        assert!(registry
+            .addresses_for_symbol(chains::BASE, "WBTC")
+            .is_empty());
+        assert!(registry
+            .addresses_for_symbol(chains::ETHEREUM, "PEPE")
+            .is_empty());
+    }
+
     #[test]
     fn test_global_registry() {
         let reg = registry();
@@ -0,0 +1,193 @@
+//! Swap execution tool
+//!
+//! Executes a transaction produced by `defi/odos_swap` `prepare_swap`:
+//! simulate → sign → broadcast → receipt.
+//!
+//! SECURITY NOTE:
+//! - Signing happens inside `SwapExecutor` via SecureWallet; no key exposure.
+//! - Only transactions recorded by `prepare_swap` are executed, each at most
+//!   once, and `SwapExecutor` decodes their intent before signing.
+//! - Calls pass through the interceptor pipeline (policy, spend limits, audit)
+//!   before anything is simulated or signed.
+//! - Only registered when a wallet is configured and `execute_swaps` is set.
+
+use crate::config::RpcConfig;
+use crate::execution::{ExecutionError, PreparedSwaps, SwapExecutor};
+use crate::tools::{AnyJson, DefiBundle};
+use crate::wallet::{PreparedTransaction, SecureWallet};
+use alloy::primitives::{hex, Address, Bytes, U256};
+use async_trait::async_trait;
+use baml_rt::error::{BamlRtError, Result};
+use baml_rt::tools::BamlTool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::str::FromStr;
+use std::sync::Arc;
+use ts_rs::TS;
+
+/// Transaction fields as returned by `prepare_swap`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, TS)]
+#[ts(export)]
+pub struct PreparedSwapTransaction {
+    pub to: String,
+    pub data: String,
+    pub value: String,
+    pub gas_limit: Option<u64>,
+    pub chain_id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, TS)]
+#[ts(export)]
+pub struct ExecuteSwapInput {
+    /// The `transaction` object from a `prepare_swap` result
+    pub transaction: PreparedSwapTransaction,
+    /// Wait for the receipt (default: true)
+    pub wait_for_receipt: Option<bool>,
+}
+
+impl TryFrom<&PreparedSwapTransaction> for PreparedTransaction {
+    type Error = BamlRtError;
+
+    fn try_from(tx: &PreparedSwapTransaction) -> Result<Self> {
+        let to = Address::from_str(&tx.to).map_err(|e| {
+            BamlRtError::InvalidArgument(format!("Invalid transaction 'to' address: {}", e))
+        })?;
+        let data = hex::decode(tx.data.strip_prefix("0x").unwrap_or(&tx.data)).map_err(|e| {
+            BamlRtError::InvalidArgument(format!("Invalid transaction data: {}", e))
+        })?;
+        let value = if tx.value.is_empty() {
+            U256::ZERO
+        } else {
+            U256::from_str(&tx.value).map_err(|e| {
+                BamlRtError::InvalidArgument(format!("Invalid transaction value: {}", e))
+            })?
+        };
+
+        Ok(PreparedTransaction {
+            to,
+            data: Bytes::from(data),
+            value,
+            gas_limit: tx.gas_limit.unwrap_or(0),
+            chain_id: tx.chain_id,
+        })
+    }
+}
+
+/// Tool that executes prepared swaps on-chain
+pub struct ExecuteSwapTool {
+    executor: SwapExecutor,
+    /// Transactions built by `prepare_swap`, the only ones executed
+    prepared_swaps: Arc<PreparedSwaps>,
+}
+
+impl ExecuteSwapTool {
+    pub fn new(
+        wallet: Arc<SecureWallet>,
+        rpc_config: RpcConfig,
+        prepared_swaps: Arc<PreparedSwaps>,
+    ) -> Self {
+        Self {
+            executor: SwapExecutor::new(wallet, rpc_config),
+            prepared_swaps,
+        }
+    }
+}
+
+#[async_trait]
+impl BamlTool for ExecuteSwapTool {
+    type Bundle = DefiBundle;
+    const LOCAL_NAME: &'static str = "execute_swap";
+    type OpenInput = ();
+    type Input = ExecuteSwapInput;
+    type Output = AnyJson;
+
+    fn description(&self) -> &'static str {
+        "Execute a transaction from odos_swap prepare_swap (policy-gated): simulates, signs, \
+         broadcasts, and waits for the receipt. Returns tx hash and status \
+         (pending/confirmed/reverted). Pass the transaction unchanged; a stale quote is blocked \
+         and must be refreshed with odos_swap requote."
+    }
+
+    async fn execute(&self, args: Self::Input) -> Result<Self::Output> {
+        let prepared = PreparedTransaction::try_from(&args.transaction)?;
+        // Claim the prepared swap so it is executed at most once
+        let Some(swap) = self.prepared_swaps.remove(&prepared) else {
+            return Err(BamlRtError::InvalidArgument(
+                "Transaction was not built by odos_swap prepare_swap (or was already executed); \
+                 pass its transaction unchanged"
+                    .to_string(),
+            ));
+        };
+        let wait_for_receipt = args.wait_for_receipt.unwrap_or(true);
+
+        let result = self
+            .executor
+            .execute(&prepared, wait_for_receipt)
+            .await
+            .map_err(|e| {
+                // Nothing was sent, so the swap can still be executed
+                if !e.was_broadcast() {
+                    self.prepared_swaps.record(&prepared, swap);
+                }
+                e
+            })
+            .map_err(|e| match e {
+                ExecutionError::NoRpcUrl(_)
+                | ExecutionError::ChainMismatch { .. }
+                | ExecutionError::Intent(_)
+                | ExecutionError::NotASwap => BamlRtError::InvalidArgument(e.to_string()),
+                _ => BamlRtError::ToolExecution(e.to_string()),
+            })?;
+
+        Ok(AnyJson::new(json!({
+            "action": "execute_swap",
+            "from": self.executor.wallet_address(),
+            "status": result.status,
+            "tx_hash": result.tx_hash,
+            "chain_id": result.chain_id,
+            "nonce": result.nonce,
+            "gas_limit": result.gas_limit,
+            "simulated_gas": result.simulated_gas,
+            "block_number": result.block_number,
+            "gas_used": result.gas_used,
+            "effective_gas_price": result.effective_gas_price,
+            "intent": result.intent,
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepared_transaction_from_prepare_swap_output() {
+        let tx = PreparedSwapTransaction {
+            to: "0xCf5540fFFCdC3d510B18bFcA6d2b9987b0772559".to_string(),
+            data: "0x83bd37f9".to_string(),
+            value: "1000".to_string(),
+            gas_limit: None,
+            chain_id: 1,
+        };
+
+        let prepared = PreparedTransaction::try_from(&tx).unwrap();
+        assert_eq!(prepared.data.as_ref(), &[0x83, 0xbd, 0x37, 0xf9]);
+        assert_eq!(prepared.value, U256::from(1000));
+        assert_eq!(prepared.gas_limit, 0);
+        assert_eq!(prepared.chain_id, 1);
+    }
+
+    #[test]
+    fn test_prepared_transaction_rejects_bad_address() {
+        let tx = PreparedSwapTransaction {
+            to: "not-an-address".to_string(),
+            data: "0x".to_string(),
+            value: "0".to_string(),
+            gas_limit: Some(21_000),
+            chain_id: 1,
+        };
+
+        assert!(PreparedTransaction::try_from(&tx).is_err());
+    }
+}
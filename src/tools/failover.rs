@@ -0,0 +1,177 @@
+//! Failover between a subgraph's endpoints
+//!
+//! A network/protocol pair can be served by several endpoints, e.g. a
+//! self-hosted graph-node backed by the gateway. Queries try them in
+//! configured order, except that endpoints with a poor health score or found
+//! lagging behind the chain head are tried last until their demotion expires.
+//! Which endpoints served a tool call is collected for its response.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a failing or lagging endpoint is tried last
+pub(crate) const ENDPOINT_DEMOTION_SECS: u64 = 60;
+
+/// Health score below which a recent failure demotes an endpoint
+const DEMOTION_SCORE: f64 = 0.5;
+
+/// Weight of the newest outcome in the health score
+const SMOOTHING: f64 = 0.3;
+
+tokio::task_local! {
+    /// Endpoints that served queries in the current tool call
+    static SERVED_BY: RefCell<Vec<String>>;
+}
+
+/// Run `call`, collecting the endpoints that served its queries (first use first)
+pub(crate) async fn track_served<F: Future>(call: F) -> (F::Output, Vec<String>) {
+    SERVED_BY
+        .scope(RefCell::new(Vec::new()), async move {
+            let output = call.await;
+            let served = SERVED_BY.with(|served| served.take());
+            (output, served)
+        })
+        .await
+}
+
+/// Note that `label` served a query (a no-op outside [`track_served`])
+pub(crate) fn note_served(label: &str) {
+    let _ = SERVED_BY.try_with(|served| {
+        let mut served = served.borrow_mut();
+        if !served.iter().any(|known| known == label) {
+            served.push(label.to_string());
+        }
+    });
+}
+
+struct Health {
+    /// Moving average of query outcomes (1.0 = always succeeds)
+    score: f64,
+    demoted_until: Option<Instant>,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            score: 1.0,
+            demoted_until: None,
+        }
+    }
+}
+
+/// Health of subgraph endpoints, keyed by label and shared by tool clones
+pub(crate) struct EndpointHealth {
+    demotion: Duration,
+    endpoints: Mutex<HashMap<String, Health>>,
+}
+
+impl Default for EndpointHealth {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(ENDPOINT_DEMOTION_SECS))
+    }
+}
+
+impl EndpointHealth {
+    pub(crate) fn new(demotion: Duration) -> Self {
+        Self {
+            demotion,
+            endpoints: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Indices of `labels` in the order to try them: configured order, with
+    /// demoted endpoints last
+    pub(crate) fn order(&self, labels: &[&str]) -> Vec<usize> {
+        let endpoints = self.endpoints.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let mut order: Vec<usize> = (0..labels.len()).collect();
+        // Stable sort keeps configured order within each group
+        order.sort_by_key(|&index| {
+            endpoints
+                .get(labels[index])
+                .and_then(|health| health.demoted_until)
+                .is_some_and(|until| until > now)
+        });
+        order
+    }
+
+    pub(crate) fn record_success(&self, label: &str) {
+        self.update(label, |health| {
+            health.score += SMOOTHING * (1.0 - health.score);
+            health.demoted_until = None;
+        });
+    }
+
+    /// An endpoint failed; repeated failures demote it
+    pub(crate) fn record_failure(&self, label: &str) {
+        let until = Instant::now() + self.demotion;
+        self.update(label, |health| {
+            health.score -= SMOOTHING * health.score;
+            if health.score < DEMOTION_SCORE {
+                health.demoted_until = Some(until);
+            }
+        });
+    }
+
+    /// An endpoint is too far behind the chain head; demote it right away
+    pub(crate) fn record_lag(&self, label: &str) {
+        let until = Instant::now() + self.demotion;
+        self.update(label, |health| {
+            health.score -= SMOOTHING * health.score;
+            health.demoted_until = Some(until);
+        });
+    }
+
+    fn update(&self, label: &str, apply: impl FnOnce(&mut Health)) {
+        let mut endpoints = self.endpoints.lock().unwrap_or_else(|e| e.into_inner());
+        apply(endpoints.entry(label.to_string()).or_default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failures_and_lag_demote_until_recovery() {
+        let health = EndpointHealth::default();
+        let labels = ["self-hosted", "gateway"];
+        assert_eq!(health.order(&labels), vec![0, 1]);
+
+        // One failure lowers the score; a second demotes
+        health.record_failure("self-hosted");
+        assert_eq!(health.order(&labels), vec![0, 1]);
+        health.record_failure("self-hosted");
+        assert_eq!(health.order(&labels), vec![1, 0]);
+
+        health.record_success("self-hosted");
+        assert_eq!(health.order(&labels), vec![0, 1]);
+
+        health.record_lag("self-hosted");
+        assert_eq!(health.order(&labels), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_demotion_expires() {
+        let health = EndpointHealth::new(Duration::ZERO);
+        health.record_lag("self-hosted");
+        assert_eq!(health.order(&["self-hosted", "gateway"]), vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_track_served() {
+        let ((), served) = track_served(async {
+            note_served("gateway");
+            note_served("self-hosted");
+            note_served("gateway");
+        })
+        .await;
+        assert_eq!(served, vec!["gateway", "self-hosted"]);
+
+        // Outside a tracked call nothing is recorded
+        note_served("gateway");
+    }
+}
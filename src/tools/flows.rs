@@ -0,0 +1,265 @@
+//! Order flow aggregates over swap, mint and burn events
+//!
+//! Turns a window of raw events into a few numbers the strategist can reason
+//! about directly: which way the flow is going, how much of it is large, how
+//! many distinct wallets are behind it, and how much the price moved.
+
+use crate::analytics::volatility::{annualize, realized_volatility};
+use crate::graphql::uniswap_v3::{LiquidityEvent, Swap};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Default USD size at which a trade counts as large
+pub const DEFAULT_LARGE_TRADE_USD: f64 = 100_000.0;
+
+/// Net flow below this fraction of volume is reported as neutral
+const NEUTRAL_FLOW_RATIO: f64 = 0.05;
+
+/// Dominant direction of flow over a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowDirection {
+    /// Net buying of the base token (swaps) or liquidity added (mints)
+    Inflow,
+    /// Net selling of the base token (swaps) or liquidity removed (burns)
+    Outflow,
+    Neutral,
+}
+
+/// Token that swap flow is measured against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowBase<'a> {
+    /// Each pool's token0 (pool-scoped queries)
+    Token0,
+    /// A specific token address (token-scoped queries)
+    Token(&'a str),
+}
+
+/// Aggregates over a window of events
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FlowMetrics {
+    pub event_count: usize,
+    pub volume_usd: f64,
+    /// Signed USD flow (positive = inflow)
+    pub net_flow_usd: f64,
+    pub flow_direction: FlowDirection,
+    /// Events at or above the large trade threshold
+    pub large_trade_count: usize,
+    pub large_trade_usd: f64,
+    /// Distinct transaction senders
+    pub unique_traders: usize,
+    /// Realized volatility of the base token price over the window (swaps only)
+    pub realized_volatility: Option<f64>,
+    /// Realized volatility scaled to one year (swaps only)
+    pub annualized_volatility: Option<f64>,
+}
+
+/// Aggregate swaps into flow metrics
+///
+/// # Arguments
+/// * `swaps` - Swaps in any order
+/// * `base` - Token whose buying/selling defines the flow direction
+/// * `large_trade_usd` - Threshold for counting a trade as large
+/// * `window_secs` - Length of the window the swaps cover, for annualizing
+///
+/// Pool-scoped prices come from the pool tick, which is exact; token-scoped
+/// prices (across pools) are the USD execution price of each swap.
+pub fn swap_flow_metrics(
+    swaps: &[Swap],
+    base: FlowBase<'_>,
+    large_trade_usd: f64,
+    window_secs: i64,
+) -> FlowMetrics {
+    let mut ordered: Vec<&Swap> = swaps.iter().collect();
+    ordered.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.id.cmp(&b.id)));
+
+    let mut net_flow_usd = 0.0;
+    let mut prices = Vec::with_capacity(ordered.len());
+    for swap in &ordered {
+        let base_amount = match base {
+            FlowBase::Token0 => swap.amount0,
+            FlowBase::Token(token) if swap.token0.id.eq_ignore_ascii_case(token) => swap.amount0,
+            FlowBase::Token(token) if swap.token1.id.eq_ignore_ascii_case(token) => swap.amount1,
+            FlowBase::Token(_) => continue,
+        };
+        // Amounts are from the pool's side: a negative base amount left the
+        // pool, i.e. the trader bought it
+        if base_amount < 0.0 {
+            net_flow_usd += swap.amount_usd;
+        } else if base_amount > 0.0 {
+            net_flow_usd -= swap.amount_usd;
+        }
+
+        let price = match base {
+            FlowBase::Token0 => 1.0001_f64.powi(swap.tick),
+            FlowBase::Token(_) if base_amount != 0.0 => swap.amount_usd / base_amount.abs(),
+            FlowBase::Token(_) => continue,
+        };
+        if price.is_finite() && price > 0.0 {
+            prices.push(price);
+        }
+    }
+
+    let volume_usd: f64 = swaps.iter().map(|s| s.amount_usd).sum();
+    let realized = realized_volatility(&prices);
+    FlowMetrics {
+        event_count: swaps.len(),
+        volume_usd,
+        net_flow_usd,
+        flow_direction: flow_direction(net_flow_usd, volume_usd),
+        large_trade_count: swaps
+            .iter()
+            .filter(|s| s.amount_usd >= large_trade_usd)
+            .count(),
+        large_trade_usd,
+        unique_traders: unique(swaps.iter().map(|s| s.origin.as_str())),
+        realized_volatility: realized,
+        annualized_volatility: realized.and_then(|rv| annualize(rv, window_secs)),
+    }
+}
+
+/// Aggregate mints (`added = true`) or burns into flow metrics
+///
+/// Events the subgraph can't price count toward `event_count` and unique
+/// traders but not toward volume.
+pub fn liquidity_flow_metrics(
+    events: &[LiquidityEvent],
+    added: bool,
+    large_trade_usd: f64,
+) -> FlowMetrics {
+    let volume_usd: f64 = events.iter().filter_map(|e| e.amount_usd).sum();
+    let net_flow_usd = if added { volume_usd } else { -volume_usd };
+    FlowMetrics {
+        event_count: events.len(),
+        volume_usd,
+        net_flow_usd,
+        flow_direction: flow_direction(net_flow_usd, volume_usd),
+        large_trade_count: events
+            .iter()
+            .filter(|e| e.amount_usd.unwrap_or(0.0) >= large_trade_usd)
+            .count(),
+        large_trade_usd,
+        unique_traders: unique(events.iter().map(|e| e.origin.as_str())),
+        realized_volatility: None,
+        annualized_volatility: None,
+    }
+}
+
+fn flow_direction(net_flow_usd: f64, volume_usd: f64) -> FlowDirection {
+    if volume_usd <= 0.0 || net_flow_usd.abs() < volume_usd * NEUTRAL_FLOW_RATIO {
+        FlowDirection::Neutral
+    } else if net_flow_usd > 0.0 {
+        FlowDirection::Inflow
+    } else {
+        FlowDirection::Outflow
+    }
+}
+
+fn unique<'a>(addresses: impl Iterator<Item = &'a str>) -> usize {
+    addresses.collect::<HashSet<_>>().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphql::uniswap_v3::TokenRef;
+
+    fn swap(id: &str, timestamp: i64, origin: &str, amount0: f64, usd: f64, tick: i32) -> Swap {
+        Swap {
+            id: id.to_string(),
+            timestamp,
+            pool_id: "0xpool".to_string(),
+            token0: TokenRef {
+                id: "0x01".to_string(),
+                symbol: "WETH".to_string(),
+            },
+            token1: TokenRef {
+                id: "0x02".to_string(),
+                symbol: "USDC".to_string(),
+            },
+            origin: origin.to_string(),
+            amount0,
+            amount1: -amount0 * 2000.0,
+            amount_usd: usd,
+            tick,
+        }
+    }
+
+    #[test]
+    fn test_net_flow_follows_base_token() {
+        let swaps = vec![
+            // Trader buys 10 WETH, then sells 1 WETH
+            swap("a", 1, "0xaa", -10.0, 20_000.0, 0),
+            swap("b", 2, "0xbb", 1.0, 2_000.0, 0),
+        ];
+        let metrics = swap_flow_metrics(&swaps, FlowBase::Token0, 10_000.0, 3600);
+        assert_eq!(metrics.net_flow_usd, 18_000.0);
+        assert_eq!(metrics.flow_direction, FlowDirection::Inflow);
+        assert_eq!(metrics.large_trade_count, 1);
+        assert_eq!(metrics.unique_traders, 2);
+
+        // Measured against USDC the same flow is selling
+        let usdc = swap_flow_metrics(&swaps, FlowBase::Token("0x02"), 10_000.0, 3600);
+        assert_eq!(usdc.net_flow_usd, -18_000.0);
+        assert_eq!(usdc.flow_direction, FlowDirection::Outflow);
+    }
+
+    #[test]
+    fn test_balanced_flow_is_neutral() {
+        let swaps = vec![
+            swap("a", 1, "0xaa", -1.0, 2_000.0, 0),
+            swap("b", 2, "0xaa", 1.0, 2_000.0, 0),
+        ];
+        let metrics = swap_flow_metrics(&swaps, FlowBase::Token0, DEFAULT_LARGE_TRADE_USD, 3600);
+        assert_eq!(metrics.flow_direction, FlowDirection::Neutral);
+        assert_eq!(metrics.unique_traders, 1);
+    }
+
+    #[test]
+    fn test_volatility_from_ticks_in_time_order() {
+        // Newest first, as returned by the subgraph
+        let swaps = vec![
+            swap("c", 3, "0xaa", 1.0, 1.0, 100),
+            swap("b", 2, "0xaa", 1.0, 1.0, 0),
+            swap("a", 1, "0xaa", 1.0, 1.0, 100),
+        ];
+        let metrics = swap_flow_metrics(&swaps, FlowBase::Token0, 1e9, 86_400);
+        // Two moves of 100 ticks each: sqrt(2) * 100 * ln(1.0001)
+        let expected = 2f64.sqrt() * 100.0 * 1.0001f64.ln();
+        let rv = metrics.realized_volatility.unwrap();
+        assert!((rv - expected).abs() < 1e-12);
+        let annualized = metrics.annualized_volatility.unwrap();
+        assert!((annualized - expected * 365f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_liquidity_flow_skips_unpriced_events() {
+        let event = |usd: Option<f64>, origin: &str| LiquidityEvent {
+            id: origin.to_string(),
+            timestamp: 1,
+            pool_id: "0xpool".to_string(),
+            token0: TokenRef {
+                id: "0x01".to_string(),
+                symbol: "WETH".to_string(),
+            },
+            token1: TokenRef {
+                id: "0x02".to_string(),
+                symbol: "USDC".to_string(),
+            },
+            origin: origin.to_string(),
+            amount0: 1.0,
+            amount1: 1.0,
+            amount_usd: usd,
+            tick_lower: -60,
+            tick_upper: 60,
+        };
+        let burns = vec![event(Some(250_000.0), "0xaa"), event(None, "0xbb")];
+        let metrics = liquidity_flow_metrics(&burns, false, DEFAULT_LARGE_TRADE_USD);
+        assert_eq!(metrics.event_count, 2);
+        assert_eq!(metrics.net_flow_usd, -250_000.0);
+        assert_eq!(metrics.flow_direction, FlowDirection::Outflow);
+        assert_eq!(metrics.large_trade_count, 1);
+        assert_eq!(metrics.unique_traders, 2);
+        assert_eq!(metrics.realized_volatility, None);
+    }
+}
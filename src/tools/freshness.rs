@@ -0,0 +1,434 @@
+//! Subgraph freshness checks
+//!
+//! Compares a subgraph's indexed head (`_meta`) with wall-clock time, or the
+//! chain head from RPC when a block-distance limit is set, so the agent never
+//! trades on prices from a subgraph that has fallen behind. The `_meta` block
+//! is selected in the same request as the data it vouches for, so checking
+//! costs no extra query.
+
+use alloy::eips::BlockNumberOrTag;
+use alloy::providers::{Provider, ProviderBuilder};
+use graphql_parser::query::{parse_query, Definition, OperationDefinition};
+use serde::Serialize;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+
+/// Alias of the `_meta` selection added to freshness-checked queries
+///
+/// An alias keeps it apart from any `_meta` field the query selects itself.
+pub(crate) const META_ALIAS: &str = "_freshness";
+
+/// Selection added to freshness-checked queries
+const META_SELECTION: &str =
+    "{ _freshness: _meta { block { number timestamp } hasIndexingErrors } }";
+
+/// Chain head as reported by an RPC node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainHead {
+    pub number: u64,
+    pub timestamp: i64,
+}
+
+/// Indexing status of a subgraph relative to the chain head
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SubgraphFreshness {
+    /// Latest block indexed by the subgraph
+    pub subgraph_block: u64,
+    /// Timestamp of that block (older graph-node versions omit it)
+    pub subgraph_timestamp: Option<i64>,
+    /// Chain head from RPC, if reachable
+    pub chain_head_block: Option<u64>,
+    /// Blocks the subgraph is behind the chain head
+    pub lag_blocks: Option<u64>,
+    /// Seconds between the subgraph's block and the chain head (or now)
+    pub data_age_seconds: Option<i64>,
+    /// Subgraph reported indexing errors
+    pub has_indexing_errors: bool,
+}
+
+/// Limits applied to subgraph freshness
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FreshnessPolicy {
+    /// Maximum data age before results are rejected (None disables the check)
+    pub max_data_age_secs: Option<u64>,
+    /// Maximum blocks behind the chain head (None disables the check; needs RPC)
+    pub max_lag_blocks: Option<u64>,
+    /// Reject results from subgraphs that report indexing errors
+    pub reject_indexing_errors: bool,
+}
+
+impl Default for FreshnessPolicy {
+    fn default() -> Self {
+        Self {
+            max_data_age_secs: Some(crate::config::DEFAULT_MAX_DATA_AGE_SECS),
+            max_lag_blocks: None,
+            reject_indexing_errors: true,
+        }
+    }
+}
+
+/// Error type for stale or unhealthy subgraphs
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FreshnessError {
+    #[error(
+        "Subgraph data is stale: {data_age_seconds}s old at block {subgraph_block} (max {max_data_age_secs}s)"
+    )]
+    Stale {
+        subgraph_block: u64,
+        data_age_seconds: i64,
+        max_data_age_secs: u64,
+    },
+
+    #[error(
+        "Subgraph is lagging: {lag_blocks} blocks behind the chain head at block {subgraph_block} (max {max_lag_blocks})"
+    )]
+    Lagging {
+        subgraph_block: u64,
+        lag_blocks: u64,
+        max_lag_blocks: u64,
+    },
+
+    #[error("Subgraph reports indexing errors at block {0}")]
+    IndexingErrors(u64),
+
+    #[error("Invalid _meta response: {0}")]
+    InvalidMeta(String),
+}
+
+impl SubgraphFreshness {
+    /// Build from a `_meta { block { number timestamp } hasIndexingErrors }` object
+    ///
+    /// # Arguments
+    /// * `meta` - The `_meta` object from the subgraph response
+    /// * `head` - Chain head from RPC (falls back to `now` for data age)
+    /// * `now` - Current unix time in seconds
+    pub fn from_meta(
+        meta: &Value,
+        head: Option<ChainHead>,
+        now: i64,
+    ) -> Result<Self, FreshnessError> {
+        let block = meta
+            .get("block")
+            .ok_or_else(|| FreshnessError::InvalidMeta("missing block".to_string()))?;
+        let subgraph_block = block
+            .get("number")
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| FreshnessError::InvalidMeta("missing block number".to_string()))?;
+        let subgraph_timestamp = block.get("timestamp").and_then(|v| v.as_i64());
+        let has_indexing_errors = meta
+            .get("hasIndexingErrors")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let reference_time = head.map_or(now, |h| h.timestamp);
+        let data_age_seconds = subgraph_timestamp.map(|ts| (reference_time - ts).max(0));
+
+        Ok(Self {
+            subgraph_block,
+            subgraph_timestamp,
+            chain_head_block: head.map(|h| h.number),
+            lag_blocks: head.map(|h| h.number.saturating_sub(subgraph_block)),
+            data_age_seconds,
+            has_indexing_errors,
+        })
+    }
+
+    /// Check against a policy
+    ///
+    /// Data age can't be enforced when the subgraph omits block timestamps,
+    /// nor block distance without a chain head; such results pass (the
+    /// missing measure is visible in the output).
+    pub fn check(&self, policy: &FreshnessPolicy) -> Result<(), FreshnessError> {
+        if policy.reject_indexing_errors && self.has_indexing_errors {
+            return Err(FreshnessError::IndexingErrors(self.subgraph_block));
+        }
+        if let (Some(max), Some(age)) = (policy.max_data_age_secs, self.data_age_seconds) {
+            if age > i64::try_from(max).unwrap_or(i64::MAX) {
+                return Err(FreshnessError::Stale {
+                    subgraph_block: self.subgraph_block,
+                    data_age_seconds: age,
+                    max_data_age_secs: max,
+                });
+            }
+        }
+        if let (Some(max), Some(lag)) = (policy.max_lag_blocks, self.lag_blocks) {
+            if lag > max {
+                return Err(FreshnessError::Lagging {
+                    subgraph_block: self.subgraph_block,
+                    lag_blocks: lag,
+                    max_lag_blocks: max,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `self` is further behind than `other`
+    fn is_behind(&self, other: &Self) -> bool {
+        self.subgraph_block < other.subgraph_block
+    }
+}
+
+/// Freshness checks in force for the current tool call
+#[derive(Default)]
+struct FreshnessScope {
+    /// Stalest freshness read so far
+    stalest: Option<SubgraphFreshness>,
+    /// Chain heads looked up so far, by chain id (None if the lookup failed)
+    heads: HashMap<u64, Option<ChainHead>>,
+}
+
+tokio::task_local! {
+    static SCOPE: RefCell<FreshnessScope>;
+}
+
+/// Run `call` with freshness checks on its subgraph queries, returning the
+/// stalest freshness they read (None if it ran no checked query)
+pub(crate) async fn track_freshness<F: Future>(call: F) -> (F::Output, Option<SubgraphFreshness>) {
+    SCOPE
+        .scope(RefCell::new(FreshnessScope::default()), async move {
+            let output = call.await;
+            let stalest = SCOPE.with(|scope| scope.borrow_mut().stalest.take());
+            (output, stalest)
+        })
+        .await
+}
+
+/// Whether queries run now should be freshness-checked (inside [`track_freshness`])
+pub(crate) fn checking_freshness() -> bool {
+    SCOPE.try_with(|_| ()).is_ok()
+}
+
+/// Record freshness read by a checked query (a no-op outside [`track_freshness`])
+pub(crate) fn note_freshness(freshness: &SubgraphFreshness) {
+    let _ = SCOPE.try_with(|scope| {
+        let mut scope = scope.borrow_mut();
+        if !scope
+            .stalest
+            .as_ref()
+            .is_some_and(|stalest| !freshness.is_behind(stalest))
+        {
+            scope.stalest = Some(freshness.clone());
+        }
+    });
+}
+
+/// Chain head already looked up in this tool call, if any
+pub(crate) fn known_chain_head(chain_id: u64) -> Option<Option<ChainHead>> {
+    SCOPE
+        .try_with(|scope| scope.borrow().heads.get(&chain_id).copied())
+        .ok()
+        .flatten()
+}
+
+/// Remember a chain head lookup for the rest of the tool call
+pub(crate) fn note_chain_head(chain_id: u64, head: Option<ChainHead>) {
+    let _ = SCOPE.try_with(|scope| {
+        scope.borrow_mut().heads.insert(chain_id, head);
+    });
+}
+
+/// Add the aliased `_meta` selection to a single-operation query
+///
+/// Returns None for documents that don't parse or hold more than one
+/// operation; those are sent unchanged and left to the subgraph to reject.
+pub(crate) fn with_meta_selection(query: &str) -> Option<String> {
+    let mut document = parse_query::<String>(query).ok()?;
+    let meta = match parse_query::<String>(META_SELECTION)
+        .ok()?
+        .definitions
+        .into_iter()
+        .next()?
+    {
+        Definition::Operation(OperationDefinition::SelectionSet(set)) => {
+            set.items.into_iter().next()?
+        }
+        _ => return None,
+    };
+
+    let mut operations = document.definitions.iter_mut().filter_map(|d| match d {
+        Definition::Operation(operation) => Some(operation),
+        Definition::Fragment(_) => None,
+    });
+    let operation = operations.next()?;
+    if operations.next().is_some() {
+        return None;
+    }
+    match operation {
+        OperationDefinition::SelectionSet(set) => set.items.push(meta),
+        OperationDefinition::Query(query) => query.selection_set.items.push(meta),
+        _ => return None,
+    }
+    Some(document.to_string())
+}
+
+/// Remove the `_meta` added by [`with_meta_selection`] from response data
+pub(crate) fn take_meta(data: &mut Value) -> Option<Value> {
+    data.as_object_mut()?.remove(META_ALIAS)
+}
+
+/// Fetch the latest block from an RPC node
+pub async fn fetch_chain_head(rpc_url: &str) -> Result<ChainHead, String> {
+    let url: url::Url = rpc_url
+        .parse()
+        .map_err(|e| format!("Invalid RPC URL: {}", e))?;
+    let provider = ProviderBuilder::new().connect_http(url);
+    let block = provider
+        .get_block_by_number(BlockNumberOrTag::Latest)
+        .await
+        .map_err(|e| format!("Failed to get latest block: {}", e))?
+        .ok_or_else(|| "RPC returned no latest block".to_string())?;
+
+    Ok(ChainHead {
+        number: block.header.number,
+        timestamp: i64::try_from(block.header.timestamp).unwrap_or(i64::MAX),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn meta(number: u64, timestamp: i64, errors: bool) -> Value {
+        json!({
+            "block": { "number": number, "timestamp": timestamp },
+            "hasIndexingErrors": errors
+        })
+    }
+
+    #[test]
+    fn test_age_measured_against_chain_head() {
+        let head = ChainHead {
+            number: 1_010,
+            timestamp: 10_120,
+        };
+        let freshness =
+            SubgraphFreshness::from_meta(&meta(1_000, 10_000, false), Some(head), 99_999).unwrap();
+
+        assert_eq!(freshness.lag_blocks, Some(10));
+        assert_eq!(freshness.data_age_seconds, Some(120));
+        assert_eq!(freshness.chain_head_block, Some(1_010));
+    }
+
+    #[test]
+    fn test_age_falls_back_to_wall_clock() {
+        let freshness =
+            SubgraphFreshness::from_meta(&meta(1_000, 10_000, false), None, 10_030).unwrap();
+        assert_eq!(freshness.data_age_seconds, Some(30));
+        assert_eq!(freshness.lag_blocks, None);
+    }
+
+    #[test]
+    fn test_stale_data_is_rejected() {
+        let policy = FreshnessPolicy {
+            max_data_age_secs: Some(60),
+            max_lag_blocks: None,
+            reject_indexing_errors: true,
+        };
+        let fresh = SubgraphFreshness::from_meta(&meta(1, 1_000, false), None, 1_060).unwrap();
+        assert!(fresh.check(&policy).is_ok());
+
+        let stale = SubgraphFreshness::from_meta(&meta(1, 1_000, false), None, 1_061).unwrap();
+        assert!(matches!(
+            stale.check(&policy),
+            Err(FreshnessError::Stale {
+                data_age_seconds: 61,
+                ..
+            })
+        ));
+
+        let unbounded = FreshnessPolicy {
+            max_data_age_secs: None,
+            ..policy
+        };
+        assert!(stale.check(&unbounded).is_ok());
+    }
+
+    #[test]
+    fn test_indexing_errors_are_rejected() {
+        let freshness = SubgraphFreshness::from_meta(&meta(7, 1_000, true), None, 1_000).unwrap();
+        assert_eq!(
+            freshness.check(&FreshnessPolicy::default()),
+            Err(FreshnessError::IndexingErrors(7))
+        );
+
+        let lenient = FreshnessPolicy {
+            reject_indexing_errors: false,
+            ..FreshnessPolicy::default()
+        };
+        assert!(freshness.check(&lenient).is_ok());
+    }
+
+    #[test]
+    fn test_block_distance_is_rejected() {
+        let policy = FreshnessPolicy {
+            max_data_age_secs: None,
+            max_lag_blocks: Some(5),
+            reject_indexing_errors: true,
+        };
+        let head = |number: u64| ChainHead {
+            number,
+            timestamp: 1_000,
+        };
+        let close = SubgraphFreshness::from_meta(&meta(100, 1_000, false), Some(head(105)), 0);
+        assert!(close.unwrap().check(&policy).is_ok());
+
+        let behind = SubgraphFreshness::from_meta(&meta(100, 1_000, false), Some(head(106)), 0);
+        assert!(matches!(
+            behind.unwrap().check(&policy),
+            Err(FreshnessError::Lagging { lag_blocks: 6, .. })
+        ));
+
+        // Without a chain head the distance is unknown and not enforced
+        let unknown = SubgraphFreshness::from_meta(&meta(100, 1_000, false), None, 1_000);
+        assert!(unknown.unwrap().check(&policy).is_ok());
+    }
+
+    #[test]
+    fn test_meta_selected_alongside_data() {
+        let query = "query Pools($first: Int!) { pools(first: $first) { ...PoolFields } } \
+                     fragment PoolFields on Pool { id }";
+        let checked = with_meta_selection(query).unwrap();
+        let document = parse_query::<String>(&checked).unwrap();
+        let Some(Definition::Operation(OperationDefinition::Query(operation))) =
+            document.definitions.first()
+        else {
+            panic!("expected the query operation first");
+        };
+        assert_eq!(operation.selection_set.items.len(), 2);
+        assert!(checked.contains("_freshness: _meta"));
+        // The fragment is left alone
+        assert_eq!(checked.matches("_meta").count(), 1);
+
+        assert!(with_meta_selection("query A { a } query B { b }").is_none());
+        assert!(with_meta_selection("not graphql").is_none());
+
+        let mut data = json!({ "pools": [], "_freshness": { "block": { "number": 1 } } });
+        assert!(take_meta(&mut data).is_some());
+        assert_eq!(data, json!({ "pools": [] }));
+    }
+
+    #[tokio::test]
+    async fn test_track_freshness_keeps_stalest() {
+        assert!(!checking_freshness());
+        let ((), stalest) = track_freshness(async {
+            assert!(checking_freshness());
+            for block in [120, 100, 110] {
+                let freshness =
+                    SubgraphFreshness::from_meta(&meta(block, 1_000, false), None, 1_000).unwrap();
+                note_freshness(&freshness);
+            }
+        })
+        .await;
+        assert_eq!(stalest.unwrap().subgraph_block, 100);
+    }
+
+    #[test]
+    fn test_missing_block_number_is_invalid() {
+        let err = SubgraphFreshness::from_meta(&json!({ "block": {} }), None, 0).unwrap_err();
+        assert!(matches!(err, FreshnessError::InvalidMeta(_)));
+    }
+}
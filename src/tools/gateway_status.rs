@@ -0,0 +1,93 @@
+//! Graph gateway status tool
+//!
+//! Reports which subgraphs have open circuits after repeated failures and how
+//! long until they accept requests again, plus gateway cache statistics and
+//! (for x402 gateways) today's query payments, so the agent can wait instead
+//! of re-querying a subgraph that will fail fast.
+
+use crate::tools::the_graph::TheGraphTool;
+use crate::tools::wallet_signing::EmptyArgs;
+use crate::tools::{AnyJson, DefiBundle};
+use async_trait::async_trait;
+use baml_rt::error::Result;
+use baml_rt::tools::BamlTool;
+use serde_json::json;
+
+/// Tool for inspecting gateway health
+pub struct GraphGatewayStatusTool {
+    graph: TheGraphTool,
+}
+
+impl GraphGatewayStatusTool {
+    /// Create a tool reporting on the given subgraph tool's gateway
+    pub fn new(graph: TheGraphTool) -> Self {
+        Self { graph }
+    }
+}
+
+#[async_trait]
+impl BamlTool for GraphGatewayStatusTool {
+    type Bundle = DefiBundle;
+    const LOCAL_NAME: &'static str = "graph_gateway_status";
+    type OpenInput = ();
+    type Input = EmptyArgs;
+    type Output = AnyJson;
+
+    fn description(&self) -> &'static str {
+        "Reports Graph gateway health: subgraphs whose circuit is open after repeated failures \
+         (with retry_after_ms until they accept queries again), cache hit rate, and x402 query \
+         payments made today against the daily limit. Check this before retrying a subgraph \
+         query that failed with an open circuit."
+    }
+
+    async fn execute(&self, _args: Self::Input) -> Result<Self::Output> {
+        let Some(status) = self.graph.gateway_status().await else {
+            return Ok(AnyJson::new(json!({
+                "gateway": null,
+                "circuits": [],
+                "cache": null,
+                "payments": null
+            })));
+        };
+        let cache = self.graph.gateway_cache_stats().map(|stats| {
+            json!({
+                "hits": stats.hits,
+                "stale_hits": stats.stale_hits,
+                "misses": stats.misses,
+                "evictions": stats.evictions,
+                "entries": stats.entries,
+                "hit_rate": stats.hit_rate()
+            })
+        });
+
+        Ok(AnyJson::new(json!({
+            "gateway": self.graph.gateway_name(),
+            "circuits": status.circuits,
+            "cache": cache,
+            "payments": status.payments
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_status_without_gateway() {
+        let tool = GraphGatewayStatusTool::new(TheGraphTool::new());
+        let output = tool.execute(EmptyArgs {}).await.unwrap();
+        assert!(output.0["gateway"].is_null());
+        assert_eq!(output.0["circuits"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_status_with_gateway() {
+        let tool =
+            GraphGatewayStatusTool::new(TheGraphTool::with_gateway("test-api-key".to_string()));
+        let output = tool.execute(EmptyArgs {}).await.unwrap();
+        assert_eq!(output.0["gateway"], "BasicGraphGateway");
+        assert_eq!(output.0["cache"]["hit_rate"], 0.0);
+        assert!(output.0["payments"].is_null());
+    }
+}
@@ -0,0 +1,683 @@
+//! Gateway result caches
+//!
+//! Query results are cached under a blake3 key of the subgraph ID, query text
+//! and variables, so keys are stable across processes and Rust versions. The
+//! in-memory store is the default, bounded to a number of entries with LRU
+//! eviction; the disk store keeps results across restarts, with an expiry
+//! stored alongside each entry and a cap on the total size of the cache
+//! directory (least recently used entries are evicted first).
+//!
+//! Expired entries are only returned when the caller allows stale results
+//! (stale-while-revalidate); otherwise they are dropped on lookup, and swept
+//! when a cache is opened and every [`PURGE_INTERVAL`] after.
+//!
+//! Caching is best-effort: a store that fails to read or write logs a warning
+//! and behaves as a miss.
+
+use super::GatewayQueryResult;
+use crate::config::{GatewayCacheSettings, DEFAULT_GATEWAY_CACHE_MAX_ENTRIES};
+use async_trait::async_trait;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::ErrorKind;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+/// How often an open cache is swept of expired entries
+pub const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// A cached result found by [`CacheBackend::get`]
+#[derive(Debug, Clone)]
+pub struct CacheHit {
+    pub result: GatewayQueryResult,
+    /// The entry's TTL has passed
+    pub stale: bool,
+}
+
+/// Cache effectiveness counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Lookups answered with an unexpired entry
+    pub hits: u64,
+    /// Lookups answered with an expired entry (stale-while-revalidate)
+    pub stale_hits: u64,
+    /// Lookups with no usable entry
+    pub misses: u64,
+    /// Entries removed to stay within the size bound
+    pub evictions: u64,
+    /// Entries currently held (`None` if the backend doesn't track it)
+    pub entries: Option<usize>,
+}
+
+impl CacheStats {
+    /// Share of lookups answered from the cache, stale or not (0 with no lookups)
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.stale_hits + self.misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        (self.hits + self.stale_hits) as f64 / lookups as f64
+    }
+}
+
+/// Counters shared by the backends
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    stale_hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl Counters {
+    fn record_lookup(&self, hit: Option<&CacheHit>) {
+        let counter = match hit {
+            Some(hit) if hit.stale => &self.stale_hits,
+            Some(_) => &self.hits,
+            None => &self.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_eviction(&self) {
+        self.evictions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self, entries: Option<usize>) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            stale_hits: self.stale_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries,
+        }
+    }
+}
+
+/// Storage for cached gateway query results
+#[async_trait]
+pub trait CacheBackend: Send + Sync {
+    /// Result stored under `key`
+    ///
+    /// Expired entries are returned (marked stale) only if `allow_stale` is set.
+    async fn get(&self, key: &str, allow_stale: bool) -> Option<CacheHit>;
+
+    /// Store a result under `key` for `ttl`
+    async fn put(&self, key: &str, result: &GatewayQueryResult, ttl: Duration);
+
+    /// Drop expired entries
+    async fn purge_expired(&self);
+
+    /// Hit, miss and eviction counts since the cache was opened
+    fn stats(&self) -> CacheStats;
+
+    /// Backend name for logging
+    fn name(&self) -> &'static str;
+}
+
+/// Stable cache key for a query (hex blake3 digest)
+pub fn cache_key(subgraph_id: &str, query: &str, variables: &Value) -> String {
+    let mut hasher = blake3::Hasher::new();
+    // Separators keep ("ab", "c") and ("a", "bc") apart
+    hasher.update(subgraph_id.as_bytes());
+    hasher.update(&[0]);
+    hasher.update(query.as_bytes());
+    hasher.update(&[0]);
+    hasher.update(variables.to_string().as_bytes());
+    hasher.finalize().to_hex().to_string()
+}
+
+/// Open the cache described by `settings`
+///
+/// Falls back to an in-memory cache if the cache directory can't be created.
+/// Unless stale results are served, expired entries are swept right away and
+/// every [`PURGE_INTERVAL`] while the cache is in use.
+pub fn open_cache(settings: &GatewayCacheSettings) -> Arc<dyn CacheBackend> {
+    let cache: Arc<dyn CacheBackend> = match settings.dir.as_deref() {
+        None => Arc::new(MemoryCache::with_capacity(settings.max_entries)),
+        Some(dir) => match DiskCache::open(dir, settings.max_bytes) {
+            Ok(cache) => Arc::new(cache),
+            Err(e) => {
+                tracing::warn!(
+                    dir = dir,
+                    error = %e,
+                    "Failed to open gateway disk cache, falling back to memory"
+                );
+                Arc::new(MemoryCache::with_capacity(settings.max_entries))
+            }
+        },
+    };
+    // Under stale-while-revalidate expired entries are still served, and the
+    // size bounds keep the cache in check
+    if !settings.stale_while_revalidate {
+        spawn_purge(&cache, PURGE_INTERVAL);
+    }
+    cache
+}
+
+/// Drop expired entries now and every `interval` until the cache is dropped
+///
+/// Needs a Tokio runtime; without one nothing is swept.
+fn spawn_purge(cache: &Arc<dyn CacheBackend>, interval: Duration) {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        tracing::debug!("No async runtime, expired cache entries are not swept");
+        return;
+    };
+    let cache = Arc::downgrade(cache);
+    runtime.spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            // The first tick completes immediately
+            ticks.tick().await;
+            let Some(cache) = cache.upgrade() else {
+                break;
+            };
+            cache.purge_expired().await;
+        }
+    });
+}
+
+struct MemoryEntry {
+    result: GatewayQueryResult,
+    expires_at: Instant,
+}
+
+/// In-memory LRU cache, lost on restart
+pub struct MemoryCache {
+    entries: Mutex<LruCache<String, MemoryEntry>>,
+    counters: Counters,
+}
+
+impl MemoryCache {
+    /// Create with the default entry bound
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_GATEWAY_CACHE_MAX_ENTRIES)
+    }
+
+    /// Create holding at most `max_entries` results (at least one)
+    pub fn with_capacity(max_entries: usize) -> Self {
+        let capacity = NonZeroUsize::new(max_entries).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            counters: Counters::default(),
+        }
+    }
+}
+
+impl Default for MemoryCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl CacheBackend for MemoryCache {
+    async fn get(&self, key: &str, allow_stale: bool) -> Option<CacheHit> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().await;
+        let hit = entries.get(key).and_then(|entry| {
+            let stale = entry.expires_at <= now;
+            (!stale || allow_stale).then(|| CacheHit {
+                result: entry.result.clone(),
+                stale,
+            })
+        });
+        if hit.is_none() {
+            // Drop an expired entry the caller can't use
+            entries.pop(key);
+        }
+        self.counters.record_lookup(hit.as_ref());
+        hit
+    }
+
+    async fn put(&self, key: &str, result: &GatewayQueryResult, ttl: Duration) {
+        let entry = MemoryEntry {
+            result: result.clone(),
+            expires_at: Instant::now() + ttl,
+        };
+        let displaced = self.entries.lock().await.push(key.to_string(), entry);
+        // `push` also returns the previous entry when a key is overwritten
+        if displaced.is_some_and(|(displaced_key, _)| displaced_key != key) {
+            self.counters.record_eviction();
+        }
+    }
+
+    async fn purge_expired(&self) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().await;
+        let expired: Vec<String> = entries
+            .iter()
+            .filter(|(_, entry)| entry.expires_at <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired {
+            entries.pop(&key);
+        }
+    }
+
+    fn stats(&self) -> CacheStats {
+        // Reading the length needs the lock; skip it rather than block
+        let entries = self.entries.try_lock().ok().map(|entries| entries.len());
+        self.counters.snapshot(entries)
+    }
+
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+}
+
+/// A cached result as written to disk
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    /// When the entry was written (unix milliseconds)
+    stored_at_ms: u64,
+    /// When the entry stops being served (unix milliseconds)
+    expires_at_ms: u64,
+    result: GatewayQueryResult,
+}
+
+/// On-disk cache that survives restarts
+///
+/// Each entry is a JSON file named by its key. Reads refresh the file's
+/// modification time, and when the directory grows past `max_bytes` the least
+/// recently used entries are removed.
+pub struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// Serializes writes so size enforcement sees a consistent directory
+    write_lock: Mutex<()>,
+    counters: Counters,
+}
+
+impl DiskCache {
+    /// Open (creating if needed) a cache directory capped at `max_bytes`
+    pub fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            max_bytes,
+            write_lock: Mutex::new(()),
+            counters: Counters::default(),
+        })
+    }
+
+    /// Cache directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    async fn read_entry(&self, path: &Path) -> Option<DiskEntry> {
+        let bytes = match tokio::fs::read(path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return None,
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "Failed to read cache entry");
+                return None;
+            }
+        };
+        match serde_json::from_slice(&bytes) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "Removing corrupt cache entry");
+                let _ = tokio::fs::remove_file(path).await;
+                None
+            }
+        }
+    }
+
+    /// Entry files with their size and modification time
+    async fn entry_files(&self) -> std::io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let mut files = Vec::new();
+        let mut dir = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(item) = dir.next_entry().await? {
+            let path = item.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let metadata = item.metadata().await?;
+            let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+            files.push((path, metadata.len(), modified));
+        }
+        Ok(files)
+    }
+
+    /// Remove the oldest entries (never `keep`) until the cache fits `max_bytes`
+    async fn enforce_size_limit(&self, keep: &Path) -> std::io::Result<()> {
+        let mut files = self.entry_files().await?;
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        if total <= self.max_bytes {
+            return Ok(());
+        }
+        files.sort_by(|a, b| a.2.cmp(&b.2).then_with(|| a.0.cmp(&b.0)));
+        for (path, len, _) in files {
+            if total <= self.max_bytes {
+                break;
+            }
+            if path == keep {
+                continue;
+            }
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => {
+                    total -= len;
+                    self.counters.record_eviction();
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => total -= len,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CacheBackend for DiskCache {
+    async fn get(&self, key: &str, allow_stale: bool) -> Option<CacheHit> {
+        let path = self.entry_path(key);
+        let hit = match self.read_entry(&path).await {
+            Some(entry) => {
+                let stale = entry.expires_at_ms <= unix_millis();
+                if stale && !allow_stale {
+                    let _ = tokio::fs::remove_file(&path).await;
+                    None
+                } else {
+                    touch(&path);
+                    Some(CacheHit {
+                        result: entry.result,
+                        stale,
+                    })
+                }
+            }
+            None => None,
+        };
+        self.counters.record_lookup(hit.as_ref());
+        hit
+    }
+
+    async fn put(&self, key: &str, result: &GatewayQueryResult, ttl: Duration) {
+        let now = unix_millis();
+        let entry = DiskEntry {
+            stored_at_ms: now,
+            expires_at_ms: now.saturating_add(ttl.as_millis() as u64),
+            result: result.clone(),
+        };
+        let bytes = match serde_json::to_vec(&entry) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to serialize cache entry");
+                return;
+            }
+        };
+        if bytes.len() as u64 > self.max_bytes {
+            tracing::debug!(
+                size = bytes.len(),
+                max_bytes = self.max_bytes,
+                "Result larger than the disk cache, not cached"
+            );
+            return;
+        }
+
+        let _guard = self.write_lock.lock().await;
+        let path = self.entry_path(key);
+        // Write then rename so readers never see a partial entry
+        let tmp = path.with_extension("json.tmp");
+        let written = async {
+            tokio::fs::write(&tmp, &bytes).await?;
+            tokio::fs::rename(&tmp, &path).await?;
+            self.enforce_size_limit(&path).await
+        };
+        if let Err(e) = written.await {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to write cache entry");
+            let _ = tokio::fs::remove_file(&tmp).await;
+        }
+    }
+
+    async fn purge_expired(&self) {
+        let _guard = self.write_lock.lock().await;
+        let files = match self.entry_files().await {
+            Ok(files) => files,
+            Err(e) => {
+                tracing::warn!(dir = %self.dir.display(), error = %e, "Failed to list cache");
+                return;
+            }
+        };
+        let now = unix_millis();
+        for (path, _, _) in files {
+            if let Some(entry) = self.read_entry(&path).await {
+                if entry.expires_at_ms <= now {
+                    let _ = tokio::fs::remove_file(&path).await;
+                }
+            }
+        }
+    }
+
+    fn stats(&self) -> CacheStats {
+        self.counters.snapshot(None)
+    }
+
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+}
+
+/// Mark a disk entry as recently used
+fn touch(path: &Path) {
+    let touched = std::fs::File::options()
+        .append(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    if let Err(e) = touched {
+        tracing::debug!(path = %path.display(), error = %e, "Failed to touch cache entry");
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn result(subgraph_id: &str) -> GatewayQueryResult {
+        GatewayQueryResult {
+            data: json!({ "pools": [{ "id": "0xabc" }] }),
+            indexer: None,
+            latency_ms: 12,
+            cached: false,
+            stale: false,
+            subgraph_id: subgraph_id.to_string(),
+            payment: None,
+        }
+    }
+
+    #[test]
+    fn test_cache_key_deterministic() {
+        let key1 = cache_key("abc", "query { pools }", &json!({"first": 10}));
+        let key2 = cache_key("abc", "query { pools }", &json!({"first": 10}));
+        assert_eq!(key1, key2);
+        assert_eq!(key1.len(), 64);
+    }
+
+    #[test]
+    fn test_cache_key_different_for_different_inputs() {
+        let key1 = cache_key("abc", "query { pools }", &json!({"first": 10}));
+        let key2 = cache_key("abc", "query { pools }", &json!({"first": 20}));
+        assert_ne!(key1, key2);
+        assert_ne!(
+            cache_key("ab", "c", &Value::Null),
+            cache_key("a", "bc", &Value::Null)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_cache_expiry() {
+        let cache = MemoryCache::new();
+        cache
+            .put("live", &result("a"), Duration::from_secs(60))
+            .await;
+        cache.put("dead", &result("b"), Duration::ZERO).await;
+
+        cache.put("old", &result("c"), Duration::ZERO).await;
+
+        let live = cache.get("live", false).await.unwrap();
+        assert_eq!(live.result.subgraph_id, "a");
+        assert!(!live.stale);
+        assert!(cache.get("dead", false).await.is_none());
+        // A strict lookup drops the expired entry
+        assert!(cache.get("dead", true).await.is_none());
+
+        // Stale-while-revalidate lookups get the expired value, marked stale
+        let old = cache.get("old", true).await.unwrap();
+        assert!(old.stale);
+        cache.purge_expired().await;
+        assert_eq!(cache.entries.lock().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_memory_cache_lru_and_stats() {
+        let cache = MemoryCache::with_capacity(2);
+        let ttl = Duration::from_secs(60);
+        cache.put("a", &result("a"), ttl).await;
+        cache.put("b", &result("b"), ttl).await;
+        // Using "a" makes "b" the least recently used
+        assert!(cache.get("a", false).await.is_some());
+        cache.put("c", &result("c"), ttl).await;
+        // Overwriting a key is not an eviction
+        cache.put("c", &result("c"), ttl).await;
+
+        assert!(cache.get("b", false).await.is_none());
+        assert!(cache.get("c", false).await.is_some());
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.entries, Some(2));
+        assert!((stats.hit_rate() - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(CacheStats::default().hit_rate(), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_survives_reopen() {
+        let dir = tempdir().unwrap();
+        let key = cache_key("a", "{ pools { id } }", &json!({}));
+        {
+            let cache = DiskCache::open(dir.path(), 1 << 20).unwrap();
+            cache.put(&key, &result("a"), Duration::from_secs(60)).await;
+        }
+
+        let reopened = DiskCache::open(dir.path(), 1 << 20).unwrap();
+        let cached = reopened.get(&key, false).await.unwrap().result;
+        assert_eq!(cached.subgraph_id, "a");
+        assert_eq!(cached.data["pools"][0]["id"], "0xabc");
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_expiry_and_corruption() {
+        let dir = tempdir().unwrap();
+        let cache = DiskCache::open(dir.path(), 1 << 20).unwrap();
+        cache.put("dead", &result("a"), Duration::ZERO).await;
+        assert!(cache.get("dead", true).await.unwrap().stale);
+        assert!(cache.get("dead", false).await.is_none());
+        assert!(!cache.entry_path("dead").exists());
+
+        std::fs::write(cache.entry_path("corrupt"), b"not json").unwrap();
+        assert!(cache.get("corrupt", true).await.is_none());
+        assert!(!cache.entry_path("corrupt").exists());
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.stale_hits, stats.misses), (0, 1, 2));
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_size_limit_evicts_oldest() {
+        let dir = tempdir().unwrap();
+        // Room for one entry but not two
+        let entry_size = serde_json::to_vec(&DiskEntry {
+            stored_at_ms: unix_millis(),
+            expires_at_ms: unix_millis(),
+            result: result("a"),
+        })
+        .unwrap()
+        .len() as u64;
+        let cache = DiskCache::open(dir.path(), entry_size + entry_size / 2).unwrap();
+
+        cache
+            .put("first", &result("a"), Duration::from_secs(60))
+            .await;
+        cache
+            .put("second", &result("b"), Duration::from_secs(60))
+            .await;
+        assert!(cache.get("first", false).await.is_none());
+        assert_eq!(
+            cache.get("second", false).await.unwrap().result.subgraph_id,
+            "b"
+        );
+        assert_eq!(cache.stats().evictions, 1);
+
+        // Entries larger than the whole cache are not stored
+        let tiny = DiskCache::open(dir.path().join("tiny"), 8).unwrap();
+        tiny.put("big", &result("a"), Duration::from_secs(60)).await;
+        assert!(tiny.get("big", false).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_open_cache_from_settings() {
+        let dir = tempdir().unwrap();
+        let memory = open_cache(&GatewayCacheSettings::default());
+        assert_eq!(memory.name(), "memory");
+
+        let disk = open_cache(&GatewayCacheSettings {
+            dir: Some(dir.path().join("graph").to_string_lossy().into_owned()),
+            ..GatewayCacheSettings::default()
+        });
+        assert_eq!(disk.name(), "disk");
+    }
+
+    #[tokio::test]
+    async fn test_open_cache_sweeps_expired_entries() {
+        let dir = tempdir().unwrap();
+        let previous = DiskCache::open(dir.path(), 1 << 20).unwrap();
+        previous.put("dead", &result("a"), Duration::ZERO).await;
+        previous
+            .put("live", &result("b"), Duration::from_secs(60))
+            .await;
+        let settings = GatewayCacheSettings {
+            dir: Some(dir.path().to_string_lossy().into_owned()),
+            ..GatewayCacheSettings::default()
+        };
+
+        // Stale results are served, so nothing is swept
+        let _swr = open_cache(&GatewayCacheSettings {
+            stale_while_revalidate: true,
+            ..settings.clone()
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(previous.entry_path("dead").exists());
+
+        let _cache = open_cache(&settings);
+        for _ in 0..100 {
+            if !previous.entry_path("dead").exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!previous.entry_path("dead").exists());
+        assert!(previous.entry_path("live").exists());
+    }
+}
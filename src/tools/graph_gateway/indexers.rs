@@ -0,0 +1,275 @@
+//! Indexer discovery and selection
+//!
+//! Indexers serving a deployment are found through its active allocations in
+//! The Graph's network subgraph. Latency is measured on queries sent directly
+//! to an indexer and used, with the caller's preferred indexers and latency
+//! budget, to pick where a query goes.
+
+use super::{IndexerInfo, QueryRoutingHints};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+/// How long discovered indexers are cached (seconds)
+pub(crate) const INDEXER_DISCOVERY_TTL_SECS: u64 = 300;
+
+/// Indexers with an active allocation on a deployment, largest allocation first
+pub(crate) const ALLOCATIONS_QUERY: &str = r#"
+query IndexerAllocations($deployment: String!) {
+  allocations(
+    first: 100
+    orderBy: allocatedTokens
+    orderDirection: desc
+    where: { status: Active, subgraphDeployment_: { ipfsHash: $deployment } }
+  ) {
+    indexer {
+      id
+      url
+      stakedTokens
+      queryFeesCollected
+    }
+  }
+}
+"#;
+
+/// Current deployment of a subgraph
+pub(crate) const SUBGRAPH_DEPLOYMENT_QUERY: &str = r#"
+query SubgraphDeployment($id: String!) {
+  subgraph(id: $id) {
+    currentVersion {
+      subgraphDeployment {
+        ipfsHash
+      }
+    }
+  }
+}
+"#;
+
+#[derive(Deserialize)]
+struct AllocationsData {
+    allocations: Vec<Allocation>,
+}
+
+#[derive(Deserialize)]
+struct Allocation {
+    indexer: RawIndexer,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawIndexer {
+    id: String,
+    url: Option<String>,
+    staked_tokens: String,
+    query_fees_collected: String,
+}
+
+/// Indexers from an [`ALLOCATIONS_QUERY`] response, one per indexer
+pub(crate) fn parse_allocations(data: Value) -> Result<Vec<IndexerInfo>, serde_json::Error> {
+    let data: AllocationsData = serde_json::from_value(data)?;
+    let mut indexers: Vec<IndexerInfo> = Vec::new();
+    for allocation in data.allocations {
+        let indexer = allocation.indexer;
+        let address = indexer.id.to_lowercase();
+        if indexers.iter().any(|known| known.address == address) {
+            continue;
+        }
+        indexers.push(IndexerInfo {
+            address,
+            url: indexer.url.filter(|url| !url.is_empty()),
+            staked_tokens: indexer.staked_tokens,
+            query_fees: indexer.query_fees_collected,
+            avg_latency_ms: None,
+        });
+    }
+    Ok(indexers)
+}
+
+/// Deployment hash from a [`SUBGRAPH_DEPLOYMENT_QUERY`] response
+pub(crate) fn parse_deployment(data: &Value) -> Option<String> {
+    data.pointer("/subgraph/currentVersion/subgraphDeployment/ipfsHash")
+        .and_then(Value::as_str)
+        .map(String::from)
+}
+
+/// Indexers to try for a query, best first
+///
+/// Only indexers with a query URL qualify. Preferred indexers (in the order
+/// given) are the only candidates when `preferred_indexers` is set; indexers
+/// measured slower than `max_latency_ms` are dropped. Without preferences,
+/// faster indexers come first and unmeasured ones keep allocation order.
+pub(crate) fn select_indexers(
+    indexers: &[IndexerInfo],
+    hints: &QueryRoutingHints,
+) -> Vec<IndexerInfo> {
+    let within_budget = |indexer: &IndexerInfo| match (hints.max_latency_ms, indexer.avg_latency_ms)
+    {
+        (Some(max), Some(observed)) => observed <= max,
+        _ => true,
+    };
+    let candidates = indexers
+        .iter()
+        .filter(|indexer| indexer.url.is_some() && within_budget(indexer));
+
+    match hints.preferred_indexers.as_deref() {
+        Some(preferred) if !preferred.is_empty() => preferred
+            .iter()
+            .filter_map(|address| {
+                candidates
+                    .clone()
+                    .find(|indexer| indexer.address.eq_ignore_ascii_case(address))
+                    .cloned()
+            })
+            .collect(),
+        _ => {
+            let mut selected: Vec<IndexerInfo> = candidates.cloned().collect();
+            // Stable sort keeps allocation order among unmeasured indexers
+            selected.sort_by_key(|indexer| indexer.avg_latency_ms.unwrap_or(u64::MAX));
+            selected
+        }
+    }
+}
+
+/// Moving average of each indexer's query latency
+#[derive(Default)]
+pub(crate) struct IndexerLatencies {
+    averages: Mutex<HashMap<String, f64>>,
+}
+
+impl IndexerLatencies {
+    /// Weight of the newest sample in the moving average
+    const SMOOTHING: f64 = 0.3;
+
+    pub(crate) async fn record(&self, address: &str, latency_ms: u64) {
+        let mut averages = self.averages.lock().await;
+        let sample = latency_ms as f64;
+        averages
+            .entry(address.to_string())
+            .and_modify(|avg| *avg += Self::SMOOTHING * (sample - *avg))
+            .or_insert(sample);
+    }
+
+    /// Fill in `avg_latency_ms` for indexers with measurements
+    pub(crate) async fn annotate(&self, indexers: &mut [IndexerInfo]) {
+        let averages = self.averages.lock().await;
+        for indexer in indexers {
+            if let Some(avg) = averages.get(&indexer.address) {
+                indexer.avg_latency_ms = Some(avg.round() as u64);
+            }
+        }
+    }
+}
+
+/// Network subgraph response: two allocations from one indexer, one
+/// indexer without a URL
+#[cfg(test)]
+pub(crate) fn allocations_fixture() -> Value {
+    serde_json::json!({
+        "allocations": [
+            { "indexer": {
+                "id": "0xAAAA000000000000000000000000000000000001",
+                "url": "https://indexer-a.example/",
+                "stakedTokens": "5000000000000000000000000",
+                "queryFeesCollected": "12000000000000000000"
+            } },
+            { "indexer": {
+                "id": "0xbbbb000000000000000000000000000000000002",
+                "url": "https://indexer-b.example/",
+                "stakedTokens": "900000000000000000000000",
+                "queryFeesCollected": "300000000000000000"
+            } },
+            { "indexer": {
+                "id": "0xaaaa000000000000000000000000000000000001",
+                "url": "https://indexer-a.example/",
+                "stakedTokens": "5000000000000000000000000",
+                "queryFeesCollected": "12000000000000000000"
+            } },
+            { "indexer": {
+                "id": "0xcccc000000000000000000000000000000000003",
+                "url": null,
+                "stakedTokens": "100",
+                "queryFeesCollected": "0"
+            } }
+        ]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const A: &str = "0xaaaa000000000000000000000000000000000001";
+    const B: &str = "0xbbbb000000000000000000000000000000000002";
+
+    #[test]
+    fn test_parse_allocations() {
+        let indexers = parse_allocations(allocations_fixture()).unwrap();
+        assert_eq!(indexers.len(), 3);
+        assert_eq!(indexers[0].address, A);
+        assert_eq!(
+            indexers[0].url.as_deref(),
+            Some("https://indexer-a.example/")
+        );
+        assert_eq!(indexers[0].staked_tokens, "5000000000000000000000000");
+        assert_eq!(indexers[1].query_fees, "300000000000000000");
+        assert!(indexers[2].url.is_none());
+    }
+
+    #[test]
+    fn test_parse_deployment() {
+        let data = json!({ "subgraph": { "currentVersion": {
+            "subgraphDeployment": { "ipfsHash": "QmDeployment" }
+        } } });
+        assert_eq!(parse_deployment(&data).as_deref(), Some("QmDeployment"));
+        assert!(parse_deployment(&json!({ "subgraph": null })).is_none());
+    }
+
+    #[test]
+    fn test_select_indexers() {
+        let mut indexers = parse_allocations(allocations_fixture()).unwrap();
+        indexers[0].avg_latency_ms = Some(900);
+        indexers[1].avg_latency_ms = Some(120);
+
+        // Fastest first; indexers without a URL are never selected
+        let selected = select_indexers(&indexers, &QueryRoutingHints::default());
+        let order: Vec<&str> = selected.iter().map(|i| i.address.as_str()).collect();
+        assert_eq!(order, vec![B, A]);
+
+        let fast_only = QueryRoutingHints {
+            max_latency_ms: Some(500),
+            ..QueryRoutingHints::default()
+        };
+        assert_eq!(select_indexers(&indexers, &fast_only).len(), 1);
+
+        // Preferences are honored in order, case-insensitively
+        let preferred = QueryRoutingHints {
+            preferred_indexers: Some(vec![A.to_uppercase().replace("0X", "0x"), B.to_string()]),
+            ..QueryRoutingHints::default()
+        };
+        let order: Vec<String> = select_indexers(&indexers, &preferred)
+            .into_iter()
+            .map(|i| i.address)
+            .collect();
+        assert_eq!(order, vec![A.to_string(), B.to_string()]);
+
+        let unknown = QueryRoutingHints {
+            preferred_indexers: Some(vec!["0xdead".to_string()]),
+            ..QueryRoutingHints::default()
+        };
+        assert!(select_indexers(&indexers, &unknown).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_latency_moving_average() {
+        let latencies = IndexerLatencies::default();
+        latencies.record(A, 100).await;
+        latencies.record(A, 200).await;
+
+        let mut indexers = parse_allocations(allocations_fixture()).unwrap();
+        latencies.annotate(&mut indexers).await;
+        assert_eq!(indexers[0].avg_latency_ms, Some(130));
+        assert_eq!(indexers[1].avg_latency_ms, None);
+    }
+}
@@ -0,0 +1,1361 @@
+//! x402 Gateway Protocol Abstraction
+//!
+//! Provides intelligent subgraph routing, load balancing, and caching
+//! for The Graph decentralized network queries.
+//!
+//! This module defines a trait-based abstraction that allows:
+//! - Query routing with latency and indexer preferences
+//! - Result caching with configurable TTL, in memory or on disk (see [`cache`])
+//! - Indexer selection based on performance and stake (see [`indexers`])
+//! - Pay-per-query access with x402 instead of an API key (see [`x402`])
+//! - Query and cost accounting with a daily query budget (see [`usage`])
+//!
+//! The `BasicGraphGateway` provides a simple implementation using
+//! the standard Graph API; [`x402::X402GraphGateway`] builds on it to pay
+//! for queries from a wallet.
+
+pub mod cache;
+mod indexers;
+mod resilience;
+mod single_flight;
+pub mod usage;
+pub mod x402;
+
+pub use resilience::{CircuitState, CircuitStatus};
+pub use usage::{SubgraphUsage, UsageReport};
+pub use x402::{PaymentLimits, PaymentReceipt, PaymentSpend, X402GraphGateway};
+
+use crate::config::{
+    gateway_query_url, CircuitBreakerSettings, GatewayRetrySettings, GraphUsageSettings,
+    DEFAULT_GATEWAY_CACHE_TTL_SECS, DEFAULT_GATEWAY_URL, DEFAULT_NETWORK_SUBGRAPH_ID,
+};
+use async_trait::async_trait;
+use cache::{cache_key, CacheBackend, CacheStats, MemoryCache};
+use indexers::{
+    parse_allocations, parse_deployment, select_indexers, IndexerLatencies, ALLOCATIONS_QUERY,
+    INDEXER_DISCOVERY_TTL_SECS, SUBGRAPH_DEPLOYMENT_QUERY,
+};
+use reqwest::Client;
+use resilience::{backoff_delay, CircuitBreakers};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use single_flight::SingleFlight;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use usage::UsageTracker;
+use x402::{PaymentRequired, X402Payer, PAYMENT_HEADER, PAYMENT_RESPONSE_HEADER};
+
+/// Query routing hints for x402 gateway
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct QueryRoutingHints {
+    /// Preferred indexers (by address)
+    #[serde(default)]
+    pub preferred_indexers: Option<Vec<String>>,
+    /// Maximum latency tolerance (ms)
+    #[serde(default)]
+    pub max_latency_ms: Option<u64>,
+    /// Cache TTL (seconds)
+    #[serde(default)]
+    pub cache_ttl_secs: Option<u64>,
+    /// Require fresh data (bypass cache)
+    #[serde(default)]
+    pub force_fresh: bool,
+    /// Serve an expired cached result immediately and refresh it in the background
+    #[serde(default)]
+    pub stale_while_revalidate: bool,
+}
+
+/// Gateway query result with metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayQueryResult {
+    /// The query result data
+    pub data: Value,
+    /// The indexer that served this query (if known)
+    pub indexer: Option<String>,
+    /// Query latency in milliseconds
+    pub latency_ms: u64,
+    /// Whether this result was served from cache
+    pub cached: bool,
+    /// Whether the cached result had expired (served under stale-while-revalidate)
+    #[serde(default)]
+    pub stale: bool,
+    /// The subgraph ID that was queried
+    pub subgraph_id: String,
+    /// x402 payment made for this query (never set on cache hits)
+    #[serde(default)]
+    pub payment: Option<PaymentReceipt>,
+}
+
+/// Information about an indexer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexerInfo {
+    /// Indexer's Ethereum address
+    pub address: String,
+    /// Indexer's query service URL (if registered)
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Amount of GRT staked
+    pub staked_tokens: String,
+    /// Query fees charged
+    pub query_fees: String,
+    /// Average latency of queries routed to this indexer (if measured)
+    pub avg_latency_ms: Option<u64>,
+}
+
+/// Error type for gateway operations
+#[derive(Debug, Clone)]
+pub enum GatewayError {
+    /// HTTP request failed without a response
+    HttpError(String),
+    /// Request timed out
+    Timeout(String),
+    /// Gateway answered with an error status and no GraphQL errors
+    HttpStatus { status: u16, message: String },
+    /// Successful response whose body could not be read
+    InvalidResponse(String),
+    /// GraphQL query returned errors
+    GraphQLError(Vec<String>),
+    /// No data in response
+    NoData,
+    /// Subgraph not found
+    SubgraphNotFound(String),
+    /// All indexers failed (retries exhausted on transient errors)
+    AllIndexersFailed { attempts: u32, last_error: String },
+    /// Subgraph's circuit is open after repeated failures; requests fail fast
+    CircuitOpen {
+        subgraph_id: String,
+        retry_after_ms: u64,
+    },
+    /// The gateway wants payment that can't be made (no wallet, unsupported
+    /// option, spending limit reached, or payment rejected)
+    PaymentRequired(String),
+    /// The daily query budget is used up (cached results are still served)
+    BudgetExceeded {
+        limit: u64,
+        used: u64,
+        resets_in_secs: u64,
+    },
+    /// A request failed after its x402 payment was attached; never retried,
+    /// since a retry would pay again
+    PaidRequestFailed {
+        payment: PaymentReceipt,
+        error: Box<GatewayError>,
+    },
+}
+
+impl GatewayError {
+    /// Whether retrying the same request might succeed: timeouts and
+    /// requests that got no response, server errors and rate limits
+    pub fn is_transient(&self) -> bool {
+        match self {
+            GatewayError::HttpError(_) | GatewayError::Timeout(_) => true,
+            GatewayError::HttpStatus { status, .. } => *status >= 500 || *status == 429,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for GatewayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GatewayError::HttpError(msg) => write!(f, "HTTP error: {}", msg),
+            GatewayError::Timeout(msg) => write!(f, "Request timed out: {}", msg),
+            GatewayError::HttpStatus { status, message } => {
+                write!(f, "HTTP {}: {}", status, message)
+            }
+            GatewayError::InvalidResponse(msg) => write!(f, "Invalid response: {}", msg),
+            GatewayError::GraphQLError(errors) => {
+                write!(f, "GraphQL errors: {}", errors.join(", "))
+            }
+            GatewayError::NoData => write!(f, "No data in response"),
+            GatewayError::SubgraphNotFound(id) => write!(f, "Subgraph not found: {}", id),
+            GatewayError::AllIndexersFailed {
+                attempts,
+                last_error,
+            } => write!(
+                f,
+                "All indexers failed to respond after {} attempts (last error: {})",
+                attempts, last_error
+            ),
+            GatewayError::CircuitOpen {
+                subgraph_id,
+                retry_after_ms,
+            } => write!(
+                f,
+                "Circuit open for subgraph {} after repeated failures, retry in {}s",
+                subgraph_id,
+                retry_after_ms.div_ceil(1000)
+            ),
+            GatewayError::PaymentRequired(reason) => write!(f, "Payment required: {}", reason),
+            GatewayError::BudgetExceeded {
+                limit,
+                used,
+                resets_in_secs,
+            } => write!(
+                f,
+                "Daily query budget exhausted ({} of {} queries used), resets in {}s",
+                used, limit, resets_in_secs
+            ),
+            GatewayError::PaidRequestFailed { payment, error } => write!(
+                f,
+                "{} (after paying {} to {}, nonce {})",
+                error, payment.amount, payment.pay_to, payment.nonce
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GatewayError {}
+
+/// Gateway health, for deciding whether to wait before querying
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GatewayStatus {
+    /// Subgraphs with recent failures and their circuit state
+    pub circuits: Vec<CircuitStatus>,
+    /// x402 payments made today (paying gateways only)
+    #[serde(default)]
+    pub payments: Option<PaymentSpend>,
+}
+
+/// Trait for Graph gateway implementations
+///
+/// This trait abstracts over different ways to query The Graph:
+/// - Direct API calls (BasicGraphGateway)
+/// - x402 protocol routing (future X402GraphGateway)
+/// - Custom routing logic
+#[async_trait]
+pub trait GraphGateway: Send + Sync {
+    /// Execute a query with x402 routing hints
+    ///
+    /// # Arguments
+    /// * `subgraph_id` - The subgraph deployment ID to query
+    /// * `query` - The GraphQL query string
+    /// * `variables` - Query variables as JSON
+    /// * `routing_hints` - Optional routing preferences
+    ///
+    /// # Returns
+    /// Query result with metadata about execution
+    async fn query_with_routing(
+        &self,
+        subgraph_id: &str,
+        query: &str,
+        variables: Value,
+        routing_hints: QueryRoutingHints,
+    ) -> Result<GatewayQueryResult, GatewayError>;
+
+    /// Get available indexers for a subgraph
+    ///
+    /// # Arguments
+    /// * `subgraph_id` - The subgraph deployment ID
+    ///
+    /// # Returns
+    /// List of indexers serving this subgraph
+    async fn get_indexers(&self, subgraph_id: &str) -> Result<Vec<IndexerInfo>, GatewayError>;
+
+    /// Result cache hit, miss and eviction counts (`None` if the gateway doesn't cache)
+    fn cache_stats(&self) -> Option<CacheStats> {
+        None
+    }
+
+    /// Billable queries, estimated cost and cache savings (`None` if not tracked)
+    fn usage(&self) -> Option<UsageReport> {
+        None
+    }
+
+    /// Circuit breaker state of subgraphs with recent failures
+    async fn status(&self) -> GatewayStatus {
+        GatewayStatus::default()
+    }
+
+    /// Get the gateway name for logging/metrics
+    fn name(&self) -> &'static str;
+}
+
+/// Basic gateway implementation using current The Graph API
+///
+/// This implementation provides:
+/// - Direct queries to The Graph's gateway API
+/// - Result caching with TTL (a bounded in-memory LRU unless another
+///   [`CacheBackend`] is set), with optional stale-while-revalidate
+/// - Concurrent identical queries share one request
+/// - Retries of transient failures with jittered exponential backoff, and a
+///   per-subgraph circuit breaker
+/// - Indexer discovery from the network subgraph; queries with preferred
+///   indexers go straight to the allocated ones within the latency budget,
+///   falling back to the gateway
+/// - Per-subgraph query and cost accounting, with an optional daily query budget
+pub struct BasicGraphGateway {
+    client: Client,
+    api_key: String,
+    /// Gateway base URL (The Graph's hosted gateway unless overridden)
+    gateway_url: String,
+    cache: Arc<dyn CacheBackend>,
+    default_cache_ttl: Duration,
+    /// Queries awaiting a response, shared by identical concurrent calls
+    inflight: SingleFlight<Result<GatewayQueryResult, GatewayError>>,
+    retry: GatewayRetrySettings,
+    breakers: Arc<CircuitBreakers>,
+    /// Network subgraph queried for indexer allocations
+    network_subgraph_id: String,
+    /// Observed latency of indexers queries were routed to
+    latencies: Arc<IndexerLatencies>,
+    /// Pays for queries answered with HTTP 402 (x402 gateways only)
+    payer: Option<Arc<X402Payer>>,
+    usage: Arc<UsageTracker>,
+}
+
+impl BasicGraphGateway {
+    /// Create a new BasicGraphGateway
+    ///
+    /// # Arguments
+    /// * `api_key` - The Graph API key for authentication
+    pub fn new(api_key: String) -> Self {
+        Self::with_cache_ttl(api_key, Duration::from_secs(DEFAULT_GATEWAY_CACHE_TTL_SECS))
+    }
+
+    /// Create with custom cache TTL
+    pub fn with_cache_ttl(api_key: String, cache_ttl: Duration) -> Self {
+        Self {
+            client: Client::new(),
+            api_key,
+            gateway_url: DEFAULT_GATEWAY_URL.to_string(),
+            cache: Arc::new(MemoryCache::new()),
+            default_cache_ttl: cache_ttl,
+            inflight: SingleFlight::new(),
+            retry: GatewayRetrySettings::default(),
+            breakers: Arc::new(CircuitBreakers::new(CircuitBreakerSettings::default())),
+            network_subgraph_id: DEFAULT_NETWORK_SUBGRAPH_ID.to_string(),
+            latencies: Arc::new(IndexerLatencies::default()),
+            payer: None,
+            usage: Arc::new(UsageTracker::new(GraphUsageSettings::default())),
+        }
+    }
+
+    /// Retry transient failures as `retry` describes
+    pub fn with_retry(mut self, retry: GatewayRetrySettings) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Break circuits of failing subgraphs as `settings` describes
+    pub fn with_circuit_breaker(mut self, settings: CircuitBreakerSettings) -> Self {
+        self.breakers = Arc::new(CircuitBreakers::new(settings));
+        self
+    }
+
+    /// Store results in `cache` (e.g. a [`cache::DiskCache`] that survives restarts)
+    pub fn with_cache(mut self, cache: Arc<dyn CacheBackend>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Discover indexers through a different network subgraph (e.g. a testnet's)
+    pub fn with_network_subgraph(mut self, subgraph_id: impl Into<String>) -> Self {
+        self.network_subgraph_id = subgraph_id.into();
+        self
+    }
+
+    /// Price queries and limit them per day as `settings` describe
+    pub fn with_usage(mut self, settings: GraphUsageSettings) -> Self {
+        self.usage = Arc::new(UsageTracker::new(settings));
+        self
+    }
+
+    /// Pay for queries the gateway answers with HTTP 402
+    pub(crate) fn with_payer(mut self, payer: Arc<X402Payer>) -> Self {
+        self.payer = Some(payer);
+        self
+    }
+
+    /// Send queries to a different gateway (e.g. a local stand-in)
+    pub fn with_gateway_url(mut self, gateway_url: impl Into<String>) -> Self {
+        self.gateway_url = gateway_url.into();
+        self
+    }
+
+    /// Build the API endpoint URL for a subgraph or deployment ID
+    ///
+    /// Gateways paid per query take no API key in the path.
+    fn build_endpoint(&self, subgraph_id: &str) -> String {
+        let kind = if is_deployment_id(subgraph_id) {
+            "deployments"
+        } else {
+            "subgraphs"
+        };
+        if self.api_key.is_empty() {
+            return format!(
+                "{}/{}/id/{}",
+                self.gateway_url.trim_end_matches('/'),
+                kind,
+                subgraph_id
+            );
+        }
+        gateway_query_url(&self.gateway_url, &self.api_key, kind, subgraph_id)
+    }
+
+    /// Name of the cache backend in use
+    pub fn cache_name(&self) -> &'static str {
+        self.cache.name()
+    }
+}
+
+#[async_trait]
+impl GraphGateway for BasicGraphGateway {
+    async fn query_with_routing(
+        &self,
+        subgraph_id: &str,
+        query: &str,
+        variables: Value,
+        routing_hints: QueryRoutingHints,
+    ) -> Result<GatewayQueryResult, GatewayError> {
+        let cache_key = cache_key(subgraph_id, query, &variables);
+
+        // Check cache first (unless force_fresh is set)
+        if !routing_hints.force_fresh {
+            let hit = self
+                .cache
+                .get(&cache_key, routing_hints.stale_while_revalidate)
+                .await;
+            if let Some(hit) = hit {
+                self.usage.record_cache_hit(subgraph_id);
+                if hit.stale {
+                    self.spawn_refresh(cache_key, subgraph_id, query, variables, routing_hints);
+                }
+                let mut cached_result = hit.result;
+                cached_result.cached = true;
+                cached_result.stale = hit.stale;
+                cached_result.payment = None;
+                return Ok(cached_result);
+            }
+        }
+
+        self.fetch_coalesced(cache_key, subgraph_id, query, variables, routing_hints)
+            .await
+    }
+
+    async fn get_indexers(&self, subgraph_id: &str) -> Result<Vec<IndexerInfo>, GatewayError> {
+        let (_, indexers) = self.discover_indexers(subgraph_id).await?;
+        Ok(indexers)
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        Some(self.cache.stats())
+    }
+
+    fn usage(&self) -> Option<UsageReport> {
+        Some(self.usage.report())
+    }
+
+    async fn status(&self) -> GatewayStatus {
+        GatewayStatus {
+            circuits: self.breakers.status().await,
+            payments: None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "BasicGraphGateway"
+    }
+}
+
+impl BasicGraphGateway {
+    /// Query the gateway, retrying transient failures, and cache the result
+    async fn fetch(
+        &self,
+        cache_key: &str,
+        subgraph_id: &str,
+        query: &str,
+        variables: &Value,
+        routing_hints: &QueryRoutingHints,
+    ) -> Result<GatewayQueryResult, GatewayError> {
+        self.usage.check_budget()?;
+        self.breakers.admit(subgraph_id).await?;
+
+        // A latency budget alone doesn't justify discovery: its network
+        // subgraph queries are billed like any other
+        let has_preferences = routing_hints
+            .preferred_indexers
+            .as_ref()
+            .is_some_and(|preferred| !preferred.is_empty());
+        let routed = if has_preferences {
+            self.route_to_indexer(subgraph_id, query, variables, routing_hints)
+                .await
+        } else {
+            None
+        };
+
+        let mut attempt = 0;
+        let result = if let Some(result) = routed {
+            result
+        } else {
+            loop {
+                match self
+                    .send(subgraph_id, query, variables, routing_hints)
+                    .await
+                {
+                    Ok(result) => break result,
+                    Err(e) if e.is_transient() && attempt < self.retry.max_retries => {
+                        let delay = backoff_delay(&self.retry, attempt);
+                        tracing::warn!(
+                            subgraph_id = subgraph_id,
+                            attempt = attempt + 1,
+                            delay_ms = delay.as_millis() as u64,
+                            error = %e,
+                            "Transient gateway error, retrying"
+                        );
+                        tokio::time::sleep(delay).await;
+                        attempt += 1;
+                    }
+                    Err(e) if e.is_transient() => {
+                        self.breakers.record_failure(subgraph_id).await;
+                        return Err(GatewayError::AllIndexersFailed {
+                            attempts: attempt + 1,
+                            last_error: e.to_string(),
+                        });
+                    }
+                    Err(GatewayError::PaidRequestFailed { payment, error }) => {
+                        if error.is_transient() {
+                            self.breakers.record_failure(subgraph_id).await;
+                        } else {
+                            self.breakers.record_success(subgraph_id).await;
+                        }
+                        return Err(GatewayError::PaidRequestFailed { payment, error });
+                    }
+                    Err(e) => {
+                        // The subgraph answered, so the failure is the query's
+                        self.breakers.record_success(subgraph_id).await;
+                        return Err(e);
+                    }
+                }
+            }
+        };
+        self.breakers.record_success(subgraph_id).await;
+
+        // Cache the result
+        let cache_ttl = routing_hints
+            .cache_ttl_secs
+            .map(Duration::from_secs)
+            .unwrap_or(self.default_cache_ttl);
+        self.cache.put(cache_key, &result, cache_ttl).await;
+
+        Ok(result)
+    }
+
+    /// Send one request to the gateway
+    async fn send(
+        &self,
+        subgraph_id: &str,
+        query: &str,
+        variables: &Value,
+        routing_hints: &QueryRoutingHints,
+    ) -> Result<GatewayQueryResult, GatewayError> {
+        let endpoint = self.build_endpoint(subgraph_id);
+        let result = self
+            .post(
+                Upstream::Gateway,
+                &endpoint,
+                self.request_timeout(),
+                subgraph_id,
+                query,
+                variables,
+            )
+            .await?;
+
+        // Check if we exceeded max latency (for metrics/logging, not failure)
+        if let Some(max_latency) = routing_hints.max_latency_ms {
+            if result.latency_ms > max_latency {
+                tracing::warn!(
+                    subgraph_id = subgraph_id,
+                    latency_ms = result.latency_ms,
+                    max_latency_ms = max_latency,
+                    "Query exceeded maximum latency threshold"
+                );
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.retry.request_timeout_secs)
+    }
+
+    /// POST a GraphQL query to `endpoint`
+    ///
+    /// The gateway is paid if it answers HTTP 402, and its answers count as
+    /// billable queries. An indexer is never paid or billed.
+    async fn post(
+        &self,
+        upstream: Upstream,
+        endpoint: &str,
+        timeout: Duration,
+        subgraph_id: &str,
+        query: &str,
+        variables: &Value,
+    ) -> Result<GatewayQueryResult, GatewayError> {
+        let start = Instant::now();
+
+        let mut response = self
+            .post_once(endpoint, timeout, query, variables, None)
+            .await?;
+        let mut payment = None;
+        if response.status() == reqwest::StatusCode::PAYMENT_REQUIRED {
+            let payer = match (upstream, &self.payer) {
+                (Upstream::Gateway, Some(payer)) => payer,
+                (Upstream::Gateway, None) => {
+                    return Err(GatewayError::PaymentRequired(
+                        "gateway requires x402 payment and no paying wallet is configured"
+                            .to_string(),
+                    ))
+                }
+                (Upstream::Indexer, _) => {
+                    return Err(GatewayError::PaymentRequired(
+                        "indexer wants payment; only the gateway is paid".to_string(),
+                    ))
+                }
+            };
+            let required: PaymentRequired = response.json().await.map_err(|e| {
+                GatewayError::PaymentRequired(format!("unreadable payment requirements: {}", e))
+            })?;
+            let signed = payer.pay(endpoint, &required).await?;
+            let sent = self
+                .post_once(endpoint, timeout, query, variables, Some(&signed.header))
+                .await;
+            response = match sent {
+                Ok(response) => response,
+                Err(e) => return Err(self.paid_failure(subgraph_id, signed.settle(None), e)),
+            };
+            if response.status() == reqwest::StatusCode::PAYMENT_REQUIRED {
+                let rejected: Option<PaymentRequired> = response.json().await.ok();
+                return Err(GatewayError::PaymentRequired(format!(
+                    "payment rejected: {}",
+                    rejected
+                        .and_then(|r| r.error)
+                        .unwrap_or_else(|| "no reason given".to_string())
+                )));
+            }
+            let settlement = response
+                .headers()
+                .get(PAYMENT_RESPONSE_HEADER)
+                .and_then(|value| value.to_str().ok());
+            payment = Some(signed.settle(settlement));
+        }
+
+        let latency_ms = start.elapsed().as_millis() as u64;
+
+        // Error statuses are never billed. A body with GraphQL errors (e.g. a
+        // 400 for an invalid query) is reported as such; otherwise the status
+        // is, and only server errors and rate limits are retried.
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let error = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|body| graphql_errors(&body))
+                .map(GatewayError::GraphQLError)
+                .unwrap_or_else(|| GatewayError::HttpStatus {
+                    status: status.as_u16(),
+                    message: body.chars().take(200).collect(),
+                });
+            return Err(match payment {
+                Some(payment) => self.paid_failure(subgraph_id, payment, error),
+                None => error,
+            });
+        }
+        // Anything the gateway serves from here on is billable, GraphQL
+        // errors included
+        if upstream == Upstream::Gateway {
+            self.usage.record_query(
+                subgraph_id,
+                payment.as_ref().and_then(PaymentReceipt::amount_usd),
+            );
+        }
+
+        let response_data: Value = response
+            .json()
+            .await
+            .map_err(|e| GatewayError::InvalidResponse(e.to_string()))?;
+
+        if let Some(errors) = graphql_errors(&response_data) {
+            return Err(GatewayError::GraphQLError(errors));
+        }
+
+        let data = response_data
+            .get("data")
+            .cloned()
+            .ok_or(GatewayError::NoData)?;
+
+        Ok(GatewayQueryResult {
+            data,
+            indexer: None, // The gateway doesn't say which indexer answered
+            latency_ms,
+            cached: false,
+            stale: false,
+            subgraph_id: subgraph_id.to_string(),
+            payment,
+        })
+    }
+
+    /// A request failed after `payment` was attached: the payment is
+    /// accounted for, and the error carries its receipt
+    fn paid_failure(
+        &self,
+        subgraph_id: &str,
+        payment: PaymentReceipt,
+        error: GatewayError,
+    ) -> GatewayError {
+        tracing::warn!(
+            subgraph_id = subgraph_id,
+            nonce = %payment.nonce,
+            error = %error,
+            "Paid gateway request failed; not retrying"
+        );
+        self.usage.record_query(subgraph_id, payment.amount_usd());
+        GatewayError::PaidRequestFailed {
+            payment,
+            error: Box::new(error),
+        }
+    }
+
+    async fn post_once(
+        &self,
+        endpoint: &str,
+        timeout: Duration,
+        query: &str,
+        variables: &Value,
+        payment_header: Option<&str>,
+    ) -> Result<reqwest::Response, GatewayError> {
+        let mut request = self.client.post(endpoint).timeout(timeout).json(&json!({
+            "query": query,
+            "variables": variables
+        }));
+        if let Some(payment) = payment_header {
+            request = request.header(PAYMENT_HEADER, payment);
+        }
+        request.send().await.map_err(|e| {
+            if e.is_timeout() {
+                GatewayError::Timeout(e.to_string())
+            } else {
+                GatewayError::HttpError(e.to_string())
+            }
+        })
+    }
+
+    /// Deployment behind `subgraph_id` and the indexers allocated to it
+    ///
+    /// Lookups go through the network subgraph like any other query, so they
+    /// are cached (and refreshed in the background once expired).
+    async fn discover_indexers(
+        &self,
+        subgraph_id: &str,
+    ) -> Result<(String, Vec<IndexerInfo>), GatewayError> {
+        let hints = QueryRoutingHints {
+            cache_ttl_secs: Some(INDEXER_DISCOVERY_TTL_SECS),
+            stale_while_revalidate: true,
+            ..QueryRoutingHints::default()
+        };
+
+        let deployment = if is_deployment_id(subgraph_id) {
+            subgraph_id.to_string()
+        } else {
+            let result = self
+                .query_with_routing(
+                    &self.network_subgraph_id,
+                    SUBGRAPH_DEPLOYMENT_QUERY,
+                    json!({ "id": subgraph_id }),
+                    hints.clone(),
+                )
+                .await?;
+            parse_deployment(&result.data)
+                .ok_or_else(|| GatewayError::SubgraphNotFound(subgraph_id.to_string()))?
+        };
+
+        let result = self
+            .query_with_routing(
+                &self.network_subgraph_id,
+                ALLOCATIONS_QUERY,
+                json!({ "deployment": deployment }),
+                hints,
+            )
+            .await?;
+        let mut indexers = parse_allocations(result.data).map_err(|e| {
+            GatewayError::InvalidResponse(format!("unreadable indexer allocations: {}", e))
+        })?;
+        self.latencies.annotate(&mut indexers).await;
+        Ok((deployment, indexers))
+    }
+
+    /// Send a query straight to the indexers the routing hints allow, best
+    /// ranked first
+    ///
+    /// Direct requests carry no payment or receipt and their answers are not
+    /// attested, so this suits indexers that serve the agent free of charge
+    /// (e.g. its own). Returns `None`, leaving the query to the gateway, when
+    /// discovery fails, no allocated indexer fits the hints, or every one
+    /// fails. A failure counts as a request that took the whole latency
+    /// budget, so the indexer drops down the ranking.
+    async fn route_to_indexer(
+        &self,
+        subgraph_id: &str,
+        query: &str,
+        variables: &Value,
+        routing_hints: &QueryRoutingHints,
+    ) -> Option<GatewayQueryResult> {
+        let (deployment, indexers) = match self.discover_indexers(subgraph_id).await {
+            Ok(found) => found,
+            Err(e) => {
+                tracing::warn!(
+                    subgraph_id = subgraph_id,
+                    error = %e,
+                    "Indexer discovery failed, querying through the gateway"
+                );
+                return None;
+            }
+        };
+        let candidates = select_indexers(&indexers, routing_hints);
+        if candidates.is_empty() {
+            tracing::debug!(
+                subgraph_id = subgraph_id,
+                allocated = indexers.len(),
+                "No allocated indexer fits the routing hints, querying through the gateway"
+            );
+            return None;
+        }
+
+        let timeout = routing_hints
+            .max_latency_ms
+            .map(Duration::from_millis)
+            .unwrap_or_else(|| self.request_timeout());
+        for indexer in candidates {
+            let Some(url) = indexer.url.as_deref() else {
+                continue;
+            };
+            let endpoint = format!("{}/subgraphs/id/{}", url.trim_end_matches('/'), deployment);
+            match self
+                .post(
+                    Upstream::Indexer,
+                    &endpoint,
+                    timeout,
+                    subgraph_id,
+                    query,
+                    variables,
+                )
+                .await
+            {
+                Ok(mut result) => {
+                    self.latencies
+                        .record(&indexer.address, result.latency_ms)
+                        .await;
+                    result.indexer = Some(indexer.address);
+                    return Some(result);
+                }
+                Err(e) => {
+                    self.latencies
+                        .record(&indexer.address, timeout.as_millis() as u64)
+                        .await;
+                    tracing::warn!(
+                        subgraph_id = subgraph_id,
+                        indexer = indexer.address.as_str(),
+                        error = %e,
+                        "Indexer query failed, trying the next one"
+                    );
+                }
+            }
+        }
+        tracing::warn!(
+            subgraph_id = subgraph_id,
+            "No routed indexer answered, querying through the gateway"
+        );
+        None
+    }
+
+    /// [`Self::fetch`], sharing one request among concurrent identical queries
+    ///
+    /// Callers that join an in-flight query get its result, cached under the
+    /// first caller's routing hints.
+    async fn fetch_coalesced(
+        &self,
+        cache_key: String,
+        subgraph_id: &str,
+        query: &str,
+        variables: Value,
+        routing_hints: QueryRoutingHints,
+    ) -> Result<GatewayQueryResult, GatewayError> {
+        let gateway = self.clone();
+        let key = cache_key.clone();
+        let subgraph_id = subgraph_id.to_string();
+        let query = query.to_string();
+        self.inflight
+            .run(&cache_key, move || async move {
+                gateway
+                    .fetch(&key, &subgraph_id, &query, &variables, &routing_hints)
+                    .await
+            })
+            .await
+    }
+
+    /// Refresh a stale cache entry in the background
+    ///
+    /// Joins an in-flight query for the same key rather than sending another.
+    fn spawn_refresh(
+        &self,
+        cache_key: String,
+        subgraph_id: &str,
+        query: &str,
+        variables: Value,
+        routing_hints: QueryRoutingHints,
+    ) {
+        let gateway = self.clone();
+        let subgraph_id = subgraph_id.to_string();
+        let query = query.to_string();
+        tokio::spawn(async move {
+            let refreshed = gateway
+                .fetch_coalesced(cache_key, &subgraph_id, &query, variables, routing_hints)
+                .await;
+            if let Err(e) = refreshed {
+                tracing::warn!(
+                    subgraph_id = subgraph_id.as_str(),
+                    error = %e,
+                    "Background refresh of stale cache entry failed"
+                );
+            }
+        });
+    }
+}
+
+impl Clone for BasicGraphGateway {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            api_key: self.api_key.clone(),
+            gateway_url: self.gateway_url.clone(),
+            cache: Arc::clone(&self.cache),
+            default_cache_ttl: self.default_cache_ttl,
+            inflight: self.inflight.clone(),
+            retry: self.retry.clone(),
+            breakers: Arc::clone(&self.breakers),
+            network_subgraph_id: self.network_subgraph_id.clone(),
+            latencies: Arc::clone(&self.latencies),
+            payer: self.payer.clone(),
+            usage: Arc::clone(&self.usage),
+        }
+    }
+}
+
+/// Where [`BasicGraphGateway::post`] sends a query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Upstream {
+    /// The gateway: paid for if it asks, and billed
+    Gateway,
+    /// An indexer queried directly: never paid or billed
+    Indexer,
+}
+
+/// Whether an ID is a deployment (IPFS CIDv0 `Qm...` hash) rather than a subgraph ID
+fn is_deployment_id(id: &str) -> bool {
+    id.len() == 46 && id.starts_with("Qm")
+}
+
+/// Messages of a GraphQL response's `errors`, if it has any
+fn graphql_errors(response: &Value) -> Option<Vec<String>> {
+    let messages: Vec<String> = response
+        .get("errors")?
+        .as_array()?
+        .iter()
+        .filter_map(|e| e.get("message").and_then(|m| m.as_str()).map(String::from))
+        .collect();
+    (!messages.is_empty()).then_some(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(subgraph_id: &str, data: Value) -> GatewayQueryResult {
+        GatewayQueryResult {
+            data,
+            indexer: None,
+            latency_ms: 5,
+            cached: false,
+            stale: false,
+            subgraph_id: subgraph_id.to_string(),
+            payment: None,
+        }
+    }
+
+    /// Give up on the first transient failure
+    pub(super) fn no_retry() -> GatewayRetrySettings {
+        GatewayRetrySettings {
+            max_retries: 0,
+            base_delay_ms: 1,
+            max_delay_ms: 1,
+            request_timeout_secs: 5,
+        }
+    }
+
+    #[test]
+    fn test_cache_backend_defaults_to_memory() {
+        let gateway = BasicGraphGateway::new("key".to_string());
+        assert_eq!(gateway.cache_name(), "memory");
+
+        let dir = tempfile::tempdir().unwrap();
+        let disk = cache::DiskCache::open(dir.path(), 1024).unwrap();
+        let gateway = gateway.with_cache(Arc::new(disk));
+        assert_eq!(gateway.cache_name(), "disk");
+    }
+
+    #[tokio::test]
+    async fn test_stale_while_revalidate_serves_expired_result() {
+        let cache = Arc::new(MemoryCache::new());
+        // Nothing listens here, so the background refresh fails quietly
+        let gateway = BasicGraphGateway::new("key".to_string())
+            .with_gateway_url("http://127.0.0.1:9")
+            .with_cache(cache.clone());
+        let query = "{ pools { id } }";
+        cache
+            .put(
+                &cache_key("abc", query, &json!({})),
+                &result("abc", json!({ "pools": [] })),
+                Duration::ZERO,
+            )
+            .await;
+
+        let hints = QueryRoutingHints {
+            stale_while_revalidate: true,
+            ..QueryRoutingHints::default()
+        };
+        let result = gateway
+            .query_with_routing("abc", query, json!({}), hints)
+            .await
+            .unwrap();
+        assert!(result.cached);
+        assert!(result.stale);
+        assert_eq!(result.data, json!({ "pools": [] }));
+
+        let stats = gateway.cache_stats().unwrap();
+        assert_eq!(stats.stale_hits, 1);
+        assert_eq!(stats.misses, 0);
+    }
+
+    #[tokio::test]
+    async fn test_retries_then_opens_circuit() {
+        // Nothing listens here, so every attempt fails to connect
+        let gateway = BasicGraphGateway::new("key".to_string())
+            .with_gateway_url("http://127.0.0.1:9")
+            .with_retry(GatewayRetrySettings {
+                max_retries: 1,
+                ..no_retry()
+            })
+            .with_circuit_breaker(CircuitBreakerSettings {
+                failure_threshold: 1,
+                open_secs: 60,
+            });
+        let query = |gateway: BasicGraphGateway| async move {
+            gateway
+                .query_with_routing("abc", "{ pools { id } }", json!({}), Default::default())
+                .await
+        };
+
+        match query(gateway.clone()).await {
+            Err(GatewayError::AllIndexersFailed { attempts, .. }) => assert_eq!(attempts, 2),
+            other => panic!("expected exhausted retries, got {:?}", other),
+        }
+        assert!(matches!(
+            query(gateway.clone()).await,
+            Err(GatewayError::CircuitOpen { .. })
+        ));
+
+        let status = gateway.status().await;
+        assert_eq!(status.circuits.len(), 1);
+        assert_eq!(status.circuits[0].subgraph_id, "abc");
+        assert_eq!(status.circuits[0].state, CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_budget_still_serves_cache() {
+        let cache = Arc::new(MemoryCache::new());
+        let gateway = BasicGraphGateway::new("key".to_string())
+            .with_gateway_url("http://127.0.0.1:9")
+            .with_cache(cache.clone())
+            .with_usage(GraphUsageSettings {
+                daily_query_limit: Some(0),
+                ..GraphUsageSettings::default()
+            });
+        let cached = "{ pools { id } }";
+        cache
+            .put(
+                &cache_key("abc", cached, &json!({})),
+                &result("abc", json!({ "pools": [] })),
+                Duration::from_secs(60),
+            )
+            .await;
+
+        let hit = gateway
+            .query_with_routing("abc", cached, json!({}), Default::default())
+            .await
+            .unwrap();
+        assert!(hit.cached);
+
+        // The budget is checked before anything is sent
+        let fresh = gateway
+            .query_with_routing("abc", "{ swaps { id } }", json!({}), Default::default())
+            .await;
+        assert!(matches!(
+            fresh,
+            Err(GatewayError::BudgetExceeded { limit: 0, .. })
+        ));
+
+        let usage = gateway.usage().unwrap();
+        assert_eq!((usage.queries, usage.cache_hits), (0, 1));
+        assert_eq!(usage.subgraphs[0].subgraph_id, "abc");
+        // Breakers only count upstream failures
+        assert!(gateway.status().await.circuits.is_empty());
+    }
+
+    const DEPLOYMENT: &str = "QmTZ8ejXJxRo7vDBS4uwqBeGoxLSWbhaA7oXa1RvxunLy7";
+
+    /// Cache a network subgraph response so discovery runs offline
+    async fn seed_network_subgraph(
+        cache: &MemoryCache,
+        query: &str,
+        variables: Value,
+        data: Value,
+    ) {
+        cache
+            .put(
+                &cache_key(DEFAULT_NETWORK_SUBGRAPH_ID, query, &variables),
+                &result(DEFAULT_NETWORK_SUBGRAPH_ID, data),
+                Duration::from_secs(60),
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_get_indexers_from_network_subgraph() {
+        let cache = Arc::new(MemoryCache::new());
+        seed_network_subgraph(
+            &cache,
+            SUBGRAPH_DEPLOYMENT_QUERY,
+            json!({ "id": "5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV" }),
+            json!({ "subgraph": { "currentVersion": {
+                "subgraphDeployment": { "ipfsHash": DEPLOYMENT }
+            } } }),
+        )
+        .await;
+        seed_network_subgraph(
+            &cache,
+            ALLOCATIONS_QUERY,
+            json!({ "deployment": DEPLOYMENT }),
+            indexers::allocations_fixture(),
+        )
+        .await;
+        let gateway = BasicGraphGateway::new("key".to_string())
+            .with_gateway_url("http://127.0.0.1:9")
+            .with_cache(cache);
+
+        let indexers = gateway
+            .get_indexers("5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV")
+            .await
+            .unwrap();
+        assert_eq!(indexers.len(), 3);
+        assert_eq!(
+            indexers[0].address,
+            "0xaaaa000000000000000000000000000000000001"
+        );
+        assert_eq!(indexers[0].staked_tokens, "5000000000000000000000000");
+        assert!(indexers[0].avg_latency_ms.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failed_indexer_falls_back_to_gateway() {
+        let cache = Arc::new(MemoryCache::new());
+        // Both the indexer and the gateway are unreachable
+        seed_network_subgraph(
+            &cache,
+            ALLOCATIONS_QUERY,
+            json!({ "deployment": DEPLOYMENT }),
+            json!({ "allocations": [{ "indexer": {
+                "id": "0xaaaa000000000000000000000000000000000001",
+                "url": "http://127.0.0.1:9/",
+                "stakedTokens": "1",
+                "queryFeesCollected": "0"
+            } }] }),
+        )
+        .await;
+        let gateway = BasicGraphGateway::new("key".to_string())
+            .with_gateway_url("http://127.0.0.1:9")
+            .with_cache(cache)
+            .with_retry(no_retry());
+        let hints = QueryRoutingHints {
+            preferred_indexers: Some(vec![
+                "0xAAAA000000000000000000000000000000000001".to_string()
+            ]),
+            max_latency_ms: Some(2_000),
+            ..QueryRoutingHints::default()
+        };
+
+        let result = gateway
+            .query_with_routing(DEPLOYMENT, "{ pools { id } }", json!({}), hints)
+            .await;
+        assert!(matches!(
+            result,
+            Err(GatewayError::AllIndexersFailed { attempts: 1, .. })
+        ));
+
+        // The failure counts against the indexer's latency
+        let indexers = gateway.get_indexers(DEPLOYMENT).await.unwrap();
+        assert_eq!(indexers[0].avg_latency_ms, Some(2_000));
+    }
+
+    #[tokio::test]
+    async fn test_routed_indexers_are_tried_in_order_and_never_paid() {
+        // The first indexer is down; the second wants x402 payment
+        let paywalled = x402::mock::MockX402Gateway::start(
+            x402::mock::usdc_requirements(1_000),
+            json!({ "pools": [] }),
+        )
+        .await
+        .unwrap();
+        let allocation = |id: &str, url: &str| {
+            json!({ "indexer": {
+                "id": id,
+                "url": url,
+                "stakedTokens": "1",
+                "queryFeesCollected": "0"
+            } })
+        };
+        let cache = Arc::new(MemoryCache::new());
+        seed_network_subgraph(
+            &cache,
+            ALLOCATIONS_QUERY,
+            json!({ "deployment": DEPLOYMENT }),
+            json!({ "allocations": [
+                allocation("0xaaaa000000000000000000000000000000000001", "http://127.0.0.1:9/"),
+                allocation("0xaaaa000000000000000000000000000000000002", paywalled.url()),
+            ] }),
+        )
+        .await;
+        let wallet = crate::wallet::SecureWallet::from_hex(
+            "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80",
+        )
+        .unwrap();
+        let payer = Arc::new(X402Payer::new(
+            Arc::new(wallet),
+            PaymentLimits::from_usd(1.0, 10.0),
+            "http://127.0.0.1:9",
+            vec![super::x402::mock::PAY_TO.parse().unwrap()],
+        ));
+        let gateway = BasicGraphGateway::new("key".to_string())
+            .with_gateway_url("http://127.0.0.1:9")
+            .with_cache(cache)
+            .with_payer(Arc::clone(&payer))
+            .with_retry(no_retry());
+        let hints = QueryRoutingHints {
+            preferred_indexers: Some(vec![
+                "0xaaaa000000000000000000000000000000000001".to_string(),
+                "0xaaaa000000000000000000000000000000000002".to_string(),
+            ]),
+            max_latency_ms: Some(2_000),
+            ..QueryRoutingHints::default()
+        };
+
+        let result = gateway
+            .query_with_routing(DEPLOYMENT, "{ pools { id } }", json!({}), hints)
+            .await;
+        assert!(matches!(
+            result,
+            Err(GatewayError::AllIndexersFailed { .. })
+        ));
+
+        // Both indexers were tried, and the paywalled one was not paid
+        let indexers = gateway.get_indexers(DEPLOYMENT).await.unwrap();
+        assert!(indexers
+            .iter()
+            .all(|indexer| indexer.avg_latency_ms == Some(2_000)));
+        assert_eq!(paywalled.facilitator().settled().await, 0);
+        assert_eq!(payer.spend().await.payments, 0);
+    }
+
+    #[tokio::test]
+    async fn test_latency_budget_alone_skips_discovery() {
+        // Nothing is seeded: discovery would have to query the network subgraph
+        let gateway = BasicGraphGateway::new("key".to_string())
+            .with_gateway_url("http://127.0.0.1:9")
+            .with_retry(no_retry());
+        let hints = QueryRoutingHints {
+            max_latency_ms: Some(2_000),
+            ..QueryRoutingHints::default()
+        };
+        let result = gateway
+            .query_with_routing(DEPLOYMENT, "{ pools { id } }", json!({}), hints)
+            .await;
+        assert!(matches!(
+            result,
+            Err(GatewayError::AllIndexersFailed { attempts: 1, .. })
+        ));
+        // Only the query itself failed; the network subgraph was never asked
+        let circuits = gateway.status().await.circuits;
+        assert_eq!(circuits.len(), 1);
+        assert_eq!(circuits[0].subgraph_id, DEPLOYMENT);
+    }
+
+    #[test]
+    fn test_graphql_errors() {
+        let response = json!({ "errors": [{ "message": "bad query" }, { "locations": [] }] });
+        assert_eq!(
+            graphql_errors(&response),
+            Some(vec!["bad query".to_string()])
+        );
+        assert_eq!(graphql_errors(&json!({ "errors": [] })), None);
+        assert_eq!(graphql_errors(&json!({ "data": {} })), None);
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        let status = |status| GatewayError::HttpStatus {
+            status,
+            message: String::new(),
+        };
+        assert!(GatewayError::Timeout("slow".to_string()).is_transient());
+        assert!(status(503).is_transient());
+        assert!(status(429).is_transient());
+        assert!(!status(400).is_transient());
+        assert!(!status(401).is_transient());
+        assert!(GatewayError::HttpError("connection refused".to_string()).is_transient());
+        assert!(!GatewayError::InvalidResponse("not json".to_string()).is_transient());
+        assert!(!GatewayError::GraphQLError(vec!["bad query".to_string()]).is_transient());
+        assert!(!GatewayError::NoData.is_transient());
+    }
+
+    #[test]
+    fn test_build_endpoint_for_subgraphs_and_deployments() {
+        let gateway = BasicGraphGateway::new("key".to_string());
+        assert_eq!(
+            gateway.build_endpoint("5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV"),
+            "https://gateway.thegraph.com/api/key/subgraphs/id/5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV"
+        );
+
+        let local = gateway.with_gateway_url("http://127.0.0.1:7600/");
+        assert_eq!(
+            local.build_endpoint("QmTZ8ejXJxRo7vDBS4uwqBeGoxLSWbhaA7oXa1RvxunLy7"),
+            "http://127.0.0.1:7600/api/key/deployments/id/QmTZ8ejXJxRo7vDBS4uwqBeGoxLSWbhaA7oXa1RvxunLy7"
+        );
+
+        // Pay-per-query gateways take no API key
+        let paid = BasicGraphGateway::new(String::new()).with_gateway_url("http://127.0.0.1:4020");
+        assert_eq!(
+            paid.build_endpoint("abc"),
+            "http://127.0.0.1:4020/subgraphs/id/abc"
+        );
+    }
+
+    #[test]
+    fn test_routing_hints_default() {
+        let hints = QueryRoutingHints::default();
+        assert!(!hints.force_fresh);
+        assert!(!hints.stale_while_revalidate);
+        assert!(hints.preferred_indexers.is_none());
+        assert!(hints.max_latency_ms.is_none());
+        assert!(hints.cache_ttl_secs.is_none());
+    }
+}
@@ -0,0 +1,258 @@
+//! Retry backoff and per-subgraph circuit breaking
+//!
+//! Transient failures are retried with jittered exponential backoff. A
+//! subgraph whose requests keep failing after retries has its circuit opened:
+//! further requests fail fast with [`GatewayError::CircuitOpen`] until the
+//! open period ends, then a single trial request decides whether the circuit
+//! closes again or stays open.
+
+use super::GatewayError;
+use crate::config::{CircuitBreakerSettings, GatewayRetrySettings};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Delay before retry number `attempt` (0-based)
+///
+/// Exponential in `attempt` and capped at `max_delay_ms`, with "equal jitter":
+/// half the delay is fixed and half is random, so retries from concurrent
+/// callers spread out.
+pub(crate) fn backoff_delay(retry: &GatewayRetrySettings, attempt: u32) -> Duration {
+    let exponential = retry
+        .base_delay_ms
+        .saturating_mul(1u64.checked_shl(attempt).unwrap_or(u64::MAX));
+    let delay = exponential.min(retry.max_delay_ms);
+    let half = delay / 2;
+    Duration::from_millis(half + (random_fraction() * (delay - half) as f64) as u64)
+}
+
+/// Uniform value in [0, 1) from the standard library's randomly keyed hasher
+fn random_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Circuit state of a subgraph
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Requests flow normally
+    Closed,
+    /// Requests fail fast until the open period ends
+    Open,
+    /// A trial request is deciding whether to close the circuit
+    HalfOpen,
+}
+
+/// Breaker state of one subgraph, as reported by the gateway status
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CircuitStatus {
+    pub subgraph_id: String,
+    pub state: CircuitState,
+    /// Failed requests since the last success
+    pub consecutive_failures: u32,
+    /// Time until requests are let through again (open circuits only)
+    pub retry_after_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Circuit {
+    Closed,
+    Open { until: Instant },
+    HalfOpen { since: Instant },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Breaker {
+    circuit: Circuit,
+    consecutive_failures: u32,
+}
+
+/// Circuit breakers keyed by subgraph ID
+pub(crate) struct CircuitBreakers {
+    settings: CircuitBreakerSettings,
+    breakers: Mutex<HashMap<String, Breaker>>,
+}
+
+impl CircuitBreakers {
+    pub(crate) fn new(settings: CircuitBreakerSettings) -> Self {
+        Self {
+            settings,
+            breakers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn open_for(&self) -> Duration {
+        Duration::from_secs(self.settings.open_secs)
+    }
+
+    /// Admit a request to `subgraph_id`, or fail fast if its circuit is open
+    ///
+    /// The first request after the open period becomes the half-open trial;
+    /// others keep failing fast until it finishes. A trial that never reports
+    /// back is replaced after another open period.
+    pub(crate) async fn admit(&self, subgraph_id: &str) -> Result<(), GatewayError> {
+        let mut breakers = self.breakers.lock().await;
+        let Some(breaker) = breakers.get_mut(subgraph_id) else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let retry_at = match breaker.circuit {
+            Circuit::Closed => return Ok(()),
+            Circuit::Open { until } => until,
+            Circuit::HalfOpen { since } => since + self.open_for(),
+        };
+        if now >= retry_at {
+            breaker.circuit = Circuit::HalfOpen { since: now };
+            tracing::info!(
+                subgraph_id = subgraph_id,
+                "Circuit half-open, sending trial request"
+            );
+            return Ok(());
+        }
+        Err(GatewayError::CircuitOpen {
+            subgraph_id: subgraph_id.to_string(),
+            retry_after_ms: retry_at.duration_since(now).as_millis() as u64,
+        })
+    }
+
+    /// The subgraph answered; close its circuit
+    pub(crate) async fn record_success(&self, subgraph_id: &str) {
+        let mut breakers = self.breakers.lock().await;
+        if let Some(breaker) = breakers.remove(subgraph_id) {
+            if !matches!(breaker.circuit, Circuit::Closed) {
+                tracing::info!(subgraph_id = subgraph_id, "Circuit closed");
+            }
+        }
+    }
+
+    /// A request failed after its retries; open the circuit once failures
+    /// reach the threshold, or straight away if it was the half-open trial
+    pub(crate) async fn record_failure(&self, subgraph_id: &str) {
+        let mut breakers = self.breakers.lock().await;
+        let breaker = breakers.entry(subgraph_id.to_string()).or_insert(Breaker {
+            circuit: Circuit::Closed,
+            consecutive_failures: 0,
+        });
+        breaker.consecutive_failures += 1;
+        let trip = match breaker.circuit {
+            Circuit::HalfOpen { .. } => true,
+            Circuit::Closed => breaker.consecutive_failures >= self.settings.failure_threshold,
+            Circuit::Open { .. } => false,
+        };
+        if trip {
+            breaker.circuit = Circuit::Open {
+                until: Instant::now() + self.open_for(),
+            };
+            tracing::warn!(
+                subgraph_id = subgraph_id,
+                consecutive_failures = breaker.consecutive_failures,
+                open_secs = self.settings.open_secs,
+                "Circuit opened after repeated failures"
+            );
+        }
+    }
+
+    /// Subgraphs with recent failures (healthy subgraphs are omitted)
+    pub(crate) async fn status(&self) -> Vec<CircuitStatus> {
+        let now = Instant::now();
+        let breakers = self.breakers.lock().await;
+        let mut status: Vec<CircuitStatus> = breakers
+            .iter()
+            .map(|(subgraph_id, breaker)| {
+                let (state, retry_after) = match breaker.circuit {
+                    Circuit::Closed => (CircuitState::Closed, None),
+                    Circuit::Open { until } => (
+                        CircuitState::Open,
+                        Some(until.saturating_duration_since(now)),
+                    ),
+                    Circuit::HalfOpen { .. } => (CircuitState::HalfOpen, None),
+                };
+                CircuitStatus {
+                    subgraph_id: subgraph_id.clone(),
+                    state,
+                    consecutive_failures: breaker.consecutive_failures,
+                    retry_after_ms: retry_after.map(|d| d.as_millis() as u64),
+                }
+            })
+            .collect();
+        status.sort_by(|a, b| a.subgraph_id.cmp(&b.subgraph_id));
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breakers(failure_threshold: u32, open_secs: u64) -> CircuitBreakers {
+        CircuitBreakers::new(CircuitBreakerSettings {
+            failure_threshold,
+            open_secs,
+        })
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let retry = GatewayRetrySettings {
+            max_retries: 5,
+            base_delay_ms: 100,
+            max_delay_ms: 1_000,
+            request_timeout_secs: 30,
+        };
+        for _ in 0..20 {
+            let first = backoff_delay(&retry, 0).as_millis();
+            assert!((50..=100).contains(&first), "{first}");
+            let third = backoff_delay(&retry, 2).as_millis();
+            assert!((200..=400).contains(&third), "{third}");
+            let capped = backoff_delay(&retry, 40).as_millis();
+            assert!((500..=1_000).contains(&capped), "{capped}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_circuit_opens_after_threshold() {
+        let breakers = breakers(2, 60);
+        breakers.record_failure("a").await;
+        assert!(breakers.admit("a").await.is_ok());
+        breakers.record_failure("a").await;
+
+        match breakers.admit("a").await {
+            Err(GatewayError::CircuitOpen {
+                subgraph_id,
+                retry_after_ms,
+            }) => {
+                assert_eq!(subgraph_id, "a");
+                assert!(retry_after_ms > 59_000);
+            }
+            other => panic!("expected open circuit, got {:?}", other),
+        }
+        // Other subgraphs are unaffected
+        assert!(breakers.admit("b").await.is_ok());
+
+        let status = breakers.status().await;
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].state, CircuitState::Open);
+        assert_eq!(status[0].consecutive_failures, 2);
+    }
+
+    #[tokio::test]
+    async fn test_half_open_trial() {
+        let breakers = breakers(1, 0);
+        breakers.record_failure("a").await;
+
+        // The open period has passed, so the next request is the trial
+        assert!(breakers.admit("a").await.is_ok());
+        assert_eq!(breakers.status().await[0].state, CircuitState::HalfOpen);
+
+        // A failed trial reopens the circuit; a successful one closes it
+        breakers.record_failure("a").await;
+        assert_eq!(breakers.status().await[0].state, CircuitState::Open);
+        assert!(breakers.admit("a").await.is_ok());
+        breakers.record_success("a").await;
+        assert!(breakers.status().await.is_empty());
+    }
+}
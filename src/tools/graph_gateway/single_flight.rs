@@ -0,0 +1,184 @@
+//! Single-flight deduplication of in-flight work
+//!
+//! Concurrent calls with the same key share one execution: the first caller
+//! starts the work, later callers wait on it, and all of them get a clone of
+//! its output. The key is released as soon as the work finishes, so results
+//! are never reused after completion (that is the cache's job).
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+/// Shared output of a spawned flight (None if the work panicked)
+type Flight<T> = Shared<BoxFuture<'static, Option<T>>>;
+
+type Registry<T> = Arc<Mutex<HashMap<String, Flight<T>>>>;
+
+/// Shares one execution among concurrent calls with the same key
+pub(crate) struct SingleFlight<T> {
+    inflight: Registry<T>,
+}
+
+/// Releases a flight's key when its task ends, however it ends
+struct Release<T> {
+    inflight: Registry<T>,
+    key: String,
+}
+
+impl<T> Drop for Release<T> {
+    fn drop(&mut self) {
+        let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+        inflight.remove(&self.key);
+    }
+}
+
+impl<T> SingleFlight<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            inflight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Run `start()` unless work for `key` is already in flight, then await it
+    ///
+    /// The work runs on its own task, so it keeps running if every caller is
+    /// dropped; the next caller for `key` picks it up. If the work panics, the
+    /// key is released and every caller waiting on it panics too.
+    pub(crate) async fn run<F, Fut>(&self, key: &str, start: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        let flight = {
+            // Held until the flight is registered, so a task that finishes
+            // straight away can't release its key before it is inserted
+            let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+            match inflight.get(key) {
+                Some(flight) => {
+                    tracing::debug!(key = key, "Joining in-flight query");
+                    flight.clone()
+                }
+                None => {
+                    let release = Release {
+                        inflight: Arc::clone(&self.inflight),
+                        key: key.to_string(),
+                    };
+                    let work = start();
+                    let task = tokio::spawn(async move {
+                        let _release = release;
+                        work.await
+                    });
+                    let flight = task.map(|joined| joined.ok()).boxed().shared();
+                    inflight.insert(key.to_string(), flight.clone());
+                    flight
+                }
+            }
+        };
+        match flight.await {
+            Some(output) => output,
+            None => panic!("In-flight work for key '{}' panicked", key),
+        }
+    }
+
+    /// Number of keys with work in flight
+    #[cfg(test)]
+    fn in_flight(&self) -> usize {
+        self.inflight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+}
+
+impl<T> Clone for SingleFlight<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inflight: Arc::clone(&self.inflight),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    async fn counted(flights: &SingleFlight<usize>, key: &str, runs: &Arc<AtomicUsize>) -> usize {
+        let runs = Arc::clone(runs);
+        flights
+            .run(key, move || async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                runs.fetch_add(1, Ordering::SeqCst) + 1
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_calls_share_one_run() {
+        let flights = SingleFlight::new();
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let outputs =
+            futures::future::join_all((0..5).map(|_| counted(&flights, "q", &runs))).await;
+        assert_eq!(outputs, vec![1; 5]);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(flights.in_flight(), 0);
+
+        // Finished work is not reused
+        assert_eq!(counted(&flights, "q", &runs).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_different_keys_run_separately() {
+        let flights = SingleFlight::new();
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let (a, b) = tokio::join!(counted(&flights, "a", &runs), counted(&flights, "b", &runs));
+        assert_ne!(a, b);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_work_survives_dropped_caller() {
+        let flights = SingleFlight::new();
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        // The first caller gives up before the work finishes
+        let abandoned =
+            tokio::time::timeout(Duration::from_millis(10), counted(&flights, "q", &runs)).await;
+        assert!(abandoned.is_err());
+        assert_eq!(flights.in_flight(), 1);
+
+        // The next caller joins the same run instead of starting another
+        assert_eq!(counted(&flights, "q", &runs).await, 1);
+        assert_eq!(flights.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_panicking_work_releases_key() {
+        let flights = SingleFlight::new();
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let caller = {
+            let flights = flights.clone();
+            tokio::spawn(async move {
+                flights
+                    .run("q", || async {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                        panic!("query blew up")
+                    })
+                    .await
+            })
+        };
+        assert!(caller.await.unwrap_err().is_panic());
+        assert_eq!(flights.in_flight(), 0);
+
+        // The key isn't poisoned: the next caller starts fresh work
+        assert_eq!(counted(&flights, "q", &runs).await, 1);
+    }
+}
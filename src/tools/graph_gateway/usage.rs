@@ -0,0 +1,225 @@
+//! Query usage accounting and the daily query budget
+//!
+//! Every query the gateway sends upstream is billable; cache hits are not.
+//! Both are counted per subgraph, with an estimated cost (GRT and USD, from
+//! configured per-query pricing, or the actual amount for x402-paid queries)
+//! and the cost cache hits saved. Upstream queries also count against an
+//! optional daily limit, reset at UTC midnight.
+
+use super::GatewayError;
+use crate::config::GraphUsageSettings;
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Usage of one subgraph since startup
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SubgraphUsage {
+    pub subgraph_id: String,
+    /// Queries sent upstream (billable)
+    pub queries: u64,
+    /// Queries answered from cache (free)
+    pub cache_hits: u64,
+    pub estimated_grt: f64,
+    pub estimated_usd: f64,
+    /// Estimated cost of the cache hits had they gone upstream
+    pub saved_usd: f64,
+}
+
+/// Usage across all subgraphs, and the daily budget
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageReport {
+    /// Billable queries since UTC midnight
+    pub queries_today: u64,
+    pub daily_query_limit: Option<u64>,
+    /// Billable queries left today (`None` without a limit)
+    pub remaining_today: Option<u64>,
+    /// Totals since startup
+    pub queries: u64,
+    pub cache_hits: u64,
+    pub estimated_grt: f64,
+    pub estimated_usd: f64,
+    pub saved_usd: f64,
+    /// Per subgraph, most queried first
+    pub subgraphs: Vec<SubgraphUsage>,
+}
+
+struct Ledger {
+    day: NaiveDate,
+    queries_today: u64,
+    subgraphs: HashMap<String, SubgraphUsage>,
+}
+
+/// Usage counters shared by a gateway and its clones
+pub(crate) struct UsageTracker {
+    settings: GraphUsageSettings,
+    ledger: Mutex<Ledger>,
+}
+
+impl UsageTracker {
+    pub(crate) fn new(settings: GraphUsageSettings) -> Self {
+        Self {
+            settings,
+            ledger: Mutex::new(Ledger {
+                day: Utc::now().date_naive(),
+                queries_today: 0,
+                subgraphs: HashMap::new(),
+            }),
+        }
+    }
+
+    fn ledger(&self) -> std::sync::MutexGuard<'_, Ledger> {
+        let mut ledger = self.ledger.lock().unwrap_or_else(|e| e.into_inner());
+        let today = Utc::now().date_naive();
+        if ledger.day != today {
+            ledger.day = today;
+            ledger.queries_today = 0;
+        }
+        ledger
+    }
+
+    /// Fail with [`GatewayError::BudgetExceeded`] if today's queries are used up
+    ///
+    /// Concurrent queries admitted together may overshoot the limit by the
+    /// number in flight.
+    pub(crate) fn check_budget(&self) -> Result<(), GatewayError> {
+        let Some(limit) = self.settings.daily_query_limit else {
+            return Ok(());
+        };
+        let used = self.ledger().queries_today;
+        if used < limit {
+            return Ok(());
+        }
+        let now = Utc::now();
+        let midnight = (now.date_naive() + chrono::Days::new(1))
+            .and_hms_opt(0, 0, 0)
+            .map(|t| t.and_utc())
+            .unwrap_or(now);
+        Err(GatewayError::BudgetExceeded {
+            limit,
+            used,
+            resets_in_secs: (midnight - now).num_seconds().max(0) as u64,
+        })
+    }
+
+    /// A query went upstream; `paid_usd` is its actual cost if known
+    pub(crate) fn record_query(&self, subgraph_id: &str, paid_usd: Option<f64>) {
+        let usd = paid_usd.unwrap_or(self.settings.usd_per_query);
+        let grt = self.usd_to_grt(usd);
+        let mut ledger = self.ledger();
+        ledger.queries_today += 1;
+        let usage = Self::entry(&mut ledger, subgraph_id);
+        usage.queries += 1;
+        usage.estimated_usd += usd;
+        usage.estimated_grt += grt;
+    }
+
+    /// A query was answered from cache
+    pub(crate) fn record_cache_hit(&self, subgraph_id: &str) {
+        let saved = self.settings.usd_per_query;
+        let mut ledger = self.ledger();
+        let usage = Self::entry(&mut ledger, subgraph_id);
+        usage.cache_hits += 1;
+        usage.saved_usd += saved;
+    }
+
+    fn entry<'a>(ledger: &'a mut Ledger, subgraph_id: &str) -> &'a mut SubgraphUsage {
+        ledger
+            .subgraphs
+            .entry(subgraph_id.to_string())
+            .or_insert_with(|| SubgraphUsage {
+                subgraph_id: subgraph_id.to_string(),
+                ..SubgraphUsage::default()
+            })
+    }
+
+    fn usd_to_grt(&self, usd: f64) -> f64 {
+        if self.settings.grt_usd_price > 0.0 {
+            usd / self.settings.grt_usd_price
+        } else {
+            0.0
+        }
+    }
+
+    pub(crate) fn report(&self) -> UsageReport {
+        let ledger = self.ledger();
+        let mut subgraphs: Vec<SubgraphUsage> = ledger.subgraphs.values().cloned().collect();
+        subgraphs.sort_by(|a, b| {
+            b.queries
+                .cmp(&a.queries)
+                .then_with(|| a.subgraph_id.cmp(&b.subgraph_id))
+        });
+        let limit = self.settings.daily_query_limit;
+        UsageReport {
+            queries_today: ledger.queries_today,
+            daily_query_limit: limit,
+            remaining_today: limit.map(|limit| limit.saturating_sub(ledger.queries_today)),
+            queries: subgraphs.iter().map(|s| s.queries).sum(),
+            cache_hits: subgraphs.iter().map(|s| s.cache_hits).sum(),
+            estimated_grt: subgraphs.iter().map(|s| s.estimated_grt).sum(),
+            estimated_usd: subgraphs.iter().map(|s| s.estimated_usd).sum(),
+            saved_usd: subgraphs.iter().map(|s| s.saved_usd).sum(),
+            subgraphs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(daily_query_limit: Option<u64>) -> UsageTracker {
+        UsageTracker::new(GraphUsageSettings {
+            daily_query_limit,
+            usd_per_query: 0.0001,
+            grt_usd_price: 0.1,
+        })
+    }
+
+    #[test]
+    fn test_counts_queries_and_savings_per_subgraph() {
+        let usage = tracker(None);
+        usage.record_query("a", None);
+        usage.record_query("a", Some(0.002));
+        usage.record_query("b", None);
+        usage.record_cache_hit("a");
+        usage.record_cache_hit("a");
+
+        let report = usage.report();
+        assert_eq!(report.queries, 3);
+        assert_eq!(report.queries_today, 3);
+        assert_eq!(report.cache_hits, 2);
+        assert!(report.remaining_today.is_none());
+
+        let a = &report.subgraphs[0];
+        assert_eq!(a.subgraph_id, "a");
+        assert_eq!(a.queries, 2);
+        assert!((a.estimated_usd - 0.0021).abs() < 1e-12);
+        assert!((a.estimated_grt - 0.021).abs() < 1e-9);
+        assert!((a.saved_usd - 0.0002).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_daily_budget() {
+        let usage = tracker(Some(2));
+        assert!(usage.check_budget().is_ok());
+        usage.record_query("a", None);
+        usage.record_query("a", None);
+        // Cache hits don't count against the budget
+        usage.record_cache_hit("a");
+
+        match usage.check_budget() {
+            Err(GatewayError::BudgetExceeded {
+                limit,
+                used,
+                resets_in_secs,
+            }) => {
+                assert_eq!((limit, used), (2, 2));
+                assert!(resets_in_secs <= 86_400);
+            }
+            other => panic!("expected exhausted budget, got {:?}", other),
+        }
+        assert_eq!(usage.report().remaining_today, Some(0));
+    }
+}
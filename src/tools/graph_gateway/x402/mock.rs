@@ -0,0 +1,358 @@
+//! In-process x402 gateway and facilitator for offline tests
+//!
+//! [`MockX402Gateway`] listens on a local port and answers every GraphQL
+//! query with the same data for a fixed price. An unpaid request gets a 402
+//! listing its [`PaymentRequirements`]; a paid one is checked and settled by
+//! [`MockFacilitator`] and answered with the data and an `X-PAYMENT-RESPONSE`
+//! header. Nothing touches a chain: settlement only records the nonce.
+
+use super::{
+    authorization_hash, decode_header, encode_header, PaymentPayload, PaymentRequired,
+    PaymentRequirements, SettlementResponse, PAYMENT_HEADER, PAYMENT_RESPONSE_HEADER, X402_VERSION,
+};
+use alloy::primitives::{hex, keccak256, Signature, U256};
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+/// USDC on Base Sepolia
+pub const BASE_SEPOLIA_USDC: &str = "0x036CbD53842c5426634e7929541eC2318f3dCF7e";
+
+/// Recipient of the mock gateway's payments
+pub const PAY_TO: &str = "0x209693Bc6afc0C5328bA36FaF03C514EF312287C";
+
+/// `exact` requirements for `price` (smallest unit) of Base Sepolia USDC
+pub fn usdc_requirements(price: u128) -> PaymentRequirements {
+    PaymentRequirements {
+        scheme: "exact".to_string(),
+        network: "base-sepolia".to_string(),
+        max_amount_required: price.to_string(),
+        resource: "graphql".to_string(),
+        description: "Subgraph query".to_string(),
+        pay_to: PAY_TO.to_string(),
+        max_timeout_seconds: 60,
+        asset: BASE_SEPOLIA_USDC.to_string(),
+        extra: Some(json!({ "name": "USDC", "version": "2" })),
+    }
+}
+
+/// Verifies and settles `exact` payments against fixed requirements
+pub struct MockFacilitator {
+    requirements: PaymentRequirements,
+    used_nonces: Mutex<HashSet<String>>,
+}
+
+impl MockFacilitator {
+    pub fn new(requirements: PaymentRequirements) -> Self {
+        Self {
+            requirements,
+            used_nonces: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn requirements(&self) -> &PaymentRequirements {
+        &self.requirements
+    }
+
+    /// Check a payment's scheme, amount, recipient, validity window and
+    /// signature (nonce reuse is caught at settlement)
+    pub fn verify(&self, payment: &PaymentPayload) -> Result<(), String> {
+        let required = &self.requirements;
+        let authorization = &payment.payload.authorization;
+        if payment.scheme != required.scheme || payment.network != required.network {
+            return Err(format!(
+                "unsupported scheme {} on {}",
+                payment.scheme, payment.network
+            ));
+        }
+        if !authorization.to.eq_ignore_ascii_case(&required.pay_to) {
+            return Err("payment is to the wrong recipient".to_string());
+        }
+        let value = U256::from_str(&authorization.value).map_err(|e| e.to_string())?;
+        if value < U256::from(required.amount()?) {
+            return Err("payment is below the price".to_string());
+        }
+        let now = U256::from(Utc::now().timestamp().max(0) as u64);
+        let valid_after = U256::from_str(&authorization.valid_after).map_err(|e| e.to_string())?;
+        let valid_before =
+            U256::from_str(&authorization.valid_before).map_err(|e| e.to_string())?;
+        if now < valid_after || now >= valid_before {
+            return Err("authorization is outside its validity window".to_string());
+        }
+
+        let hash = authorization_hash(required, authorization)?;
+        let signature = hex::decode(&payment.payload.signature)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| Signature::from_raw(&bytes).map_err(|e| e.to_string()))?;
+        let signer = signature
+            .recover_address_from_prehash(&hash)
+            .map_err(|e| e.to_string())?;
+        if !format!("{:?}", signer).eq_ignore_ascii_case(&authorization.from) {
+            return Err("signature is not from the payer".to_string());
+        }
+        Ok(())
+    }
+
+    /// Verify a payment and record its nonce so it can't be replayed
+    pub async fn settle(&self, payment: &PaymentPayload) -> Result<SettlementResponse, String> {
+        self.verify(payment)?;
+        let authorization = &payment.payload.authorization;
+        if !self
+            .used_nonces
+            .lock()
+            .await
+            .insert(authorization.nonce.to_lowercase())
+        {
+            return Err("authorization nonce already used".to_string());
+        }
+        Ok(SettlementResponse {
+            success: true,
+            transaction: Some(hex::encode_prefixed(keccak256(
+                authorization.nonce.as_bytes(),
+            ))),
+            network: payment.network.clone(),
+            payer: Some(authorization.from.clone()),
+        })
+    }
+
+    /// Payments settled so far
+    pub async fn settled(&self) -> usize {
+        self.used_nonces.lock().await.len()
+    }
+}
+
+/// Local HTTP server charging for GraphQL queries with x402
+///
+/// Stops when dropped.
+pub struct MockX402Gateway {
+    url: String,
+    facilitator: Arc<MockFacilitator>,
+    server: JoinHandle<()>,
+}
+
+impl MockX402Gateway {
+    /// Serve `data` for every query, charging as `requirements` describe
+    pub async fn start(requirements: PaymentRequirements, data: Value) -> std::io::Result<Self> {
+        Self::spawn(requirements, data, None).await
+    }
+
+    /// Settle every payment, then answer the paid query with HTTP `status`
+    pub async fn start_failing(
+        requirements: PaymentRequirements,
+        status: u16,
+    ) -> std::io::Result<Self> {
+        Self::spawn(requirements, Value::Null, Some(status)).await
+    }
+
+    async fn spawn(
+        requirements: PaymentRequirements,
+        data: Value,
+        failure: Option<u16>,
+    ) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let facilitator = Arc::new(MockFacilitator::new(requirements));
+        let data = Arc::new(data);
+
+        let shared = Arc::clone(&facilitator);
+        let server = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let facilitator = Arc::clone(&shared);
+                let data = Arc::clone(&data);
+                tokio::spawn(async move {
+                    if let Err(e) = serve(stream, &facilitator, &data, failure).await {
+                        tracing::debug!(error = %e, "Mock x402 gateway connection failed");
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            url,
+            facilitator,
+            server,
+        })
+    }
+
+    /// Base URL to point an [`super::X402GraphGateway`] at
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn facilitator(&self) -> &MockFacilitator {
+        &self.facilitator
+    }
+}
+
+impl Drop for MockX402Gateway {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// Answer one request, then close the connection
+async fn serve(
+    stream: TcpStream,
+    facilitator: &MockFacilitator,
+    data: &Value,
+    failure: Option<u16>,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let headers = read_request(&mut reader).await?;
+
+    let payment_required = |error: &str| {
+        let body = PaymentRequired {
+            x402_version: X402_VERSION,
+            error: Some(error.to_string()),
+            accepts: vec![facilitator.requirements().clone()],
+        };
+        http_response(
+            402,
+            "Payment Required",
+            None,
+            &serde_json::to_value(body).unwrap_or_default(),
+        )
+    };
+    let response = match headers.get(&PAYMENT_HEADER.to_ascii_lowercase()) {
+        None => payment_required("payment required"),
+        Some(header) => match decode_header::<PaymentPayload>(header) {
+            None => payment_required("malformed payment header"),
+            Some(payment) => match (facilitator.settle(&payment).await, failure) {
+                (Ok(settlement), Some(status)) => http_response(
+                    status,
+                    "Error",
+                    Some((PAYMENT_RESPONSE_HEADER, encode_header(&settlement))),
+                    &json!({ "message": "upstream indexer failed" }),
+                ),
+                (Ok(settlement), None) => http_response(
+                    200,
+                    "OK",
+                    Some((PAYMENT_RESPONSE_HEADER, encode_header(&settlement))),
+                    &json!({ "data": data }),
+                ),
+                (Err(reason), _) => payment_required(&reason),
+            },
+        },
+    };
+
+    let mut stream = reader.into_inner();
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Read a request's headers (lowercased names) and drain its body
+async fn read_request(
+    reader: &mut BufReader<TcpStream>,
+) -> std::io::Result<HashMap<String, String>> {
+    let mut headers = HashMap::new();
+    let mut line = String::new();
+    // Request line
+    reader.read_line(&mut line).await?;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = headers
+        .get("content-length")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(headers)
+}
+
+fn http_response(
+    status: u16,
+    reason: &str,
+    header: Option<(&str, String)>,
+    body: &Value,
+) -> String {
+    let body = body.to_string();
+    let mut response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status,
+        reason,
+        body.len()
+    );
+    if let Some((name, value)) = header {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str("\r\n");
+    response.push_str(&body);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{PaymentLimits, X402Payer};
+    use super::*;
+    use crate::wallet::SecureWallet;
+
+    const TEST_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+    async fn signed_payment(requirements: &PaymentRequirements) -> PaymentPayload {
+        let payer = X402Payer::new(
+            Arc::new(SecureWallet::from_hex(TEST_KEY).unwrap()),
+            PaymentLimits::from_usd(1.0, 10.0),
+            "http://127.0.0.1",
+            vec![PAY_TO.parse().unwrap()],
+        );
+        let required = PaymentRequired {
+            x402_version: X402_VERSION,
+            error: None,
+            accepts: vec![requirements.clone()],
+        };
+        let signed = payer.pay("http://127.0.0.1/", &required).await.unwrap();
+        decode_header(&signed.header).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_facilitator_settles_once() {
+        let facilitator = MockFacilitator::new(usdc_requirements(1_000));
+        let payment = signed_payment(facilitator.requirements()).await;
+
+        let settlement = facilitator.settle(&payment).await.unwrap();
+        assert!(settlement.success);
+        assert_eq!(
+            settlement.payer.unwrap().to_lowercase(),
+            "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266"
+        );
+
+        let replay = facilitator.settle(&payment).await.unwrap_err();
+        assert!(replay.contains("already used"), "{replay}");
+        assert_eq!(facilitator.settled().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_facilitator_rejects_tampered_payment() {
+        let facilitator = MockFacilitator::new(usdc_requirements(1_000));
+        let mut payment = signed_payment(facilitator.requirements()).await;
+
+        // Raising the amount invalidates the signature
+        payment.payload.authorization.value = "2000".to_string();
+        let error = facilitator.verify(&payment).unwrap_err();
+        assert!(error.contains("signature"), "{error}");
+
+        // Underpaying is caught before the signature
+        let cheaper = MockFacilitator::new(usdc_requirements(5_000));
+        let payment = signed_payment(facilitator.requirements()).await;
+        let error = cheaper.verify(&payment).unwrap_err();
+        assert!(error.contains("below the price"), "{error}");
+    }
+}
@@ -0,0 +1,890 @@
+//! x402 pay-per-query gateway
+//!
+//! An x402 gateway answers an unpaid query with HTTP 402 and the payments it
+//! accepts. [`X402GraphGateway`] picks an `exact` requirement (an EIP-3009
+//! `transferWithAuthorization` of the payment token), signs it with the
+//! [`SecureWallet`] and resends the query with an `X-PAYMENT` header. Only
+//! USDC is paid, only to configured recipients and only for queries to the
+//! configured gateway host. Payments are capped per query and per UTC day,
+//! and the settlement the gateway reports comes back as a [`PaymentReceipt`]
+//! on the query result.
+//!
+//! [`mock`] provides an in-process gateway and facilitator for offline tests.
+
+pub mod mock;
+
+use super::cache::{CacheBackend, CacheStats};
+use super::{
+    BasicGraphGateway, GatewayError, GatewayQueryResult, GatewayStatus, GraphGateway, IndexerInfo,
+    QueryRoutingHints, UsageReport,
+};
+use crate::config::{CircuitBreakerSettings, GatewayRetrySettings, GraphUsageSettings};
+use crate::wallet::SecureWallet;
+use alloy::primitives::{address, hex, Address, B256, U256};
+use alloy::sol;
+use alloy::sol_types::{Eip712Domain, SolStruct};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use chrono::{NaiveDate, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// x402 protocol version spoken by the client
+pub const X402_VERSION: u32 = 1;
+
+/// Request header carrying the signed payment
+pub const PAYMENT_HEADER: &str = "X-PAYMENT";
+
+/// Response header carrying the settlement
+pub const PAYMENT_RESPONSE_HEADER: &str = "X-PAYMENT-RESPONSE";
+
+/// Decimals of the payment token (USDC), used to convert USD limits
+pub const PAYMENT_ASSET_DECIMALS: u32 = 6;
+
+/// Backdating of `validAfter`, to tolerate clock skew with the facilitator
+const VALID_AFTER_SKEW_SECS: u64 = 600;
+
+sol! {
+    /// EIP-3009 authorization signed for an `exact` payment
+    struct TransferWithAuthorization {
+        address from;
+        address to;
+        uint256 value;
+        uint256 validAfter;
+        uint256 validBefore;
+        bytes32 nonce;
+    }
+}
+
+/// Body of a 402 response: the payments the server accepts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentRequired {
+    pub x402_version: u32,
+    /// Why the request wasn't served (e.g. a rejected payment)
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub accepts: Vec<PaymentRequirements>,
+}
+
+/// One accepted way to pay for a resource
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentRequirements {
+    /// Payment scheme (only `exact` is supported)
+    pub scheme: String,
+    /// x402 network name (e.g. `base`, `base-sepolia`)
+    pub network: String,
+    /// Price in the token's smallest unit
+    pub max_amount_required: String,
+    pub resource: String,
+    #[serde(default)]
+    pub description: String,
+    /// Recipient address
+    pub pay_to: String,
+    /// Lifetime of a payment authorization (seconds)
+    pub max_timeout_seconds: u64,
+    /// Token contract address
+    pub asset: String,
+    /// Scheme-specific data; the token's EIP-712 domain `name` and `version`
+    /// for `exact`
+    #[serde(default)]
+    pub extra: Option<Value>,
+}
+
+impl PaymentRequirements {
+    /// Price in the token's smallest unit
+    pub fn amount(&self) -> Result<u128, String> {
+        self.max_amount_required
+            .parse()
+            .map_err(|e| format!("invalid amount {:?}: {}", self.max_amount_required, e))
+    }
+
+    /// EIP-712 domain of the token's `transferWithAuthorization`
+    fn domain(&self) -> Result<Eip712Domain, String> {
+        let chain_id = network_chain_id(&self.network)
+            .ok_or_else(|| format!("unsupported network {}", self.network))?;
+        let asset = parse_address(&self.asset)?;
+        let extra = |key: &str| {
+            self.extra
+                .as_ref()
+                .and_then(|extra| extra.get(key))
+                .and_then(Value::as_str)
+                .map(|value| Cow::Owned(value.to_string()))
+        };
+        let (Some(name), Some(version)) = (extra("name"), extra("version")) else {
+            return Err("requirements lack the token's EIP-712 name and version".to_string());
+        };
+        Ok(Eip712Domain::new(
+            Some(name),
+            Some(version),
+            Some(U256::from(chain_id)),
+            Some(asset),
+            None,
+        ))
+    }
+}
+
+/// Signed payment sent in the `X-PAYMENT` header
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentPayload {
+    pub x402_version: u32,
+    pub scheme: String,
+    pub network: String,
+    pub payload: ExactPayload,
+}
+
+/// `exact` scheme payload: an authorization and its signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExactPayload {
+    /// 65-byte EIP-712 signature, hex encoded
+    pub signature: String,
+    pub authorization: Authorization,
+}
+
+/// EIP-3009 authorization (amounts and times as decimal strings)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Authorization {
+    pub from: String,
+    pub to: String,
+    pub value: String,
+    pub valid_after: String,
+    pub valid_before: String,
+    /// Random 32-byte nonce, hex encoded
+    pub nonce: String,
+}
+
+/// Settlement reported in the `X-PAYMENT-RESPONSE` header
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettlementResponse {
+    pub success: bool,
+    #[serde(default)]
+    pub transaction: Option<String>,
+    pub network: String,
+    #[serde(default)]
+    pub payer: Option<String>,
+}
+
+/// Record of a payment made for a query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaymentReceipt {
+    pub network: String,
+    /// Token contract address
+    pub asset: String,
+    pub pay_to: String,
+    pub payer: String,
+    /// Amount in the token's smallest unit
+    pub amount: String,
+    /// Authorization nonce (unique per payment)
+    pub nonce: String,
+    /// Settlement transaction hash (if the gateway reported one)
+    pub transaction: Option<String>,
+    /// Whether the gateway reported the payment as settled
+    pub settled: bool,
+}
+
+impl PaymentReceipt {
+    /// Amount in USD, for a USD stablecoin with [`PAYMENT_ASSET_DECIMALS`]
+    /// decimals
+    pub fn amount_usd(&self) -> Option<f64> {
+        let units: u128 = self.amount.parse().ok()?;
+        Some(units as f64 / 10f64.powi(PAYMENT_ASSET_DECIMALS as i32))
+    }
+}
+
+/// Chain ID of an x402 network name
+pub fn network_chain_id(network: &str) -> Option<u64> {
+    match network {
+        "base" => Some(8453),
+        "base-sepolia" => Some(84532),
+        "polygon" => Some(137),
+        "polygon-amoy" => Some(80002),
+        "avalanche" => Some(43114),
+        "avalanche-fuji" => Some(43113),
+        _ => None,
+    }
+}
+
+/// USDC contract on an x402 network, the only asset payments are made in
+pub fn usdc_address(network: &str) -> Option<Address> {
+    match network {
+        "base" => Some(address!("833589fCD6eDb6E08f4c7C32D4f71b54bdA02913")),
+        "base-sepolia" => Some(address!("036CbD53842c5426634e7929541eC2318f3dCF7e")),
+        "polygon" => Some(address!("3c499c542cEF5E3811e1192ce70d8cC03d5c3359")),
+        "polygon-amoy" => Some(address!("41E94Eb019C0762f9Bfcf9Fb1E58725BfB0e7582")),
+        "avalanche" => Some(address!("B97EF9Ef8734C71904D8002F8b6Bc66Dd9c48a6E")),
+        "avalanche-fuji" => Some(address!("5425890298aed601595a70AB815c96711a31Bc65")),
+        _ => None,
+    }
+}
+
+/// Lowercased host of a URL, if it parses and has one
+fn url_host(url: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+}
+
+fn parse_address(value: &str) -> Result<Address, String> {
+    Address::from_str(value).map_err(|e| format!("invalid address {:?}: {}", value, e))
+}
+
+fn parse_u256(value: &str) -> Result<U256, String> {
+    U256::from_str(value).map_err(|e| format!("invalid number {:?}: {}", value, e))
+}
+
+/// EIP-712 hash signed for `authorization` under `requirements`
+pub(crate) fn authorization_hash(
+    requirements: &PaymentRequirements,
+    authorization: &Authorization,
+) -> Result<B256, String> {
+    let message = TransferWithAuthorization {
+        from: parse_address(&authorization.from)?,
+        to: parse_address(&authorization.to)?,
+        value: parse_u256(&authorization.value)?,
+        validAfter: parse_u256(&authorization.valid_after)?,
+        validBefore: parse_u256(&authorization.valid_before)?,
+        nonce: B256::from_str(&authorization.nonce)
+            .map_err(|e| format!("invalid nonce {:?}: {}", authorization.nonce, e))?,
+    };
+    Ok(message.eip712_signing_hash(&requirements.domain()?))
+}
+
+/// Base64 JSON, as carried in the payment headers
+pub(crate) fn encode_header<T: Serialize>(value: &T) -> String {
+    BASE64.encode(serde_json::to_vec(value).unwrap_or_default())
+}
+
+pub(crate) fn decode_header<T: DeserializeOwned>(header: &str) -> Option<T> {
+    let bytes = BASE64.decode(header.trim()).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+fn random_nonce() -> String {
+    let mut nonce = [0u8; 32];
+    nonce[..16].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+    nonce[16..].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+    hex::encode_prefixed(nonce)
+}
+
+/// Limits on query payments, in the payment token's smallest unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaymentLimits {
+    /// Most a single query may cost
+    pub per_query: u128,
+    /// Most all queries may cost per UTC day
+    pub daily: u128,
+}
+
+impl PaymentLimits {
+    /// Limits from USD amounts, for a USD stablecoin with
+    /// [`PAYMENT_ASSET_DECIMALS`] decimals
+    pub fn from_usd(per_query_usd: f64, daily_usd: f64) -> Self {
+        let units =
+            |usd: f64| (usd.max(0.0) * 10f64.powi(PAYMENT_ASSET_DECIMALS as i32)).round() as u128;
+        Self {
+            per_query: units(per_query_usd),
+            daily: units(daily_usd),
+        }
+    }
+}
+
+/// Query payments made since UTC midnight
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PaymentSpend {
+    /// Payments signed today
+    pub payments: u64,
+    /// Amount authorized today (token's smallest unit)
+    pub spent: String,
+    pub daily_limit: String,
+    pub per_query_limit: String,
+}
+
+struct DailySpend {
+    day: NaiveDate,
+    payments: u64,
+    spent: u128,
+}
+
+impl DailySpend {
+    fn today() -> Self {
+        Self {
+            day: Utc::now().date_naive(),
+            payments: 0,
+            spent: 0,
+        }
+    }
+
+    fn roll_over(&mut self) {
+        if self.day != Utc::now().date_naive() {
+            *self = Self::today();
+        }
+    }
+}
+
+/// A signed payment awaiting the server's answer
+pub(crate) struct SignedPayment {
+    /// Value of the `X-PAYMENT` header
+    pub(crate) header: String,
+    receipt: PaymentReceipt,
+}
+
+impl SignedPayment {
+    /// The receipt, completed from the `X-PAYMENT-RESPONSE` header (if any)
+    pub(crate) fn settle(self, settlement_header: Option<&str>) -> PaymentReceipt {
+        let mut receipt = self.receipt;
+        if let Some(settlement) = settlement_header.and_then(decode_header::<SettlementResponse>) {
+            receipt.settled = settlement.success;
+            receipt.transaction = settlement.transaction;
+        }
+        receipt
+    }
+}
+
+/// Signs x402 payments from a wallet, within spending limits
+pub(crate) struct X402Payer {
+    wallet: Arc<SecureWallet>,
+    limits: PaymentLimits,
+    /// Host whose queries are paid for (none if the gateway URL has none)
+    host: Option<String>,
+    /// Recipients payments may go to
+    pay_to: Vec<Address>,
+    today: Mutex<DailySpend>,
+}
+
+impl X402Payer {
+    /// Pay for queries to `gateway_url`'s host, to the `pay_to` recipients only
+    pub(crate) fn new(
+        wallet: Arc<SecureWallet>,
+        limits: PaymentLimits,
+        gateway_url: &str,
+        pay_to: Vec<Address>,
+    ) -> Self {
+        Self {
+            wallet,
+            limits,
+            host: url_host(gateway_url),
+            pay_to,
+            today: Mutex::new(DailySpend::today()),
+        }
+    }
+
+    /// Why `requirements` can't be paid, if they can't: anything but `exact`
+    /// USDC on a supported network, to a configured recipient, for a resource
+    /// on the gateway
+    fn rejection(&self, requirements: &PaymentRequirements) -> Option<String> {
+        if requirements.scheme != "exact" {
+            return Some(format!("unsupported scheme {}", requirements.scheme));
+        }
+        let Some(usdc) = usdc_address(&requirements.network) else {
+            return Some(format!("unsupported network {}", requirements.network));
+        };
+        if parse_address(&requirements.asset).ok() != Some(usdc) {
+            return Some(format!(
+                "asset {} is not USDC on {}",
+                requirements.asset, requirements.network
+            ));
+        }
+        if !parse_address(&requirements.pay_to).is_ok_and(|to| self.pay_to.contains(&to)) {
+            return Some(format!(
+                "recipient {} is not a configured payee",
+                requirements.pay_to
+            ));
+        }
+        // Resources are often just a path; one given as a URL must be ours
+        match url_host(&requirements.resource) {
+            Some(host) if Some(&host) != self.host.as_ref() => {
+                Some(format!("resource host {} is not the gateway", host))
+            }
+            _ => None,
+        }
+    }
+
+    /// Sign a payment for the first acceptable requirement of a query sent to
+    /// `endpoint`
+    ///
+    /// The amount counts against the daily limit once signed: the server may
+    /// settle the authorization even if the query then fails.
+    pub(crate) async fn pay(
+        &self,
+        endpoint: &str,
+        required: &PaymentRequired,
+    ) -> Result<SignedPayment, GatewayError> {
+        if self.host.is_none() || url_host(endpoint) != self.host {
+            return Err(GatewayError::PaymentRequired(format!(
+                "{} is not the configured x402 gateway",
+                endpoint
+            )));
+        }
+        let requirements = required
+            .accepts
+            .iter()
+            .find(|r| self.rejection(r).is_none())
+            .ok_or_else(|| {
+                let rejections: Vec<String> = required
+                    .accepts
+                    .iter()
+                    .filter_map(|r| self.rejection(r))
+                    .collect();
+                GatewayError::PaymentRequired(format!(
+                    "no acceptable payment option offered ({}; server said: {})",
+                    if rejections.is_empty() {
+                        "none listed".to_string()
+                    } else {
+                        rejections.join("; ")
+                    },
+                    required.error.as_deref().unwrap_or("nothing")
+                ))
+            })?;
+        let amount = requirements
+            .amount()
+            .map_err(GatewayError::PaymentRequired)?;
+        if amount > self.limits.per_query {
+            return Err(GatewayError::PaymentRequired(format!(
+                "query costs {} units, above the per-query limit of {}",
+                amount, self.limits.per_query
+            )));
+        }
+
+        // Held until the payment is counted, so concurrent queries can't
+        // overshoot the daily limit together
+        let mut today = self.today.lock().await;
+        today.roll_over();
+        if today.spent + amount > self.limits.daily {
+            return Err(GatewayError::PaymentRequired(format!(
+                "daily payment limit of {} units reached ({} spent today)",
+                self.limits.daily, today.spent
+            )));
+        }
+
+        let now = Utc::now().timestamp().max(0) as u64;
+        let authorization = Authorization {
+            from: self.wallet.address_string(),
+            to: requirements.pay_to.clone(),
+            value: amount.to_string(),
+            valid_after: now.saturating_sub(VALID_AFTER_SKEW_SECS).to_string(),
+            valid_before: (now + requirements.max_timeout_seconds).to_string(),
+            nonce: random_nonce(),
+        };
+        let hash = authorization_hash(requirements, &authorization)
+            .map_err(GatewayError::PaymentRequired)?;
+        let signature =
+            self.wallet.sign_hash(&hash.0).await.map_err(|e| {
+                GatewayError::PaymentRequired(format!("failed to sign payment: {}", e))
+            })?;
+        today.payments += 1;
+        today.spent += amount;
+        drop(today);
+
+        tracing::info!(
+            network = requirements.network.as_str(),
+            amount = amount as u64,
+            pay_to = requirements.pay_to.as_str(),
+            "Signed x402 query payment"
+        );
+        let receipt = PaymentReceipt {
+            network: requirements.network.clone(),
+            asset: requirements.asset.clone(),
+            pay_to: requirements.pay_to.clone(),
+            payer: authorization.from.clone(),
+            amount: authorization.value.clone(),
+            nonce: authorization.nonce.clone(),
+            transaction: None,
+            settled: false,
+        };
+        let payload = PaymentPayload {
+            x402_version: X402_VERSION,
+            scheme: requirements.scheme.clone(),
+            network: requirements.network.clone(),
+            payload: ExactPayload {
+                signature: hex::encode_prefixed(signature.as_bytes()),
+                authorization,
+            },
+        };
+        Ok(SignedPayment {
+            header: encode_header(&payload),
+            receipt,
+        })
+    }
+
+    pub(crate) async fn spend(&self) -> PaymentSpend {
+        let mut today = self.today.lock().await;
+        today.roll_over();
+        PaymentSpend {
+            payments: today.payments,
+            spent: today.spent.to_string(),
+            daily_limit: self.limits.daily.to_string(),
+            per_query_limit: self.limits.per_query.to_string(),
+        }
+    }
+}
+
+/// Gateway that pays for queries with x402 instead of an API key
+///
+/// Queries go to `{gateway_url}/subgraphs/id/{id}` (or `/deployments/id/`).
+/// Caching, coalescing, retries, circuit breaking and indexer routing are
+/// those of [`BasicGraphGateway`]; cache hits cost nothing.
+pub struct X402GraphGateway {
+    inner: BasicGraphGateway,
+    payer: Arc<X402Payer>,
+}
+
+impl X402GraphGateway {
+    /// Pay for queries to `gateway_url` from `wallet`, within `limits`, to
+    /// the `pay_to` recipients only
+    pub fn new(
+        gateway_url: impl Into<String>,
+        wallet: Arc<SecureWallet>,
+        limits: PaymentLimits,
+        pay_to: Vec<Address>,
+    ) -> Self {
+        let gateway_url = gateway_url.into();
+        let payer = Arc::new(X402Payer::new(wallet, limits, &gateway_url, pay_to));
+        let inner = BasicGraphGateway::new(String::new())
+            .with_gateway_url(gateway_url)
+            .with_payer(Arc::clone(&payer));
+        Self { inner, payer }
+    }
+
+    /// Keep results for `ttl` unless a query asks otherwise
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.inner.default_cache_ttl = ttl;
+        self
+    }
+
+    /// Store results in `cache`
+    pub fn with_cache(mut self, cache: Arc<dyn CacheBackend>) -> Self {
+        self.inner = self.inner.with_cache(cache);
+        self
+    }
+
+    /// Retry transient failures as `retry` describes
+    pub fn with_retry(mut self, retry: GatewayRetrySettings) -> Self {
+        self.inner = self.inner.with_retry(retry);
+        self
+    }
+
+    /// Break circuits of failing subgraphs as `settings` describes
+    pub fn with_circuit_breaker(mut self, settings: CircuitBreakerSettings) -> Self {
+        self.inner = self.inner.with_circuit_breaker(settings);
+        self
+    }
+
+    /// Discover indexers through a different network subgraph
+    pub fn with_network_subgraph(mut self, subgraph_id: impl Into<String>) -> Self {
+        self.inner = self.inner.with_network_subgraph(subgraph_id);
+        self
+    }
+
+    /// Price queries and limit them per day as `settings` describe
+    pub fn with_usage(mut self, settings: GraphUsageSettings) -> Self {
+        self.inner = self.inner.with_usage(settings);
+        self
+    }
+
+    /// Payments made today against the limits
+    pub async fn spend(&self) -> PaymentSpend {
+        self.payer.spend().await
+    }
+}
+
+#[async_trait]
+impl GraphGateway for X402GraphGateway {
+    async fn query_with_routing(
+        &self,
+        subgraph_id: &str,
+        query: &str,
+        variables: Value,
+        routing_hints: QueryRoutingHints,
+    ) -> Result<GatewayQueryResult, GatewayError> {
+        self.inner
+            .query_with_routing(subgraph_id, query, variables, routing_hints)
+            .await
+    }
+
+    async fn get_indexers(&self, subgraph_id: &str) -> Result<Vec<IndexerInfo>, GatewayError> {
+        self.inner.get_indexers(subgraph_id).await
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn usage(&self) -> Option<UsageReport> {
+        self.inner.usage()
+    }
+
+    async fn status(&self) -> GatewayStatus {
+        GatewayStatus {
+            payments: Some(self.payer.spend().await),
+            ..self.inner.status().await
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "X402GraphGateway"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::no_retry;
+    use super::mock::{usdc_requirements, MockX402Gateway, PAY_TO};
+    use super::*;
+    use serde_json::json;
+
+    // Test private key (DO NOT use in production!)
+    const TEST_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+    fn wallet() -> Arc<SecureWallet> {
+        Arc::new(SecureWallet::from_hex(TEST_KEY).unwrap())
+    }
+
+    fn payees() -> Vec<Address> {
+        vec![PAY_TO.parse().unwrap()]
+    }
+
+    fn gateway(mock: &MockX402Gateway, limits: PaymentLimits) -> X402GraphGateway {
+        X402GraphGateway::new(mock.url(), wallet(), limits, payees()).with_retry(no_retry())
+    }
+
+    async fn query(
+        gateway: &X402GraphGateway,
+        force_fresh: bool,
+    ) -> Result<GatewayQueryResult, GatewayError> {
+        let hints = QueryRoutingHints {
+            force_fresh,
+            ..QueryRoutingHints::default()
+        };
+        gateway
+            .query_with_routing("abc", "{ pools { id } }", json!({}), hints)
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_pays_for_query_and_records_receipt() {
+        let mock = MockX402Gateway::start(usdc_requirements(1_000), json!({ "pools": [] }))
+            .await
+            .unwrap();
+        let gateway = gateway(&mock, PaymentLimits::from_usd(0.01, 1.0));
+
+        let result = query(&gateway, false).await.unwrap();
+        assert_eq!(result.data, json!({ "pools": [] }));
+        let receipt = result.payment.expect("payment receipt");
+        assert!(receipt.settled);
+        assert!(receipt.transaction.is_some());
+        assert_eq!(receipt.amount, "1000");
+        assert_eq!(
+            receipt.payer.to_lowercase(),
+            "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266"
+        );
+        assert_eq!(mock.facilitator().settled().await, 1);
+
+        // Cache hits are free
+        let cached = query(&gateway, false).await.unwrap();
+        assert!(cached.cached);
+        assert!(cached.payment.is_none());
+
+        // The paid amount is the query's recorded cost
+        let usage = gateway.usage().unwrap();
+        assert_eq!((usage.queries, usage.cache_hits), (1, 1));
+        assert!((usage.estimated_usd - 0.001).abs() < 1e-12);
+
+        let spend = gateway.spend().await;
+        assert_eq!(spend.payments, 1);
+        assert_eq!(spend.spent, "1000");
+        assert_eq!(gateway.status().await.payments, Some(spend));
+    }
+
+    #[tokio::test]
+    async fn test_paid_failure_is_not_retried() {
+        let mock = MockX402Gateway::start_failing(usdc_requirements(1_000), 503)
+            .await
+            .unwrap();
+        let gateway =
+            gateway(&mock, PaymentLimits::from_usd(0.01, 1.0)).with_retry(GatewayRetrySettings {
+                max_retries: 3,
+                ..no_retry()
+            });
+
+        // A 503 is transient, but retrying it would pay again
+        match query(&gateway, false).await {
+            Err(GatewayError::PaidRequestFailed { payment, error }) => {
+                assert_eq!(payment.amount, "1000");
+                assert!(payment.settled);
+                assert!(matches!(
+                    *error,
+                    GatewayError::HttpStatus { status: 503, .. }
+                ));
+            }
+            other => panic!("expected a paid failure, got {:?}", other),
+        }
+        assert_eq!(mock.facilitator().settled().await, 1);
+        assert_eq!(gateway.spend().await.payments, 1);
+
+        // The payment is accounted for even though the query failed
+        let usage = gateway.usage().unwrap();
+        assert_eq!(usage.queries, 1);
+        assert!((usage.estimated_usd - 0.001).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn test_per_query_limit() {
+        let mock = MockX402Gateway::start(usdc_requirements(20_000), json!({}))
+            .await
+            .unwrap();
+        let gateway = gateway(&mock, PaymentLimits::from_usd(0.01, 1.0));
+
+        match query(&gateway, false).await {
+            Err(GatewayError::PaymentRequired(reason)) => {
+                assert!(reason.contains("per-query limit"), "{reason}")
+            }
+            other => panic!("expected payment refusal, got {:?}", other),
+        }
+        assert_eq!(gateway.spend().await.payments, 0);
+        assert_eq!(mock.facilitator().settled().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_daily_limit() {
+        let mock = MockX402Gateway::start(usdc_requirements(1_000), json!({}))
+            .await
+            .unwrap();
+        let gateway = gateway(
+            &mock,
+            PaymentLimits {
+                per_query: 1_000,
+                daily: 1_500,
+            },
+        );
+
+        assert!(query(&gateway, true).await.is_ok());
+        match query(&gateway, true).await {
+            Err(GatewayError::PaymentRequired(reason)) => {
+                assert!(reason.contains("daily payment limit"), "{reason}")
+            }
+            other => panic!("expected payment refusal, got {:?}", other),
+        }
+        assert_eq!(gateway.spend().await.spent, "1000");
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_wallet_cannot_pay() {
+        let mock = MockX402Gateway::start(usdc_requirements(1_000), json!({}))
+            .await
+            .unwrap();
+        let wallet = Arc::new(SecureWallet::from_hex(TEST_KEY).unwrap().with_dry_run(true));
+        let gateway = X402GraphGateway::new(
+            mock.url(),
+            wallet,
+            PaymentLimits::from_usd(0.01, 1.0),
+            payees(),
+        );
+
+        assert!(matches!(
+            query(&gateway, false).await,
+            Err(GatewayError::PaymentRequired(_))
+        ));
+        assert_eq!(gateway.spend().await.payments, 0);
+    }
+
+    #[tokio::test]
+    async fn test_pays_only_usdc_to_configured_payees_on_the_gateway() {
+        let payer = X402Payer::new(
+            wallet(),
+            PaymentLimits::from_usd(0.01, 1.0),
+            "https://x402.example/api",
+            payees(),
+        );
+        let offer = |requirements: PaymentRequirements| PaymentRequired {
+            x402_version: X402_VERSION,
+            error: None,
+            accepts: vec![requirements],
+        };
+        let refusal = |result: Result<SignedPayment, GatewayError>| match result {
+            Err(GatewayError::PaymentRequired(reason)) => reason,
+            Err(e) => panic!("expected payment refusal, got {}", e),
+            Ok(_) => panic!("expected payment refusal, got a payment"),
+        };
+        let endpoint = "https://x402.example/api/subgraphs/id/abc";
+
+        let other_asset = PaymentRequirements {
+            asset: "0x4200000000000000000000000000000000000006".to_string(),
+            ..usdc_requirements(1_000)
+        };
+        let reason = refusal(payer.pay(endpoint, &offer(other_asset)).await);
+        assert!(reason.contains("not USDC"), "{reason}");
+
+        // USDC of another network doesn't pass either
+        let wrong_network = PaymentRequirements {
+            network: "base".to_string(),
+            ..usdc_requirements(1_000)
+        };
+        let reason = refusal(payer.pay(endpoint, &offer(wrong_network)).await);
+        assert!(reason.contains("not USDC"), "{reason}");
+
+        let stranger = PaymentRequirements {
+            pay_to: "0x000000000000000000000000000000000000dEaD".to_string(),
+            ..usdc_requirements(1_000)
+        };
+        let reason = refusal(payer.pay(endpoint, &offer(stranger)).await);
+        assert!(reason.contains("not a configured payee"), "{reason}");
+
+        let elsewhere = PaymentRequirements {
+            resource: "https://evil.example/graphql".to_string(),
+            ..usdc_requirements(1_000)
+        };
+        let reason = refusal(payer.pay(endpoint, &offer(elsewhere)).await);
+        assert!(reason.contains("not the gateway"), "{reason}");
+
+        let reason = refusal(
+            payer
+                .pay(
+                    "https://evil.example/subgraphs/id/abc",
+                    &offer(usdc_requirements(1_000)),
+                )
+                .await,
+        );
+        assert!(
+            reason.contains("not the configured x402 gateway"),
+            "{reason}"
+        );
+        assert_eq!(payer.spend().await.payments, 0);
+
+        // An acceptable option is picked from among unacceptable ones
+        let required = PaymentRequired {
+            accepts: vec![
+                PaymentRequirements {
+                    pay_to: "0x000000000000000000000000000000000000dEaD".to_string(),
+                    ..usdc_requirements(1_000)
+                },
+                usdc_requirements(1_000),
+            ],
+            ..offer(usdc_requirements(1_000))
+        };
+        let signed = payer.pay(endpoint, &required).await.unwrap();
+        let payment: PaymentPayload = decode_header(&signed.header).unwrap();
+        assert!(payment
+            .payload
+            .authorization
+            .to
+            .eq_ignore_ascii_case(PAY_TO));
+    }
+
+    #[test]
+    fn test_limits_from_usd() {
+        let limits = PaymentLimits::from_usd(0.01, 5.0);
+        assert_eq!(limits.per_query, 10_000);
+        assert_eq!(limits.daily, 5_000_000);
+    }
+}
@@ -0,0 +1,74 @@
+//! Graph query usage tool
+//!
+//! Reports how many billable queries the gateway has sent per subgraph, their
+//! estimated cost in GRT and USD, what the cache saved, and how much of the
+//! daily query budget is left, so the agent can ration its queries.
+
+use crate::tools::the_graph::TheGraphTool;
+use crate::tools::wallet_signing::EmptyArgs;
+use crate::tools::{AnyJson, DefiBundle};
+use async_trait::async_trait;
+use baml_rt::error::Result;
+use baml_rt::tools::BamlTool;
+use serde_json::json;
+
+/// Tool for inspecting gateway query usage and budget
+pub struct GraphUsageTool {
+    graph: TheGraphTool,
+}
+
+impl GraphUsageTool {
+    /// Create a tool reporting on the given subgraph tool's gateway
+    pub fn new(graph: TheGraphTool) -> Self {
+        Self { graph }
+    }
+}
+
+#[async_trait]
+impl BamlTool for GraphUsageTool {
+    type Bundle = DefiBundle;
+    const LOCAL_NAME: &'static str = "graph_usage";
+    type OpenInput = ();
+    type Input = EmptyArgs;
+    type Output = AnyJson;
+
+    fn description(&self) -> &'static str {
+        "Reports Graph query usage: billable queries sent today against the daily query budget \
+         (remaining_today), and per subgraph the queries sent, cache hits, estimated cost in GRT \
+         and USD, and the cost saved by the cache. Check this before broad exploratory queries; \
+         once the budget is spent only cached results are served until UTC midnight."
+    }
+
+    async fn execute(&self, _args: Self::Input) -> Result<Self::Output> {
+        let Some(usage) = self.graph.gateway_usage() else {
+            return Ok(AnyJson::new(json!({ "gateway": null, "usage": null })));
+        };
+        Ok(AnyJson::new(json!({
+            "gateway": self.graph.gateway_name(),
+            "usage": usage
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_usage_without_gateway() {
+        let tool = GraphUsageTool::new(TheGraphTool::new());
+        let output = tool.execute(EmptyArgs {}).await.unwrap();
+        assert!(output.0["gateway"].is_null());
+        assert!(output.0["usage"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_usage_with_gateway() {
+        let tool = GraphUsageTool::new(TheGraphTool::with_gateway("test-api-key".to_string()));
+        let output = tool.execute(EmptyArgs {}).await.unwrap();
+        assert_eq!(output.0["gateway"], "BasicGraphGateway");
+        assert_eq!(output.0["usage"]["queries_today"], 0);
+        assert!(output.0["usage"]["remaining_today"].is_null());
+        assert_eq!(output.0["usage"]["subgraphs"], json!([]));
+    }
+}
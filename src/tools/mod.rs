@@ -3,9 +3,17 @@
 //! Tools implement the `BamlTool` trait from baml-rt and are exposed
 //! to the TypeScript agent via the QuickJS bridge.
 
+mod execute_swap;
+mod failover;
+mod flows;
+mod freshness;
+mod gateway_status;
 pub mod graph_gateway;
+mod graph_usage;
 mod odos;
 mod paper_trading;
+mod pool_analytics;
+mod subgraph_schema;
 mod the_graph;
 mod types;
 mod wallet;
@@ -13,15 +21,28 @@ mod wallet_signing;
 
 use baml_rt_tools::BundleType;
 
-pub use graph_gateway::{BasicGraphGateway, GatewayError, GraphGateway, QueryRoutingHints};
-pub use odos::{OdosAction, OdosInput, OdosTool};
+pub use execute_swap::{ExecuteSwapInput, ExecuteSwapTool, PreparedSwapTransaction};
+pub use flows::{FlowDirection, FlowMetrics};
+pub use freshness::{fetch_chain_head, FreshnessError, FreshnessPolicy, SubgraphFreshness};
+pub use gateway_status::GraphGatewayStatusTool;
+pub use graph_gateway::{
+    BasicGraphGateway, GatewayError, GatewayStatus, GraphGateway, QueryRoutingHints,
+};
+pub use graph_usage::GraphUsageTool;
+pub use odos::{OdosAction, OdosInput, OdosTool, QuoteStamp, ODOS_QUOTE_TTL_SECS};
 pub use paper_trading::PaperTradingTool;
+pub use pool_analytics::{
+    IlScenario, PoolAnalytics, PoolAnalyticsInput, PoolAnalyticsOutput, PoolAnalyticsTool,
+};
+pub use subgraph_schema::{SubgraphSchemaInput, SubgraphSchemaTool};
 pub use the_graph::{
     GraphQueryInput, GraphQueryParams, GraphQueryType, QueryFilters, QueryPlan, TheGraphTool,
 };
 pub use types::AnyJson;
 pub use wallet::WalletTool;
-pub use wallet_signing::{WalletDeriveAddressTool, WalletSignMessageTool, WalletSignTxTool};
+pub use wallet_signing::{
+    UnsignedTxInput, WalletDeriveAddressTool, WalletSignMessageTool, WalletSignTxTool,
+};
 
 /// Bundle for all agent tools in this repo.
 pub struct DefiBundle;
@@ -36,7 +57,12 @@ impl BundleType for DefiBundle {
 
 pub const TOOL_PAPER_TRADING: &str = "defi/paper_trading";
 pub const TOOL_QUERY_SUBGRAPH: &str = "defi/query_subgraph";
+pub const TOOL_POOL_ANALYTICS: &str = "defi/pool_analytics";
+pub const TOOL_SUBGRAPH_SCHEMA: &str = "defi/subgraph_schema";
+pub const TOOL_GRAPH_GATEWAY_STATUS: &str = "defi/graph_gateway_status";
+pub const TOOL_GRAPH_USAGE: &str = "defi/graph_usage";
 pub const TOOL_ODOS_SWAP: &str = "defi/odos_swap";
+pub const TOOL_EXECUTE_SWAP: &str = "defi/execute_swap";
 pub const TOOL_WALLET_BALANCE: &str = "defi/wallet_balance";
 pub const TOOL_WALLET_DERIVE_ADDRESS: &str = "defi/wallet_derive_address";
 pub const TOOL_WALLET_SIGN_MESSAGE: &str = "defi/wallet_sign_message";
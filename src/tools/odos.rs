@@ -8,8 +8,10 @@
 //! - Signing happens in the SecureWallet module after interceptor approval
 //! - The tool has no access to private keys
 
+use crate::execution::{PreparedSwap, PreparedSwaps};
 use crate::tokens::{addresses, registry};
-use crate::tools::{AnyJson, DefiBundle};
+use crate::tools::{AnyJson, DefiBundle, PreparedSwapTransaction};
+use crate::wallet::PreparedTransaction;
 use alloy::primitives::{Address, U256};
 use async_trait::async_trait;
 use baml_rt::error::{BamlRtError, Result};
@@ -19,14 +21,20 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::str::FromStr;
+use std::sync::Arc;
 use ts_rs::TS;
 
+/// Validity window of an Odos path ID: how long after quoting Odos will still
+/// assemble the quoted path (seconds)
+pub const ODOS_QUOTE_TTL_SECS: i64 = 60;
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, TS)]
 #[ts(export)]
 #[serde(rename_all = "snake_case")]
 pub enum OdosAction {
     Quote,
     PrepareSwap,
+    Requote,
     GetPrice,
     GetPrices,
 }
@@ -43,18 +51,54 @@ pub struct OdosInput {
     pub slippage_percent: Option<f64>,
     pub chain_id: Option<u64>,
     pub network: Option<String>,
+    /// Transaction from an earlier `prepare_swap` (for `requote`)
+    pub transaction: Option<PreparedSwapTransaction>,
+}
+
+/// When and at which block an Odos quote was priced, and until when it can
+/// be assembled
+///
+/// The quote freshness guard may retire it earlier (`risk.max_quote_age_secs`).
+/// Odos identifies a quote by its path ID, so `quote_id` is the path ID.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuoteStamp {
+    pub quote_id: String,
+    /// Block the quote was priced at
+    pub block_number: u64,
+    /// Unix time the quote was received
+    pub issued_at: i64,
+    /// Unix time after which the path can no longer be assembled
+    pub expires_at: i64,
+}
+
+impl QuoteStamp {
+    pub fn new(quote_id: impl Into<String>, block_number: u64, issued_at: i64) -> Self {
+        Self {
+            quote_id: quote_id.into(),
+            block_number,
+            issued_at,
+            expires_at: issued_at + ODOS_QUOTE_TTL_SECS,
+        }
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.expires_at
+    }
 }
 
 /// Tool for interacting with Odos DEX aggregator
 ///
-/// Provides two actions:
+/// Provides three actions:
 /// - `quote`: Get a swap quote (read-only, safe)
 /// - `prepare_swap`: Prepare transaction data (requires interceptor approval)
+/// - `requote`: Prepare a prepared swap again from a fresh quote
 pub struct OdosTool {
     /// Odos SDK client
     client: odos_sdk::OdosClient,
     /// Wallet address (public, safe to share)
     wallet_address: Address,
+    /// Where prepared transactions are recorded for execution
+    prepared_swaps: Option<Arc<PreparedSwaps>>,
 }
 
 impl OdosTool {
@@ -70,6 +114,7 @@ impl OdosTool {
         Self {
             client: odos_sdk::OdosClient::new().expect("Failed to create Odos client"),
             wallet_address: addr,
+            prepared_swaps: None,
         }
     }
 
@@ -86,9 +131,16 @@ impl OdosTool {
         Ok(Self {
             client,
             wallet_address: addr,
+            prepared_swaps: None,
         })
     }
 
+    /// Record prepared transactions so `defi/execute_swap` will accept them
+    pub fn with_prepared_swaps(mut self, prepared_swaps: Arc<PreparedSwaps>) -> Self {
+        self.prepared_swaps = Some(prepared_swaps);
+        self
+    }
+
     /// Get a swap quote from Odos using the SwapBuilder API
     async fn get_quote(&self, args: &OdosInput) -> Result<Value> {
         let input_token = args
@@ -141,6 +193,11 @@ impl OdosTool {
             .quote()
             .await
             .map_err(|e| BamlRtError::ToolExecution(format!("Odos quote failed: {}", e)))?;
+        let stamp = QuoteStamp::new(
+            quote.path_id(),
+            quote.block_number(),
+            chrono::Utc::now().timestamp(),
+        );
 
         Ok(json!({
             "action": "quote",
@@ -151,6 +208,10 @@ impl OdosTool {
             "price_impact_percent": quote.price_impact(),
             "gas_estimate": quote.gas_estimate(),
             "path_id": quote.path_id(),
+            "quote_id": stamp.quote_id,
+            "block_number": stamp.block_number,
+            "issued_at": stamp.issued_at,
+            "expires_at": stamp.expires_at,
         }))
     }
 
@@ -185,6 +246,70 @@ impl OdosTool {
         let amount_u256 = U256::from_str(amount)
             .map_err(|e| BamlRtError::InvalidArgument(format!("Invalid amount: {}", e)))?;
 
+        self.prepare(
+            input_addr,
+            output_addr,
+            amount_u256,
+            chain_id,
+            slippage_percent,
+        )
+        .await
+    }
+
+    /// Prepare the swap `args.transaction` was built for again, from a fresh
+    /// quote, replacing it
+    ///
+    /// The trade is the one recorded when it was prepared, not what the agent
+    /// passes, so it was already checked and charged by the interceptors.
+    async fn requote(&self, args: &OdosInput) -> Result<Value> {
+        let prepared_swaps = self.prepared_swaps.as_ref().ok_or_else(|| {
+            BamlRtError::InvalidArgument("No record of prepared swaps to requote".to_string())
+        })?;
+        let transaction = args
+            .transaction
+            .as_ref()
+            .ok_or_else(|| BamlRtError::InvalidArgument("Missing 'transaction'".to_string()))?;
+        let previous = PreparedTransaction::try_from(transaction)?;
+        // Claimed first so it can't be executed while it is being replaced
+        let swap = prepared_swaps.remove(&previous).ok_or_else(|| {
+            BamlRtError::InvalidArgument(
+                "Transaction was not built by prepare_swap (or was already executed); \
+                 prepare the swap instead"
+                    .to_string(),
+            )
+        })?;
+
+        match self
+            .prepare(
+                swap.input_token,
+                swap.output_token,
+                swap.input_amount,
+                swap.chain_id,
+                swap.slippage_percent,
+            )
+            .await
+        {
+            Ok(mut result) => {
+                result["action"] = json!("requote");
+                Ok(result)
+            }
+            Err(e) => {
+                prepared_swaps.record(&previous, swap);
+                Err(e)
+            }
+        }
+    }
+
+    /// Quote a swap once and assemble that quote's path, so the calldata and
+    /// the reported quote details always describe the same route
+    async fn prepare(
+        &self,
+        input_addr: Address,
+        output_addr: Address,
+        amount_u256: U256,
+        chain_id: u64,
+        slippage_percent: f64,
+    ) -> Result<Value> {
         // Get chain from chain_id
         let chain = Self::chain_from_id(chain_id).ok_or_else(|| {
             BamlRtError::InvalidArgument(format!("Unsupported chain ID: {}", chain_id))
@@ -194,22 +319,6 @@ impl OdosTool {
         let slippage = Slippage::percent(slippage_percent)
             .map_err(|e| BamlRtError::InvalidArgument(format!("Invalid slippage: {}", e)))?;
 
-        // Use SwapBuilder to build transaction
-        let tx = self
-            .client
-            .swap()
-            .chain(chain)
-            .from_token(input_addr, amount_u256)
-            .to_token(output_addr)
-            .slippage(slippage)
-            .signer(self.wallet_address)
-            .build_transaction()
-            .await
-            .map_err(|e| {
-                BamlRtError::ToolExecution(format!("Odos transaction build failed: {}", e))
-            })?;
-
-        // Get quote for details
         let quote = self
             .client
             .swap()
@@ -221,6 +330,20 @@ impl OdosTool {
             .quote()
             .await
             .map_err(|e| BamlRtError::ToolExecution(format!("Odos quote failed: {}", e)))?;
+        let stamp = QuoteStamp::new(
+            quote.path_id(),
+            quote.block_number(),
+            chrono::Utc::now().timestamp(),
+        );
+
+        // Assemble exactly the quoted path
+        let tx = self
+            .client
+            .assemble_tx_data(self.wallet_address, self.wallet_address, quote.path_id())
+            .await
+            .map_err(|e| {
+                BamlRtError::ToolExecution(format!("Odos transaction build failed: {}", e))
+            })?;
 
         // Extract transaction fields
         let to_address = tx
@@ -238,6 +361,33 @@ impl OdosTool {
             .map(|v| v.to_string())
             .unwrap_or_else(|| "0".to_string());
 
+        if let (Some(prepared_swaps), Some(to)) = (
+            &self.prepared_swaps,
+            tx.to.and_then(|kind| kind.to().copied()),
+        ) {
+            let native_value = tx.value.unwrap_or_default();
+            prepared_swaps.record(
+                &PreparedTransaction {
+                    to,
+                    data: tx.input.input.clone().unwrap_or_default(),
+                    value: native_value,
+                    gas_limit: tx.gas.unwrap_or(0),
+                    chain_id,
+                },
+                PreparedSwap {
+                    chain_id,
+                    input_token: input_addr,
+                    input_amount: amount_u256,
+                    output_token: output_addr,
+                    slippage_percent,
+                    native_value,
+                    issued_at: stamp.issued_at,
+                    expires_at: stamp.expires_at,
+                    block_number: stamp.block_number,
+                },
+            );
+        }
+
         // Return the prepared transaction - NOT signed
         Ok(json!({
             "action": "prepare_swap",
@@ -250,11 +400,16 @@ impl OdosTool {
                 "chain_id": chain_id,
             },
             "quote_details": {
-                "input_token": input_token,
-                "output_token": output_token,
-                "input_amount": amount,
+                "input_token": input_addr.to_string(),
+                "output_token": output_addr.to_string(),
+                "input_amount": amount_u256.to_string(),
                 "expected_output": quote.out_amount().unwrap_or(&"0".to_string()),
                 "price_impact_percent": quote.price_impact(),
+                "gas_estimate": quote.gas_estimate(),
+                "quote_id": stamp.quote_id,
+                "block_number": stamp.block_number,
+                "issued_at": stamp.issued_at,
+                "expires_at": stamp.expires_at,
             },
             "path_id": quote.path_id(),
             "note": "Transaction prepared but NOT signed. Requires interceptor approval and wallet signature."
@@ -425,7 +580,9 @@ impl BamlTool for OdosTool {
 
     fn description(&self) -> &'static str {
         "Interacts with Odos DEX aggregator for optimal swap routing and real-time pricing. \
-         Actions: 'quote' (read-only swap quote), 'prepare_swap' (prepare transaction), \
+         Actions: 'quote' (read-only swap quote), 'prepare_swap' (prepare transaction from a \
+         single quote, with its quote_id, block_number, issued_at and expires_at), 'requote' (prepare \
+         the swap of a prepare_swap transaction again when its quote went stale), \
          'get_price' (get token USD price via quote), 'get_prices' (batch price lookup). \
          Supports Ethereum, Arbitrum, Optimism, and Base networks."
     }
@@ -440,6 +597,7 @@ impl BamlTool for OdosTool {
         let result = match args.action {
             OdosAction::Quote => self.get_quote(&args).await?,
             OdosAction::PrepareSwap => self.prepare_swap(&args).await?,
+            OdosAction::Requote => self.requote(&args).await?,
             OdosAction::GetPrice => self.get_price(&args).await?,
             OdosAction::GetPrices => self.get_prices(&args).await?,
         };
@@ -472,6 +630,15 @@ mod tests {
         assert!(schema["properties"]["amount"].is_object());
     }
 
+    #[test]
+    fn test_quote_stamp_expiry() {
+        let stamp = QuoteStamp::new("path-1", 21_000_000, 1_700_000_000);
+        assert_eq!(stamp.quote_id, "path-1");
+        assert_eq!(stamp.expires_at, 1_700_000_000 + ODOS_QUOTE_TTL_SECS);
+        assert!(!stamp.is_expired(1_700_000_000 + ODOS_QUOTE_TTL_SECS - 1));
+        assert!(stamp.is_expired(1_700_000_000 + ODOS_QUOTE_TTL_SECS));
+    }
+
     #[test]
     fn test_usdc_for_chain() {
         // Ethereum mainnet
@@ -0,0 +1,428 @@
+//! Pool analytics tool
+//!
+//! Derives per-pool numbers the strategist would otherwise estimate from raw
+//! subgraph JSON: fee APR from daily volume and the fee tier, realized
+//! volatility of the pool price, impermanent loss for a price range, and a
+//! ranking by volume/TVL (capital efficiency).
+//!
+//! Data comes from the Uniswap V3 subgraph through a shared [`TheGraphTool`],
+//! so queries go through the same gateway, cache and freshness checks.
+
+use crate::analytics::volatility::{annualized_daily_volatility, SECONDS_PER_DAY};
+use crate::analytics::{impermanent_loss, DailyActivity, PriceRange};
+use crate::config::Network;
+use crate::graphql::uniswap_v3::{Pool, PoolSnapshot};
+use crate::graphql::BlockPin;
+use crate::tools::freshness::track_freshness;
+use crate::tools::the_graph::TheGraphTool;
+use crate::tools::{AnyJson, DefiBundle};
+use async_trait::async_trait;
+use baml_rt::error::{BamlRtError, Result};
+use baml_rt::tools::BamlTool;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use ts_rs::TS;
+
+/// Default number of top pools analyzed when no ids are given
+const DEFAULT_POOL_LIMIT: u32 = 10;
+
+/// Upper bound on pools analyzed per call (one day-data query each)
+const MAX_POOLS: u32 = 50;
+
+/// Default lookback in days
+const DEFAULT_DAYS: u32 = 30;
+
+/// Upper bound on the lookback (one page of day data)
+const MAX_DAYS: u32 = 365;
+
+/// Day-data queries in flight at once
+const DAY_DATA_CONCURRENCY: usize = 4;
+
+/// Price moves (percent) at which impermanent loss is reported
+const IL_PRICE_MOVES_PERCENT: [f64; 7] = [-50.0, -25.0, -10.0, 10.0, 25.0, 50.0, 100.0];
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, TS)]
+#[ts(export)]
+pub struct PoolAnalyticsInput {
+    pub network: String,
+    /// Pools to analyze (defaults to the top pools by TVL)
+    pub pool_ids: Option<Vec<String>>,
+    /// Number of top pools to analyze when `pool_ids` is not set
+    pub limit: Option<u32>,
+    /// Lookback window in days for fees and volatility
+    pub days: Option<u32>,
+    /// Lower edge of the LP range, percent below the current price (full range if unset)
+    pub range_lower_percent: Option<f64>,
+    /// Upper edge of the LP range, percent above the current price (full range if unset)
+    pub range_upper_percent: Option<f64>,
+}
+
+/// Impermanent loss after a move in the pool price (token0 in token1)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema, TS)]
+#[ts(export)]
+pub struct IlScenario {
+    pub price_move_percent: f64,
+    /// Loss versus holding the deposited tokens (non-positive)
+    pub impermanent_loss_percent: f64,
+}
+
+/// Derived metrics for one pool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema, TS)]
+#[ts(export)]
+pub struct PoolAnalytics {
+    /// Position by volume/TVL, 1 = most volume per dollar of liquidity
+    pub rank: u32,
+    pub pool_id: String,
+    /// Token symbols, e.g. "WETH/USDC"
+    pub pair: String,
+    /// Fee in hundredths of a bip (3000 = 0.3%)
+    pub fee_tier: u32,
+    /// Current TVL
+    pub tvl_usd: f64,
+    pub avg_daily_volume_usd: Option<f64>,
+    /// Average daily volume over average TVL
+    pub volume_tvl_ratio: Option<f64>,
+    /// Annualized fees to LPs over average TVL, without compounding
+    pub fee_apr_percent: Option<f64>,
+    /// Annualized realized volatility of the daily pool price
+    pub volatility_percent: Option<f64>,
+    /// Impermanent loss after a one standard deviation move over the lookback
+    pub il_one_sigma_percent: Option<f64>,
+    /// Daily snapshots the metrics are based on
+    pub days_sampled: u32,
+    pub impermanent_loss: Vec<IlScenario>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema, TS)]
+#[ts(export)]
+pub struct PoolAnalyticsOutput {
+    pub network: String,
+    pub days: u32,
+    /// LP range the IL figures assume, as percent below/above the current price
+    pub range_lower_percent: Option<f64>,
+    pub range_upper_percent: Option<f64>,
+    /// Pools ordered by rank
+    pub pools: Vec<PoolAnalytics>,
+}
+
+/// Tool for derived Uniswap V3 pool metrics
+pub struct PoolAnalyticsTool {
+    graph: TheGraphTool,
+}
+
+impl PoolAnalyticsTool {
+    /// Create a tool that reads through the given subgraph tool
+    pub fn new(graph: TheGraphTool) -> Self {
+        Self { graph }
+    }
+
+    /// Resolve the pools to analyze
+    async fn candidate_pools(
+        &self,
+        network: Network,
+        args: &PoolAnalyticsInput,
+        block: &BlockPin,
+    ) -> Result<Vec<Pool>> {
+        match &args.pool_ids {
+            Some(ids) if !ids.is_empty() => {
+                if ids.len() > MAX_POOLS as usize {
+                    return Err(BamlRtError::InvalidArgument(format!(
+                        "At most {} pool_ids can be analyzed at once",
+                        MAX_POOLS
+                    )));
+                }
+                stream::iter(ids)
+                    .map(|id| async move {
+                        self.graph.pool(network, id, block).await?.ok_or_else(|| {
+                            BamlRtError::InvalidArgument(format!("Pool not found: {}", id))
+                        })
+                    })
+                    .buffered(DAY_DATA_CONCURRENCY)
+                    .try_collect()
+                    .await
+            }
+            _ => {
+                let limit = args.limit.unwrap_or(DEFAULT_POOL_LIMIT).min(MAX_POOLS);
+                self.graph.top_pools(network, limit, block).await
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl BamlTool for PoolAnalyticsTool {
+    type Bundle = DefiBundle;
+    const LOCAL_NAME: &'static str = "pool_analytics";
+    type OpenInput = ();
+    type Input = PoolAnalyticsInput;
+    type Output = AnyJson;
+
+    fn description(&self) -> &'static str {
+        "Computes Uniswap V3 pool metrics from subgraph day data: fee APR from volume and fee tier, \
+         realized volatility, impermanent loss for a price range, and a ranking by volume/TVL. \
+         Analyzes the top pools by TVL or the given pool ids."
+    }
+
+    async fn execute(&self, args: Self::Input) -> Result<Self::Output> {
+        let network = TheGraphTool::parse_network(&args.network)?;
+        let days = args.days.unwrap_or(DEFAULT_DAYS);
+        if days == 0 || days > MAX_DAYS {
+            return Err(BamlRtError::InvalidArgument(format!(
+                "'days' must be between 1 and {}",
+                MAX_DAYS
+            )));
+        }
+        let range = price_range(args.range_lower_percent, args.range_upper_percent)?;
+
+        // Stale subgraph data is rejected by the queries that read it
+        let block = BlockPin::Latest;
+        let (analytics, _) = track_freshness(async {
+            let pools = self.candidate_pools(network, &args, &block).await?;
+
+            let (start, end) = completed_days(chrono::Utc::now().timestamp(), days);
+            let graph = &self.graph;
+            let block = &block;
+            stream::iter(pools)
+                .map(|pool| async move {
+                    let snapshots = graph
+                        .pool_day_snapshots(network, &pool.id, start, end, block)
+                        .await?;
+                    Ok::<_, BamlRtError>(analyze_pool(&pool, &snapshots, range))
+                })
+                .buffered(DAY_DATA_CONCURRENCY)
+                .try_collect::<Vec<PoolAnalytics>>()
+                .await
+        })
+        .await;
+        let mut analytics = analytics?;
+        rank_by_volume_tvl(&mut analytics);
+
+        let output = PoolAnalyticsOutput {
+            network: network.name().to_string(),
+            days,
+            range_lower_percent: args.range_lower_percent,
+            range_upper_percent: args.range_upper_percent,
+            pools: analytics,
+        };
+        Ok(AnyJson::new(json!(output)))
+    }
+}
+
+/// Window covering the last `days` whole UTC days before `now`
+///
+/// Today's snapshot is still accumulating, so counting it would pull the
+/// averages and the last price return towards a partial day.
+fn completed_days(now: i64, days: u32) -> (i64, i64) {
+    let today = now - now.rem_euclid(SECONDS_PER_DAY);
+    (today - i64::from(days) * SECONDS_PER_DAY, today - 1)
+}
+
+/// LP range from the input percentages (full range when neither is set)
+fn price_range(lower_percent: Option<f64>, upper_percent: Option<f64>) -> Result<PriceRange> {
+    let range = match (lower_percent, upper_percent) {
+        (None, None) => return Ok(PriceRange::FULL),
+        (Some(below), Some(above)) => PriceRange::from_percent(below, above),
+        _ => {
+            return Err(BamlRtError::InvalidArgument(
+                "Set both 'range_lower_percent' and 'range_upper_percent', or neither".to_string(),
+            ))
+        }
+    };
+    if !range.contains_entry() {
+        return Err(BamlRtError::InvalidArgument(
+            "Range must include the current price (both percentages > 0)".to_string(),
+        ));
+    }
+    Ok(range)
+}
+
+/// Derive metrics for one pool from its daily snapshots (rank is assigned later)
+fn analyze_pool(pool: &Pool, snapshots: &[PoolSnapshot], range: PriceRange) -> PoolAnalytics {
+    let activity = DailyActivity::from_snapshots(snapshots);
+    let prices: Vec<(i64, f64)> = snapshots
+        .iter()
+        .map(|s| (s.timestamp, s.token0_price))
+        .collect();
+    let volatility = annualized_daily_volatility(&prices);
+
+    // One sigma over the lookback, in whichever direction loses more
+    let il_one_sigma = volatility.and_then(|vol| {
+        let sigma = vol * (snapshots.len() as f64 / 365.0).sqrt();
+        let down = impermanent_loss((-sigma).exp(), range)?;
+        let up = impermanent_loss(sigma.exp(), range)?;
+        Some(down.min(up))
+    });
+
+    PoolAnalytics {
+        rank: 0,
+        pool_id: pool.id.clone(),
+        pair: format!("{}/{}", pool.token0.symbol, pool.token1.symbol),
+        fee_tier: pool.fee_tier,
+        tvl_usd: pool.total_value_locked_usd,
+        avg_daily_volume_usd: activity.map(|a| a.avg_volume_usd),
+        volume_tvl_ratio: activity.and_then(|a| a.volume_tvl_ratio()),
+        fee_apr_percent: activity
+            .and_then(|a| a.fee_apr(pool.fee_tier))
+            .map(|apr| apr * 100.0),
+        volatility_percent: volatility.map(|v| v * 100.0),
+        il_one_sigma_percent: il_one_sigma.map(|il| il * 100.0),
+        days_sampled: snapshots.len() as u32,
+        impermanent_loss: IL_PRICE_MOVES_PERCENT
+            .iter()
+            .filter_map(|&mv| {
+                impermanent_loss(1.0 + mv / 100.0, range).map(|il| IlScenario {
+                    price_move_percent: mv,
+                    impermanent_loss_percent: il * 100.0,
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Sort by volume/TVL desc (pools without data last, then by TVL) and assign ranks
+fn rank_by_volume_tvl(pools: &mut [PoolAnalytics]) {
+    pools.sort_by(|a, b| {
+        let ratio = |p: &PoolAnalytics| p.volume_tvl_ratio.unwrap_or(f64::NEG_INFINITY);
+        ratio(b)
+            .total_cmp(&ratio(a))
+            .then_with(|| b.tvl_usd.total_cmp(&a.tvl_usd))
+            .then_with(|| a.pool_id.cmp(&b.pool_id))
+    });
+    for (i, pool) in pools.iter_mut().enumerate() {
+        pool.rank = i as u32 + 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphql::uniswap_v3::Token;
+
+    fn token(symbol: &str) -> Token {
+        Token {
+            id: format!("0x{}", symbol.to_lowercase()),
+            symbol: symbol.to_string(),
+            name: symbol.to_string(),
+            decimals: 18,
+            derived_eth: 1.0,
+            volume_usd: None,
+            total_value_locked_usd: None,
+        }
+    }
+
+    fn pool(id: &str, fee_tier: u32, tvl: f64) -> Pool {
+        Pool {
+            id: id.to_string(),
+            token0: token("WETH"),
+            token1: token("USDC"),
+            fee_tier,
+            liquidity: 0.0,
+            sqrt_price: 0.0,
+            tick: None,
+            token0_price: 1.0,
+            token1_price: 1.0,
+            volume_usd: 0.0,
+            total_value_locked_usd: tvl,
+            tx_count: 0,
+            volume_24h_usd: None,
+            fees_24h_usd: None,
+            volume_tvl_ratio_24h: None,
+        }
+    }
+
+    fn day(n: i64, volume_usd: f64, tvl_usd: f64, price: f64) -> PoolSnapshot {
+        PoolSnapshot {
+            timestamp: n * SECONDS_PER_DAY,
+            volume_usd,
+            fees_usd: 0.0,
+            tvl_usd,
+            liquidity: 0.0,
+            token0_price: price,
+            token1_price: 1.0 / price,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            tx_count: 0,
+        }
+    }
+
+    #[test]
+    fn test_analyze_pool() {
+        let days = [
+            day(0, 1_000_000.0, 10_000_000.0, 2000.0),
+            day(1, 1_000_000.0, 10_000_000.0, 2020.0),
+            day(2, 1_000_000.0, 10_000_000.0, 2000.0),
+        ];
+        let analytics = analyze_pool(&pool("0xp", 3000, 10_000_000.0), &days, PriceRange::FULL);
+        assert_eq!(analytics.pair, "WETH/USDC");
+        assert_eq!(analytics.days_sampled, 3);
+        assert!((analytics.fee_apr_percent.unwrap() - 10.95).abs() < 1e-9);
+        assert!(analytics.volatility_percent.unwrap() > 0.0);
+        assert!(analytics.il_one_sigma_percent.unwrap() < 0.0);
+        assert_eq!(
+            analytics.impermanent_loss.len(),
+            IL_PRICE_MOVES_PERCENT.len()
+        );
+        let doubled = analytics.impermanent_loss.last().unwrap();
+        assert_eq!(doubled.price_move_percent, 100.0);
+        assert!((doubled.impermanent_loss_percent + 5.719).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_analyze_pool_without_day_data() {
+        let analytics = analyze_pool(&pool("0xp", 500, 1.0), &[], PriceRange::FULL);
+        assert_eq!(analytics.fee_apr_percent, None);
+        assert_eq!(analytics.volatility_percent, None);
+        assert_eq!(analytics.days_sampled, 0);
+    }
+
+    #[test]
+    fn test_rank_by_volume_tvl() {
+        let mut pools = vec![
+            analyze_pool(&pool("0xa", 500, 1.0), &[], PriceRange::FULL),
+            analyze_pool(
+                &pool("0xb", 500, 1.0),
+                &[day(10.0, 100.0, 1.0)],
+                PriceRange::FULL,
+            ),
+            analyze_pool(
+                &pool("0xc", 500, 1.0),
+                &[day(50.0, 100.0, 1.0)],
+                PriceRange::FULL,
+            ),
+        ];
+        rank_by_volume_tvl(&mut pools);
+        let order: Vec<(&str, u32)> = pools.iter().map(|p| (p.pool_id.as_str(), p.rank)).collect();
+        assert_eq!(order, vec![("0xc", 1), ("0xb", 2), ("0xa", 3)]);
+    }
+
+    #[test]
+    fn test_window_excludes_the_day_in_progress() {
+        let now = 20 * SECONDS_PER_DAY + 3_600;
+        let (start, end) = completed_days(now, 7);
+        let included: Vec<i64> = (0..30)
+            .map(|n| n * SECONDS_PER_DAY)
+            .filter(|date| (start..=end).contains(date))
+            .collect();
+        assert_eq!(included.len(), 7);
+        assert_eq!(included.first(), Some(&(13 * SECONDS_PER_DAY)));
+        assert_eq!(included.last(), Some(&(19 * SECONDS_PER_DAY)));
+
+        // Right at midnight the day that just ended is complete
+        let (_, end) = completed_days(20 * SECONDS_PER_DAY, 7);
+        assert_eq!(end, 20 * SECONDS_PER_DAY - 1);
+    }
+
+    #[test]
+    fn test_price_range_validation() {
+        assert_eq!(price_range(None, None).unwrap(), PriceRange::FULL);
+        assert!(price_range(Some(10.0), None).is_err());
+        assert!(price_range(Some(0.0), Some(10.0)).is_err());
+        let range = price_range(Some(10.0), Some(25.0)).unwrap();
+        assert!((range.lower - 0.9).abs() < 1e-12);
+        assert!((range.upper - 1.25).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,84 @@
+//! Subgraph schema tool
+//!
+//! Lets the agent discover what a subgraph can answer before writing a
+//! `custom` query for `defi/query_subgraph`. Returns entity types with their
+//! fields and the root query fields, introspected from the configured
+//! endpoint, plus the query budget the custom query must fit in.
+
+use crate::tools::the_graph::TheGraphTool;
+use crate::tools::{AnyJson, DefiBundle};
+use async_trait::async_trait;
+use baml_rt::error::Result;
+use baml_rt::tools::BamlTool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use ts_rs::TS;
+
+/// Graph Node argument conventions, shared by every subgraph
+const QUERY_CONVENTIONS: &str = "Collection fields (e.g. pools) accept first, skip, \
+    orderBy (a field name), orderDirection (asc|desc), where and block ({number} or {hash}). \
+    where takes a field for equality plus suffixed variants (_gt, _gte, _lt, _lte, _in, \
+    _not, _contains) and nested and/or lists. Singular fields (e.g. pool) take id. \
+    BigInt and BigDecimal values are strings.";
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, TS)]
+#[ts(export)]
+pub struct SubgraphSchemaInput {
+    pub protocol: String,
+    pub network: String,
+    /// Only these entity types and the queries returning them (all if unset)
+    pub entities: Option<Vec<String>>,
+}
+
+/// Tool for introspecting a configured subgraph
+pub struct SubgraphSchemaTool {
+    graph: TheGraphTool,
+}
+
+impl SubgraphSchemaTool {
+    /// Create a tool that introspects through the given subgraph tool
+    pub fn new(graph: TheGraphTool) -> Self {
+        Self { graph }
+    }
+}
+
+#[async_trait]
+impl BamlTool for SubgraphSchemaTool {
+    type Bundle = DefiBundle;
+    const LOCAL_NAME: &'static str = "subgraph_schema";
+    type OpenInput = ();
+    type Input = SubgraphSchemaInput;
+    type Output = AnyJson;
+
+    fn description(&self) -> &'static str {
+        "Describes a subgraph's entities, their fields and the root query fields, for writing \
+         custom queries with defi/query_subgraph (query_type custom). Pass entities to limit the \
+         output to the types you need."
+    }
+
+    async fn execute(&self, args: Self::Input) -> Result<Self::Output> {
+        let network = TheGraphTool::parse_network(&args.network)?;
+        let protocol = TheGraphTool::parse_protocol(&args.protocol)?;
+
+        let schema = self.graph.subgraph_schema(network, protocol).await?;
+        let schema = match args.entities.as_deref() {
+            Some(names) if !names.is_empty() => schema.only_entities(names),
+            _ => (*schema).clone(),
+        };
+        let budget = self.graph.query_budget();
+
+        Ok(AnyJson::new(json!({
+            "protocol": protocol.name(),
+            "network": network.name(),
+            "entity_count": schema.entities.len(),
+            "queries": schema.queries,
+            "entities": schema.entities,
+            "conventions": QUERY_CONVENTIONS,
+            "budget": {
+                "max_depth": budget.max_depth,
+                "max_first": budget.max_first
+            }
+        })))
+    }
+}
@@ -10,23 +10,103 @@
 //! - **Caching**: Results cached with configurable TTL (default 60s)
 //! - **Latency tracking**: Query performance metrics
 //! - **Future x402 support**: Same interface for advanced routing
-
-use crate::config::{Network, Protocol, SubgraphEndpoints, SubgraphIds};
+//!
+//! ## Typed Queries
+//!
+//! Uniswap V3 queries are compiled against the vendored subgraph schema in
+//! `crate::graphql::uniswap_v3`; responses are decoded into numeric domain
+//! types there. Aave and `_meta` queries are still raw GraphQL strings.
+
+use crate::analytics::{tick_spacing, DepthPoint, LiquidityCurve, TickLiquidity};
+use crate::config::{
+    GraphSettings, IndexerRoutingSettings, Network, Protocol, RpcConfig, SubgraphEndpoints,
+    SubgraphIds, DEFAULT_MAX_GRAPH_RESULTS, DEFAULT_PLAN_CONCURRENCY,
+    DEFAULT_PLAN_LEG_TIMEOUT_SECS, GRAPH_API_KEY_ENV,
+};
+use crate::graphql::introspection::{SubgraphSchema, INTROSPECTION_QUERY};
+use crate::graphql::uniswap_v3::{
+    self, decode_all, EventScope, EventWindow, HourlyActivity, LiquidityEvent, Pool, PoolSnapshot,
+    Swap, Tick, TimedEvent, Token, TokenSnapshot,
+};
+use crate::graphql::validation::{validate_query, QueryBudget};
+use crate::graphql::{BlockPin, DecodeError};
+use crate::tokens;
+use crate::tools::failover::{note_served, track_served, EndpointHealth};
+use crate::tools::flows::{
+    liquidity_flow_metrics, swap_flow_metrics, FlowBase, DEFAULT_LARGE_TRADE_USD,
+};
+use crate::tools::freshness::{
+    checking_freshness, fetch_chain_head, known_chain_head, note_chain_head, note_freshness,
+    take_meta, track_freshness, with_meta_selection, ChainHead, FreshnessError, FreshnessPolicy,
+    SubgraphFreshness,
+};
+use crate::tools::graph_gateway::cache::{open_cache, CacheStats};
 use crate::tools::graph_gateway::{
-    BasicGraphGateway, GatewayError, GraphGateway, QueryRoutingHints,
+    BasicGraphGateway, GatewayError, GatewayStatus, GraphGateway, PaymentLimits, QueryRoutingHints,
+    UsageReport, X402GraphGateway,
 };
 use crate::tools::{AnyJson, DefiBundle};
+use crate::wallet::SecureWallet;
+use alloy::primitives::Address;
 use async_trait::async_trait;
 use baml_rt::error::{BamlRtError, Result};
 use baml_rt::tools::BamlTool;
-use reqwest::Client;
+use futures::stream::{self, StreamExt};
+use graphql_client::GraphQLQuery;
+use reqwest::{Client, StatusCode};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::collections::HashSet;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
 use ts_rs::TS;
 
+/// Seconds per year used by Aave's rate math
+const SECONDS_PER_YEAR: f64 = 31_536_000.0;
+
+/// Aave rates are expressed in ray units (1e27)
+const RAY: f64 = 1e27;
+
+/// Aave V3 oracle prices are quoted in USD with 8 decimals
+const AAVE_PRICE_DECIMALS: i32 = 8;
+
+/// Aave liquidation thresholds and LTVs are in basis points
+const BPS: f64 = 10_000.0;
+
+/// Seconds in the trailing window used for "24h" pool metrics
+const TRAILING_WINDOW_SECS: i64 = 24 * 60 * 60;
+
+/// Default lookback for day-granularity time series (30 days)
+const DEFAULT_DAY_LOOKBACK_SECS: i64 = 30 * 24 * 60 * 60;
+
+/// Default lookback for hour-granularity time series (7 days)
+const DEFAULT_HOUR_LOOKBACK_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// Default tick spacings fetched either side of the current tick
+const DEFAULT_TICK_SPACINGS: u32 = 100;
+
+/// Largest tick window that fits in one page of initialized ticks
+const MAX_TICK_SPACINGS: u32 = 499;
+
+/// Price moves reported in a pool's depth curve (percent)
+const DEPTH_MOVES_PERCENT: [f64; 4] = [0.5, 1.0, 2.0, 5.0];
+
+/// Default number of events returned by swap/mint/burn queries
+const DEFAULT_EVENT_LIMIT: u32 = 1000;
+
+/// Maximum `first` accepted by Graph Node
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// Pools fetched per pool still wanted when trailing activity filters apply
+const FILTERED_POOL_OVERFETCH: usize = 2;
+
+/// How long an introspected subgraph schema is reused
+const SCHEMA_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
 /// Query filters for intelligent data fetching (from InferQueryPlan)
 #[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema, TS)]
 #[ts(export)]
@@ -58,12 +138,36 @@ pub struct QueryPlan {
 
 /// Tool for querying The Graph subgraphs
 ///
-/// Optionally uses a `GraphGateway` for caching and routing.
+/// Optionally uses a `GraphGateway` for caching and routing. Clones share the
+/// gateway (and its cache).
+#[derive(Clone)]
 pub struct TheGraphTool {
     client: Client,
     endpoints: SubgraphEndpoints,
+    /// Gateway API key for direct queries to subgraph/deployment ID endpoints
+    api_key: Option<String>,
     /// Optional gateway for caching and x402 routing
     gateway: Option<Arc<dyn GraphGateway>>,
+    /// Hard upper bound on entities returned by a paginated query
+    max_results: usize,
+    /// Maximum query plan legs in flight at once
+    plan_concurrency: usize,
+    /// Timeout for a single query plan leg
+    plan_leg_timeout: Duration,
+    /// RPC endpoints for chain head lookups (wall clock is used without one)
+    rpc_config: Option<RpcConfig>,
+    /// Limits on subgraph lag behind the chain head
+    freshness: FreshnessPolicy,
+    /// Limits on agent-authored (custom) queries
+    query_budget: QueryBudget,
+    /// Serve expired gateway results while they refresh in the background
+    stale_while_revalidate: bool,
+    /// Preferred indexers and latency budget for gateway queries
+    indexer_routing: IndexerRoutingSettings,
+    /// Health of failover endpoints, deciding which is tried first
+    endpoint_health: Arc<EndpointHealth>,
+    /// Introspected schemas by network/protocol, with the time they were fetched
+    schemas: Arc<RwLock<HashMap<(Network, Protocol), (Instant, Arc<SubgraphSchema>)>>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, TS)]
@@ -75,6 +179,28 @@ pub enum GraphQueryType {
     TokenPrice,
     FilteredPools,
     QueryPlan,
+    /// Aave V3 reserves (supply/borrow APY, utilization, liquidity)
+    Reserves,
+    /// Aave V3 positions and health factor for `user_address`
+    UserPositions,
+    /// Daily pool snapshots for `pool_id` between `start_time` and `end_time`
+    PoolDayData,
+    /// Hourly pool snapshots for `pool_id` between `start_time` and `end_time`
+    PoolHourData,
+    /// Daily token snapshots for `token_address` between `start_time` and `end_time`
+    TokenDayData,
+    /// Swaps on `pool_id` or `token_address` between `start_time` and `end_time`, with flow metrics
+    Swaps,
+    /// Liquidity additions on `pool_id` or `token_address`, with flow metrics
+    Mints,
+    /// Liquidity removals on `pool_id` or `token_address`, with flow metrics
+    Burns,
+    /// Initialized ticks around the current tick of `pool_id`, with a depth curve
+    /// and an optional swap estimate for `amount_in` of `token_address`
+    Ticks,
+    /// Agent-authored `query` (with `variables`), checked against the subgraph
+    /// schema from `defi/subgraph_schema` and the query budget before it runs
+    Custom,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, TS)]
@@ -85,6 +211,27 @@ pub struct GraphQueryParams {
     pub token_address: Option<String>,
     pub filters: Option<QueryFilters>,
     pub query_plan: Option<QueryPlan>,
+    pub user_address: Option<String>,
+    /// Start of the time range (unix seconds, inclusive)
+    pub start_time: Option<i64>,
+    /// End of the time range (unix seconds, inclusive; defaults to now)
+    pub end_time: Option<i64>,
+    /// Pin all queries to this block number (time-travel query)
+    pub block_number: Option<u64>,
+    /// Pin all queries to this block hash (mutually exclusive with `block_number`)
+    pub block_hash: Option<String>,
+    /// For query plans: pin every query on a network to one block and report it
+    pub snapshot: Option<bool>,
+    /// For swap/mint/burn queries: USD size at which an event counts as large
+    pub large_trade_usd: Option<f64>,
+    /// For tick queries: tick spacings fetched either side of the current tick
+    pub tick_spacings: Option<u32>,
+    /// For tick queries: input size to simulate, in whole tokens of `token_address`
+    pub amount_in: Option<f64>,
+    /// For custom queries: GraphQL query text (read-only, single operation)
+    pub query: Option<String>,
+    /// For custom queries: variables for `query`
+    pub variables: Option<AnyJson>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, TS)]
@@ -98,21 +245,17 @@ pub struct GraphQueryInput {
 
 impl TheGraphTool {
     /// Create a new TheGraphTool with default endpoints (no caching)
+    ///
+    /// Gateway endpoints are queried directly with `GRAPH_API_KEY` if it is set.
     pub fn new() -> Self {
-        Self {
-            client: Client::new(),
-            endpoints: SubgraphEndpoints::default(),
-            gateway: None,
-        }
+        Self::with_endpoints(SubgraphEndpoints::default())
     }
 
     /// Create with custom endpoints (no caching)
+    ///
+    /// Gateway endpoints are queried directly with `GRAPH_API_KEY` if it is set.
     pub fn with_endpoints(endpoints: SubgraphEndpoints) -> Self {
-        Self {
-            client: Client::new(),
-            endpoints,
-            gateway: None,
-        }
+        Self::from_parts(endpoints, std::env::var(GRAPH_API_KEY_ENV).ok(), None)
     }
 
     /// Create with gateway for caching and x402 routing
@@ -125,26 +268,169 @@ impl TheGraphTool {
     /// # Arguments
     /// * `api_key` - The Graph API key for authentication
     pub fn with_gateway(api_key: String) -> Self {
-        let endpoints = SubgraphEndpoints::with_api_key(&api_key);
-        Self {
-            client: Client::new(),
-            endpoints,
-            gateway: Some(Arc::new(BasicGraphGateway::new(api_key))),
-        }
+        let gateway: Arc<dyn GraphGateway> = Arc::new(BasicGraphGateway::new(api_key.clone()));
+        Self::from_parts(SubgraphEndpoints::default(), Some(api_key), Some(gateway))
     }
 
     /// Create with custom endpoints and gateway
+    ///
+    /// Gateway endpoints go through `gateway`; URL endpoints are queried directly.
     pub fn with_endpoints_and_gateway(
         endpoints: SubgraphEndpoints,
         gateway: Arc<dyn GraphGateway>,
+    ) -> Self {
+        Self::from_parts(endpoints, None, Some(gateway))
+    }
+
+    /// Create for configured endpoints, with gateway caching when `GRAPH_API_KEY` is set
+    pub fn from_env(endpoints: SubgraphEndpoints) -> Self {
+        Self::from_env_with_settings(endpoints, &GraphSettings::default())
+    }
+
+    /// Like [`TheGraphTool::from_env`], with the gateway's cache, retries and
+    /// circuit breaker configured from `settings`
+    pub fn from_env_with_settings(endpoints: SubgraphEndpoints, settings: &GraphSettings) -> Self {
+        match std::env::var(GRAPH_API_KEY_ENV) {
+            Ok(api_key) => {
+                let cache = &settings.cache;
+                let gateway = BasicGraphGateway::with_cache_ttl(
+                    api_key.clone(),
+                    Duration::from_secs(cache.ttl_secs),
+                )
+                .with_cache(open_cache(cache))
+                .with_retry(settings.retry.clone())
+                .with_circuit_breaker(settings.circuit_breaker.clone())
+                .with_network_subgraph(settings.indexers.network_subgraph_id.clone())
+                .with_usage(settings.usage.clone())
+                .with_gateway_url(endpoints.gateway_url.clone());
+                Self::with_endpoints_and_gateway(endpoints, Arc::new(gateway))
+                    .with_api_key(api_key)
+                    .with_stale_while_revalidate(cache.stale_while_revalidate)
+                    .with_indexer_routing(settings.indexers.clone())
+            }
+            Err(_) => Self::from_parts(endpoints, None, None),
+        }
+    }
+
+    /// Like [`TheGraphTool::from_env_with_settings`], but paying for gateway
+    /// queries from `wallet` with x402 (`settings.x402`) instead of an API key
+    pub fn with_x402_payments(
+        endpoints: SubgraphEndpoints,
+        settings: &GraphSettings,
+        wallet: Arc<SecureWallet>,
+    ) -> Self {
+        let x402 = &settings.x402;
+        let gateway_url = x402
+            .gateway_url
+            .clone()
+            .unwrap_or_else(|| endpoints.gateway_url.clone());
+        let limits =
+            PaymentLimits::from_usd(x402.max_payment_per_query_usd, x402.max_daily_payment_usd);
+        let pay_to = x402
+            .pay_to
+            .iter()
+            .filter_map(|address| match address.parse() {
+                Ok(address) => Some(address),
+                Err(e) => {
+                    tracing::warn!(address = %address, error = %e, "Ignoring invalid x402 payee");
+                    None
+                }
+            })
+            .collect();
+        let gateway = X402GraphGateway::new(gateway_url, wallet, limits, pay_to)
+            .with_cache_ttl(Duration::from_secs(settings.cache.ttl_secs))
+            .with_cache(open_cache(&settings.cache))
+            .with_retry(settings.retry.clone())
+            .with_circuit_breaker(settings.circuit_breaker.clone())
+            .with_network_subgraph(settings.indexers.network_subgraph_id.clone())
+            .with_usage(settings.usage.clone());
+        Self::with_endpoints_and_gateway(endpoints, Arc::new(gateway))
+            .with_stale_while_revalidate(settings.cache.stale_while_revalidate)
+            .with_indexer_routing(settings.indexers.clone())
+    }
+
+    fn from_parts(
+        endpoints: SubgraphEndpoints,
+        api_key: Option<String>,
+        gateway: Option<Arc<dyn GraphGateway>>,
     ) -> Self {
         Self {
             client: Client::new(),
             endpoints,
-            gateway: Some(gateway),
+            api_key,
+            gateway,
+            max_results: DEFAULT_MAX_GRAPH_RESULTS,
+            plan_concurrency: DEFAULT_PLAN_CONCURRENCY,
+            plan_leg_timeout: Duration::from_secs(DEFAULT_PLAN_LEG_TIMEOUT_SECS),
+            rpc_config: None,
+            freshness: FreshnessPolicy::default(),
+            query_budget: QueryBudget::default(),
+            stale_while_revalidate: false,
+            indexer_routing: IndexerRoutingSettings::default(),
+            endpoint_health: Arc::new(EndpointHealth::default()),
+            schemas: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
+    /// Set the gateway API key used for direct queries to gateway endpoints
+    pub fn with_api_key(mut self, api_key: String) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    /// Set the hard upper bound on results for paginated queries
+    pub fn with_max_results(mut self, max_results: usize) -> Self {
+        self.max_results = max_results.max(1);
+        self
+    }
+
+    /// Set how many query plan legs (network/protocol pairs) run concurrently
+    pub fn with_plan_concurrency(mut self, concurrency: usize) -> Self {
+        self.plan_concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Set the timeout for a single query plan leg
+    pub fn with_plan_leg_timeout(mut self, timeout: Duration) -> Self {
+        self.plan_leg_timeout = timeout;
+        self
+    }
+
+    /// Use RPC endpoints to measure subgraph lag against the chain head
+    pub fn with_rpc_config(mut self, rpc_config: RpcConfig) -> Self {
+        self.rpc_config = Some(rpc_config);
+        self
+    }
+
+    /// Set the limits on subgraph lag (stale results become errors)
+    pub fn with_freshness_policy(mut self, policy: FreshnessPolicy) -> Self {
+        self.freshness = policy;
+        self
+    }
+
+    /// Set the depth and `first` limits for custom queries
+    pub fn with_query_budget(mut self, budget: QueryBudget) -> Self {
+        self.query_budget = budget;
+        self
+    }
+
+    /// Limits applied to custom queries
+    pub fn query_budget(&self) -> QueryBudget {
+        self.query_budget
+    }
+
+    /// Serve expired gateway results immediately while they refresh in the background
+    pub fn with_stale_while_revalidate(mut self, enabled: bool) -> Self {
+        self.stale_while_revalidate = enabled;
+        self
+    }
+
+    /// Route gateway queries to preferred indexers within a latency budget
+    pub fn with_indexer_routing(mut self, routing: IndexerRoutingSettings) -> Self {
+        self.indexer_routing = routing;
+        self
+    }
+
     /// Get the subgraph ID for a network/protocol combination
     #[allow(dead_code)] // Used in tests, may be useful for future direct lookups
     fn get_subgraph_id(network: Network, protocol: Protocol) -> Option<&'static str> {
@@ -153,35 +439,256 @@ impl TheGraphTool {
             (Network::Arbitrum, Protocol::UniswapV3) => Some(SubgraphIds::UNISWAP_V3_ARBITRUM),
             (Network::Optimism, Protocol::UniswapV3) => Some(SubgraphIds::UNISWAP_V3_OPTIMISM),
             (Network::Base, Protocol::UniswapV3) => Some(SubgraphIds::UNISWAP_V3_BASE),
-            _ => None,
+            (Network::Ethereum, Protocol::AaveV3) => Some(SubgraphIds::AAVE_V3_ETHEREUM),
+            (Network::Arbitrum, Protocol::AaveV3) => Some(SubgraphIds::AAVE_V3_ARBITRUM),
+            (Network::Optimism, Protocol::AaveV3) => Some(SubgraphIds::AAVE_V3_OPTIMISM),
+            (Network::Base, Protocol::AaveV3) => Some(SubgraphIds::AAVE_V3_BASE),
         }
     }
 
+    /// Resolve the configured endpoints for a network/protocol combination,
+    /// in failover order
+    fn endpoint(&self, network: Network, protocol: Protocol) -> Result<SubgraphRoute> {
+        let mut configured = self.endpoints.all(network, protocol).peekable();
+        if configured.peek().is_none() {
+            return Err(BamlRtError::InvalidArgument(format!(
+                "No {} endpoint configured for {:?}",
+                protocol.name(),
+                network
+            )));
+        }
+        // Gateway endpoints are unusable without an API key or a gateway
+        let targets: Vec<RouteTarget> = configured
+            .filter_map(|configured| {
+                let url =
+                    configured.query_url(&self.endpoints.gateway_url, self.api_key.as_deref());
+                if url.is_none() && self.gateway.is_none() {
+                    return None;
+                }
+                // A gateway URL configured as a plain `url` still goes through the gateway cache
+                let gateway_id = configured.gateway_id().map(str::to_string).or_else(|| {
+                    url.as_deref()
+                        .filter(|_| configured.headers.is_empty())
+                        .and_then(Self::extract_subgraph_id)
+                        .map(str::to_string)
+                });
+                Some(RouteTarget {
+                    label: configured.label(),
+                    url,
+                    gateway_id,
+                    headers: configured
+                        .headers
+                        .iter()
+                        .map(|(name, value)| (name.clone(), value.clone()))
+                        .collect(),
+                })
+            })
+            .collect();
+        if targets.is_empty() {
+            return Err(BamlRtError::InvalidArgument(format!(
+                "{} on {:?} is served by The Graph gateway; set {} to query it",
+                protocol.name(),
+                network,
+                GRAPH_API_KEY_ENV
+            )));
+        }
+        Ok(SubgraphRoute {
+            chain_id: network.chain_id(),
+            targets,
+        })
+    }
+
     /// Execute a raw GraphQL query against a subgraph
     ///
-    /// If a gateway is configured, routes the query through the gateway for caching.
-    /// Otherwise falls back to direct HTTP requests.
-    async fn query_subgraph(&self, endpoint: &str, query: &str, variables: Value) -> Result<Value> {
-        // Try to use gateway if available
+    /// Tries the route's endpoints in failover order until one answers.
+    /// GraphQL errors are returned straight away: the query, not the
+    /// endpoint, is at fault. Inside [`track_freshness`] the query also
+    /// selects `_meta`, and an endpoint whose answer fails the freshness
+    /// policy is demoted and the next one tried.
+    async fn query_subgraph(
+        &self,
+        route: &SubgraphRoute,
+        query: &str,
+        variables: Value,
+    ) -> Result<Value> {
+        let checked = checking_freshness()
+            .then(|| with_meta_selection(query))
+            .flatten();
+        let head = match (&checked, self.freshness.max_lag_blocks) {
+            (Some(_), Some(_)) => self.chain_head(route.chain_id).await,
+            _ => None,
+        };
+        let query = checked.as_deref().unwrap_or(query);
+
+        let mut lagging = None;
+        let mut last_error = None;
+        for index in self.endpoint_health.order(&route.labels()) {
+            let target = &route.targets[index];
+            match self.query_target(target, query, variables.clone()).await {
+                Ok(mut data) => {
+                    if checked.is_some() {
+                        if let Err(e) = self.check_meta(&mut data, head) {
+                            if route.targets.len() > 1 {
+                                tracing::warn!(
+                                    endpoint = %target.label,
+                                    error = %e,
+                                    "Subgraph endpoint is lagging, trying the next one"
+                                );
+                            }
+                            self.endpoint_health.record_lag(&target.label);
+                            lagging.get_or_insert(QueryError::new(QueryErrorKind::Stale, e));
+                            continue;
+                        }
+                    }
+                    self.endpoint_health.record_success(&target.label);
+                    note_served(&target.label);
+                    note_query_error(None);
+                    return Ok(data);
+                }
+                Err(e) if e.kind == QueryErrorKind::Graphql => return Err(e.noted()),
+                // The endpoint hasn't indexed what the query asks for yet
+                Err(e) if e.kind == QueryErrorKind::Stale => {
+                    if route.targets.len() > 1 {
+                        tracing::warn!(
+                            endpoint = %target.label,
+                            error = %e,
+                            "Subgraph endpoint is behind, trying the next one"
+                        );
+                    }
+                    self.endpoint_health.record_lag(&target.label);
+                    lagging.get_or_insert(e);
+                }
+                Err(e) => {
+                    // Budget, payment and circuit errors are the agent's limits,
+                    // not the endpoint's health
+                    if !matches!(
+                        e.kind,
+                        QueryErrorKind::Payment | QueryErrorKind::CircuitOpen
+                    ) {
+                        self.endpoint_health.record_failure(&target.label);
+                    }
+                    if route.targets.len() > 1 {
+                        tracing::warn!(
+                            endpoint = %target.label,
+                            error = %e,
+                            "Subgraph endpoint failed, trying the next one"
+                        );
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+        // Every endpoint lagged or failed; staleness is the more useful error
+        let error = match (lagging, last_error) {
+            (Some(e), _) | (None, Some(e)) => e,
+            (None, None) => QueryError::new(
+                QueryErrorKind::InvalidArgument,
+                "No subgraph endpoint to query",
+            ),
+        };
+        Err(error.noted())
+    }
+
+    /// Take the `_meta` read alongside a query's data and check it against
+    /// the freshness policy
+    fn check_meta(
+        &self,
+        data: &mut Value,
+        head: Option<ChainHead>,
+    ) -> std::result::Result<(), FreshnessError> {
+        let meta = take_meta(data)
+            .ok_or_else(|| FreshnessError::InvalidMeta("missing _meta".to_string()))?;
+        let freshness = SubgraphFreshness::from_meta(&meta, head, chrono::Utc::now().timestamp())?;
+        freshness.check(&self.freshness)?;
+        note_freshness(&freshness);
+        Ok(())
+    }
+
+    /// Chain head for block-distance checks, looked up once per tool call
+    ///
+    /// None without an RPC URL for the chain or if the lookup fails; block
+    /// distance then goes unchecked.
+    async fn chain_head(&self, chain_id: u64) -> Option<ChainHead> {
+        if let Some(head) = known_chain_head(chain_id) {
+            return head;
+        }
+        let rpc_url = self.rpc_config.as_ref().and_then(|c| c.get(chain_id));
+        let head = match rpc_url {
+            Some(url) => fetch_chain_head(url)
+                .await
+                .map_err(|e| {
+                    tracing::warn!(
+                        chain_id,
+                        error = %e,
+                        "Chain head lookup failed, block distance is not checked"
+                    );
+                })
+                .ok(),
+            None => None,
+        };
+        note_chain_head(chain_id, head);
+        head
+    }
+
+    /// Execute a raw GraphQL query against one endpoint
+    ///
+    /// If a gateway is configured and serves the endpoint, routes the query
+    /// through the gateway for caching. Otherwise falls back to direct HTTP requests.
+    async fn query_target(
+        &self,
+        endpoint: &RouteTarget,
+        query: &str,
+        variables: Value,
+    ) -> std::result::Result<Value, QueryError> {
         if let Some(ref gateway) = self.gateway {
-            // Extract subgraph ID from endpoint URL
-            // Format: https://gateway.thegraph.com/api/{api_key}/subgraphs/id/{subgraph_id}
-            if let Some(subgraph_id) = Self::extract_subgraph_id(endpoint) {
+            if let Some(subgraph_id) = endpoint.gateway_id.as_deref() {
                 return self
                     .query_via_gateway(gateway, subgraph_id, query, variables)
                     .await;
             }
-            // If we can't extract subgraph ID, fall through to direct query
             tracing::debug!(
-                endpoint = endpoint,
-                "Could not extract subgraph ID from endpoint, falling back to direct query"
+                endpoint = endpoint.url.as_deref(),
+                "Endpoint is not served by the gateway, falling back to direct query"
             );
         }
 
-        // Direct query (no gateway or couldn't extract subgraph ID)
         self.query_direct(endpoint, query, variables).await
     }
 
+    /// Run a compile-time checked query and decode its typed response
+    async fn query_typed<Q: GraphQLQuery>(
+        &self,
+        endpoint: &SubgraphRoute,
+        variables: Q::Variables,
+    ) -> Result<Q::ResponseData> {
+        self.query_body::<Q>(endpoint, Q::build_query(variables))
+            .await
+    }
+
+    /// Send a built query body and decode the response as `Q`'s
+    ///
+    /// The body may carry another checked document than `Q`'s own, as long as
+    /// it declares the same variables and selection.
+    async fn query_body<Q: GraphQLQuery>(
+        &self,
+        endpoint: &SubgraphRoute,
+        body: graphql_client::QueryBody<Q::Variables>,
+    ) -> Result<Q::ResponseData> {
+        let variables = serde_json::to_value(&body.variables).map_err(|e| {
+            BamlRtError::ToolExecution(format!(
+                "Failed to encode {} variables: {}",
+                body.operation_name, e
+            ))
+        })?;
+        let data = self.query_subgraph(endpoint, body.query, variables).await?;
+        serde_json::from_value(data).map_err(|e| {
+            BamlRtError::ToolExecution(format!(
+                "Failed to decode {} response: {}",
+                body.operation_name, e
+            ))
+        })
+    }
+
     /// Extract subgraph ID from a Graph API endpoint URL
     fn extract_subgraph_id(endpoint: &str) -> Option<&str> {
         // Format: https://gateway.thegraph.com/api/{api_key}/subgraphs/id/{subgraph_id}
@@ -202,13 +709,25 @@ impl TheGraphTool {
         subgraph_id: &str,
         query: &str,
         variables: Value,
-    ) -> Result<Value> {
+    ) -> std::result::Result<Value, QueryError> {
+        let routing = &self.indexer_routing;
+        let hints = QueryRoutingHints {
+            preferred_indexers: (!routing.preferred.is_empty()).then(|| routing.preferred.clone()),
+            max_latency_ms: routing.max_latency_ms,
+            stale_while_revalidate: self.stale_while_revalidate,
+            ..QueryRoutingHints::default()
+        };
         let result = gateway
-            .query_with_routing(subgraph_id, query, variables, QueryRoutingHints::default())
+            .query_with_routing(subgraph_id, query, variables, hints)
             .await
-            .map_err(Self::gateway_error_to_baml_error)?;
+            .map_err(QueryError::from)?;
 
-        if result.cached {
+        if result.stale {
+            tracing::debug!(
+                subgraph_id = subgraph_id,
+                "Served stale result from gateway cache, refreshing in background"
+            );
+        } else if result.cached {
             tracing::debug!(
                 subgraph_id = subgraph_id,
                 latency_ms = result.latency_ms,
@@ -218,6 +737,7 @@ impl TheGraphTool {
             tracing::debug!(
                 subgraph_id = subgraph_id,
                 latency_ms = result.latency_ms,
+                indexer = result.indexer.as_deref(),
                 "Fresh query via gateway"
             );
         }
@@ -226,53 +746,76 @@ impl TheGraphTool {
     }
 
     /// Direct HTTP query (no caching)
-    async fn query_direct(&self, endpoint: &str, query: &str, variables: Value) -> Result<Value> {
-        let response = self
-            .client
-            .post(endpoint)
+    async fn query_direct(
+        &self,
+        endpoint: &RouteTarget,
+        query: &str,
+        variables: Value,
+    ) -> std::result::Result<Value, QueryError> {
+        let url = endpoint.url.as_deref().ok_or_else(|| {
+            QueryError::new(
+                QueryErrorKind::InvalidArgument,
+                format!("Endpoint requires {} for direct queries", GRAPH_API_KEY_ENV),
+            )
+        })?;
+        let mut request = self.client.post(url);
+        for (name, value) in &endpoint.headers {
+            request = request.header(name, value);
+        }
+        let response = request
             .json(&json!({
                 "query": query,
                 "variables": variables
             }))
             .send()
             .await
-            .map_err(|e| BamlRtError::ToolExecution(format!("GraphQL request failed: {}", e)))?;
+            .map_err(|e| {
+                QueryError::new(
+                    QueryErrorKind::Transport,
+                    format!("GraphQL request failed: {}", e),
+                )
+            })?;
 
-        let result: GraphQLResponse = response.json().await.map_err(|e| {
-            BamlRtError::ToolExecution(format!("Failed to parse GraphQL response: {}", e))
+        // Error statuses are reported as such unless they carry GraphQL errors
+        let status = response.status();
+        let body = response.text().await.map_err(|e| {
+            QueryError::new(
+                QueryErrorKind::Transport,
+                format!("GraphQL response failed: {}", e),
+            )
+        })?;
+        let result = serde_json::from_str::<GraphQLResponse>(&body);
+        if !status.is_success() && !result.as_ref().is_ok_and(|r| r.errors.is_some()) {
+            let kind = if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+                QueryErrorKind::Transport
+            } else {
+                QueryErrorKind::Rejected
+            };
+            let body: String = body.chars().take(200).collect();
+            return Err(QueryError::new(
+                kind,
+                format!("Subgraph endpoint answered HTTP {}: {}", status, body),
+            ));
+        }
+        let result = result.map_err(|e| {
+            QueryError::new(
+                QueryErrorKind::BadResponse,
+                format!("Failed to parse GraphQL response: {}", e),
+            )
         })?;
 
         if let Some(errors) = result.errors {
-            return Err(BamlRtError::ToolExecution(format!(
-                "GraphQL errors: {:?}",
-                errors
-            )));
+            let messages: Vec<String> = errors.into_iter().map(|e| e.message).collect();
+            let messages = messages.join(", ");
+            return Err(QueryError::new(
+                graphql_error_kind(&messages),
+                format!("GraphQL errors: {}", messages),
+            ));
         }
 
-        result
-            .data
-            .ok_or_else(|| BamlRtError::ToolExecution("No data in GraphQL response".to_string()))
-    }
-
-    /// Convert gateway error to BAML error
-    fn gateway_error_to_baml_error(e: GatewayError) -> BamlRtError {
-        match e {
-            GatewayError::HttpError(msg) => {
-                BamlRtError::ToolExecution(format!("Gateway HTTP error: {}", msg))
-            }
-            GatewayError::GraphQLError(errors) => {
-                BamlRtError::ToolExecution(format!("GraphQL errors: {}", errors.join(", ")))
-            }
-            GatewayError::NoData => {
-                BamlRtError::ToolExecution("No data in GraphQL response".to_string())
-            }
-            GatewayError::SubgraphNotFound(id) => {
-                BamlRtError::InvalidArgument(format!("Subgraph not found: {}", id))
-            }
-            GatewayError::AllIndexersFailed => {
-                BamlRtError::ToolExecution("All indexers failed to respond".to_string())
-            }
-        }
+        result.data.ok_or_else(|| {
+            QueryError::new(QueryErrorKind::BadResponse, "No data in GraphQL response")
+        })
     }
 
     /// Check if gateway caching is enabled
@@ -285,338 +828,419 @@ impl TheGraphTool {
         self.gateway.as_ref().map(|g| g.name())
     }
 
-    /// Query top pools from Uniswap V3
-    async fn query_uniswap_top_pools(&self, network: Network, limit: u32) -> Result<Value> {
-        let endpoint = self
-            .endpoints
-            .endpoints
-            .get(&(network, Protocol::UniswapV3))
-            .ok_or_else(|| {
-                BamlRtError::InvalidArgument(format!(
-                    "No Uniswap V3 endpoint configured for {:?}",
-                    network
-                ))
-            })?;
+    /// Gateway cache hit/miss/eviction counts (if a caching gateway is configured)
+    pub fn gateway_cache_stats(&self) -> Option<CacheStats> {
+        self.gateway.as_ref().and_then(|g| g.cache_stats())
+    }
 
-        let query = r#"
-            query TopPools($first: Int!) {
-                pools(
-                    first: $first
-                    orderBy: totalValueLockedUSD
-                    orderDirection: desc
-                ) {
-                    id
-                    token0 {
-                        id
-                        symbol
-                        name
-                        decimals
-                    }
-                    token1 {
-                        id
-                        symbol
-                        name
-                        decimals
-                    }
-                    feeTier
-                    liquidity
-                    sqrtPrice
-                    token0Price
-                    token1Price
-                    volumeUSD
-                    totalValueLockedUSD
-                    txCount
-                }
-            }
-        "#;
+    /// Gateway query counts, estimated cost and budget (if a gateway is configured)
+    pub fn gateway_usage(&self) -> Option<UsageReport> {
+        self.gateway.as_ref().and_then(|g| g.usage())
+    }
 
-        let variables = json!({ "first": limit });
-        let data = self.query_subgraph(endpoint, query, variables).await?;
+    /// Gateway circuit breaker state (if a gateway is configured)
+    pub async fn gateway_status(&self) -> Option<GatewayStatus> {
+        match &self.gateway {
+            Some(gateway) => Some(gateway.status().await),
+            None => None,
+        }
+    }
 
-        Ok(json!({
-            "protocol": "uniswap_v3",
-            "network": network.name(),
-            "pools": data.get("pools").cloned().unwrap_or(json!([]))
-        }))
+    /// Fetch the top Uniswap V3 pools by TVL
+    pub(crate) async fn top_pools(
+        &self,
+        network: Network,
+        limit: u32,
+        block: &BlockPin,
+    ) -> Result<Vec<Pool>> {
+        let endpoint = &self.endpoint(network, Protocol::UniswapV3)?;
+        self.fetch_pools_paginated(
+            endpoint,
+            &uniswap_v3::pools::Pool_filter::default(),
+            &mut PoolCursor::default(),
+            self.capped_limit(limit),
+            block,
+        )
+        .await
     }
 
-    /// Query a specific pool by ID
-    async fn query_uniswap_pool(&self, network: Network, pool_id: &str) -> Result<Value> {
-        let endpoint = self
-            .endpoints
-            .endpoints
-            .get(&(network, Protocol::UniswapV3))
-            .ok_or_else(|| {
-                BamlRtError::InvalidArgument(format!(
-                    "No Uniswap V3 endpoint configured for {:?}",
-                    network
-                ))
-            })?;
+    /// Fetch a single Uniswap V3 pool by id
+    pub(crate) async fn pool(
+        &self,
+        network: Network,
+        pool_id: &str,
+        block: &BlockPin,
+    ) -> Result<Option<Pool>> {
+        let endpoint = &self.endpoint(network, Protocol::UniswapV3)?;
+        self.fetch_pool(endpoint, pool_id, block).await
+    }
 
-        let query = r#"
-            query PoolById($id: ID!) {
-                pool(id: $id) {
-                    id
-                    token0 {
-                        id
-                        symbol
-                        name
-                        decimals
-                        derivedETH
-                    }
-                    token1 {
-                        id
-                        symbol
-                        name
-                        decimals
-                        derivedETH
-                    }
-                    feeTier
-                    liquidity
-                    sqrtPrice
-                    tick
-                    token0Price
-                    token1Price
-                    volumeUSD
-                    totalValueLockedUSD
-                    txCount
-                }
+    /// Fetch a Uniswap V3 pool's daily snapshots over a time range, oldest first
+    pub(crate) async fn pool_day_snapshots(
+        &self,
+        network: Network,
+        pool_id: &str,
+        start_time: i64,
+        end_time: i64,
+        block: &BlockPin,
+    ) -> Result<Vec<PoolSnapshot>> {
+        let endpoint = &self.endpoint(network, Protocol::UniswapV3)?;
+        self.fetch_pool_series(
+            endpoint,
+            &pool_id.to_lowercase(),
+            TimeGranularity::Day,
+            start_time,
+            end_time,
+            block,
+        )
+        .await
+    }
+
+    /// Introspect a subgraph's schema (cached per network/protocol)
+    pub(crate) async fn subgraph_schema(
+        &self,
+        network: Network,
+        protocol: Protocol,
+    ) -> Result<Arc<SubgraphSchema>> {
+        if let Some((fetched_at, schema)) = self.schemas.read().await.get(&(network, protocol)) {
+            if fetched_at.elapsed() < SCHEMA_CACHE_TTL {
+                return Ok(Arc::clone(schema));
             }
-        "#;
+        }
+
+        let endpoint = &self.endpoint(network, protocol)?;
+        let data = self
+            .query_subgraph(endpoint, INTROSPECTION_QUERY, json!({}))
+            .await?;
+        let schema = Arc::new(SubgraphSchema::from_introspection(data).map_err(|e| {
+            BamlRtError::ToolExecution(format!("Failed to decode subgraph schema: {}", e))
+        })?);
+        self.schemas
+            .write()
+            .await
+            .insert((network, protocol), (Instant::now(), Arc::clone(&schema)));
+        Ok(schema)
+    }
+
+    /// Run an agent-authored query once it passes the schema and budget checks
+    async fn query_custom(
+        &self,
+        network: Network,
+        protocol: Protocol,
+        query: &str,
+        variables: Value,
+    ) -> Result<Value> {
+        let schema = self.subgraph_schema(network, protocol).await?;
+        validate_query(&schema, query, &variables, &self.query_budget)
+            .map_err(|e| BamlRtError::InvalidArgument(format!("Rejected custom query: {}", e)))?;
 
-        let variables = json!({ "id": pool_id });
+        let endpoint = &self.endpoint(network, protocol)?;
         let data = self.query_subgraph(endpoint, query, variables).await?;
 
         Ok(json!({
-            "protocol": "uniswap_v3",
+            "protocol": protocol.name(),
             "network": network.name(),
-            "pool": data.get("pool").cloned().unwrap_or(json!(null))
+            "data": data
         }))
     }
 
-    /// Query token price from Uniswap V3
-    async fn query_token_price(&self, network: Network, token_address: &str) -> Result<Value> {
-        let endpoint = self
-            .endpoints
-            .endpoints
-            .get(&(network, Protocol::UniswapV3))
-            .ok_or_else(|| {
-                BamlRtError::InvalidArgument(format!(
-                    "No Uniswap V3 endpoint configured for {:?}",
-                    network
-                ))
-            })?;
-
-        let query = r#"
-            query TokenPrice($id: ID!) {
-                token(id: $id) {
-                    id
-                    symbol
-                    name
-                    decimals
-                    derivedETH
-                    volumeUSD
-                    totalValueLockedUSD
-                }
-                bundle(id: "1") {
-                    ethPriceUSD
-                }
-            }
-        "#;
-
-        let variables = json!({ "id": token_address.to_lowercase() });
-        let data = self.query_subgraph(endpoint, query, variables).await?;
-
-        // Calculate USD price from ETH price
-        let token = data.get("token");
-        let bundle = data.get("bundle");
-
-        let price_usd = match (token, bundle) {
-            (Some(t), Some(b)) => {
-                let derived_eth = t
-                    .get("derivedETH")
-                    .and_then(|v| v.as_str())
-                    .and_then(|s| s.parse::<f64>().ok())
-                    .unwrap_or(0.0);
-                let eth_price = b
-                    .get("ethPriceUSD")
-                    .and_then(|v| v.as_str())
-                    .and_then(|s| s.parse::<f64>().ok())
-                    .unwrap_or(0.0);
-                derived_eth * eth_price
-            }
-            _ => 0.0,
-        };
+    /// Query top pools from Uniswap V3
+    async fn query_uniswap_top_pools(
+        &self,
+        network: Network,
+        limit: u32,
+        block: &BlockPin,
+    ) -> Result<Value> {
+        let pools = self.top_pools(network, limit, block).await?;
 
         Ok(json!({
+            "protocol": "uniswap_v3",
             "network": network.name(),
-            "token": token.cloned().unwrap_or(json!(null)),
-            "price_usd": price_usd,
-            "eth_price_usd": bundle.and_then(|b| b.get("ethPriceUSD")).cloned().unwrap_or(json!(null))
+            "pools": pools
         }))
     }
 
-    /// Query pools with filters applied (for bidirectional Graph-Inference flow)
-    async fn query_filtered_pools(
+    /// Query a specific pool by ID
+    async fn query_uniswap_pool(
         &self,
         network: Network,
-        filters: &QueryFilters,
-        limit: u32,
+        pool_id: &str,
+        block: &BlockPin,
     ) -> Result<Value> {
-        let endpoint = self
-            .endpoints
-            .endpoints
-            .get(&(network, Protocol::UniswapV3))
-            .ok_or_else(|| {
-                BamlRtError::InvalidArgument(format!(
-                    "No Uniswap V3 endpoint configured for {:?}",
-                    network
-                ))
-            })?;
+        let pool = self.pool(network, pool_id, block).await?;
 
-        // Build GraphQL where clause from filters
-        let mut where_clauses = Vec::new();
+        Ok(json!({
+            "protocol": "uniswap_v3",
+            "network": network.name(),
+            "pool": pool
+        }))
+    }
 
-        if let Some(min_tvl) = filters.min_tvl_usd {
-            where_clauses.push(format!("totalValueLockedUSD_gte: \"{}\"", min_tvl));
-        }
+    /// Fetch a single pool by id
+    async fn fetch_pool(
+        &self,
+        endpoint: &SubgraphRoute,
+        pool_id: &str,
+        block: &BlockPin,
+    ) -> Result<Option<Pool>> {
+        let filter = uniswap_v3::pools::Pool_filter {
+            id: Some(pool_id.to_lowercase()),
+            ..Default::default()
+        };
+        let variables = uniswap_v3::pools::Variables {
+            first: 1,
+            filter,
+            block: block.height(),
+        };
+        let data = self
+            .query_typed::<uniswap_v3::Pools>(endpoint, variables)
+            .await?;
+        Ok(decode_all(data.pools)
+            .map_err(decode_error)?
+            .into_iter()
+            .next())
+    }
 
-        if let Some(min_vol) = filters.min_volume_24h_usd {
-            where_clauses.push(format!("volumeUSD_gte: \"{}\"", min_vol));
-        }
+    /// Query initialized ticks around a pool's current tick and derive its local depth
+    ///
+    /// Depth and the swap estimate only use liquidity inside the fetched
+    /// window; moves beyond it are reported as unknown, not extrapolated.
+    async fn query_pool_ticks(
+        &self,
+        network: Network,
+        pool_id: &str,
+        spacings: u32,
+        trade: Option<(&str, f64)>,
+        block: &BlockPin,
+    ) -> Result<Value> {
+        let endpoint = &self.endpoint(network, Protocol::UniswapV3)?;
+        let pool = self
+            .fetch_pool(endpoint, pool_id, block)
+            .await?
+            .ok_or_else(|| BamlRtError::InvalidArgument(format!("Pool not found: {}", pool_id)))?;
+        let tick = pool.tick.ok_or_else(|| {
+            BamlRtError::ToolExecution(format!("Pool {} is not initialized", pool.id))
+        })?;
+        let spacing = tick_spacing(pool.fee_tier).ok_or_else(|| {
+            BamlRtError::ToolExecution(format!("Unknown fee tier: {}", pool.fee_tier))
+        })?;
+
+        let (tick_lower, tick_upper) = tick_window(tick, spacing, spacings);
 
-        if let Some(ref fee_tiers) = filters.fee_tiers {
-            let fee_list = fee_tiers
+        let variables = uniswap_v3::pool_ticks::Variables {
+            pool: pool.id.clone(),
+            tick_lower: tick_lower.to_string(),
+            tick_upper: tick_upper.to_string(),
+            first: MAX_PAGE_SIZE as i64,
+            block: block.height(),
+        };
+        let data = self
+            .query_typed::<uniswap_v3::PoolTicks>(endpoint, variables)
+            .await?;
+        let ticks: Vec<Tick> = decode_all(data.ticks).map_err(decode_error)?;
+
+        let curve = LiquidityCurve::new(
+            pool.sqrt_price,
+            tick,
+            pool.liquidity,
+            pool.fee_tier,
+            ticks
                 .iter()
-                .map(|f| f.to_string())
-                .collect::<Vec<_>>()
-                .join(", ");
-            where_clauses.push(format!("feeTier_in: [{}]", fee_list));
-        }
+                .map(|t| TickLiquidity {
+                    tick: t.tick_idx,
+                    liquidity_net: t.liquidity_net,
+                    liquidity_gross: t.liquidity_gross,
+                })
+                .collect(),
+            tick_lower,
+            tick_upper,
+        );
 
-        let where_clause = if where_clauses.is_empty() {
-            String::new()
-        } else {
-            format!("where: {{ {} }}", where_clauses.join(", "))
+        let (decimals0, decimals1) = (pool.token0.decimals, pool.token1.decimals);
+        let depth = |zero_for_one: bool, decimals_in: u32| -> Vec<DepthPoint> {
+            let scale = 10_f64.powi(decimals_in as i32);
+            curve
+                .depth(zero_for_one, &DEPTH_MOVES_PERCENT)
+                .into_iter()
+                .map(|point| DepthPoint {
+                    amount_in: point.amount_in.map(|amount| amount / scale),
+                    ..point
+                })
+                .collect()
         };
 
-        let query = format!(
-            r#"
-            query FilteredPools($first: Int!) {{
-                pools(
-                    first: $first
-                    orderBy: totalValueLockedUSD
-                    orderDirection: desc
-                    {}
-                ) {{
-                    id
-                    token0 {{
-                        id
-                        symbol
-                        name
-                        decimals
-                    }}
-                    token1 {{
-                        id
-                        symbol
-                        name
-                        decimals
-                    }}
-                    feeTier
-                    liquidity
-                    sqrtPrice
-                    token0Price
-                    token1Price
-                    volumeUSD
-                    totalValueLockedUSD
-                    txCount
-                }}
-            }}
-            "#,
-            where_clause
-        );
+        let estimate = match trade {
+            Some((token_in, amount_in)) => {
+                let zero_for_one = if token_in.eq_ignore_ascii_case(&pool.token0.id) {
+                    true
+                } else if token_in.eq_ignore_ascii_case(&pool.token1.id) {
+                    false
+                } else {
+                    return Err(BamlRtError::InvalidArgument(format!(
+                        "Token {} is not in pool {}",
+                        token_in, pool.id
+                    )));
+                };
+                let (decimals_in, decimals_out) = if zero_for_one {
+                    (decimals0, decimals1)
+                } else {
+                    (decimals1, decimals0)
+                };
+                let raw_in = amount_in * 10_f64.powi(decimals_in as i32);
+                Some(
+                    curve
+                        .estimate_exact_input(raw_in, zero_for_one)
+                        .to_whole_units(decimals_in, decimals_out),
+                )
+            }
+            None => None,
+        };
 
-        let variables = json!({ "first": limit });
-        let data = self.query_subgraph(endpoint, &query, variables).await?;
+        Ok(json!({
+            "protocol": "uniswap_v3",
+            "network": network.name(),
+            "pool_id": pool.id,
+            "token0": pool.token0.symbol,
+            "token1": pool.token1.symbol,
+            "fee_tier": pool.fee_tier,
+            "tick": tick,
+            "tick_spacing": spacing,
+            "tick_lower": tick_lower,
+            "tick_upper": tick_upper,
+            "liquidity": pool.liquidity,
+            "ticks": ticks,
+            // Whole tokens of input needed to move the token0 price by each step
+            "depth": {
+                "sell_token0": depth(true, decimals0),
+                "sell_token1": depth(false, decimals1)
+            },
+            "estimate": estimate
+        }))
+    }
 
-        // Get pools array for post-query filtering
-        let mut pools: Vec<Value> = data
-            .get("pools")
-            .and_then(|v| v.as_array())
-            .cloned()
-            .unwrap_or_default();
+    /// Query token price from Uniswap V3
+    async fn query_token_price(
+        &self,
+        network: Network,
+        token_address: &str,
+        block: &BlockPin,
+    ) -> Result<Value> {
+        let endpoint = &self.endpoint(network, Protocol::UniswapV3)?;
 
-        // Apply post-query filters (volume/TVL ratio, token pairs, exclude tokens)
-
-        // Filter by volume/TVL ratio
-        if let Some(min_ratio) = filters.min_volume_tvl_ratio {
-            pools.retain(|pool| {
-                let tvl = pool
-                    .get("totalValueLockedUSD")
-                    .and_then(|v| v.as_str())
-                    .and_then(|s| s.parse::<f64>().ok())
-                    .unwrap_or(0.0);
-                let volume = pool
-                    .get("volumeUSD")
-                    .and_then(|v| v.as_str())
-                    .and_then(|s| s.parse::<f64>().ok())
-                    .unwrap_or(0.0);
-
-                if tvl > 0.0 {
-                    (volume / tvl) >= min_ratio
-                } else {
-                    false
-                }
-            });
-        }
+        let variables = uniswap_v3::token_price::Variables {
+            id: token_address.to_lowercase(),
+            block: block.height(),
+        };
+        let data = self
+            .query_typed::<uniswap_v3::TokenPrice>(endpoint, variables)
+            .await?;
+
+        let token = data
+            .token
+            .map(Token::try_from)
+            .transpose()
+            .map_err(decode_error)?;
+        let eth_price_usd = data
+            .bundle
+            .map(|b| crate::graphql::parse_decimal("ethPriceUSD", &b.eth_price_usd))
+            .transpose()
+            .map_err(decode_error)?;
 
-        // Filter by token pairs (if specified)
-        if let Some(ref pairs) = filters.token_pairs {
-            let pair_set: HashSet<String> = pairs
-                .iter()
-                .map(|p| p.to_lowercase().replace('/', "-"))
-                .collect();
+        // Calculate USD price from ETH price
+        let price_usd = match (&token, eth_price_usd) {
+            (Some(t), Some(eth_price)) => t.derived_eth * eth_price,
+            _ => 0.0,
+        };
 
-            pools.retain(|pool| {
-                let token0 = pool
-                    .get("token0")
-                    .and_then(|t| t.get("symbol"))
-                    .and_then(|s| s.as_str())
-                    .unwrap_or("");
-                let token1 = pool
-                    .get("token1")
-                    .and_then(|t| t.get("symbol"))
-                    .and_then(|s| s.as_str())
-                    .unwrap_or("");
-
-                let pair1 = format!("{}-{}", token0, token1).to_lowercase();
-                let pair2 = format!("{}-{}", token1, token0).to_lowercase();
-
-                pair_set.contains(&pair1) || pair_set.contains(&pair2)
-            });
-        }
+        Ok(json!({
+            "network": network.name(),
+            "token": token,
+            "price_usd": price_usd,
+            "eth_price_usd": eth_price_usd
+        }))
+    }
 
-        // Exclude tokens
-        if let Some(ref exclude) = filters.exclude_tokens {
-            let exclude_set: HashSet<String> = exclude.iter().map(|t| t.to_lowercase()).collect();
-
-            pools.retain(|pool| {
-                let token0_id = pool
-                    .get("token0")
-                    .and_then(|t| t.get("id"))
-                    .and_then(|s| s.as_str())
-                    .unwrap_or("")
-                    .to_lowercase();
-                let token1_id = pool
-                    .get("token1")
-                    .and_then(|t| t.get("id"))
-                    .and_then(|s| s.as_str())
-                    .unwrap_or("")
-                    .to_lowercase();
-
-                !exclude_set.contains(&token0_id) && !exclude_set.contains(&token1_id)
-            });
+    /// Query pools with filters applied (for bidirectional Graph-Inference flow)
+    async fn query_filtered_pools(
+        &self,
+        network: Network,
+        filters: &QueryFilters,
+        limit: u32,
+        block: &BlockPin,
+    ) -> Result<Value> {
+        let endpoint = &self.endpoint(network, Protocol::UniswapV3)?;
+
+        // Token pairs and exclusions run server-side so `limit` counts matching pools
+        let filter = pool_filter_from(filters, network.chain_id())?;
+
+        let limit = self.capped_limit(limit);
+        let since = match block {
+            BlockPin::Latest => chrono::Utc::now().timestamp(),
+            // Anchor the window at the pinned block rather than wall-clock time
+            _ => self.block_timestamp(endpoint, block).await?,
+        } - TRAILING_WINDOW_SECS;
+
+        // Only the derived trailing metrics can't be expressed in the where
+        // clause, so with those filters keep paging down the TVL ranking until
+        // `limit` pools pass, scanning at most `max_results` pools
+        let trailing_filters =
+            filters.min_volume_24h_usd.is_some() || filters.min_volume_tvl_ratio.is_some();
+        let mut cursor = PoolCursor::default();
+        let mut pools: Vec<Pool> = Vec::new();
+        let mut scanned = 0;
+        loop {
+            let wanted = if trailing_filters {
+                (limit - pools.len()) * FILTERED_POOL_OVERFETCH
+            } else {
+                limit
+            };
+            let mut batch = self
+                .fetch_pools_paginated(
+                    endpoint,
+                    &filter,
+                    &mut cursor,
+                    wanted.min(self.max_results - scanned),
+                    block,
+                )
+                .await?;
+            scanned += batch.len();
+
+            // Annotate pools with trailing 24h activity from poolHourDatas
+            // (pool-level volumeUSD is all-time cumulative)
+            let pool_ids: Vec<String> = batch.iter().map(|p| p.id.clone()).collect();
+            let activity = self
+                .query_trailing_activity(endpoint, &pool_ids, since, block)
+                .await?;
+            for pool in batch.iter_mut() {
+                annotate_trailing_activity(pool, &activity);
+            }
+
+            // Filter by trailing 24h volume
+            if let Some(min_vol) = filters.min_volume_24h_usd {
+                batch.retain(|pool| pool.volume_24h_usd.unwrap_or(0.0) >= min_vol);
+            }
+
+            // Filter by trailing 24h volume / TVL ratio
+            if let Some(min_ratio) = filters.min_volume_tvl_ratio {
+                batch.retain(|pool| pool.volume_tvl_ratio_24h.unwrap_or(0.0) >= min_ratio);
+            }
+
+            pools.extend(batch);
+            if !trailing_filters || pools.len() >= limit || cursor.exhausted {
+                break;
+            }
+            if scanned >= self.max_results {
+                tracing::warn!(
+                    matched = pools.len(),
+                    requested = limit,
+                    scanned,
+                    "Stopped scanning pools for trailing activity filters at max_results"
+                );
+                break;
+            }
         }
+        sort_pools_by_tvl(&mut pools);
+        pools.truncate(limit);
 
         let count = pools.len();
         Ok(json!({
@@ -628,53 +1252,762 @@ impl TheGraphTool {
         }))
     }
 
-    /// Execute a full query plan across multiple networks/protocols
-    async fn execute_query_plan(&self, plan: &QueryPlan) -> Result<Value> {
-        let mut results: Vec<Value> = Vec::new();
+    /// Clamp a requested result count to the configured hard upper bound
+    fn capped_limit(&self, requested: u32) -> usize {
+        (requested as usize).min(self.max_results)
+    }
 
-        // Execute queries for each network/protocol combination
-        for network_str in &plan.target_networks {
-            let network = match Self::parse_network(network_str) {
-                Ok(n) => n,
-                Err(e) => {
+    /// Fetch up to `total` more pools ordered by TVL, paginating past the 1000-entity cap
+    ///
+    /// Uses keyset pagination on `(totalValueLockedUSD, id)` rather than
+    /// `skip`, which Graph Node caps at 5000. Each page continues strictly below
+    /// the last TVL taken. A full page may end partway through a run of pools
+    /// with equal TVL, whose order within the run is up to the indexer, so that
+    /// run is dropped from the page and walked whole by id with `id_gt`.
+    /// `cursor` carries the position across calls, so a caller can keep paging
+    /// down the ranking. The result is sorted by TVL desc, then id, so output
+    /// is deterministic.
+    async fn fetch_pools_paginated(
+        &self,
+        endpoint: &SubgraphRoute,
+        filter: &uniswap_v3::pools::Pool_filter,
+        cursor: &mut PoolCursor,
+        total: usize,
+        block: &BlockPin,
+    ) -> Result<Vec<Pool>> {
+        let mut pools: Vec<Pool> = Vec::new();
+
+        while pools.len() < total && !cursor.exhausted {
+            let first = (total - pools.len()).min(MAX_PAGE_SIZE);
+
+            if let Some((tvl, after)) = cursor.run.take() {
+                let mut run_filter = filter.clone();
+                run_filter.total_value_locked_usd = Some(tvl.clone());
+                run_filter.id_gt = after.clone();
+                let mut body = uniswap_v3::Pools::build_query(uniswap_v3::pools::Variables {
+                    first: first as i64,
+                    filter: run_filter,
+                    block: block.height(),
+                });
+                body.query = uniswap_v3::pools_at_tvl::QUERY;
+                body.operation_name = uniswap_v3::pools_at_tvl::OPERATION_NAME;
+                let run = self
+                    .query_body::<uniswap_v3::Pools>(endpoint, body)
+                    .await?
+                    .pools;
+                let run_len = run.len();
+
+                let mut last = after;
+                for raw in run {
+                    last = Some(raw.id.clone());
+                    if cursor.seen.insert(raw.id.clone()) {
+                        pools.push(Pool::try_from(raw).map_err(decode_error)?);
+                    }
+                }
+                if run_len < first {
+                    cursor.below = Some(tvl);
+                } else {
+                    cursor.run = Some((tvl, last));
+                }
+                continue;
+            }
+
+            let mut page_filter = filter.clone();
+            page_filter.total_value_locked_usd_lt = cursor.below.clone();
+            let variables = uniswap_v3::pools::Variables {
+                first: first as i64,
+                filter: page_filter,
+                block: block.height(),
+            };
+            let page = self
+                .query_typed::<uniswap_v3::Pools>(endpoint, variables)
+                .await?
+                .pools;
+
+            // Exact decimal string of the TVL run a full page may have cut short
+            let boundary = (page.len() == first)
+                .then(|| page.last().map(|raw| raw.total_value_locked_usd.clone()))
+                .flatten();
+            for raw in page {
+                if boundary.as_ref() == Some(&raw.total_value_locked_usd) {
+                    continue;
+                }
+                if cursor.seen.insert(raw.id.clone()) {
+                    pools.push(Pool::try_from(raw).map_err(decode_error)?);
+                }
+            }
+
+            // A short page means the filter is exhausted
+            match boundary {
+                Some(tvl) => cursor.run = Some((tvl, None)),
+                None => cursor.exhausted = true,
+            }
+        }
+
+        sort_pools_by_tvl(&mut pools);
+        Ok(pools)
+    }
+
+    /// Fetch trailing hourly volume/fees for a set of pools
+    ///
+    /// Pools are batched so each request stays under Graph Node's `first` cap.
+    async fn query_trailing_activity(
+        &self,
+        endpoint: &SubgraphRoute,
+        pool_ids: &[String],
+        since: i64,
+        block: &BlockPin,
+    ) -> Result<HashMap<String, PoolActivity>> {
+        // One hourly row per pool per hour in the window (+1 for the partial hour)
+        let rows_per_pool = (TRAILING_WINDOW_SECS / 3600) as usize + 1;
+        let pools_per_batch = (MAX_PAGE_SIZE / rows_per_pool).max(1);
+
+        let mut rows: Vec<HourlyActivity> = Vec::new();
+        for batch in pool_ids.chunks(pools_per_batch) {
+            // Batches are sized to fit a page, but page on by id in case a
+            // window holds more rows than expected rather than drop them
+            let mut after = String::new();
+            loop {
+                let variables = uniswap_v3::trailing_activity::Variables {
+                    pools: batch.to_vec(),
+                    since,
+                    after: after.clone(),
+                    first: MAX_PAGE_SIZE as i64,
+                    block: block.height(),
+                };
+                let page = self
+                    .query_typed::<uniswap_v3::TrailingActivity>(endpoint, variables)
+                    .await?
+                    .pool_hour_datas;
+                let page_len = page.len();
+                if let Some(last) = page.last() {
+                    after = last.id.clone();
+                }
+                rows.extend(decode_all(page).map_err(decode_error)?);
+                if page_len < MAX_PAGE_SIZE {
+                    break;
+                }
+            }
+        }
+
+        Ok(aggregate_pool_activity(&rows))
+    }
+
+    /// Query a pool's daily or hourly snapshots over a time range
+    async fn query_pool_time_series(
+        &self,
+        network: Network,
+        pool_id: &str,
+        granularity: TimeGranularity,
+        start_time: i64,
+        end_time: i64,
+        block: &BlockPin,
+    ) -> Result<Value> {
+        let endpoint = &self.endpoint(network, Protocol::UniswapV3)?;
+
+        let pool = pool_id.to_lowercase();
+        let series = self
+            .fetch_pool_series(endpoint, &pool, granularity, start_time, end_time, block)
+            .await?;
+        let count = series.len();
+
+        Ok(json!({
+            "protocol": "uniswap_v3",
+            "network": network.name(),
+            "pool_id": pool,
+            "granularity": granularity.name(),
+            "start_time": start_time,
+            "end_time": end_time,
+            "series": series,
+            "count": count
+        }))
+    }
+
+    /// Fetch a pool's daily or hourly snapshots, oldest first
+    async fn fetch_pool_series(
+        &self,
+        endpoint: &SubgraphRoute,
+        pool: &str,
+        granularity: TimeGranularity,
+        start_time: i64,
+        end_time: i64,
+        block: &BlockPin,
+    ) -> Result<Vec<PoolSnapshot>> {
+        page_series(
+            start_time,
+            |snapshot: &PoolSnapshot| snapshot.timestamp,
+            |start| async move {
+                match granularity {
+                    TimeGranularity::Day => {
+                        let variables = uniswap_v3::pool_day_series::Variables {
+                            pool: pool.to_string(),
+                            start,
+                            end: end_time,
+                            first: MAX_PAGE_SIZE as i64,
+                            block: block.height(),
+                        };
+                        let data = self
+                            .query_typed::<uniswap_v3::PoolDaySeries>(endpoint, variables)
+                            .await?;
+                        decode_all(data.pool_day_datas)
+                    }
+                    TimeGranularity::Hour => {
+                        let variables = uniswap_v3::pool_hour_series::Variables {
+                            pool: pool.to_string(),
+                            start,
+                            end: end_time,
+                            first: MAX_PAGE_SIZE as i64,
+                            block: block.height(),
+                        };
+                        let data = self
+                            .query_typed::<uniswap_v3::PoolHourSeries>(endpoint, variables)
+                            .await?;
+                        decode_all(data.pool_hour_datas)
+                    }
+                }
+                .map_err(decode_error)
+            },
+        )
+        .await
+    }
+
+    /// Query recent swaps, mints or burns with flow aggregates
+    ///
+    /// Events come back newest first. When `limit` cuts the window short,
+    /// `truncated` is set and the metrics cover only the returned events.
+    async fn query_flow_events(
+        &self,
+        network: Network,
+        kind: FlowEvents,
+        window: &EventWindow,
+        limit: u32,
+        large_trade_usd: f64,
+        block: &BlockPin,
+    ) -> Result<Value> {
+        let endpoint = &self.endpoint(network, Protocol::UniswapV3)?;
+        let total = self.capped_limit(limit);
+        // One event past the limit tells whether the window holds more
+        let probe = total + 1;
+
+        let (events, oldest, metrics, truncated) = match kind {
+            FlowEvents::Swaps => {
+                let mut swaps: Vec<Swap> = self
+                    .fetch_events_paginated::<uniswap_v3::Swaps, _, _>(
+                        endpoint,
+                        window,
+                        probe,
+                        |first, window| uniswap_v3::swaps::Variables {
+                            first,
+                            filter: window.into(),
+                            block: block.height(),
+                        },
+                        |data| data.swaps,
+                    )
+                    .await?;
+                let truncated = swaps.len() > total;
+                swaps.truncate(total);
+                let oldest = swaps.last().map(|s| s.timestamp);
+                let base = match &window.scope {
+                    EventScope::Pool(_) => FlowBase::Token0,
+                    EventScope::Token(token) => FlowBase::Token(token.as_str()),
+                };
+                let covered = covered_window(window, oldest, truncated);
+                let metrics = swap_flow_metrics(&swaps, base, large_trade_usd, covered);
+                (json!(swaps), oldest, metrics, truncated)
+            }
+            FlowEvents::Mints | FlowEvents::Burns => {
+                let mut events: Vec<LiquidityEvent> = if matches!(kind, FlowEvents::Mints) {
+                    self.fetch_events_paginated::<uniswap_v3::Mints, _, _>(
+                        endpoint,
+                        window,
+                        probe,
+                        |first, window| uniswap_v3::mints::Variables {
+                            first,
+                            filter: window.into(),
+                            block: block.height(),
+                        },
+                        |data| data.mints,
+                    )
+                    .await?
+                } else {
+                    self.fetch_events_paginated::<uniswap_v3::Burns, _, _>(
+                        endpoint,
+                        window,
+                        probe,
+                        |first, window| uniswap_v3::burns::Variables {
+                            first,
+                            filter: window.into(),
+                            block: block.height(),
+                        },
+                        |data| data.burns,
+                    )
+                    .await?
+                };
+                let truncated = events.len() > total;
+                events.truncate(total);
+                let oldest = events.last().map(|e| e.timestamp);
+                let metrics = liquidity_flow_metrics(
+                    &events,
+                    matches!(kind, FlowEvents::Mints),
+                    large_trade_usd,
+                );
+                (json!(events), oldest, metrics, truncated)
+            }
+        };
+        let count = metrics.event_count;
+
+        let mut result = json!({
+            "protocol": "uniswap_v3",
+            "network": network.name(),
+            "start_time": window.start,
+            "end_time": window.end,
+            "count": count,
+            "truncated": truncated,
+            // Earliest event returned; later than start_time when truncated
+            "oldest_timestamp": oldest,
+            "metrics": metrics
+        });
+        if let Some(obj) = result.as_object_mut() {
+            match &window.scope {
+                EventScope::Pool(pool) => obj.insert("pool_id".to_string(), json!(pool)),
+                EventScope::Token(token) => obj.insert("token_address".to_string(), json!(token)),
+            };
+            obj.insert(kind.name().to_string(), events);
+        }
+        Ok(result)
+    }
+
+    /// Fetch up to `total` events in a window, newest first, paginating past the 1000-entity cap
+    ///
+    /// Uses keyset pagination on `timestamp` (each page ends at the oldest
+    /// timestamp seen so far). Events repeated across page boundaries are
+    /// de-duplicated by id.
+    async fn fetch_events_paginated<Q, R, T>(
+        &self,
+        endpoint: &SubgraphRoute,
+        window: &EventWindow,
+        total: usize,
+        variables: impl Fn(i64, &EventWindow) -> Q::Variables,
+        rows: impl Fn(Q::ResponseData) -> Vec<R>,
+    ) -> Result<Vec<T>>
+    where
+        Q: GraphQLQuery,
+        T: TryFrom<R, Error = DecodeError> + TimedEvent,
+    {
+        let mut events: Vec<T> = Vec::new();
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut page_window = window.clone();
+
+        while events.len() < total {
+            let first = (total - events.len()).min(MAX_PAGE_SIZE);
+            let data = self
+                .query_typed::<Q>(endpoint, variables(first as i64, &page_window))
+                .await?;
+            let page = rows(data);
+            let page_len = page.len();
+
+            let before = events.len();
+            for raw in page {
+                let event = T::try_from(raw).map_err(decode_error)?;
+                if seen.insert(event.id().to_string()) {
+                    page_window.end = event.timestamp();
+                    events.push(event);
+                }
+            }
+
+            // Exhausted, or stuck on a run of same-second events larger than a page
+            if page_len < first || events.len() == before {
+                if page_len == first && events.len() == before {
                     tracing::warn!(
-                        network = network_str,
-                        error = %e,
-                        "Skipping unknown network in query plan"
+                        collected = events.len(),
+                        requested = total,
+                        "Pagination stopped: page contained no new events"
                     );
-                    continue;
                 }
+                break;
+            }
+        }
+
+        events.truncate(total);
+        Ok(events)
+    }
+
+    /// Query a token's daily snapshots over a time range
+    async fn query_token_day_data(
+        &self,
+        network: Network,
+        token_address: &str,
+        start_time: i64,
+        end_time: i64,
+        block: &BlockPin,
+    ) -> Result<Value> {
+        let endpoint = &self.endpoint(network, Protocol::UniswapV3)?;
+
+        let series = page_series(
+            start_time,
+            |snapshot: &TokenSnapshot| snapshot.timestamp,
+            |start| async move {
+                let variables = uniswap_v3::token_day_series::Variables {
+                    token: token_address.to_lowercase(),
+                    start,
+                    end: end_time,
+                    first: MAX_PAGE_SIZE as i64,
+                    block: block.height(),
+                };
+                let data = self
+                    .query_typed::<uniswap_v3::TokenDaySeries>(endpoint, variables)
+                    .await?;
+                decode_all(data.token_day_datas).map_err(decode_error)
+            },
+        )
+        .await?;
+        let count = series.len();
+
+        Ok(json!({
+            "protocol": "uniswap_v3",
+            "network": network.name(),
+            "token_address": token_address.to_lowercase(),
+            "granularity": TimeGranularity::Day.name(),
+            "start_time": start_time,
+            "end_time": end_time,
+            "series": series,
+            "count": count
+        }))
+    }
+
+    /// Fetch a subgraph's `_meta` block (number and timestamp) at a pin
+    async fn query_meta_block(&self, endpoint: &SubgraphRoute, block: &BlockPin) -> Result<Value> {
+        let query = r#"
+            query Meta($block: Block_height) {
+                _meta(block: $block) {
+                    block {
+                        number
+                        hash
+                        timestamp
+                    }
+                }
+            }
+        "#;
+
+        let variables = json!({ "block": block.to_variable() });
+        let data = self.query_subgraph(endpoint, query, variables).await?;
+        data.get("_meta")
+            .and_then(|m| m.get("block"))
+            .cloned()
+            .ok_or_else(|| {
+                BamlRtError::ToolExecution("Missing _meta block in response".to_string())
+            })
+    }
+
+    /// Timestamp of a pinned block, according to the subgraph
+    async fn block_timestamp(&self, endpoint: &SubgraphRoute, block: &BlockPin) -> Result<i64> {
+        let meta = self.query_meta_block(endpoint, block).await?;
+        meta.get("timestamp")
+            .and_then(|v| v.as_i64())
+            .ok_or_else(|| {
+                BamlRtError::ToolExecution(
+                    "Subgraph did not report a timestamp for the pinned block".to_string(),
+                )
+            })
+    }
+
+    /// Pick one block for a network: the lowest head across the plan's subgraphs
+    async fn resolve_snapshot_block(&self, network: Network, protocols: &[String]) -> Result<u64> {
+        let mut snapshot: Option<u64> = None;
+        for protocol in protocols {
+            let protocol = match protocol.as_str() {
+                "uniswap_v3" => Protocol::UniswapV3,
+                "aave_v3" => Protocol::AaveV3,
+                _ => continue,
             };
+            let Ok(endpoint) = self.endpoint(network, protocol) else {
+                continue;
+            };
+            let meta = self.query_meta_block(&endpoint, &BlockPin::Latest).await?;
+            let number = meta.get("number").and_then(|v| v.as_u64()).ok_or_else(|| {
+                BamlRtError::ToolExecution("Missing block number in _meta".to_string())
+            })?;
+            snapshot = Some(snapshot.map_or(number, |current| current.min(number)));
+        }
+        snapshot.ok_or_else(|| {
+            BamlRtError::InvalidArgument(format!(
+                "No subgraph endpoints configured for {:?} to snapshot",
+                network
+            ))
+        })
+    }
 
-            for protocol_str in &plan.target_protocols {
-                if protocol_str == "uniswap_v3" {
-                    // Use filtered_pools with plan's filters
-                    let limit = plan.expected_data_points.clamp(10, 100);
-                    match self
-                        .query_filtered_pools(network, &plan.data_filters, limit)
-                        .await
-                    {
-                        Ok(result) => {
-                            results.push(json!({
-                                "network": network_str,
-                                "protocol": protocol_str,
-                                "data": result
-                            }));
-                        }
-                        Err(e) => {
-                            // Log error but continue with other queries
-                            tracing::warn!(
-                                network = network_str,
-                                protocol = protocol_str,
-                                error = %e,
-                                "Query failed in query plan execution"
-                            );
+    /// Resolve the requested time range, defaulting to a lookback from now
+    fn time_range(params: Option<&GraphQueryParams>, default_lookback: i64) -> Result<(i64, i64)> {
+        let end = params
+            .and_then(|p| p.end_time)
+            .unwrap_or_else(|| chrono::Utc::now().timestamp());
+        let start = params
+            .and_then(|p| p.start_time)
+            .unwrap_or(end - default_lookback);
+        if start > end {
+            return Err(BamlRtError::InvalidArgument(format!(
+                "start_time ({}) must not be after end_time ({})",
+                start, end
+            )));
+        }
+        Ok((start, end))
+    }
+
+    /// Query Aave V3 reserves with lending rates
+    async fn query_aave_reserves(
+        &self,
+        network: Network,
+        limit: u32,
+        block: &BlockPin,
+    ) -> Result<Value> {
+        let endpoint = &self.endpoint(network, Protocol::AaveV3)?;
+        // Aave has a few dozen reserves per market; a single page is enough
+        let limit = limit.min(MAX_PAGE_SIZE as u32);
+
+        let query = r#"
+            query Reserves($first: Int!, $block: Block_height) {
+                reserves(
+                    first: $first
+                    block: $block
+                    where: { isActive: true }
+                    orderBy: totalLiquidity
+                    orderDirection: desc
+                ) {
+                    id
+                    underlyingAsset
+                    symbol
+                    name
+                    decimals
+                    isFrozen
+                    borrowingEnabled
+                    usageAsCollateralEnabled
+                    baseLTVasCollateral
+                    reserveLiquidationThreshold
+                    liquidityRate
+                    variableBorrowRate
+                    stableBorrowRate
+                    totalLiquidity
+                    availableLiquidity
+                    totalCurrentVariableDebt
+                    totalPrincipalStableDebt
+                    price {
+                        priceInEth
+                    }
+                }
+            }
+        "#;
+
+        let variables = json!({ "first": limit, "block": block.to_variable() });
+        let data = self.query_subgraph(endpoint, query, variables).await?;
+
+        let reserves: Vec<Value> = data
+            .get("reserves")
+            .and_then(|v| v.as_array())
+            .map(|reserves| reserves.iter().map(summarize_aave_reserve).collect())
+            .unwrap_or_default();
+
+        let count = reserves.len();
+        Ok(json!({
+            "protocol": "aave_v3",
+            "network": network.name(),
+            "reserves": reserves,
+            "count": count
+        }))
+    }
+
+    /// Query a user's Aave V3 positions and compute their health factor
+    async fn query_aave_user_positions(
+        &self,
+        network: Network,
+        user: &str,
+        block: &BlockPin,
+    ) -> Result<Value> {
+        let endpoint = &self.endpoint(network, Protocol::AaveV3)?;
+
+        let query = r#"
+            query UserPositions($user: String!, $block: Block_height) {
+                userReserves(where: { user: $user }, block: $block) {
+                    currentATokenBalance
+                    currentVariableDebt
+                    currentStableDebt
+                    usageAsCollateralEnabledOnUser
+                    reserve {
+                        underlyingAsset
+                        symbol
+                        decimals
+                        baseLTVasCollateral
+                        reserveLiquidationThreshold
+                        liquidityRate
+                        variableBorrowRate
+                        price {
+                            priceInEth
                         }
                     }
                 }
             }
+        "#;
+
+        let variables = json!({ "user": user.to_lowercase(), "block": block.to_variable() });
+        let data = self.query_subgraph(endpoint, query, variables).await?;
+
+        let user_reserves = data
+            .get("userReserves")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        let positions: Vec<AavePosition> = user_reserves
+            .iter()
+            .map(AavePosition::from_user_reserve)
+            .filter(|p| p.supplied > 0.0 || p.borrowed > 0.0)
+            .collect();
+
+        let total_collateral_usd: f64 = positions
+            .iter()
+            .filter(|p| p.used_as_collateral)
+            .map(|p| p.supplied_usd())
+            .sum();
+        let total_debt_usd: f64 = positions.iter().map(|p| p.borrowed_usd()).sum();
+
+        Ok(json!({
+            "protocol": "aave_v3",
+            "network": network.name(),
+            "user": user.to_lowercase(),
+            "positions": positions.iter().map(AavePosition::to_json).collect::<Vec<_>>(),
+            "total_collateral_usd": total_collateral_usd,
+            "total_debt_usd": total_debt_usd,
+            // null when the user has no debt (health factor is unbounded)
+            "health_factor": health_factor(&positions)
+        }))
+    }
+
+    /// Execute a full query plan across multiple networks/protocols
+    ///
+    /// With `snapshot`, every query on a network is pinned to a single block:
+    /// the lowest indexed head across the plan's subgraphs on that network, so
+    /// no leg can see data another leg hasn't indexed yet. An explicit `block`
+    /// pin takes precedence and applies to every network.
+    async fn execute_query_plan(
+        &self,
+        plan: &QueryPlan,
+        block: &BlockPin,
+        snapshot: bool,
+    ) -> Result<Value> {
+        let mut legs: Vec<PlanLeg> = Vec::new();
+        let mut failed_legs: Vec<Value> = Vec::new();
+        let mut snapshot_blocks = serde_json::Map::new();
+
+        // Resolve each network (and its snapshot block) up front; failures
+        // here fail every leg on that network
+        for network_str in &plan.target_networks {
+            let resolved = match Self::parse_network(network_str) {
+                Ok(network) if block.is_latest() && snapshot => self
+                    .resolve_snapshot_block(network, &plan.target_protocols)
+                    .await
+                    .map(|number| {
+                        snapshot_blocks.insert(network_str.clone(), json!(number));
+                        (network, BlockPin::Number(number))
+                    })
+                    .map_err(|e| (LegErrorClass::Snapshot, e.to_string())),
+                Ok(network) => Ok((network, block.clone())),
+                Err(e) => Err((LegErrorClass::UnknownNetwork, e.to_string())),
+            };
+
+            for protocol_str in &plan.target_protocols {
+                match &resolved {
+                    Ok((network, block)) => legs.push(PlanLeg {
+                        network: *network,
+                        network_str,
+                        protocol_str,
+                        block: block.clone(),
+                    }),
+                    Err((class, error)) => failed_legs.push(json!({
+                        "network": network_str,
+                        "protocol": protocol_str,
+                        "error_class": class,
+                        "error": error,
+                        "latency_ms": 0,
+                    })),
+                }
+            }
+        }
+
+        let limit = plan
+            .expected_data_points
+            .max(10)
+            .min(u32::try_from(self.max_results).unwrap_or(u32::MAX));
+
+        // Run legs concurrently; each leg is bounded by its own timeout so
+        // one slow indexer can't stall the whole plan
+        let mut outcomes: Vec<_> = stream::iter(legs.iter().enumerate())
+            .map(|(index, leg)| async move {
+                let started = Instant::now();
+                let outcome = match tokio::time::timeout(
+                    self.plan_leg_timeout,
+                    track_served(self.run_plan_leg(plan, leg, limit)),
+                )
+                .await
+                {
+                    Ok((outcome, served_by)) => {
+                        outcome.map(|(data, freshness)| (data, freshness, served_by))
+                    }
+                    Err(_) => Err((
+                        LegErrorClass::Timeout,
+                        format!(
+                            "Leg timed out after {}ms",
+                            self.plan_leg_timeout.as_millis()
+                        ),
+                    )),
+                };
+                let latency_ms = started.elapsed().as_millis() as u64;
+                (index, leg, latency_ms, outcome)
+            })
+            .buffer_unordered(self.plan_concurrency)
+            .collect()
+            .await;
+        // Keep output order stable regardless of completion order
+        outcomes.sort_by_key(|(index, ..)| *index);
+
+        let mut results: Vec<Value> = Vec::new();
+        for (_, leg, latency_ms, outcome) in outcomes {
+            match outcome {
+                Ok((data, freshness, served_by)) => results.push(json!({
+                    "network": leg.network_str,
+                    "protocol": leg.protocol_str,
+                    "block": leg.block.to_variable(),
+                    "latency_ms": latency_ms,
+                    "data_age_seconds": freshness.as_ref().and_then(|f| f.data_age_seconds),
+                    "freshness": freshness,
+                    "served_by": served_by,
+                    "data": data
+                })),
+                Err((class, error)) => {
+                    tracing::warn!(
+                        network = leg.network_str,
+                        protocol = leg.protocol_str,
+                        error_class = ?class,
+                        error = %error,
+                        "Query failed in query plan execution"
+                    );
+                    failed_legs.push(json!({
+                        "network": leg.network_str,
+                        "protocol": leg.protocol_str,
+                        "error_class": class,
+                        "error": error,
+                        "latency_ms": latency_ms,
+                    }));
+                }
+            }
         }
 
+        let total_legs = results.len() + failed_legs.len();
+        let success_rate = if total_legs == 0 {
+            Value::Null
+        } else {
+            json!(results.len() as f64 / total_legs as f64)
+        };
+
         Ok(json!({
             "query_plan": {
                 "target_networks": plan.target_networks,
@@ -682,11 +2015,71 @@ impl TheGraphTool {
                 "priority": plan.query_priority,
                 "expected_data_points": plan.expected_data_points
             },
+            "snapshot_blocks": if snapshot { Value::Object(snapshot_blocks) } else { Value::Null },
+            "success_rate": success_rate,
+            "failed_networks": failed_networks(&plan.target_networks, &results),
+            "failed_legs": failed_legs,
             "results": results
         }))
     }
 
-    fn parse_network(s: &str) -> Result<Network> {
+    /// Run one network/protocol leg of a query plan
+    ///
+    /// Unpinned legs are freshness-checked with their data, so a lagging
+    /// subgraph fails its leg (or fails over) instead of returning stale data.
+    async fn run_plan_leg(
+        &self,
+        plan: &QueryPlan,
+        leg: &PlanLeg<'_>,
+        limit: u32,
+    ) -> std::result::Result<(Value, Option<SubgraphFreshness>), (LegErrorClass, String)> {
+        let protocol = match leg.protocol_str.as_str() {
+            "uniswap_v3" => Protocol::UniswapV3,
+            "aave_v3" => Protocol::AaveV3,
+            other => {
+                return Err((
+                    LegErrorClass::InvalidArgument,
+                    format!("Unsupported protocol in query plan: {}", other),
+                ))
+            }
+        };
+
+        let query = async {
+            match protocol {
+                // Use filtered_pools with plan's filters
+                Protocol::UniswapV3 => {
+                    self.query_filtered_pools(leg.network, &plan.data_filters, limit, &leg.block)
+                        .await
+                }
+                // Lending rates for swap-vs-park decisions
+                Protocol::AaveV3 => {
+                    self.query_aave_reserves(leg.network, limit, &leg.block)
+                        .await
+                }
+            }
+        };
+        let query = track_query_error(query);
+        let ((data, kind), freshness) = if leg.block.is_latest() {
+            track_freshness(query).await
+        } else {
+            (query.await, None)
+        };
+        let data = data.map_err(|e| (LegErrorClass::classify(&e, kind), e.to_string()))?;
+        Ok((data, freshness))
+    }
+
+    pub(crate) fn parse_protocol(s: &str) -> Result<Protocol> {
+        match s.to_lowercase().as_str() {
+            "uniswap_v3" => Ok(Protocol::UniswapV3),
+            "aave_v3" => Ok(Protocol::AaveV3),
+            _ => Err(BamlRtError::InvalidArgument(format!(
+                "Unknown protocol: {}. Supported: uniswap_v3, aave_v3",
+                s
+            ))),
+        }
+    }
+
+    pub(crate) fn parse_network(s: &str) -> Result<Network> {
         match s.to_lowercase().as_str() {
             "ethereum" | "mainnet" => Ok(Network::Ethereum),
             "arbitrum" => Ok(Network::Arbitrum),
@@ -700,72 +2093,923 @@ impl TheGraphTool {
     }
 }
 
-impl Default for TheGraphTool {
-    fn default() -> Self {
-        Self::new()
+impl Default for TheGraphTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl BamlTool for TheGraphTool {
+    type Bundle = DefiBundle;
+    const LOCAL_NAME: &'static str = "query_subgraph";
+    type OpenInput = ();
+    type Input = GraphQueryInput;
+    type Output = AnyJson;
+
+    fn description(&self) -> &'static str {
+        "Queries DeFi protocol subgraphs for Uniswap V3 pool data, liquidity, prices, \
+         trading volumes, tick depth, and swap/liquidity order flow, and Aave V3 lending rates, reserves, and user health factors. \
+         The custom query type runs a read-only GraphQL query written against defi/subgraph_schema. \
+         Supports Ethereum, Arbitrum, Optimism, and Base."
+    }
+
+    async fn execute(&self, args: Self::Input) -> Result<Self::Output> {
+        let (result, served_by) = track_served(self.run_query(args)).await;
+        let mut result = result?;
+        // Say which endpoints answered (query plans report this per leg)
+        if !served_by.is_empty() {
+            if let Some(obj) = result.as_object_mut() {
+                obj.insert("served_by".to_string(), json!(served_by));
+            }
+        }
+        Ok(AnyJson::new(result))
+    }
+}
+
+impl TheGraphTool {
+    /// Run a `defi/query_subgraph` call
+    async fn run_query(&self, args: GraphQueryInput) -> Result<Value> {
+        let network = Self::parse_network(&args.network)?;
+        let params = args.params.as_ref();
+        let block = block_pin_from_params(params)?;
+
+        // Reject stale data before the agent acts on it, read from the `_meta`
+        // each query selects alongside its data. Query plans check each leg;
+        // pinned queries are historical by design.
+        let check_freshness = matches!(args.protocol.as_str(), "uniswap_v3" | "aave_v3")
+            && block.is_latest()
+            && !matches!(args.query_type, GraphQueryType::QueryPlan);
+
+        // Arguments are validated before any query is sent
+        let query = async {
+            Ok::<_, BamlRtError>(match (args.protocol.as_str(), &args.query_type) {
+                ("uniswap_v3", GraphQueryType::TopPools) => {
+                    let limit = params.and_then(|p| p.limit).unwrap_or(10);
+                    self.query_uniswap_top_pools(network, limit, &block).await?
+                }
+                ("uniswap_v3", GraphQueryType::PoolInfo) => {
+                    let pool_id = params.and_then(|p| p.pool_id.as_deref()).ok_or_else(|| {
+                        BamlRtError::InvalidArgument("Missing 'pool_id' in params".to_string())
+                    })?;
+                    self.query_uniswap_pool(network, pool_id, &block).await?
+                }
+                ("uniswap_v3", GraphQueryType::TokenPrice) => {
+                    let token_address = params
+                        .and_then(|p| p.token_address.as_deref())
+                        .ok_or_else(|| {
+                            BamlRtError::InvalidArgument(
+                                "Missing 'token_address' in params".to_string(),
+                            )
+                        })?;
+                    self.query_token_price(network, token_address, &block)
+                        .await?
+                }
+                ("uniswap_v3", GraphQueryType::FilteredPools) => {
+                    let filters = params.and_then(|p| p.filters.clone()).unwrap_or_default();
+                    let limit = params.and_then(|p| p.limit).unwrap_or(10);
+                    self.query_filtered_pools(network, &filters, limit, &block)
+                        .await?
+                }
+                ("uniswap_v3", GraphQueryType::QueryPlan) => {
+                    let plan = params.and_then(|p| p.query_plan.clone()).ok_or_else(|| {
+                        BamlRtError::InvalidArgument("Missing 'query_plan' in params".to_string())
+                    })?;
+                    let snapshot = params.and_then(|p| p.snapshot).unwrap_or(false);
+                    self.execute_query_plan(&plan, &block, snapshot).await?
+                }
+                ("uniswap_v3", GraphQueryType::PoolDayData)
+                | ("uniswap_v3", GraphQueryType::PoolHourData) => {
+                    let pool_id = params.and_then(|p| p.pool_id.as_deref()).ok_or_else(|| {
+                        BamlRtError::InvalidArgument("Missing 'pool_id' in params".to_string())
+                    })?;
+                    let (granularity, lookback) = match args.query_type {
+                        GraphQueryType::PoolHourData => {
+                            (TimeGranularity::Hour, DEFAULT_HOUR_LOOKBACK_SECS)
+                        }
+                        _ => (TimeGranularity::Day, DEFAULT_DAY_LOOKBACK_SECS),
+                    };
+                    let (start, end) = Self::time_range(params, lookback)?;
+                    self.query_pool_time_series(network, pool_id, granularity, start, end, &block)
+                        .await?
+                }
+                ("uniswap_v3", GraphQueryType::TokenDayData) => {
+                    let token_address = params
+                        .and_then(|p| p.token_address.as_deref())
+                        .ok_or_else(|| {
+                            BamlRtError::InvalidArgument(
+                                "Missing 'token_address' in params".to_string(),
+                            )
+                        })?;
+                    let (start, end) = Self::time_range(params, DEFAULT_DAY_LOOKBACK_SECS)?;
+                    self.query_token_day_data(network, token_address, start, end, &block)
+                        .await?
+                }
+                ("uniswap_v3", GraphQueryType::Ticks) => {
+                    let pool_id = params.and_then(|p| p.pool_id.as_deref()).ok_or_else(|| {
+                        BamlRtError::InvalidArgument("Missing 'pool_id' in params".to_string())
+                    })?;
+                    let spacings = params
+                        .and_then(|p| p.tick_spacings)
+                        .unwrap_or(DEFAULT_TICK_SPACINGS);
+                    let trade = match params.and_then(|p| p.amount_in) {
+                        Some(amount_in) => {
+                            let token_in = params
+                                .and_then(|p| p.token_address.as_deref())
+                                .ok_or_else(|| {
+                                    BamlRtError::InvalidArgument(
+                                        "Missing 'token_address' (input token) for 'amount_in'"
+                                            .to_string(),
+                                    )
+                                })?;
+                            Some((token_in, amount_in))
+                        }
+                        None => None,
+                    };
+                    self.query_pool_ticks(network, pool_id, spacings, trade, &block)
+                        .await?
+                }
+                ("uniswap_v3", GraphQueryType::Swaps)
+                | ("uniswap_v3", GraphQueryType::Mints)
+                | ("uniswap_v3", GraphQueryType::Burns) => {
+                    let kind = match args.query_type {
+                        GraphQueryType::Mints => FlowEvents::Mints,
+                        GraphQueryType::Burns => FlowEvents::Burns,
+                        _ => FlowEvents::Swaps,
+                    };
+                    let (start, end) = Self::time_range(params, TRAILING_WINDOW_SECS)?;
+                    let window = EventWindow {
+                        scope: event_scope_from_params(params)?,
+                        start,
+                        end,
+                    };
+                    let limit = params.and_then(|p| p.limit).unwrap_or(DEFAULT_EVENT_LIMIT);
+                    let large_trade_usd = params
+                        .and_then(|p| p.large_trade_usd)
+                        .unwrap_or(DEFAULT_LARGE_TRADE_USD);
+                    self.query_flow_events(network, kind, &window, limit, large_trade_usd, &block)
+                        .await?
+                }
+                (protocol, GraphQueryType::Custom) => {
+                    let protocol = Self::parse_protocol(protocol)?;
+                    let query = params.and_then(|p| p.query.as_deref()).ok_or_else(|| {
+                        BamlRtError::InvalidArgument("Missing 'query' in params".to_string())
+                    })?;
+                    if !block.is_latest() {
+                        return Err(BamlRtError::InvalidArgument(
+                            "Custom queries are pinned with a `block` argument in the query, \
+                         not block_number/block_hash"
+                                .to_string(),
+                        ));
+                    }
+                    let variables = params
+                        .and_then(|p| p.variables.clone())
+                        .map(Value::from)
+                        .unwrap_or_else(|| json!({}));
+                    self.query_custom(network, protocol, query, variables)
+                        .await?
+                }
+                ("aave_v3", GraphQueryType::Reserves) => {
+                    let limit = params.and_then(|p| p.limit).unwrap_or(20);
+                    self.query_aave_reserves(network, limit, &block).await?
+                }
+                ("aave_v3", GraphQueryType::UserPositions) => {
+                    let user = params
+                        .and_then(|p| p.user_address.as_deref())
+                        .ok_or_else(|| {
+                            BamlRtError::InvalidArgument(
+                                "Missing 'user_address' in params".to_string(),
+                            )
+                        })?;
+                    self.query_aave_user_positions(network, user, &block)
+                        .await?
+                }
+                ("aave_v3", GraphQueryType::QueryPlan) => {
+                    let plan = params.and_then(|p| p.query_plan.clone()).ok_or_else(|| {
+                        BamlRtError::InvalidArgument("Missing 'query_plan' in params".to_string())
+                    })?;
+                    let snapshot = params.and_then(|p| p.snapshot).unwrap_or(false);
+                    self.execute_query_plan(&plan, &block, snapshot).await?
+                }
+                _ => {
+                    return Err(BamlRtError::InvalidArgument(format!(
+                        "Unsupported query: {}/{:?}",
+                        args.protocol, args.query_type
+                    )))
+                }
+            })
+        };
+        let (result, freshness) = if check_freshness {
+            track_freshness(query).await
+        } else {
+            (query.await, None)
+        };
+        let mut result = result?;
+
+        if let Some(freshness) = freshness {
+            if let Some(obj) = result.as_object_mut() {
+                obj.insert(
+                    "data_age_seconds".to_string(),
+                    json!(freshness.data_age_seconds),
+                );
+                obj.insert("freshness".to_string(), json!(freshness));
+            }
+        }
+
+        // Report the pin so results are reproducible
+        if !block.is_latest() {
+            if let Some(obj) = result.as_object_mut() {
+                obj.insert("block".to_string(), block.to_variable());
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// One network/protocol pair of a query plan
+struct PlanLeg<'a> {
+    network: Network,
+    network_str: &'a String,
+    protocol_str: &'a String,
+    block: BlockPin,
+}
+
+/// Why a query plan leg failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum LegErrorClass {
+    /// Leg exceeded the per-leg timeout
+    Timeout,
+    /// Subgraph is too far behind the chain head or reports indexing errors
+    Stale,
+    /// Network name not recognized
+    UnknownNetwork,
+    /// Snapshot block could not be resolved for the network
+    Snapshot,
+    /// Invalid input (unsupported protocol, missing endpoint, ...)
+    InvalidArgument,
+    /// Transport failure, timeout, server error or rate limit reaching the
+    /// subgraph or gateway
+    Transport,
+    /// Endpoint refused the request (client error status)
+    Rejected,
+    /// Subgraph returned GraphQL errors
+    Graphql,
+    /// Query needs payment that can't be made or the daily budget is used up
+    Payment,
+    /// Subgraph's circuit breaker is open
+    CircuitOpen,
+    /// Response was missing data or could not be parsed
+    BadResponse,
+}
+
+impl LegErrorClass {
+    /// Class of a leg's error, given the kind of its last failed subgraph
+    /// query (None if it had none, e.g. the response failed to decode)
+    fn classify(e: &BamlRtError, kind: Option<QueryErrorKind>) -> Self {
+        match kind {
+            Some(QueryErrorKind::Transport) => LegErrorClass::Transport,
+            Some(QueryErrorKind::Rejected) => LegErrorClass::Rejected,
+            Some(QueryErrorKind::Graphql) => LegErrorClass::Graphql,
+            Some(QueryErrorKind::Stale) => LegErrorClass::Stale,
+            Some(QueryErrorKind::Payment) => LegErrorClass::Payment,
+            Some(QueryErrorKind::CircuitOpen) => LegErrorClass::CircuitOpen,
+            Some(QueryErrorKind::BadResponse) => LegErrorClass::BadResponse,
+            Some(QueryErrorKind::InvalidArgument) => LegErrorClass::InvalidArgument,
+            None => match e {
+                BamlRtError::InvalidArgument(_) => LegErrorClass::InvalidArgument,
+                _ => LegErrorClass::BadResponse,
+            },
+        }
+    }
+}
+
+/// What went wrong with a subgraph query, for failover and leg reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QueryErrorKind {
+    /// No usable answer: connection failure, timeout, server error or rate limit
+    Transport,
+    /// Endpoint refused the request with a client error status
+    Rejected,
+    /// The query's own GraphQL errors
+    Graphql,
+    /// The endpoint is behind: it failed the freshness policy or its GraphQL
+    /// errors say the data isn't indexed yet
+    Stale,
+    /// Payment needed that can't be made, or the query budget is used up
+    Payment,
+    /// The subgraph's circuit breaker is open
+    CircuitOpen,
+    /// Answer without data, or one that could not be parsed
+    BadResponse,
+    /// Bad input or an unusable endpoint
+    InvalidArgument,
+}
+
+/// A failed subgraph query and its kind
+#[derive(Debug)]
+struct QueryError {
+    kind: QueryErrorKind,
+    message: String,
+}
+
+impl QueryError {
+    fn new(kind: QueryErrorKind, message: impl ToString) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+        }
+    }
+
+    /// Note this error's kind for [`track_query_error`] and convert it for
+    /// the caller
+    fn noted(self) -> BamlRtError {
+        note_query_error(Some(self.kind));
+        self.into()
+    }
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<QueryError> for BamlRtError {
+    fn from(e: QueryError) -> Self {
+        match e.kind {
+            QueryErrorKind::InvalidArgument => BamlRtError::InvalidArgument(e.message),
+            _ => BamlRtError::ToolExecution(e.message),
+        }
+    }
+}
+
+/// Kind of a gateway failure; a paid request that failed goes by its cause
+fn gateway_error_kind(e: &GatewayError) -> QueryErrorKind {
+    match e {
+        GatewayError::HttpError(_)
+        | GatewayError::Timeout(_)
+        | GatewayError::AllIndexersFailed { .. } => QueryErrorKind::Transport,
+        GatewayError::HttpStatus { .. } if e.is_transient() => QueryErrorKind::Transport,
+        GatewayError::HttpStatus { .. } => QueryErrorKind::Rejected,
+        GatewayError::GraphQLError(message) => graphql_error_kind(message),
+        GatewayError::NoData | GatewayError::InvalidResponse(_) => QueryErrorKind::BadResponse,
+        GatewayError::SubgraphNotFound(_) => QueryErrorKind::InvalidArgument,
+        GatewayError::CircuitOpen { .. } => QueryErrorKind::CircuitOpen,
+        GatewayError::PaymentRequired(_) | GatewayError::BudgetExceeded { .. } => {
+            QueryErrorKind::Payment
+        }
+        GatewayError::PaidRequestFailed { error, .. } => gateway_error_kind(error),
+    }
+}
+
+impl From<GatewayError> for QueryError {
+    fn from(e: GatewayError) -> Self {
+        let kind = gateway_error_kind(&e);
+        let message = match kind {
+            QueryErrorKind::CircuitOpen => format!("{} (see defi/graph_gateway_status)", e),
+            QueryErrorKind::Payment => format!("Gateway: {} (see defi/graph_usage)", e),
+            QueryErrorKind::Graphql | QueryErrorKind::Stale => e.to_string(),
+            _ => format!("Gateway: {}", e),
+        };
+        Self::new(kind, message)
+    }
+}
+
+/// GraphQL error messages of an endpoint that hasn't indexed far enough, as
+/// graph-node and the gateway word them (lowercase)
+const NOT_INDEXED_YET: &[&str] = &[
+    "not yet indexed",
+    "not yet available",
+    "has only indexed up to block",
+    "has not started syncing",
+    "indexing error",
+    "bad indexers",
+];
+
+/// Kind of a query's GraphQL errors: [`QueryErrorKind::Stale`] if they say the
+/// endpoint is behind (another endpoint may answer), else
+/// [`QueryErrorKind::Graphql`]
+fn graphql_error_kind(messages: &str) -> QueryErrorKind {
+    let messages = messages.to_lowercase();
+    if NOT_INDEXED_YET
+        .iter()
+        .any(|pattern| messages.contains(pattern))
+    {
+        QueryErrorKind::Stale
+    } else {
+        QueryErrorKind::Graphql
+    }
+}
+
+tokio::task_local! {
+    /// Kind of the last subgraph query error in the current plan leg
+    static QUERY_ERROR: Cell<Option<QueryErrorKind>>;
+}
+
+/// Run `call`, also returning the kind of its last subgraph query error
+/// (None if its last subgraph query succeeded)
+async fn track_query_error<F: Future>(call: F) -> (F::Output, Option<QueryErrorKind>) {
+    QUERY_ERROR
+        .scope(Cell::new(None), async move {
+            let output = call.await;
+            (output, QUERY_ERROR.with(Cell::get))
+        })
+        .await
+}
+
+/// Note the outcome of a subgraph query (a no-op outside [`track_query_error`])
+fn note_query_error(kind: Option<QueryErrorKind>) {
+    let _ = QUERY_ERROR.try_with(|last| last.set(kind));
+}
+
+/// Networks in a plan where no leg returned data
+fn failed_networks(target_networks: &[String], results: &[Value]) -> Vec<String> {
+    target_networks
+        .iter()
+        .filter(|network| {
+            !results
+                .iter()
+                .any(|r| r.get("network").and_then(|v| v.as_str()) == Some(network.as_str()))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Resolve the block pin requested in params
+fn block_pin_from_params(params: Option<&GraphQueryParams>) -> Result<BlockPin> {
+    let number = params.and_then(|p| p.block_number);
+    let hash = params.and_then(|p| p.block_hash.clone());
+    match (number, hash) {
+        (Some(_), Some(_)) => Err(BamlRtError::InvalidArgument(
+            "Specify either 'block_number' or 'block_hash', not both".to_string(),
+        )),
+        (Some(number), None) => Ok(BlockPin::Number(number)),
+        (None, Some(hash)) => Ok(BlockPin::Hash(hash.to_lowercase())),
+        (None, None) => Ok(BlockPin::Latest),
+    }
+}
+
+/// Build the server-side `Pool_filter` for plan filters
+///
+/// Each token pair becomes two `or` branches (either token order). The `or`
+/// list is nested under `and` because Graph Node rejects column filters next
+/// to `or` at the same level, and pagination adds its TVL bound at the top.
+fn pool_filter_from(
+    filters: &QueryFilters,
+    chain_id: u64,
+) -> Result<uniswap_v3::pools::Pool_filter> {
+    use uniswap_v3::pools::Pool_filter;
+
+    let exclude = match &filters.exclude_tokens {
+        Some(tokens) if !tokens.is_empty() => {
+            let mut addresses = Vec::new();
+            for token in tokens {
+                // An exclusion that names no known token excludes nothing;
+                // it shouldn't fail the whole query
+                match resolve_token_addresses(token, chain_id) {
+                    Ok(resolved) => addresses.extend(resolved),
+                    Err(e) => tracing::warn!(
+                        token = %token,
+                        chain_id,
+                        error = %e,
+                        "Ignoring unknown token in exclude_tokens"
+                    ),
+                }
+            }
+            (!addresses.is_empty()).then_some(addresses)
+        }
+        _ => None,
+    };
+
+    let pairs = match &filters.token_pairs {
+        Some(pairs) if !pairs.is_empty() => {
+            let mut branches = Vec::with_capacity(pairs.len() * 2);
+            for pair in pairs {
+                let (a, b) = pair
+                    .split_once('/')
+                    .or_else(|| pair.split_once('-'))
+                    .ok_or_else(|| {
+                        BamlRtError::InvalidArgument(format!(
+                            "Invalid token pair '{}', expected e.g. 'WETH/USDC'",
+                            pair
+                        ))
+                    })?;
+                let a = resolve_token_addresses(a, chain_id)?;
+                let b = resolve_token_addresses(b, chain_id)?;
+                branches.push(Some(Pool_filter {
+                    token0_in: Some(a.clone()),
+                    token1_in: Some(b.clone()),
+                    ..Default::default()
+                }));
+                branches.push(Some(Pool_filter {
+                    token0_in: Some(b),
+                    token1_in: Some(a),
+                    ..Default::default()
+                }));
+            }
+            Some(vec![Some(Pool_filter {
+                or: Some(branches),
+                ..Default::default()
+            })])
+        }
+        _ => None,
+    };
+
+    Ok(Pool_filter {
+        total_value_locked_usd_gte: filters.min_tvl_usd.map(|tvl| tvl.to_string()),
+        fee_tier_in: filters
+            .fee_tiers
+            .as_ref()
+            .map(|tiers| tiers.iter().map(|f| f.to_string()).collect()),
+        token0_not_in: exclude.clone(),
+        token1_not_in: exclude,
+        and: pairs,
+        ..Default::default()
+    })
+}
+
+/// Resolve a token given as an address or registry symbol to subgraph ids
+///
+/// Symbols resolve through the token registry rather than the subgraph's
+/// `symbol` field, which any token contract can claim.
+fn resolve_token_addresses(token: &str, chain_id: u64) -> Result<Vec<String>> {
+    let token = token.trim();
+    if let Ok(address) = token.parse::<Address>() {
+        return Ok(vec![address.to_string().to_lowercase()]);
+    }
+
+    // Pools hold wrapped ETH
+    let symbol = if token.eq_ignore_ascii_case("ETH") {
+        "WETH"
+    } else {
+        token
+    };
+    let addresses = tokens::registry().addresses_for_symbol(chain_id, symbol);
+    if addresses.is_empty() {
+        return Err(BamlRtError::InvalidArgument(format!(
+            "Unknown token '{}' on chain {}; pass its address instead",
+            token, chain_id
+        )));
+    }
+    Ok(addresses
+        .iter()
+        .map(|a| a.to_string().to_lowercase())
+        .collect())
+}
+
+/// Resolve the pool or token an event query is scoped to
+fn event_scope_from_params(params: Option<&GraphQueryParams>) -> Result<EventScope> {
+    let pool = params.and_then(|p| p.pool_id.as_deref());
+    let token = params.and_then(|p| p.token_address.as_deref());
+    match (pool, token) {
+        (Some(_), Some(_)) => Err(BamlRtError::InvalidArgument(
+            "Specify either 'pool_id' or 'token_address', not both".to_string(),
+        )),
+        (Some(pool), None) => Ok(EventScope::Pool(pool.to_lowercase())),
+        (None, Some(token)) => Ok(EventScope::Token(token.to_lowercase())),
+        (None, None) => Err(BamlRtError::InvalidArgument(
+            "Missing 'pool_id' or 'token_address' in params".to_string(),
+        )),
+    }
+}
+
+/// Tick window of whole spacing ranges either side of the current tick's range
+fn tick_window(tick: i32, spacing: i32, spacings: u32) -> (i32, i32) {
+    let spacings = spacings.min(MAX_TICK_SPACINGS) as i32;
+    let range_start = tick.div_euclid(spacing) * spacing;
+    (
+        range_start - spacings * spacing,
+        range_start + (spacings + 1) * spacing,
+    )
+}
+
+/// Seconds covered by a page of events (from the oldest event when truncated)
+fn covered_window(window: &EventWindow, oldest: Option<i64>, truncated: bool) -> i64 {
+    let start = match oldest {
+        Some(oldest) if truncated => oldest,
+        _ => window.start,
+    };
+    window.end - start
+}
+
+/// Surface a malformed subgraph value as a tool error
+fn decode_error(e: DecodeError) -> BamlRtError {
+    BamlRtError::ToolExecution(format!("Failed to decode subgraph response: {}", e))
+}
+
+/// Fetch a time series page by page, oldest first
+///
+/// Series hold one row per period in ascending order, so each page starts
+/// just after the last period of the one before.
+async fn page_series<T, F, Fut>(
+    start_time: i64,
+    timestamp: impl Fn(&T) -> i64,
+    mut fetch: F,
+) -> Result<Vec<T>>
+where
+    F: FnMut(i64) -> Fut,
+    Fut: Future<Output = Result<Vec<T>>>,
+{
+    let mut series = Vec::new();
+    let mut start = start_time;
+    loop {
+        let page = fetch(start).await?;
+        let page_len = page.len();
+        if let Some(last) = page.last() {
+            start = timestamp(last) + 1;
+        }
+        series.extend(page);
+        if page_len < MAX_PAGE_SIZE {
+            return Ok(series);
+        }
+    }
+}
+
+/// Position in a TVL-ordered walk of pools, kept across `fetch_pools_paginated` calls
+#[derive(Debug, Default)]
+struct PoolCursor {
+    /// Ids already returned
+    seen: HashSet<String>,
+    /// Continue strictly below this TVL (exact decimal string)
+    below: Option<String>,
+    /// Run of equal-TVL pools being walked by id: its TVL and the last id read
+    run: Option<(String, Option<String>)>,
+    /// No pools left under the filter
+    exhausted: bool,
+}
+
+/// Sort pools by TVL (desc), breaking ties by id
+fn sort_pools_by_tvl(pools: &mut [Pool]) {
+    pools.sort_by(|a, b| {
+        b.total_value_locked_usd
+            .total_cmp(&a.total_value_locked_usd)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+}
+
+/// Configured endpoints of a network/protocol pair resolved for querying
+#[derive(Debug, Clone)]
+struct SubgraphRoute {
+    /// Chain the subgraph indexes, for chain head lookups
+    chain_id: u64,
+    /// Usable endpoints in configured failover order
+    targets: Vec<RouteTarget>,
+}
+
+impl SubgraphRoute {
+    fn labels(&self) -> Vec<&str> {
+        self.targets.iter().map(|t| t.label.as_str()).collect()
+    }
+}
+
+/// One configured subgraph endpoint resolved for querying
+#[derive(Debug, Clone)]
+struct RouteTarget {
+    /// How the endpoint is reported and tracked for health
+    label: String,
+    /// URL for direct queries (None for a gateway endpoint without an API key)
+    url: Option<String>,
+    /// Subgraph or deployment ID when the gateway can serve the endpoint
+    gateway_id: Option<String>,
+    /// Extra headers for direct queries
+    headers: Vec<(String, String)>,
+}
+
+/// Time series granularity
+#[derive(Debug, Clone, Copy)]
+enum TimeGranularity {
+    Day,
+    Hour,
+}
+
+impl TimeGranularity {
+    fn name(&self) -> &'static str {
+        match self {
+            TimeGranularity::Day => "day",
+            TimeGranularity::Hour => "hour",
+        }
+    }
+}
+
+/// Event type for flow queries
+#[derive(Debug, Clone, Copy)]
+enum FlowEvents {
+    Swaps,
+    Mints,
+    Burns,
+}
+
+impl FlowEvents {
+    fn name(&self) -> &'static str {
+        match self {
+            FlowEvents::Swaps => "swaps",
+            FlowEvents::Mints => "mints",
+            FlowEvents::Burns => "burns",
+        }
+    }
+}
+
+/// Trailing-window activity for a pool
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct PoolActivity {
+    volume_usd: f64,
+    fees_usd: f64,
+}
+
+/// Sum hourly rows per pool
+fn aggregate_pool_activity(rows: &[HourlyActivity]) -> HashMap<String, PoolActivity> {
+    let mut activity: HashMap<String, PoolActivity> = HashMap::new();
+    for row in rows {
+        let entry = activity.entry(row.pool_id.to_lowercase()).or_default();
+        entry.volume_usd += row.volume_usd;
+        entry.fees_usd += row.fees_usd;
     }
+    activity
 }
 
-#[async_trait]
-impl BamlTool for TheGraphTool {
-    type Bundle = DefiBundle;
-    const LOCAL_NAME: &'static str = "query_subgraph";
-    type OpenInput = ();
-    type Input = GraphQueryInput;
-    type Output = AnyJson;
+/// Set trailing 24h volume, fees, and volume/TVL on a pool
+fn annotate_trailing_activity(pool: &mut Pool, activity: &HashMap<String, PoolActivity>) {
+    let stats = activity
+        .get(&pool.id.to_lowercase())
+        .copied()
+        .unwrap_or_default();
+    let ratio = if pool.total_value_locked_usd > 0.0 {
+        stats.volume_usd / pool.total_value_locked_usd
+    } else {
+        0.0
+    };
+
+    pool.volume_24h_usd = Some(stats.volume_usd);
+    pool.fees_24h_usd = Some(stats.fees_usd);
+    pool.volume_tvl_ratio_24h = Some(ratio);
+}
 
-    fn description(&self) -> &'static str {
-        "Queries DeFi protocol subgraphs (Uniswap V3) for pool data, liquidity, \
-         prices, and trading volumes. Supports Ethereum and Arbitrum networks."
+/// Parse a numeric subgraph field (BigInt/BigDecimal strings or JSON numbers)
+fn parse_f64_field(value: &Value, key: &str) -> f64 {
+    match value.get(key) {
+        Some(Value::String(s)) => s.parse().unwrap_or(0.0),
+        Some(Value::Number(n)) => n.as_f64().unwrap_or(0.0),
+        _ => 0.0,
     }
+}
 
-    async fn execute(&self, args: Self::Input) -> Result<Self::Output> {
-        let network = Self::parse_network(&args.network)?;
-        let params = args.params.as_ref();
+/// Convert an Aave ray-denominated APR into a compounded APY (fraction)
+fn ray_rate_to_apy(rate_ray: f64) -> f64 {
+    let apr = rate_ray / RAY;
+    (1.0 + apr / SECONDS_PER_YEAR).powf(SECONDS_PER_YEAR) - 1.0
+}
 
-        let result = match (args.protocol.as_str(), &args.query_type) {
-            ("uniswap_v3", GraphQueryType::TopPools) => {
-                let limit = params.and_then(|p| p.limit).unwrap_or(10);
-                self.query_uniswap_top_pools(network, limit).await?
-            }
-            ("uniswap_v3", GraphQueryType::PoolInfo) => {
-                let pool_id = params.and_then(|p| p.pool_id.as_deref()).ok_or_else(|| {
-                    BamlRtError::InvalidArgument("Missing 'pool_id' in params".to_string())
-                })?;
-                self.query_uniswap_pool(network, pool_id).await?
-            }
-            ("uniswap_v3", GraphQueryType::TokenPrice) => {
-                let token_address =
-                    params
-                        .and_then(|p| p.token_address.as_deref())
-                        .ok_or_else(|| {
-                            BamlRtError::InvalidArgument(
-                                "Missing 'token_address' in params".to_string(),
-                            )
-                        })?;
-                self.query_token_price(network, token_address).await?
-            }
-            ("uniswap_v3", GraphQueryType::FilteredPools) => {
-                let filters = params.and_then(|p| p.filters.clone()).unwrap_or_default();
-                let limit = params.and_then(|p| p.limit).unwrap_or(10);
-                self.query_filtered_pools(network, &filters, limit).await?
-            }
-            ("uniswap_v3", GraphQueryType::QueryPlan) => {
-                let plan = params.and_then(|p| p.query_plan.clone()).ok_or_else(|| {
-                    BamlRtError::InvalidArgument("Missing 'query_plan' in params".to_string())
-                })?;
-                self.execute_query_plan(&plan).await?
-            }
-            _ => {
-                return Err(BamlRtError::InvalidArgument(format!(
-                    "Unsupported query: {}/{:?}",
-                    args.protocol, args.query_type
-                )))
-            }
-        };
+/// Scale a raw token amount down by its decimals
+fn from_base_units(raw: f64, decimals: f64) -> f64 {
+    raw / 10_f64.powi(decimals as i32)
+}
 
-        Ok(AnyJson::new(result))
+/// Aave V3 reserve price in USD
+fn aave_price_usd(reserve: &Value) -> f64 {
+    reserve
+        .get("price")
+        .map(|p| parse_f64_field(p, "priceInEth"))
+        .unwrap_or(0.0)
+        / 10_f64.powi(AAVE_PRICE_DECIMALS)
+}
+
+/// Normalize a raw Aave V3 reserve into rates, utilization, and liquidity
+fn summarize_aave_reserve(reserve: &Value) -> Value {
+    let decimals = parse_f64_field(reserve, "decimals");
+    let total_liquidity = from_base_units(parse_f64_field(reserve, "totalLiquidity"), decimals);
+    let available_liquidity =
+        from_base_units(parse_f64_field(reserve, "availableLiquidity"), decimals);
+    let total_debt = from_base_units(
+        parse_f64_field(reserve, "totalCurrentVariableDebt")
+            + parse_f64_field(reserve, "totalPrincipalStableDebt"),
+        decimals,
+    );
+    let utilization = if total_liquidity > 0.0 {
+        (1.0 - available_liquidity / total_liquidity).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let price_usd = aave_price_usd(reserve);
+
+    json!({
+        "id": reserve.get("id"),
+        "underlying_asset": reserve.get("underlyingAsset"),
+        "symbol": reserve.get("symbol"),
+        "name": reserve.get("name"),
+        "decimals": decimals as u32,
+        "supply_apy_percent": ray_rate_to_apy(parse_f64_field(reserve, "liquidityRate")) * 100.0,
+        "variable_borrow_apy_percent":
+            ray_rate_to_apy(parse_f64_field(reserve, "variableBorrowRate")) * 100.0,
+        "stable_borrow_apy_percent":
+            ray_rate_to_apy(parse_f64_field(reserve, "stableBorrowRate")) * 100.0,
+        "utilization_percent": utilization * 100.0,
+        "total_liquidity": total_liquidity,
+        "available_liquidity": available_liquidity,
+        "total_debt": total_debt,
+        "price_usd": price_usd,
+        "total_liquidity_usd": total_liquidity * price_usd,
+        "available_liquidity_usd": available_liquidity * price_usd,
+        "ltv_percent": parse_f64_field(reserve, "baseLTVasCollateral") / BPS * 100.0,
+        "liquidation_threshold_percent":
+            parse_f64_field(reserve, "reserveLiquidationThreshold") / BPS * 100.0,
+        "borrowing_enabled": reserve.get("borrowingEnabled"),
+        "collateral_enabled": reserve.get("usageAsCollateralEnabled"),
+        "is_frozen": reserve.get("isFrozen"),
+    })
+}
+
+/// A user's position in a single Aave V3 reserve
+#[derive(Debug, Clone)]
+struct AavePosition {
+    symbol: String,
+    underlying_asset: String,
+    supplied: f64,
+    borrowed: f64,
+    price_usd: f64,
+    used_as_collateral: bool,
+    /// Liquidation threshold as a fraction (e.g. 0.825)
+    liquidation_threshold: f64,
+    supply_apy: f64,
+    borrow_apy: f64,
+}
+
+impl AavePosition {
+    fn from_user_reserve(user_reserve: &Value) -> Self {
+        let null = Value::Null;
+        let reserve = user_reserve.get("reserve").unwrap_or(&null);
+        let decimals = parse_f64_field(reserve, "decimals");
+
+        Self {
+            symbol: reserve
+                .get("symbol")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            underlying_asset: reserve
+                .get("underlyingAsset")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            supplied: from_base_units(
+                parse_f64_field(user_reserve, "currentATokenBalance"),
+                decimals,
+            ),
+            borrowed: from_base_units(
+                parse_f64_field(user_reserve, "currentVariableDebt")
+                    + parse_f64_field(user_reserve, "currentStableDebt"),
+                decimals,
+            ),
+            price_usd: aave_price_usd(reserve),
+            used_as_collateral: user_reserve
+                .get("usageAsCollateralEnabledOnUser")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            liquidation_threshold: parse_f64_field(reserve, "reserveLiquidationThreshold") / BPS,
+            supply_apy: ray_rate_to_apy(parse_f64_field(reserve, "liquidityRate")),
+            borrow_apy: ray_rate_to_apy(parse_f64_field(reserve, "variableBorrowRate")),
+        }
+    }
+
+    fn supplied_usd(&self) -> f64 {
+        self.supplied * self.price_usd
+    }
+
+    fn borrowed_usd(&self) -> f64 {
+        self.borrowed * self.price_usd
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "symbol": self.symbol,
+            "underlying_asset": self.underlying_asset,
+            "supplied": self.supplied,
+            "supplied_usd": self.supplied_usd(),
+            "borrowed": self.borrowed,
+            "borrowed_usd": self.borrowed_usd(),
+            "used_as_collateral": self.used_as_collateral,
+            "liquidation_threshold_percent": self.liquidation_threshold * 100.0,
+            "supply_apy_percent": self.supply_apy * 100.0,
+            "variable_borrow_apy_percent": self.borrow_apy * 100.0,
+        })
+    }
+}
+
+/// Aave health factor: threshold-weighted collateral over debt
+///
+/// Returns `None` when there is no debt (the health factor is unbounded).
+fn health_factor(positions: &[AavePosition]) -> Option<f64> {
+    let debt: f64 = positions.iter().map(AavePosition::borrowed_usd).sum();
+    if debt <= 0.0 {
+        return None;
     }
+    let weighted_collateral: f64 = positions
+        .iter()
+        .filter(|p| p.used_as_collateral)
+        .map(|p| p.supplied_usd() * p.liquidation_threshold)
+        .sum();
+    Some(weighted_collateral / debt)
 }
 
 /// GraphQL response structure
@@ -783,6 +3027,9 @@ struct GraphQLError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::SubgraphEndpoint;
+    use crate::tools::graph_gateway::cache::{cache_key, MemoryCache};
+    use crate::tools::graph_gateway::{GatewayQueryResult, PaymentReceipt};
 
     #[test]
     fn test_parse_network() {
@@ -797,6 +3044,32 @@ mod tests {
         assert!(TheGraphTool::parse_network("invalid").is_err());
     }
 
+    #[test]
+    fn test_parse_protocol() {
+        assert!(matches!(
+            TheGraphTool::parse_protocol("Uniswap_V3"),
+            Ok(Protocol::UniswapV3)
+        ));
+        assert!(matches!(
+            TheGraphTool::parse_protocol("aave_v3"),
+            Ok(Protocol::AaveV3)
+        ));
+        assert!(TheGraphTool::parse_protocol("curve").is_err());
+    }
+
+    #[test]
+    fn test_query_budget_builder() {
+        assert_eq!(TheGraphTool::new().query_budget(), QueryBudget::default());
+        let budget = QueryBudget {
+            max_depth: 3,
+            max_first: 50,
+            max_root_fields: 2,
+            max_aliases: 4,
+        };
+        let tool = TheGraphTool::new().with_query_budget(budget);
+        assert_eq!(tool.query_budget(), budget);
+    }
+
     #[test]
     fn test_input_schema() {
         let tool = TheGraphTool::new();
@@ -820,6 +3093,428 @@ mod tests {
         let tool = TheGraphTool::with_gateway("test-api-key".to_string());
         assert!(tool.has_gateway());
         assert_eq!(tool.gateway_name(), Some("BasicGraphGateway"));
+        let stats = tool.gateway_cache_stats().unwrap();
+        assert_eq!(stats.hits + stats.misses, 0);
+        assert_eq!(stats.entries, Some(0));
+        assert!(TheGraphTool::new().gateway_cache_stats().is_none());
+    }
+
+    #[test]
+    fn test_endpoint_resolves_self_hosted_url_with_headers() {
+        let endpoints = SubgraphEndpoints::new(vec![SubgraphEndpoint::url(
+            Network::Ethereum,
+            Protocol::UniswapV3,
+            "http://127.0.0.1:8000/subgraphs/name/uniswap/v3",
+        )
+        .with_header("Authorization", "Bearer local")]);
+        let tool = TheGraphTool::from_parts(endpoints, None, None);
+
+        let route = &tool
+            .endpoint(Network::Ethereum, Protocol::UniswapV3)
+            .unwrap()
+            .targets[0];
+        assert_eq!(
+            route.url.as_deref(),
+            Some("http://127.0.0.1:8000/subgraphs/name/uniswap/v3")
+        );
+        assert_eq!(route.gateway_id, None);
+        assert_eq!(
+            route.headers,
+            vec![("Authorization".to_string(), "Bearer local".to_string())]
+        );
+        assert!(tool.endpoint(Network::Base, Protocol::UniswapV3).is_err());
+    }
+
+    #[test]
+    fn test_gateway_endpoint_needs_api_key_or_gateway() {
+        let without_key = TheGraphTool::from_parts(SubgraphEndpoints::default(), None, None);
+        let err = without_key
+            .endpoint(Network::Ethereum, Protocol::UniswapV3)
+            .unwrap_err();
+        assert!(err.to_string().contains(GRAPH_API_KEY_ENV));
+
+        let with_key = without_key.with_api_key("key".to_string());
+        let route = &with_key
+            .endpoint(Network::Ethereum, Protocol::UniswapV3)
+            .unwrap()
+            .targets[0];
+        assert_eq!(
+            route.url.as_deref().unwrap(),
+            format!(
+                "https://gateway.thegraph.com/api/key/subgraphs/id/{}",
+                SubgraphIds::UNISWAP_V3_ETHEREUM
+            )
+        );
+        assert_eq!(
+            route.gateway_id.as_deref(),
+            Some(SubgraphIds::UNISWAP_V3_ETHEREUM)
+        );
+    }
+
+    /// Ethereum Uniswap V3 served by a dead self-hosted node, then the
+    /// gateway (answering from `cache`), then a gateway endpoint that is
+    /// unusable without an API key
+    fn failover_tool(cache: Arc<MemoryCache>) -> TheGraphTool {
+        let endpoints = SubgraphEndpoints::new(vec![
+            // Nothing listens here, so direct queries fail to connect
+            SubgraphEndpoint::url(
+                Network::Ethereum,
+                Protocol::UniswapV3,
+                "http://127.0.0.1:9/subgraphs/name/uniswap/v3",
+            )
+            .with_name("self-hosted"),
+            SubgraphEndpoint::subgraph_id(
+                Network::Ethereum,
+                Protocol::UniswapV3,
+                SubgraphIds::UNISWAP_V3_ETHEREUM,
+            ),
+        ]);
+        let gateway = BasicGraphGateway::new("key".to_string())
+            .with_gateway_url("http://127.0.0.1:9")
+            .with_cache(cache);
+        TheGraphTool::with_endpoints_and_gateway(endpoints, Arc::new(gateway))
+    }
+
+    async fn seed(cache: &MemoryCache, subgraph_id: &str, query: &str, data: Value) {
+        let result = GatewayQueryResult {
+            data,
+            indexer: None,
+            latency_ms: 5,
+            cached: false,
+            stale: false,
+            subgraph_id: subgraph_id.to_string(),
+            payment: None,
+        };
+        cache
+            .put(
+                &cache_key(subgraph_id, query, &json!({})),
+                &result,
+                Duration::from_secs(60),
+            )
+            .await;
+    }
+
+    #[test]
+    fn test_endpoint_keeps_usable_failover_targets_in_order() {
+        let mut endpoints = SubgraphEndpoints::gateway_defaults();
+        endpoints.endpoints.insert(
+            0,
+            SubgraphEndpoint::url(
+                Network::Ethereum,
+                Protocol::UniswapV3,
+                "http://127.0.0.1:8000/subgraphs/name/uniswap/v3",
+            ),
+        );
+
+        // Without an API key or gateway only the self-hosted node is usable
+        let tool = TheGraphTool::from_parts(endpoints.clone(), None, None);
+        let route = tool
+            .endpoint(Network::Ethereum, Protocol::UniswapV3)
+            .unwrap();
+        assert_eq!(
+            route.labels(),
+            vec!["http://127.0.0.1:8000/subgraphs/name/uniswap/v3"]
+        );
+
+        let tool = TheGraphTool::from_parts(endpoints, Some("key".to_string()), None);
+        let route = tool
+            .endpoint(Network::Ethereum, Protocol::UniswapV3)
+            .unwrap();
+        assert_eq!(route.targets.len(), 2);
+        assert_eq!(
+            route.targets[1].label,
+            format!("gateway/subgraphs/{}", SubgraphIds::UNISWAP_V3_ETHEREUM)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_fails_over_to_next_endpoint() {
+        let cache = Arc::new(MemoryCache::new());
+        let query = "{ pools { id } }";
+        seed(
+            &cache,
+            SubgraphIds::UNISWAP_V3_ETHEREUM,
+            query,
+            json!({ "pools": [{ "id": "0x1" }] }),
+        )
+        .await;
+        let tool = failover_tool(cache);
+        let route = tool
+            .endpoint(Network::Ethereum, Protocol::UniswapV3)
+            .unwrap();
+        let gateway_label = format!("gateway/subgraphs/{}", SubgraphIds::UNISWAP_V3_ETHEREUM);
+
+        let (data, served_by) = track_served(tool.query_subgraph(&route, query, json!({}))).await;
+        assert_eq!(data.unwrap()["pools"][0]["id"], "0x1");
+        assert_eq!(served_by, vec![gateway_label.clone()]);
+
+        // A second failure demotes the self-hosted node behind the gateway
+        assert_eq!(tool.endpoint_health.order(&route.labels()), vec![0, 1]);
+        tool.query_subgraph(&route, query, json!({})).await.unwrap();
+        assert_eq!(tool.endpoint_health.order(&route.labels()), vec![1, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_lagging_endpoint_is_demoted() {
+        let cache = Arc::new(MemoryCache::new());
+        let now = chrono::Utc::now().timestamp();
+        let query = "{ pools { id } }";
+        // The checked query carries `_meta` in the same request as the data
+        let checked = with_meta_selection(query).unwrap();
+        let response = |id: &str, number: u64, timestamp: i64| {
+            json!({
+                "pools": [{ "id": id }],
+                "_freshness": {
+                    "block": { "number": number, "timestamp": timestamp },
+                    "hasIndexingErrors": false
+                }
+            })
+        };
+        let stale_id = "StaleSubgraph11111111111111111111111111111";
+        seed(
+            &cache,
+            stale_id,
+            &checked,
+            response("0xold", 100, now - 86_400),
+        )
+        .await;
+        seed(
+            &cache,
+            SubgraphIds::UNISWAP_V3_ETHEREUM,
+            &checked,
+            response("0xnew", 200, now - 5),
+        )
+        .await;
+        let endpoints = SubgraphEndpoints::new(vec![
+            SubgraphEndpoint::subgraph_id(Network::Ethereum, Protocol::UniswapV3, stale_id)
+                .with_name("lagging"),
+            SubgraphEndpoint::subgraph_id(
+                Network::Ethereum,
+                Protocol::UniswapV3,
+                SubgraphIds::UNISWAP_V3_ETHEREUM,
+            )
+            .with_name("fresh"),
+        ]);
+        let gateway = BasicGraphGateway::new("key".to_string())
+            .with_gateway_url("http://127.0.0.1:9")
+            .with_cache(cache);
+        let tool = TheGraphTool::with_endpoints_and_gateway(endpoints, Arc::new(gateway));
+
+        let route = tool
+            .endpoint(Network::Ethereum, Protocol::UniswapV3)
+            .unwrap();
+
+        let (data, freshness) =
+            track_freshness(tool.query_subgraph(&route, query, json!({}))).await;
+        assert_eq!(data.unwrap(), json!({ "pools": [{ "id": "0xnew" }] }));
+        assert_eq!(freshness.unwrap().subgraph_block, 200);
+        assert_eq!(
+            tool.endpoint_health.order(&["lagging", "fresh"]),
+            vec![1, 0]
+        );
+    }
+
+    /// Local subgraph endpoint answering every query with `body`; stops with
+    /// the test's runtime
+    async fn serve_json(body: Value) -> String {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/subgraphs/name/uniswap/v3",
+            listener.local_addr().unwrap()
+        );
+        let body = body.to_string();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                let mut line = String::new();
+                while reader.read_line(&mut line).await.unwrap_or(0) > 2 {
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap_or(0);
+                    }
+                    line.clear();
+                }
+                let mut request = vec![0; length];
+                let _ = reader.read_exact(&mut request).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                     Connection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = reader.into_inner().write_all(response.as_bytes()).await;
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_flow_events_truncated_only_when_more_exist() {
+        let swap = |n: i64| {
+            json!({
+                "id": format!("0xtx#{}", n),
+                "timestamp": (2_000 - n).to_string(),
+                "pool": { "id": "0xpool" },
+                "token0": { "id": "0x01", "symbol": "WETH" },
+                "token1": { "id": "0x02", "symbol": "USDC" },
+                "origin": "0xabc",
+                "amount0": "-1",
+                "amount1": "2000",
+                "amountUSD": "2000",
+                "tick": "0"
+            })
+        };
+        let window = &EventWindow {
+            scope: EventScope::Pool("0xpool".to_string()),
+            start: 1_000,
+            end: 2_000,
+        };
+        let flows = |count: i64| async move {
+            let url = serve_json(
+                json!({ "data": { "swaps": (0..count).map(swap).collect::<Vec<_>>() } }),
+            )
+            .await;
+            let endpoints = SubgraphEndpoints::new(vec![SubgraphEndpoint::url(
+                Network::Ethereum,
+                Protocol::UniswapV3,
+                &url,
+            )]);
+            TheGraphTool::from_parts(endpoints, None, None)
+                .query_flow_events(
+                    Network::Ethereum,
+                    FlowEvents::Swaps,
+                    window,
+                    3,
+                    10_000.0,
+                    &BlockPin::Latest,
+                )
+                .await
+                .unwrap()
+        };
+
+        // Exactly the limit: the window is complete
+        let complete = flows(3).await;
+        assert_eq!(complete["count"], 3);
+        assert_eq!(complete["truncated"], false);
+
+        // One more than the limit: cut short, and the extra event is dropped
+        let cut = flows(4).await;
+        assert_eq!(cut["count"], 3);
+        assert_eq!(cut["truncated"], true);
+        assert_eq!(cut["swaps"].as_array().unwrap().len(), 3);
+        assert_eq!(cut["oldest_timestamp"], 1_998);
+    }
+
+    #[test]
+    fn test_graphql_error_kind() {
+        assert_eq!(
+            graphql_error_kind(
+                "subgraph QmAbc has only indexed up to block number 100 and data for block \
+                 number 200 is therefore not yet available"
+            ),
+            QueryErrorKind::Stale
+        );
+        assert_eq!(
+            graphql_error_kind("Block not yet indexed"),
+            QueryErrorKind::Stale
+        );
+        assert_eq!(
+            graphql_error_kind("Type `Query` has no field `poolz`"),
+            QueryErrorKind::Graphql
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_fails_over_on_not_indexed_errors_only() {
+        let query = "{ pools { id } }";
+        let tool_behind = |url: &str, cache: Arc<MemoryCache>| {
+            let endpoints = SubgraphEndpoints::new(vec![
+                SubgraphEndpoint::url(Network::Ethereum, Protocol::UniswapV3, url)
+                    .with_name("self-hosted"),
+                SubgraphEndpoint::subgraph_id(
+                    Network::Ethereum,
+                    Protocol::UniswapV3,
+                    SubgraphIds::UNISWAP_V3_ETHEREUM,
+                ),
+            ]);
+            let gateway = BasicGraphGateway::new("key".to_string())
+                .with_gateway_url("http://127.0.0.1:9")
+                .with_cache(cache);
+            TheGraphTool::with_endpoints_and_gateway(endpoints, Arc::new(gateway))
+        };
+        let cache = Arc::new(MemoryCache::new());
+        seed(
+            &cache,
+            SubgraphIds::UNISWAP_V3_ETHEREUM,
+            query,
+            json!({ "pools": [{ "id": "0x1" }] }),
+        )
+        .await;
+
+        let behind = serve_json(json!({
+            "errors": [{ "message": "Block not yet indexed" }]
+        }))
+        .await;
+        let tool = tool_behind(&behind, Arc::clone(&cache));
+        let route = tool
+            .endpoint(Network::Ethereum, Protocol::UniswapV3)
+            .unwrap();
+        let data = tool.query_subgraph(&route, query, json!({})).await.unwrap();
+        assert_eq!(data["pools"][0]["id"], "0x1");
+        // Demoted right away, like a lagging endpoint
+        assert_eq!(tool.endpoint_health.order(&route.labels()), vec![1, 0]);
+
+        // The query's own errors would be the same anywhere
+        let invalid = serve_json(json!({
+            "errors": [{ "message": "Type `Query` has no field `poolz`" }]
+        }))
+        .await;
+        let tool = tool_behind(&invalid, cache);
+        let route = tool
+            .endpoint(Network::Ethereum, Protocol::UniswapV3)
+            .unwrap();
+        let (result, kind) = track_query_error(tool.query_subgraph(&route, query, json!({}))).await;
+        assert!(result.unwrap_err().to_string().contains("poolz"));
+        assert_eq!(kind, Some(QueryErrorKind::Graphql));
+        assert_eq!(tool.endpoint_health.order(&route.labels()), vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_budget_errors_do_not_demote_endpoints() {
+        let query = "{ pools { id } }";
+        let direct = serve_json(json!({ "data": { "pools": [{ "id": "0x2" }] } })).await;
+        let endpoints = SubgraphEndpoints::new(vec![
+            SubgraphEndpoint::subgraph_id(
+                Network::Ethereum,
+                Protocol::UniswapV3,
+                SubgraphIds::UNISWAP_V3_ETHEREUM,
+            )
+            .with_name("gateway"),
+            SubgraphEndpoint::url(Network::Ethereum, Protocol::UniswapV3, direct)
+                .with_name("self-hosted"),
+        ]);
+        let gateway = BasicGraphGateway::new("key".to_string())
+            .with_gateway_url("http://127.0.0.1:9")
+            .with_cache(Arc::new(MemoryCache::new()))
+            .with_usage(crate::config::GraphUsageSettings {
+                daily_query_limit: Some(0),
+                ..Default::default()
+            });
+        let tool = TheGraphTool::with_endpoints_and_gateway(endpoints, Arc::new(gateway));
+        let route = tool
+            .endpoint(Network::Ethereum, Protocol::UniswapV3)
+            .unwrap();
+
+        for _ in 0..3 {
+            let data = tool.query_subgraph(&route, query, json!({})).await.unwrap();
+            assert_eq!(data["pools"][0]["id"], "0x2");
+        }
+        assert_eq!(
+            tool.endpoint_health.order(&["gateway", "self-hosted"]),
+            vec![0, 1]
+        );
     }
 
     #[test]
@@ -851,7 +3546,543 @@ mod tests {
             TheGraphTool::get_subgraph_id(Network::Arbitrum, Protocol::UniswapV3),
             Some(SubgraphIds::UNISWAP_V3_ARBITRUM)
         );
-        // AaveV3 not configured for Uniswap V3
-        assert!(TheGraphTool::get_subgraph_id(Network::Ethereum, Protocol::AaveV3).is_none());
+        assert_eq!(
+            TheGraphTool::get_subgraph_id(Network::Ethereum, Protocol::AaveV3),
+            Some(SubgraphIds::AAVE_V3_ETHEREUM)
+        );
+        assert_eq!(
+            TheGraphTool::get_subgraph_id(Network::Base, Protocol::AaveV3),
+            Some(SubgraphIds::AAVE_V3_BASE)
+        );
+    }
+
+    fn test_pool(id: &str, tvl: f64) -> Pool {
+        let token = |id: &str, symbol: &str| Token {
+            id: id.to_string(),
+            symbol: symbol.to_string(),
+            name: symbol.to_string(),
+            decimals: 18,
+            derived_eth: 1.0,
+            volume_usd: None,
+            total_value_locked_usd: None,
+        };
+        Pool {
+            id: id.to_string(),
+            token0: token("0x01", "WETH"),
+            token1: token("0x02", "USDC"),
+            fee_tier: 500,
+            liquidity: 1e18,
+            sqrt_price: 1e30,
+            tick: Some(0),
+            token0_price: 1.0,
+            token1_price: 1.0,
+            volume_usd: 900_000_000_000.0,
+            total_value_locked_usd: tvl,
+            tx_count: 1,
+            volume_24h_usd: None,
+            fees_24h_usd: None,
+            volume_tvl_ratio_24h: None,
+        }
+    }
+
+    fn hourly(pool_id: &str, volume_usd: f64, fees_usd: f64) -> HourlyActivity {
+        HourlyActivity {
+            pool_id: pool_id.to_string(),
+            period_start: 0,
+            volume_usd,
+            fees_usd,
+        }
+    }
+
+    #[test]
+    fn test_block_pin_from_params() {
+        let params: GraphQueryParams =
+            serde_json::from_value(json!({ "block_number": 19_000_000 })).unwrap();
+        let pin = block_pin_from_params(Some(&params)).unwrap();
+        assert_eq!(pin, BlockPin::Number(19_000_000));
+
+        let params: GraphQueryParams =
+            serde_json::from_value(json!({ "block_hash": "0xABC" })).unwrap();
+        let pin = block_pin_from_params(Some(&params)).unwrap();
+        assert_eq!(pin.to_variable(), json!({ "hash": "0xabc" }));
+
+        assert!(block_pin_from_params(None).unwrap().is_latest());
+
+        let both: GraphQueryParams =
+            serde_json::from_value(json!({ "block_number": 1, "block_hash": "0x1" })).unwrap();
+        assert!(block_pin_from_params(Some(&both)).is_err());
+    }
+
+    #[test]
+    fn test_pool_filter_pushes_pairs_and_exclusions() {
+        let filters = QueryFilters {
+            min_tvl_usd: Some(1_000_000.0),
+            token_pairs: Some(vec!["WETH/usdc".to_string()]),
+            exclude_tokens: Some(vec![
+                "DAI".to_string(),
+                "0xdAC17F958D2ee523a2206206994597C13D831ec7".to_string(),
+            ]),
+            fee_tiers: Some(vec![500]),
+            ..Default::default()
+        };
+        let filter = pool_filter_from(&filters, 1).unwrap();
+
+        let weth = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+        let usdc = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+        let value = serde_json::to_value(&filter).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "token0_not_in": [
+                    "0x6b175474e89094c44da98b954eedeac495271d0f",
+                    "0xdac17f958d2ee523a2206206994597c13d831ec7"
+                ],
+                "token1_not_in": [
+                    "0x6b175474e89094c44da98b954eedeac495271d0f",
+                    "0xdac17f958d2ee523a2206206994597c13d831ec7"
+                ],
+                "feeTier_in": ["500"],
+                "totalValueLockedUSD_gte": "1000000",
+                "and": [{
+                    "or": [
+                        { "token0_in": [weth], "token1_in": [usdc] },
+                        { "token0_in": [usdc], "token1_in": [weth] }
+                    ]
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_pool_filter_skips_unknown_exclusions() {
+        let filters = QueryFilters {
+            exclude_tokens: Some(vec!["NOTATOKEN".to_string(), "DAI".to_string()]),
+            ..Default::default()
+        };
+        let filter = pool_filter_from(&filters, 1).unwrap();
+        let dai = "0x6b175474e89094c44da98b954eedeac495271d0f".to_string();
+        assert_eq!(filter.token0_not_in, Some(vec![dai.clone()]));
+        assert_eq!(filter.token1_not_in, Some(vec![dai]));
+
+        let filters = QueryFilters {
+            exclude_tokens: Some(vec!["NOTATOKEN".to_string()]),
+            ..Default::default()
+        };
+        let filter = pool_filter_from(&filters, 1).unwrap();
+        assert_eq!(filter.token0_not_in, None);
+
+        // Unknown tokens in pairs still fail: dropping one would widen the query
+        let filters = QueryFilters {
+            token_pairs: Some(vec!["NOTATOKEN/USDC".to_string()]),
+            ..Default::default()
+        };
+        assert!(pool_filter_from(&filters, 1).is_err());
+    }
+
+    #[test]
+    fn test_pool_filter_resolves_symbols_per_chain() {
+        let filters = QueryFilters {
+            token_pairs: Some(vec!["ETH-USDC.e".to_string()]),
+            ..Default::default()
+        };
+        let filter = pool_filter_from(&filters, 42161).unwrap();
+        let branches = filter.and.unwrap()[0].clone().unwrap().or.unwrap();
+        let first = branches[0].clone().unwrap();
+        assert_eq!(
+            first.token0_in,
+            Some(vec![
+                "0x82af49447d8a07e3bd95bd0d56f35241523fbab1".to_string()
+            ])
+        );
+        assert_eq!(
+            first.token1_in,
+            Some(vec![
+                "0xff970a61a04b1ca14834a43f5de4533ebddb5cc8".to_string()
+            ])
+        );
+
+        // No pair or exclusion filters leaves the where clause unconstrained
+        let empty = pool_filter_from(&QueryFilters::default(), 1).unwrap();
+        assert_eq!(serde_json::to_value(&empty).unwrap(), json!({}));
+    }
+
+    #[test]
+    fn test_pool_filter_rejects_unknown_tokens() {
+        let unknown = QueryFilters {
+            token_pairs: Some(vec!["PEPE/WETH".to_string()]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            pool_filter_from(&unknown, 1),
+            Err(BamlRtError::InvalidArgument(_))
+        ));
+
+        let malformed = QueryFilters {
+            token_pairs: Some(vec!["WETH".to_string()]),
+            ..Default::default()
+        };
+        assert!(pool_filter_from(&malformed, 1).is_err());
+    }
+
+    #[test]
+    fn test_event_scope_from_params() {
+        let params: GraphQueryParams =
+            serde_json::from_value(json!({ "token_address": "0xABC" })).unwrap();
+        assert_eq!(
+            event_scope_from_params(Some(&params)).unwrap(),
+            EventScope::Token("0xabc".to_string())
+        );
+
+        let both: GraphQueryParams =
+            serde_json::from_value(json!({ "pool_id": "0x1", "token_address": "0x2" })).unwrap();
+        assert!(event_scope_from_params(Some(&both)).is_err());
+        assert!(event_scope_from_params(None).is_err());
+    }
+
+    #[test]
+    fn test_tick_window_is_aligned_to_spacing() {
+        assert_eq!(tick_window(-201_005, 10, 2), (-201_030, -200_980));
+        assert_eq!(tick_window(120, 60, 1), (60, 240));
+        // Capped so the window fits in one page of ticks
+        let (lower, upper) = tick_window(0, 1, 10_000);
+        assert_eq!(upper - lower, (2 * MAX_TICK_SPACINGS + 1) as i32);
+    }
+
+    #[test]
+    fn test_covered_window_shrinks_when_truncated() {
+        let window = EventWindow {
+            scope: EventScope::Pool("0xpool".to_string()),
+            start: 1_000,
+            end: 5_000,
+        };
+        assert_eq!(covered_window(&window, Some(4_000), false), 4_000);
+        assert_eq!(covered_window(&window, Some(4_000), true), 1_000);
+        assert_eq!(covered_window(&window, None, true), 4_000);
+    }
+
+    #[tokio::test]
+    async fn test_page_series_fetches_ranges_longer_than_a_page() {
+        let days: Vec<i64> = (0..(MAX_PAGE_SIZE * 5 / 2) as i64)
+            .map(|day| day * 86_400)
+            .collect();
+        let requests = Cell::new(0);
+        let fetch = |start: i64| {
+            requests.set(requests.get() + 1);
+            let page: Vec<i64> = days
+                .iter()
+                .copied()
+                .filter(|day| *day >= start)
+                .take(MAX_PAGE_SIZE)
+                .collect();
+            async move { Ok(page) }
+        };
+
+        let series = page_series(0, |day: &i64| *day, fetch).await.unwrap();
+        assert_eq!(series, days);
+        assert_eq!(requests.get(), 3);
+
+        // A range that fills whole pages ends on an empty one
+        requests.set(0);
+        let series = page_series(
+            0,
+            |day: &i64| *day,
+            |start: i64| {
+                requests.set(requests.get() + 1);
+                let page: Vec<i64> = days[..MAX_PAGE_SIZE * 2]
+                    .iter()
+                    .copied()
+                    .filter(|day| *day >= start)
+                    .take(MAX_PAGE_SIZE)
+                    .collect();
+                async move { Ok(page) }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(series.len(), MAX_PAGE_SIZE * 2);
+        assert_eq!(requests.get(), 3);
+    }
+
+    #[test]
+    fn test_leg_error_classification() {
+        let status = |status| GatewayError::HttpStatus {
+            status,
+            message: "body".to_string(),
+        };
+        let cases = [
+            (
+                GatewayError::GraphQLError(vec!["bad query".to_string()]),
+                LegErrorClass::Graphql,
+            ),
+            (
+                GatewayError::Timeout("30s".to_string()),
+                LegErrorClass::Transport,
+            ),
+            (status(503), LegErrorClass::Transport),
+            (status(429), LegErrorClass::Transport),
+            (status(400), LegErrorClass::Rejected),
+            (
+                GatewayError::AllIndexersFailed {
+                    attempts: 3,
+                    last_error: "HTTP 502".to_string(),
+                },
+                LegErrorClass::Transport,
+            ),
+            (
+                GatewayError::PaymentRequired("no wallet".to_string()),
+                LegErrorClass::Payment,
+            ),
+            (
+                GatewayError::BudgetExceeded {
+                    limit: 10,
+                    used: 10,
+                    resets_in_secs: 60,
+                },
+                LegErrorClass::Payment,
+            ),
+            (
+                GatewayError::CircuitOpen {
+                    subgraph_id: "abc".to_string(),
+                    retry_after_ms: 1_000,
+                },
+                LegErrorClass::CircuitOpen,
+            ),
+            (GatewayError::NoData, LegErrorClass::BadResponse),
+            (
+                GatewayError::PaidRequestFailed {
+                    payment: PaymentReceipt {
+                        network: "base-sepolia".to_string(),
+                        asset: "0x036CbD53842c5426634e7929541eC2318f3dCF7e".to_string(),
+                        pay_to: "0x209693Bc6afc0C5328bA36FaF03C514EF312287C".to_string(),
+                        payer: "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266".to_string(),
+                        amount: "1000".to_string(),
+                        nonce: "0x01".to_string(),
+                        transaction: None,
+                        settled: false,
+                    },
+                    error: Box::new(status(503)),
+                },
+                LegErrorClass::Transport,
+            ),
+            (
+                GatewayError::SubgraphNotFound("abc".to_string()),
+                LegErrorClass::InvalidArgument,
+            ),
+        ];
+        for (error, expected) in cases {
+            let kind = QueryError::from(error).kind;
+            let error = BamlRtError::ToolExecution("whatever the message says".to_string());
+            assert_eq!(LegErrorClass::classify(&error, Some(kind)), expected);
+        }
+
+        // Errors outside any subgraph query go by their variant
+        assert_eq!(
+            LegErrorClass::classify(&BamlRtError::InvalidArgument("limit".to_string()), None),
+            LegErrorClass::InvalidArgument
+        );
+        assert_eq!(
+            LegErrorClass::classify(&BamlRtError::ToolExecution("decode".to_string()), None),
+            LegErrorClass::BadResponse
+        );
+        assert_eq!(
+            json!(LegErrorClass::UnknownNetwork),
+            json!("unknown_network")
+        );
+    }
+
+    #[test]
+    fn test_failed_networks_lists_networks_without_results() {
+        let targets = vec!["ethereum".to_string(), "base".to_string()];
+        let results = vec![json!({ "network": "ethereum", "protocol": "uniswap_v3" })];
+        assert_eq!(
+            failed_networks(&targets, &results),
+            vec!["base".to_string()]
+        );
+        assert!(failed_networks(&targets[..1], &results).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_query_plan_reports_failed_legs() {
+        // Unknown networks and protocols fail without any network access
+        let tool = TheGraphTool::new().with_plan_concurrency(2);
+        let plan = QueryPlan {
+            target_networks: vec!["solana".to_string()],
+            target_protocols: vec!["uniswap_v3".to_string(), "aave_v3".to_string()],
+            data_filters: QueryFilters::default(),
+            query_priority: 1,
+            expected_data_points: 10,
+        };
+
+        let result = tool
+            .execute_query_plan(&plan, &BlockPin::Latest, false)
+            .await
+            .unwrap();
+        assert_eq!(result["success_rate"], json!(0.0));
+        assert_eq!(result["failed_networks"], json!(["solana"]));
+        let failed = result["failed_legs"].as_array().unwrap();
+        assert_eq!(failed.len(), 2);
+        assert!(failed
+            .iter()
+            .all(|leg| leg["error_class"] == "unknown_network"));
+        assert!(result["results"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_capped_limit_respects_max_results() {
+        let tool = TheGraphTool::new().with_max_results(2500);
+        assert_eq!(tool.capped_limit(10), 10);
+        assert_eq!(tool.capped_limit(10_000), 2500);
+        assert_eq!(
+            TheGraphTool::new().capped_limit(u32::MAX),
+            DEFAULT_MAX_GRAPH_RESULTS
+        );
+    }
+
+    #[test]
+    fn test_sort_pools_by_tvl_is_deterministic() {
+        let mut pools = vec![
+            test_pool("0xb", 100.0),
+            test_pool("0xc", 500.5),
+            test_pool("0xa", 100.0),
+        ];
+        sort_pools_by_tvl(&mut pools);
+        let ids: Vec<&str> = pools.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, vec!["0xc", "0xa", "0xb"]);
+    }
+
+    #[test]
+    fn test_aggregate_pool_activity() {
+        let rows = vec![
+            hourly("0xAAA", 100.5, 0.3),
+            hourly("0xaaa", 200.0, 0.6),
+            hourly("0xbbb", 50.0, 0.15),
+        ];
+        let activity = aggregate_pool_activity(&rows);
+        assert_eq!(activity.len(), 2);
+        assert!((activity["0xaaa"].volume_usd - 300.5).abs() < 1e-9);
+        assert!((activity["0xaaa"].fees_usd - 0.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_annotate_uses_trailing_volume_not_cumulative() {
+        let mut activity = HashMap::new();
+        activity.insert(
+            "0xaaa".to_string(),
+            PoolActivity {
+                volume_usd: 500_000.0,
+                fees_usd: 1_500.0,
+            },
+        );
+        let mut pool = test_pool("0xaaa", 1_000_000.0);
+        annotate_trailing_activity(&mut pool, &activity);
+        assert_eq!(pool.volume_24h_usd, Some(500_000.0));
+        assert_eq!(pool.volume_tvl_ratio_24h, Some(0.5));
+
+        // Pools without hourly rows had no recent activity
+        let mut idle = test_pool("0xccc", 1000.0);
+        annotate_trailing_activity(&mut idle, &activity);
+        assert_eq!(idle.volume_24h_usd, Some(0.0));
+    }
+
+    #[test]
+    fn test_time_range_defaults_and_validation() {
+        let params = GraphQueryParams {
+            limit: None,
+            pool_id: None,
+            token_address: None,
+            filters: None,
+            query_plan: None,
+            user_address: None,
+            start_time: None,
+            end_time: Some(1_700_000_000),
+            block_number: None,
+            block_hash: None,
+            snapshot: None,
+            large_trade_usd: None,
+            tick_spacings: None,
+            amount_in: None,
+        };
+        let (start, end) = TheGraphTool::time_range(Some(&params), 3600).unwrap();
+        assert_eq!((start, end), (1_699_996_400, 1_700_000_000));
+
+        let inverted = GraphQueryParams {
+            start_time: Some(10),
+            end_time: Some(5),
+            ..params
+        };
+        assert!(TheGraphTool::time_range(Some(&inverted), 3600).is_err());
+    }
+
+    #[test]
+    fn test_ray_rate_to_apy() {
+        // 5% APR in ray compounds to ~5.127% APY
+        let apy = ray_rate_to_apy(0.05 * RAY);
+        assert!((apy - 0.05127).abs() < 1e-4);
+        assert_eq!(ray_rate_to_apy(0.0), 0.0);
+    }
+
+    #[test]
+    fn test_summarize_aave_reserve() {
+        let reserve = json!({
+            "symbol": "USDC",
+            "decimals": 6,
+            "liquidityRate": "40000000000000000000000000",
+            "variableBorrowRate": "60000000000000000000000000",
+            "stableBorrowRate": "0",
+            "totalLiquidity": "1000000000000",
+            "availableLiquidity": "250000000000",
+            "totalCurrentVariableDebt": "750000000000",
+            "totalPrincipalStableDebt": "0",
+            "baseLTVasCollateral": "7500",
+            "reserveLiquidationThreshold": "7800",
+            "price": { "priceInEth": "100000000" }
+        });
+
+        let summary = summarize_aave_reserve(&reserve);
+        assert!((summary["utilization_percent"].as_f64().unwrap() - 75.0).abs() < 1e-9);
+        assert!((summary["total_liquidity_usd"].as_f64().unwrap() - 1_000_000.0).abs() < 1e-6);
+        assert!((summary["liquidation_threshold_percent"].as_f64().unwrap() - 78.0).abs() < 1e-9);
+        assert!(summary["supply_apy_percent"].as_f64().unwrap() > 4.0);
+    }
+
+    #[test]
+    fn test_health_factor() {
+        let user_reserves = [
+            json!({
+                "currentATokenBalance": "10000000000000000000",
+                "currentVariableDebt": "0",
+                "currentStableDebt": "0",
+                "usageAsCollateralEnabledOnUser": true,
+                "reserve": {
+                    "symbol": "WETH",
+                    "decimals": 18,
+                    "reserveLiquidationThreshold": "8250",
+                    "price": { "priceInEth": "200000000000" }
+                }
+            }),
+            json!({
+                "currentATokenBalance": "0",
+                "currentVariableDebt": "10000000000",
+                "currentStableDebt": "0",
+                "usageAsCollateralEnabledOnUser": false,
+                "reserve": {
+                    "symbol": "USDC",
+                    "decimals": 6,
+                    "reserveLiquidationThreshold": "7800",
+                    "price": { "priceInEth": "100000000" }
+                }
+            }),
+        ];
+        let positions: Vec<AavePosition> = user_reserves
+            .iter()
+            .map(AavePosition::from_user_reserve)
+            .collect();
+
+        // 10 WETH @ $2000 * 0.825 / $10,000 debt = 1.65
+        let hf = health_factor(&positions).unwrap();
+        assert!((hf - 1.65).abs() < 1e-9);
+
+        // No debt => unbounded
+        assert!(health_factor(&positions[..1]).is_none());
     }
 }
@@ -3,16 +3,22 @@
 //! SECURITY NOTE:
 //! - Uses SecureWallet signing only; no private key exposure.
 //! - Returns signatures and hashes, never raw key material.
+//! - Transactions are decoded and checked against the router allowlist
+//!   before signing; blind hash signing is off unless explicitly enabled.
 
 use crate::tools::{AnyJson, DefiBundle};
+use crate::wallet::intent::{decode_intent, decode_unsigned_eip1559, IntentError};
 use crate::wallet::SecureWallet;
-use alloy::primitives::{eip191_hash_message, hex, keccak256, B256};
+use alloy::consensus::{SignableTransaction, TxEip1559};
+use alloy::eips::Encodable2718;
+use alloy::primitives::{eip191_hash_message, hex, Address, Bytes, TxKind, B256, U256};
 use async_trait::async_trait;
 use baml_rt::error::{BamlRtError, Result};
 use baml_rt::tools::BamlTool;
 use schemars::{JsonSchema, Schema, SchemaGenerator};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::str::FromStr;
 use std::sync::Arc;
 use ts_rs::TS;
 
@@ -26,18 +32,40 @@ pub struct WalletSignMessageInput {
     pub message: String,
 }
 
+/// Unsigned EIP-1559 transaction fields
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, TS)]
+#[ts(export)]
+pub struct UnsignedTxInput {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub to: String,
+    pub data: String,
+    /// Native value in wei (decimal string)
+    pub value: String,
+    pub gas_limit: u64,
+    /// Max fee per gas in wei (decimal string)
+    pub max_fee_per_gas: String,
+    /// Max priority fee per gas in wei (decimal string)
+    pub max_priority_fee_per_gas: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, TS)]
 #[ts(export)]
 #[schemars(schema_with = "wallet_sign_tx_schema")]
 pub struct WalletSignTxInput {
-    pub tx_hash: Option<String>,
+    /// Structured unsigned transaction (preferred)
+    pub transaction: Option<UnsignedTxInput>,
+    /// Unsigned EIP-1559 transaction bytes (`0x02 || rlp(fields)`)
     pub tx_bytes: Option<String>,
+    /// Raw 32-byte hash (blind signing; disabled unless enabled in config)
+    pub tx_hash: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 struct WalletSignTxInputSchema {
-    pub tx_hash: Option<String>,
+    pub transaction: Option<UnsignedTxInput>,
     pub tx_bytes: Option<String>,
+    pub tx_hash: Option<String>,
 }
 
 fn wallet_sign_tx_schema(gen: &mut SchemaGenerator) -> Schema {
@@ -46,7 +74,11 @@ fn wallet_sign_tx_schema(gen: &mut SchemaGenerator) -> Schema {
     if let serde_json::Value::Object(ref mut map) = value {
         map.insert(
             "oneOf".to_string(),
-            json!([{"required": ["tx_hash"]}, {"required": ["tx_bytes"]}]),
+            json!([
+                {"required": ["transaction"]},
+                {"required": ["tx_bytes"]},
+                {"required": ["tx_hash"]}
+            ]),
         );
         return Schema::from(std::mem::take(map));
     }
@@ -67,6 +99,45 @@ fn encode_hex_prefixed(bytes: &[u8]) -> String {
     format!("0x{}", hex::encode(bytes))
 }
 
+fn parse_u128(field: &str, value: &str) -> Result<u128> {
+    value
+        .parse()
+        .map_err(|e| BamlRtError::InvalidArgument(format!("Invalid {}: {}", field, e)))
+}
+
+impl TryFrom<&UnsignedTxInput> for TxEip1559 {
+    type Error = BamlRtError;
+
+    fn try_from(input: &UnsignedTxInput) -> Result<Self> {
+        let to = Address::from_str(&input.to)
+            .map_err(|e| BamlRtError::InvalidArgument(format!("Invalid 'to' address: {}", e)))?;
+        let value = U256::from_str(&input.value)
+            .map_err(|e| BamlRtError::InvalidArgument(format!("Invalid value: {}", e)))?;
+
+        Ok(TxEip1559 {
+            chain_id: input.chain_id,
+            nonce: input.nonce,
+            gas_limit: input.gas_limit,
+            max_fee_per_gas: parse_u128("max_fee_per_gas", &input.max_fee_per_gas)?,
+            max_priority_fee_per_gas: parse_u128(
+                "max_priority_fee_per_gas",
+                &input.max_priority_fee_per_gas,
+            )?,
+            to: TxKind::Call(to),
+            value,
+            input: Bytes::from(decode_hex(&input.data)?),
+            ..Default::default()
+        })
+    }
+}
+
+fn intent_error(err: IntentError) -> BamlRtError {
+    match err {
+        IntentError::InvalidEncoding(_) => BamlRtError::InvalidArgument(err.to_string()),
+        _ => BamlRtError::ToolExecution(format!("Refusing to sign: {}", err)),
+    }
+}
+
 pub struct WalletDeriveAddressTool {
     wallet: Arc<SecureWallet>,
 }
@@ -138,11 +209,55 @@ impl BamlTool for WalletSignMessageTool {
 
 pub struct WalletSignTxTool {
     wallet: Arc<SecureWallet>,
+    allow_blind_signing: bool,
 }
 
 impl WalletSignTxTool {
     pub fn new(wallet: Arc<SecureWallet>) -> Self {
-        Self { wallet }
+        Self {
+            wallet,
+            allow_blind_signing: false,
+        }
+    }
+
+    /// Allow signing raw hashes without decoding (see `SigningSettings`)
+    pub fn with_blind_signing(mut self, allow: bool) -> Self {
+        self.allow_blind_signing = allow;
+        self
+    }
+
+    async fn sign_blind_hash(&self, tx_hash: &str) -> Result<AnyJson> {
+        if !self.allow_blind_signing {
+            return Err(BamlRtError::ToolExecution(
+                "Blind hash signing is disabled; pass a structured transaction or tx_bytes \
+                 (enable with signing.allow_blind_signing)"
+                    .to_string(),
+            ));
+        }
+
+        let bytes = decode_hex(tx_hash)?;
+        if bytes.len() != 32 {
+            return Err(BamlRtError::InvalidArgument(
+                "tx_hash must be 32 bytes".to_string(),
+            ));
+        }
+        let hash = B256::from_slice(&bytes);
+
+        tracing::warn!(tx_hash = %hash, "Blind-signing transaction hash");
+
+        let signature = self
+            .wallet
+            .sign_hash(&b256_to_array(hash))
+            .await
+            .map_err(|e| BamlRtError::ToolExecution(e.to_string()))?;
+
+        Ok(AnyJson::new(json!({
+            "address": self.wallet.address_string(),
+            "hash_source": "tx_hash",
+            "tx_hash": signature_message_hash(hash),
+            "signature": signature.to_string(),
+            "intent": null
+        })))
     }
 }
 
@@ -155,40 +270,52 @@ impl BamlTool for WalletSignTxTool {
     type Output = AnyJson;
 
     fn description(&self) -> &'static str {
-        "Sign a transaction hash or raw bytes (policy-gated). Returns signature and hash."
+        "Sign an unsigned EIP-1559 transaction (policy-gated). Only Odos router swaps and \
+         approvals to those routers are accepted. Returns signature, signed tx, and decoded intent."
     }
 
     async fn execute(&self, args: Self::Input) -> Result<Self::Output> {
-        let (hash, source) = if let Some(tx_hash) = args.tx_hash {
-            let bytes = decode_hex(&tx_hash)?;
-            if bytes.len() != 32 {
-                return Err(BamlRtError::InvalidArgument(
-                    "tx_hash must be 32 bytes".to_string(),
-                ));
-            }
-            let mut array = [0u8; 32];
-            array.copy_from_slice(&bytes);
-            (B256::from(array), "tx_hash")
-        } else if let Some(tx_bytes) = args.tx_bytes {
-            let bytes = decode_hex(&tx_bytes)?;
-            (keccak256(bytes), "tx_bytes")
+        let (tx, source) = if let Some(transaction) = args.transaction.as_ref() {
+            (TxEip1559::try_from(transaction)?, "transaction")
+        } else if let Some(tx_bytes) = args.tx_bytes.as_deref() {
+            let bytes = decode_hex(tx_bytes)?;
+            (
+                decode_unsigned_eip1559(&bytes).map_err(intent_error)?,
+                "tx_bytes",
+            )
+        } else if let Some(tx_hash) = args.tx_hash.as_deref() {
+            return self.sign_blind_hash(tx_hash).await;
         } else {
             return Err(BamlRtError::InvalidArgument(
-                "Missing tx_hash or tx_bytes".to_string(),
+                "Missing transaction, tx_bytes, or tx_hash".to_string(),
             ));
         };
 
-        let signature = self
+        let intent = decode_intent(&tx, self.wallet.address()).map_err(intent_error)?;
+        tracing::info!(
+            chain_id = tx.chain_id,
+            intent = ?intent,
+            "Decoded transaction intent for signing"
+        );
+
+        let hash = tx.signature_hash();
+        let envelope = self
             .wallet
-            .sign_hash(&b256_to_array(hash))
+            .sign_transaction(tx)
             .await
             .map_err(|e| BamlRtError::ToolExecution(e.to_string()))?;
+        let signature = envelope
+            .as_eip1559()
+            .map(|signed| signed.signature().to_string())
+            .unwrap_or_default();
 
         Ok(AnyJson::new(json!({
             "address": self.wallet.address_string(),
             "hash_source": source,
             "tx_hash": signature_message_hash(hash),
-            "signature": signature.to_string()
+            "signature": signature,
+            "signed_tx": encode_hex_prefixed(&envelope.encoded_2718()),
+            "intent": intent
         })))
     }
 }
@@ -201,9 +328,85 @@ fn signature_message_hash(hash: B256) -> String {
 mod tests {
     use super::*;
 
+    use crate::wallet::intent::ODOS_ROUTER_V3;
+
+    const TEST_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+    fn tool() -> WalletSignTxTool {
+        WalletSignTxTool::new(Arc::new(SecureWallet::from_hex(TEST_KEY).unwrap()))
+    }
+
+    fn unsigned_tx(to: &str, data: &str) -> UnsignedTxInput {
+        UnsignedTxInput {
+            chain_id: 1,
+            nonce: 0,
+            to: to.to_string(),
+            data: data.to_string(),
+            value: "0".to_string(),
+            gas_limit: 300_000,
+            max_fee_per_gas: "30000000000".to_string(),
+            max_priority_fee_per_gas: "1000000000".to_string(),
+        }
+    }
+
     #[test]
     fn decode_hex_rejects_invalid() {
         let err = decode_hex("0xzz").unwrap_err();
         assert!(format!("{err}").contains("Invalid hex"));
     }
+
+    #[tokio::test]
+    async fn blind_hash_signing_disabled_by_default() {
+        let args = WalletSignTxInput {
+            transaction: None,
+            tx_bytes: None,
+            tx_hash: Some(format!("0x{}", "11".repeat(32))),
+        };
+        let err = tool().execute(args.clone()).await.unwrap_err();
+        assert!(format!("{err}").contains("Blind hash signing is disabled"));
+
+        let allowed = tool().with_blind_signing(true).execute(args).await;
+        assert!(allowed.is_ok());
+    }
+
+    #[tokio::test]
+    async fn signs_compact_swap_to_router() {
+        // swapCompact: 1 USDC -> ETH, output to msg.sender
+        let calldata = concat!(
+            "0x83bd37f9",
+            "0001a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+            "0000",
+            "030f4240",
+            "0201f4",
+            "000000",
+            "00013333333333333333333333333333333333333333",
+            "0000",
+            "0000",
+            "00000000",
+            "00"
+        );
+        let args = WalletSignTxInput {
+            transaction: Some(unsigned_tx(&ODOS_ROUTER_V3.to_string(), calldata)),
+            tx_bytes: None,
+            tx_hash: None,
+        };
+        let output = tool().execute(args).await.unwrap();
+        let value = output.0;
+        assert_eq!(value["intent"]["kind"], "swap_compact");
+        assert!(value["signed_tx"].as_str().unwrap().starts_with("0x02"));
+    }
+
+    #[tokio::test]
+    async fn refuses_unknown_target() {
+        let args = WalletSignTxInput {
+            transaction: Some(unsigned_tx(
+                "0x1234567890123456789012345678901234567890",
+                "0xa9059cbb",
+            )),
+            tx_bytes: None,
+            tx_hash: None,
+        };
+        let err = tool().execute(args).await.unwrap_err();
+        assert!(format!("{err}").contains("Refusing to sign"));
+    }
 }
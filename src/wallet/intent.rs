@@ -0,0 +1,511 @@
+//! Transaction intent decoding and target allowlist
+//!
+//! Decodes unsigned transactions before they reach the signer so the wallet
+//! only ever signs calls whose effect is understood:
+//! - Odos router swaps paying out to the signer (per-chain router allowlist),
+//!   either ABI-encoded `swap` or packed `swapCompact` calldata
+//! - ERC20 `approve` where the spender is an allowlisted router
+//!
+//! Anything else is rejected. The decoded intent is returned to the caller
+//! (and therefore recorded by the audit log interceptor).
+
+use alloy::consensus::TxEip1559;
+use alloy::primitives::{address, Address, Bytes, TxKind, U256};
+use alloy::rlp::Decodable;
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use serde::{Deserialize, Serialize};
+
+/// Odos V3 router (same address on every supported chain)
+pub const ODOS_ROUTER_V3: Address = address!("0D05a7D3448512B78fa8A9e46c4872C88C4a0D05");
+
+/// EIP-2718 type byte for EIP-1559 transactions
+const EIP1559_TX_TYPE: u8 = 0x02;
+
+sol! {
+    /// Odos router swap parameters
+    struct swapTokenInfo {
+        address inputToken;
+        uint256 inputAmount;
+        address inputReceiver;
+        address outputToken;
+        uint256 outputQuote;
+        uint256 outputMin;
+        address outputReceiver;
+    }
+
+    function swap(
+        swapTokenInfo tokenInfo,
+        bytes pathDefinition,
+        address executor,
+        uint32 referralCode
+    ) external payable returns (uint256 amountOut);
+
+    function swapCompact() external payable returns (uint256 amountOut);
+
+    function approve(address spender, uint256 amount) external returns (bool);
+}
+
+/// Odos V2 router address for a chain
+pub fn odos_router_v2(chain_id: u64) -> Option<Address> {
+    match chain_id {
+        1 => Some(address!("Cf5540fFFCdC3d510B18bFcA6d2b9987b0772559")),
+        10 => Some(address!("Ca423977156BB05b13A2BA3b76Bc5419E2fE9680")),
+        137 => Some(address!("4E3288c9ca110bCC82bf38F09A7b425c095d92Bf")),
+        8453 => Some(address!("19cEeAd7105607Cd444F5ad10dd51356436095a1")),
+        42161 => Some(address!("a669e7A0d4b3e4Fa48af2dE86BD4CD7126Be4e13")),
+        _ => None,
+    }
+}
+
+/// Check whether an address is an allowlisted router on a chain
+pub fn is_allowed_router(chain_id: u64, target: Address) -> bool {
+    target == ODOS_ROUTER_V3 || odos_router_v2(chain_id) == Some(target)
+}
+
+/// Decoded effect of a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TxIntent {
+    /// Odos router swap with fully decoded parameters
+    Swap {
+        router: Address,
+        input_token: Address,
+        input_amount: U256,
+        output_token: Address,
+        output_min: U256,
+        recipient: Address,
+        native_value: U256,
+    },
+    /// Odos compact swap, decoded from its packed calldata
+    ///
+    /// Tokens the router reads from its address cache can't be resolved
+    /// offline and are `None`; the native currency is the zero address.
+    SwapCompact {
+        router: Address,
+        input_token: Option<Address>,
+        /// `None` swaps the signer's full balance
+        input_amount: Option<U256>,
+        output_token: Option<Address>,
+        output_quote: U256,
+        recipient: Address,
+        native_value: U256,
+    },
+    /// ERC20 approval for an allowlisted router
+    Approve {
+        token: Address,
+        spender: Address,
+        amount: U256,
+    },
+}
+
+/// Error type for intent checks
+#[derive(Debug, thiserror::Error)]
+pub enum IntentError {
+    #[error("Invalid transaction encoding: {0}")]
+    InvalidEncoding(String),
+
+    #[error("Contract creation transactions are not allowed")]
+    ContractCreation,
+
+    #[error("Target {target} is not an allowlisted router or token approval on chain {chain_id}")]
+    TargetNotAllowed { chain_id: u64, target: Address },
+
+    #[error("Approval spender {0} is not an allowlisted router")]
+    SpenderNotAllowed(Address),
+
+    #[error("Swap recipient {recipient} does not match signer {signer}")]
+    RecipientMismatch { recipient: Address, signer: Address },
+
+    #[error("Unrecognized calldata for {target}: {reason}")]
+    UnknownCalldata { target: Address, reason: String },
+}
+
+/// Address slot in `swapCompact` calldata
+enum CompactAddress {
+    /// `0x0000`: the zero address, or a default chosen by the router
+    Null,
+    /// `0x0001` followed by the 20-byte address
+    Inline(Address),
+    /// Index into the router's on-chain address cache
+    Cached(u16),
+}
+
+/// Reader over `swapCompact` packed calldata (Odos router layout: input and
+/// output token, input amount, output quote, slippage, executor, input
+/// receiver, output receiver, referral code, path)
+struct CompactReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> CompactReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| "truncated swapCompact calldata".to_string())?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn address(&mut self) -> Result<CompactAddress, String> {
+        let tag = self.take(2)?;
+        Ok(match u16::from_be_bytes([tag[0], tag[1]]) {
+            0 => CompactAddress::Null,
+            1 => CompactAddress::Inline(Address::from_slice(self.take(20)?)),
+            index => CompactAddress::Cached(index),
+        })
+    }
+
+    /// Length-prefixed big-endian integer
+    fn amount(&mut self) -> Result<Option<U256>, String> {
+        let len = usize::from(self.take(1)?[0]);
+        if len == 0 {
+            return Ok(None);
+        }
+        if len > 32 {
+            return Err(format!("amount of {} bytes in swapCompact calldata", len));
+        }
+        Ok(Some(U256::from_be_slice(self.take(len)?)))
+    }
+}
+
+fn compact_token(address: CompactAddress) -> Option<Address> {
+    match address {
+        CompactAddress::Null => Some(Address::ZERO),
+        CompactAddress::Inline(address) => Some(address),
+        CompactAddress::Cached(_) => None,
+    }
+}
+
+/// Decode `swapCompact` calldata, requiring the output to go to `signer`
+fn decode_swap_compact(
+    router: Address,
+    data: &[u8],
+    native_value: U256,
+    signer: Address,
+) -> Result<TxIntent, IntentError> {
+    let unknown = |reason: String| IntentError::UnknownCalldata {
+        target: router,
+        reason,
+    };
+    let mut reader = CompactReader { data, pos: 4 };
+    let input_token = compact_token(reader.address().map_err(unknown)?);
+    let output_token = compact_token(reader.address().map_err(unknown)?);
+    let input_amount = reader.amount().map_err(unknown)?;
+    let output_quote = reader.amount().map_err(unknown)?.unwrap_or_default();
+    let _slippage = reader.take(3).map_err(unknown)?;
+    let _executor = reader.address().map_err(unknown)?;
+    let _input_receiver = reader.address().map_err(unknown)?;
+    // The null address sends the output to msg.sender, i.e. the signer
+    let recipient = match reader.address().map_err(unknown)? {
+        CompactAddress::Null => signer,
+        CompactAddress::Inline(address) => address,
+        CompactAddress::Cached(index) => {
+            return Err(unknown(format!(
+                "swap recipient is router-cached address #{}, which can't be verified",
+                index
+            )))
+        }
+    };
+    if recipient != signer {
+        return Err(IntentError::RecipientMismatch { recipient, signer });
+    }
+    Ok(TxIntent::SwapCompact {
+        router,
+        input_token,
+        input_amount,
+        output_token,
+        output_quote,
+        recipient,
+        native_value,
+    })
+}
+
+/// Decode an unsigned EIP-1559 transaction (`0x02 || rlp(fields)`)
+pub fn decode_unsigned_eip1559(bytes: &[u8]) -> Result<TxEip1559, IntentError> {
+    let (ty, mut payload) = bytes
+        .split_first()
+        .ok_or_else(|| IntentError::InvalidEncoding("empty transaction bytes".to_string()))?;
+    if *ty != EIP1559_TX_TYPE {
+        return Err(IntentError::InvalidEncoding(format!(
+            "expected EIP-1559 type byte 0x02, got 0x{:02x}",
+            ty
+        )));
+    }
+    let tx =
+        TxEip1559::decode(&mut payload).map_err(|e| IntentError::InvalidEncoding(e.to_string()))?;
+    if !payload.is_empty() {
+        return Err(IntentError::InvalidEncoding(
+            "trailing bytes after transaction".to_string(),
+        ));
+    }
+    Ok(tx)
+}
+
+/// Decode a transaction's intent and verify it against the allowlist
+///
+/// # Arguments
+/// * `tx` - The unsigned transaction
+/// * `signer` - Address that will sign (swap output must go here)
+pub fn decode_intent(tx: &TxEip1559, signer: Address) -> Result<TxIntent, IntentError> {
+    let target = match tx.to {
+        TxKind::Call(addr) => addr,
+        TxKind::Create => return Err(IntentError::ContractCreation),
+    };
+    let data: &Bytes = &tx.input;
+    let selector = data.get(..4).unwrap_or_default();
+
+    if is_allowed_router(tx.chain_id, target) {
+        if selector == swapCall::SELECTOR {
+            let call = swapCall::abi_decode(data).map_err(|e| IntentError::UnknownCalldata {
+                target,
+                reason: e.to_string(),
+            })?;
+            let info = call.tokenInfo;
+            if info.outputReceiver != signer {
+                return Err(IntentError::RecipientMismatch {
+                    recipient: info.outputReceiver,
+                    signer,
+                });
+            }
+            return Ok(TxIntent::Swap {
+                router: target,
+                input_token: info.inputToken,
+                input_amount: info.inputAmount,
+                output_token: info.outputToken,
+                output_min: info.outputMin,
+                recipient: info.outputReceiver,
+                native_value: tx.value,
+            });
+        }
+        if selector == swapCompactCall::SELECTOR {
+            return decode_swap_compact(target, data, tx.value, signer);
+        }
+        return Err(IntentError::UnknownCalldata {
+            target,
+            reason: "unsupported router function".to_string(),
+        });
+    }
+
+    if selector == approveCall::SELECTOR {
+        let call = approveCall::abi_decode(data).map_err(|e| IntentError::UnknownCalldata {
+            target,
+            reason: e.to_string(),
+        })?;
+        if !is_allowed_router(tx.chain_id, call.spender) {
+            return Err(IntentError::SpenderNotAllowed(call.spender));
+        }
+        if !tx.value.is_zero() {
+            return Err(IntentError::UnknownCalldata {
+                target,
+                reason: "approve must not carry native value".to_string(),
+            });
+        }
+        return Ok(TxIntent::Approve {
+            token: target,
+            spender: call.spender,
+            amount: call.amount,
+        });
+    }
+
+    Err(IntentError::TargetNotAllowed {
+        chain_id: tx.chain_id,
+        target,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::consensus::SignableTransaction;
+
+    const SIGNER: Address = address!("f39Fd6e51aad88F6F4ce6aB8827279cffFb92266");
+
+    fn tx_to(chain_id: u64, to: Address, input: Vec<u8>) -> TxEip1559 {
+        TxEip1559 {
+            chain_id,
+            nonce: 0,
+            gas_limit: 300_000,
+            max_fee_per_gas: 30_000_000_000,
+            max_priority_fee_per_gas: 1_000_000_000,
+            to: TxKind::Call(to),
+            value: U256::ZERO,
+            input: input.into(),
+            ..Default::default()
+        }
+    }
+
+    fn swap_calldata(recipient: Address) -> Vec<u8> {
+        swapCall {
+            tokenInfo: swapTokenInfo {
+                inputToken: Address::repeat_byte(0x11),
+                inputAmount: U256::from(1_000_000u64),
+                inputReceiver: Address::repeat_byte(0x33),
+                outputToken: Address::repeat_byte(0x22),
+                outputQuote: U256::from(500u64),
+                outputMin: U256::from(495u64),
+                outputReceiver: recipient,
+            },
+            pathDefinition: Bytes::new(),
+            executor: Address::repeat_byte(0x33),
+            referralCode: 0,
+        }
+        .abi_encode()
+    }
+
+    #[test]
+    fn test_decodes_router_swap() {
+        let router = odos_router_v2(1).unwrap();
+        let tx = tx_to(1, router, swap_calldata(SIGNER));
+
+        let intent = decode_intent(&tx, SIGNER).unwrap();
+        match intent {
+            TxIntent::Swap {
+                input_token,
+                input_amount,
+                recipient,
+                ..
+            } => {
+                assert_eq!(input_token, Address::repeat_byte(0x11));
+                assert_eq!(input_amount, U256::from(1_000_000u64));
+                assert_eq!(recipient, SIGNER);
+            }
+            other => panic!("unexpected intent: {:?}", other),
+        }
+    }
+
+    /// `swapCompact` calldata selling 1 USDC (inline) for ETH (null), paying
+    /// out to `recipient` (null = msg.sender)
+    fn swap_compact_calldata(recipient: Option<Address>) -> Vec<u8> {
+        let mut data = swapCompactCall::SELECTOR.to_vec();
+        data.extend([0x00, 0x01]);
+        data.extend(address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48").as_slice());
+        data.extend([0x00, 0x00]);
+        data.extend([0x03, 0x0f, 0x42, 0x40]); // input amount 1_000_000
+        data.extend([0x02, 0x01, 0xf4]); // output quote 500
+        data.extend([0x00, 0x00, 0x00]); // slippage
+        data.extend([0x00, 0x01]);
+        data.extend(Address::repeat_byte(0x33).as_slice()); // executor
+        data.extend([0x00, 0x00]); // input receiver: executor
+        match recipient {
+            Some(recipient) => {
+                data.extend([0x00, 0x01]);
+                data.extend(recipient.as_slice());
+            }
+            None => data.extend([0x00, 0x00]),
+        }
+        data.extend([0x00, 0x00, 0x00, 0x00]); // referral code
+        data.push(0x00); // empty path
+        data
+    }
+
+    #[test]
+    fn test_decodes_swap_compact() {
+        let router = odos_router_v2(1).unwrap();
+        for recipient in [None, Some(SIGNER)] {
+            let tx = tx_to(1, router, swap_compact_calldata(recipient));
+            match decode_intent(&tx, SIGNER).unwrap() {
+                TxIntent::SwapCompact {
+                    input_token,
+                    input_amount,
+                    output_token,
+                    output_quote,
+                    recipient,
+                    ..
+                } => {
+                    assert_eq!(
+                        input_token,
+                        Some(address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"))
+                    );
+                    assert_eq!(input_amount, Some(U256::from(1_000_000u64)));
+                    assert_eq!(output_token, Some(Address::ZERO));
+                    assert_eq!(output_quote, U256::from(500u64));
+                    assert_eq!(recipient, SIGNER);
+                }
+                other => panic!("unexpected intent: {:?}", other),
+            }
+        }
+
+        let other = tx_to(
+            1,
+            router,
+            swap_compact_calldata(Some(Address::repeat_byte(0x99))),
+        );
+        assert!(matches!(
+            decode_intent(&other, SIGNER).unwrap_err(),
+            IntentError::RecipientMismatch { .. }
+        ));
+
+        // A bare selector is not a swap we understand
+        let bare = tx_to(1, router, swapCompactCall::SELECTOR.to_vec());
+        assert!(matches!(
+            decode_intent(&bare, SIGNER).unwrap_err(),
+            IntentError::UnknownCalldata { .. }
+        ));
+    }
+
+    #[test]
+    fn test_rejects_swap_to_other_recipient() {
+        let tx = tx_to(1, ODOS_ROUTER_V3, swap_calldata(Address::repeat_byte(0x99)));
+        let err = decode_intent(&tx, SIGNER).unwrap_err();
+        assert!(matches!(err, IntentError::RecipientMismatch { .. }));
+    }
+
+    #[test]
+    fn test_router_allowlist_is_per_chain() {
+        // Arbitrum router is not valid on mainnet
+        let arb_router = odos_router_v2(42161).unwrap();
+        let tx = tx_to(1, arb_router, swap_calldata(SIGNER));
+        let err = decode_intent(&tx, SIGNER).unwrap_err();
+        assert!(matches!(err, IntentError::TargetNotAllowed { .. }));
+    }
+
+    #[test]
+    fn test_approve_requires_allowlisted_spender() {
+        let token = Address::repeat_byte(0x44);
+        let ok = approveCall {
+            spender: ODOS_ROUTER_V3,
+            amount: U256::MAX,
+        }
+        .abi_encode();
+        let intent = decode_intent(&tx_to(8453, token, ok), SIGNER).unwrap();
+        assert!(matches!(intent, TxIntent::Approve { spender, .. } if spender == ODOS_ROUTER_V3));
+
+        let bad = approveCall {
+            spender: Address::repeat_byte(0x66),
+            amount: U256::MAX,
+        }
+        .abi_encode();
+        let err = decode_intent(&tx_to(8453, token, bad), SIGNER).unwrap_err();
+        assert!(matches!(err, IntentError::SpenderNotAllowed(_)));
+    }
+
+    #[test]
+    fn test_rejects_unknown_target() {
+        let tx = tx_to(1, Address::repeat_byte(0x77), vec![0xde, 0xad, 0xbe, 0xef]);
+        let err = decode_intent(&tx, SIGNER).unwrap_err();
+        assert!(matches!(err, IntentError::TargetNotAllowed { .. }));
+    }
+
+    #[test]
+    fn test_decode_unsigned_roundtrip() {
+        let tx = tx_to(1, ODOS_ROUTER_V3, swap_compact_calldata(None));
+        let mut encoded = Vec::new();
+        tx.encode_for_signing(&mut encoded);
+
+        let decoded = decode_unsigned_eip1559(&encoded).unwrap();
+        assert_eq!(decoded, tx);
+        assert!(matches!(
+            decode_intent(&decoded, SIGNER).unwrap(),
+            TxIntent::SwapCompact { .. }
+        ));
+    }
+
+    #[test]
+    fn test_decode_unsigned_rejects_legacy() {
+        let err = decode_unsigned_eip1559(&[0xf8, 0x00]).unwrap_err();
+        assert!(matches!(err, IntentError::InvalidEncoding(_)));
+    }
+}
@@ -3,8 +3,9 @@
 //! This module handles private key storage and transaction signing.
 //! The private key NEVER leaves this module and is NEVER exposed to JavaScript.
 
+pub mod intent;
 mod signer;
 pub mod simulator;
 
-pub use signer::SecureWallet;
+pub use signer::{PreparedTransaction, SecureWallet};
 pub use simulator::{SimulationError, SimulationResult, TransactionSimulator};
@@ -7,16 +7,17 @@
 //! - Keys are never logged
 
 use crate::{Error, Result};
+use alloy::consensus::{SignableTransaction, TxEip1559, TxEnvelope};
 use alloy::network::EthereumWallet;
 use alloy::primitives::{Address, Bytes, U256};
 use alloy::signers::local::PrivateKeySigner;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 /// A prepared transaction ready for signing
 ///
 /// Used when executing swaps through the interceptor pipeline.
-#[derive(Debug, Clone, Serialize)]
-#[allow(dead_code)] // Will be used when transaction execution is implemented
+/// A `gas_limit` of zero means "estimate during simulation".
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PreparedTransaction {
     pub to: Address,
     pub data: Bytes,
@@ -125,6 +126,16 @@ impl SecureWallet {
             .sign_hash_sync(&alloy::primitives::B256::from(*hash))
             .map_err(|e| Error::Wallet(format!("Signing failed: {}", e)))
     }
+
+    /// Sign an EIP-1559 transaction and return the signed envelope
+    ///
+    /// The envelope can be EIP-2718 encoded and broadcast as a raw transaction.
+    /// Subject to the same dry-run guard as `sign_hash`.
+    pub async fn sign_transaction(&self, tx: TxEip1559) -> Result<TxEnvelope> {
+        let hash = tx.signature_hash();
+        let signature = self.sign_hash(&hash.0).await?;
+        Ok(TxEnvelope::Eip1559(tx.into_signed(signature)))
+    }
 }
 
 // Implement Debug manually to avoid exposing the signer
@@ -167,4 +178,42 @@ mod tests {
         assert!(!debug_str.contains("ac0974bec"));
         assert!(debug_str.contains("[REDACTED]"));
     }
+
+    #[tokio::test]
+    async fn test_sign_transaction_recovers_wallet_address() {
+        use alloy::primitives::TxKind;
+
+        let test_key = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+        let wallet = SecureWallet::from_hex(test_key).unwrap();
+
+        let tx = TxEip1559 {
+            chain_id: 1,
+            nonce: 0,
+            gas_limit: 21_000,
+            max_fee_per_gas: 30_000_000_000,
+            max_priority_fee_per_gas: 1_000_000_000,
+            to: TxKind::Call(Address::ZERO),
+            value: U256::from(1u64),
+            ..Default::default()
+        };
+
+        let envelope = wallet.sign_transaction(tx).await.unwrap();
+        let TxEnvelope::Eip1559(signed) = envelope else {
+            panic!("expected EIP-1559 envelope");
+        };
+        let signer = signed.recover_signer().unwrap();
+        assert_eq!(signer, wallet.address());
+    }
+
+    #[tokio::test]
+    async fn test_sign_transaction_respects_dry_run() {
+        let test_key = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+        let wallet = SecureWallet::from_hex(test_key).unwrap().with_dry_run(true);
+
+        let err = wallet
+            .sign_transaction(TxEip1559::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Dry-run"));
+    }
 }